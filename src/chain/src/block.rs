@@ -24,12 +24,13 @@ use std::boxed::Box;
 use std::fmt::Debug;
 use std::hash::Hash as HashTrait;
 use std::net::SocketAddr;
+use transactions::Tx;
 use triomphe::Arc;
 
 /// Generic block interface
-pub trait Block: Debug + PartialEq + Eq + HashTrait + Sized {
+pub trait Block: Debug + PartialEq + Eq + HashTrait + Sized + Send + Sync + 'static {
     /// Per tip validation state
-    type ChainState: Clone + Debug + Flushable + StateInterface;
+    type ChainState: Clone + Debug + Flushable + StateInterface + Send + Sync + 'static;
 
     /// Size of the block cache.
     const BLOCK_CACHE_SIZE: usize = 100;
@@ -41,15 +42,6 @@ pub trait Block: Debug + PartialEq + Eq + HashTrait + Sized {
     #[cfg(test)]
     const MAX_ORPHANS: usize = 20;
 
-    /// Number of blocks between a valid chain and
-    /// the canonical in order for the valid chain
-    /// to become canonical
-    #[cfg(not(test))]
-    const SWITCH_OFFSET: usize = 2;
-
-    #[cfg(test)]
-    const SWITCH_OFFSET: usize = 0;
-
     /// Blocks with height below the canonical height minus
     /// this number will be rejected.
     const MIN_HEIGHT: u64 = 60;
@@ -58,20 +50,20 @@ pub trait Block: Debug + PartialEq + Eq + HashTrait + Sized {
     /// this number will be rejected.
     const MAX_HEIGHT: u64 = 60;
 
-    /// The number of blocks after which a state checkpoint will be made.
-    ///
-    /// This number **MUST** be less or equal than the minimum accepted height.
-    const CHECKPOINT_INTERVAL: usize = 20;
-
     /// Max checkpoints to keep. This number must be less or equal
-    /// than `(MAX_HEIGHT + MIN_HEIGHT) / CHECKPOINT_INTERVAL`.
+    /// than `(MAX_HEIGHT + MIN_HEIGHT) / checkpoint_interval()`.
     const MAX_CHECKPOINTS: usize = 6;
 
     /// How many blocks to keep behind the canonical
     /// chain tip when pruning is enabled. This number should
-    /// be equal to `CHECKPOINT_INTERVAL * MAX_CHECKPOINTS`.
+    /// be at least `checkpoint_interval() * MAX_CHECKPOINTS`.
     const BLOCKS_TO_KEEP: usize = 120;
 
+    /// Returns the number of blocks after which a state checkpoint will be made.
+    ///
+    /// This number **MUST** be less or equal than the minimum accepted height.
+    fn checkpoint_interval() -> u64;
+
     /// Returns the genesis block.
     fn genesis() -> Arc<Self>;
 
@@ -93,6 +85,20 @@ pub trait Block: Debug + PartialEq + Eq + HashTrait + Sized {
     /// Returns the height of the block.
     fn height(&self) -> u64;
 
+    /// Returns the transactions included in the block. This is
+    /// empty if the block does not carry any transactions.
+    ///
+    /// Their execution statuses are returned by the state which
+    /// results from appending the block.
+    fn transactions(&self) -> Vec<Arc<Tx>> {
+        Vec::new()
+    }
+
+    /// Returns the cumulative work of the chain whose tip
+    /// state is the given one. The canonical chain is the
+    /// valid chain with the most cumulative work.
+    fn total_work(chain_state: &Self::ChainState) -> u128;
+
     /// Callback that executes after a block is written to a chain.
     fn after_write() -> Option<Box<dyn FnMut(Arc<Self>)>>;
 
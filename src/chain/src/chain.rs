@@ -17,9 +17,13 @@
 */
 
 use crate::block::*;
+use crate::flusher::Flusher;
+use crate::pow_chain::merkle::{self, TxInclusionProof};
+use crate::state_proof::StateProof;
 use crate::types::*;
 use account::Address;
 use bin_tools::*;
+use crossbeam_channel::{Receiver, Sender};
 use crypto::{Hash, ShortHash};
 use elastic_array::ElasticArray128;
 use hashbrown::{HashMap, HashSet};
@@ -30,7 +34,8 @@ use parking_lot::{Mutex, RwLock};
 use persistence::PersistentDb;
 use std::collections::VecDeque;
 use std::hash::Hash as HashTrait;
-use transactions::Tx;
+use std::sync::atomic::{AtomicU64, Ordering};
+use transactions::{Feature, Tx};
 use triomphe::Arc;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -62,6 +67,14 @@ pub enum ChainErr {
     /// Tried to rewind the chain to a block with a height
     /// that is lower than the root block in the chain.
     CannotRewindPastRootBlock,
+
+    /// The block is not the parent of the earliest block
+    /// of a chain started from a snapshot.
+    NotBackfillable,
+
+    /// The root state could not be flushed to disk. No blocks
+    /// are accepted afterwards.
+    FlushFailed,
 }
 
 /// Reasons for a bad append condition
@@ -106,17 +119,32 @@ pub enum AppendCondErr {
 
     /// Tried to apply a transaction block to state without any transaction data.
     NoTxSet,
+
+    /// The transaction root in the block header does not
+    /// match the one computed from the transaction set.
+    BadTxRoot,
+
+    /// The block's timestamp is not greater than the
+    /// median timestamp of the recent blocks.
+    TimestampTooOld,
+
+    /// The block's timestamp is too far ahead of the local clock.
+    TimestampInFuture,
 }
 
 lazy_static! {
     /// Canonical tip block key
-    static ref TIP_KEY: &'static [u8] = { b"canonical_tip" };
+    pub(crate) static ref TIP_KEY: &'static [u8] = { b"canonical_tip" };
 
     /// The key of the root block
-    static ref ROOT_KEY: &'static [u8] = { b"root_block" };
+    pub(crate) static ref ROOT_KEY: &'static [u8] = { b"root_block" };
 
     /// The key to the canonical height of the chain
-    static ref CANONICAL_HEIGHT_KEY: &'static [u8] = { b"canonical_height" };
+    pub(crate) static ref CANONICAL_HEIGHT_KEY: &'static [u8] = { b"canonical_height" };
+
+    /// The key of the earliest block of a chain started
+    /// from a snapshot which is missing its parent
+    static ref BACKFILL_KEY: &'static [u8] = { b"backfill_block" };
 }
 
 #[derive(Clone)]
@@ -327,6 +355,20 @@ impl<B: Block> ChainRef<B> {
         chain.append_block(block)
     }
 
+    /// Writes the parent of the earliest block of a
+    /// chain started from a snapshot to the ledger.
+    pub fn backfill_block(&self, block: Arc<B>) -> Result<(), ChainErr> {
+        let mut chain = self.chain.write();
+        chain.backfill_block(block)
+    }
+
+    /// Returns the hash and the height of the next block that can be
+    /// backfilled, or `None` if the chain has its full history.
+    pub fn next_backfill(&self) -> Option<(Hash, u64)> {
+        let chain = self.chain.read();
+        chain.next_backfill()
+    }
+
     /// Returns true if there is any block in the canonical
     /// chain with the given `Hash`.
     pub fn is_canonical(&self, block_hash: &Hash) -> bool {
@@ -334,6 +376,83 @@ impl<B: Block> ChainRef<B> {
         chain.is_canonical(block_hash)
     }
 
+    /// Returns the canonical block at the given height.
+    pub fn query_by_height(&self, height: u64) -> Option<Arc<B>> {
+        let block_hash = self.block_hash_at_height(height)?;
+        self.query(&block_hash)
+    }
+
+    /// Returns the hash of the canonical block at the given height.
+    pub fn block_hash_at_height(&self, height: u64) -> Option<Hash> {
+        let chain = self.chain.read();
+        chain.block_hash_at_height(height)
+    }
+
+    /// Returns the height of the block with the given hash, if it
+    /// is either in the canonical chain or in the orphan pool.
+    pub fn block_height(&self, hash: &Hash) -> Option<u64> {
+        let chain = self.chain.read();
+        chain.block_height(hash)
+    }
+
+    /// Looks up a transaction of the canonical chain by its hash.
+    pub fn query_tx(&self, tx_hash: &Hash) -> Option<IndexedTx> {
+        let chain = self.chain.read();
+        chain.query_tx(tx_hash)
+    }
+
+    /// Returns the transactions of the canonical block with the given hash.
+    pub fn block_transactions(&self, block_hash: &Hash) -> Vec<Arc<Tx>> {
+        let chain = self.chain.read();
+        chain.block_transactions(block_hash)
+    }
+
+    /// Returns the hash of the canonical block which includes the
+    /// transaction with the given hash along with a proof of inclusion.
+    pub fn tx_inclusion_proof(&self, tx_hash: &Hash) -> Option<(Hash, TxInclusionProof)> {
+        let chain = self.chain.read();
+        chain.tx_inclusion_proof(tx_hash)
+    }
+
+    /// Returns the number of transactions in the history
+    /// of the given permanent address.
+    pub fn address_history_len(&self, address: &Address) -> u64 {
+        let chain = self.chain.read();
+        chain.address_history_len(address)
+    }
+
+    /// Returns a page of the history of the given permanent
+    /// address, starting with the most recent entries.
+    pub fn address_history(&self, address: &Address, offset: u64, limit: u64) -> Vec<AddressTx> {
+        let chain = self.chain.read();
+        chain.address_history(address, offset, limit)
+    }
+
+    /// Returns a receiver of the events emitted by the chain
+    /// i.e. connected and disconnected blocks and tip changes.
+    pub fn subscribe(&self) -> Receiver<ChainEvent<B>> {
+        let mut chain = self.chain.write();
+        chain.subscribe()
+    }
+
+    /// Returns the number of bytes reclaimed by pruning.
+    pub fn reclaimed_space(&self) -> u64 {
+        let chain = self.chain.read();
+        chain.reclaimed_space()
+    }
+
+    /// Returns true if the transactions of canonical blocks are indexed.
+    pub fn tx_index(&self) -> bool {
+        let chain = self.chain.read();
+        chain.tx_index()
+    }
+
+    /// Returns true if the chain keeps the data of all of its blocks.
+    pub fn archival_mode(&self) -> bool {
+        let chain = self.chain.read();
+        chain.archival_mode()
+    }
+
     /// Returns the current tip of the canonical chain.
     pub fn canonical_tip(&self) -> Arc<B> {
         let chain = self.chain.read();
@@ -346,6 +465,13 @@ impl<B: Block> ChainRef<B> {
         chain.canonical_tip_state()
     }
 
+    /// Returns the canonical tip of the chain along with its state,
+    /// read at once so that the state belongs to the returned tip.
+    pub fn canonical_tip_with_state(&self) -> (Arc<B>, B::ChainState) {
+        let chain = self.chain.read();
+        (chain.canonical_tip.clone(), chain.canonical_tip_state())
+    }
+
     /// Attempts to fetch a block by its hash from the cache
     /// and if it doesn't succeed it then attempts to retrieve
     /// it from the orphan pool.
@@ -419,6 +545,15 @@ impl<B: Block> ChainRef<B> {
         chain.canonical_tip_state.inner_ref().validate_tx(tx)
     }
 
+    /// Returns `true` if the given protocol feature is active at the given height.
+    pub fn is_active(&self, feature: Feature, height: u64) -> bool {
+        let chain = self.chain.read();
+        chain
+            .canonical_tip_state
+            .inner_ref()
+            .is_active(feature, height)
+    }
+
     pub fn get_account_nonce(&self, address: &Address) -> Option<u64> {
         let chain = self.chain.read();
         chain
@@ -427,6 +562,19 @@ impl<B: Block> ChainRef<B> {
             .get_account_nonce(address)
     }
 
+    /// Builds a proof of the value stored under the given key
+    /// in the state of the canonical block with the given hash.
+    pub fn state_proof(&self, block_hash: &Hash, key: &[u8]) -> Result<StateProof, &'static str> {
+        let chain = self.chain.read();
+        chain.state_proof(block_hash, key)
+    }
+
+    /// Returns the state of the canonical chain at the given height.
+    pub fn state_at(&self, height: u64) -> Result<B::ChainState, &'static str> {
+        let chain = self.chain.read();
+        chain.state_at(height)
+    }
+
     pub fn get_db_and_state_root(&self) -> (PersistentDb, ShortHash) {
         let chain = self.chain.read();
         (
@@ -460,8 +608,9 @@ pub struct Chain<B: Block> {
     /// all current forks descend.
     root_block: Arc<B>,
 
-    /// The root state from which all other states descend.
-    root_state: FlushedChainState<B::ChainState>,
+    /// Background flusher of the root state from
+    /// which all other states descend.
+    flusher: Flusher<B>,
 
     /// Memory pool of blocks that are not in the canonical chain.
     orphan_pool: HashMap<Hash, Arc<B>>,
@@ -485,10 +634,13 @@ pub struct Chain<B: Block> {
     /// Mapping between heights and associated
     /// checkpointed state. Note that this only
     /// stores the states of the canonical chain.
-    ///
-    /// TODO: Checkpoint states on non-canonical chains as well
     heights_state_mapping: HashMap<u64, UnflushedChainState<B::ChainState>>,
 
+    /// Mapping between blocks belonging to valid non-canonical
+    /// chains and their checkpointed states. Each branch keeps
+    /// at most `B::MAX_CHECKPOINTS` checkpoints.
+    orphan_checkpoints: HashMap<Hash, UnflushedChainState<B::ChainState>>,
+
     /// Mapping between orphans and their orphan types/validation statuses.
     validations_mapping: HashMap<Hash, OrphanType>,
 
@@ -509,8 +661,33 @@ pub struct Chain<B: Block> {
     /// Validation states associated with the valid tips
     valid_tips_states: HashMap<Hash, UnflushedChainState<B::ChainState>>,
 
-    /// Whether the chain is in archival mode or not
+    /// Whether the chain is in archival mode or not. The dead
+    /// state, indexed transactions and address history of
+    /// past blocks are pruned otherwise.
     archival_mode: bool,
+
+    /// The number of bytes reclaimed by pruning.
+    reclaimed_space: Arc<AtomicU64>,
+
+    /// Whether transactions of canonical blocks are indexed by hash
+    tx_index: bool,
+
+    /// Whether the transactions touching each permanent
+    /// address are indexed.
+    address_index: bool,
+
+    /// Senders of the chain event subscribers.
+    subscribers: Vec<Sender<ChainEvent<B>>>,
+
+    /// Whether we are switching the canonical chain. Tip
+    /// changes are only emitted once the switch is done.
+    switching: bool,
+
+    /// The most recently written canonical blocks, ordered by
+    /// height. Blocks read back from the ledger only carry their
+    /// headers so these are used when rewinding in order to keep
+    /// the transactions of disconnected blocks.
+    recent_blocks: VecDeque<Arc<B>>,
 }
 
 impl<B: Block> Chain<B> {
@@ -529,9 +706,11 @@ impl<B: Block> Chain<B> {
             None => B::genesis(),
         };
 
+        let root_state_height = root_state.height();
+        let root_state_root = root_state.state_root();
         let root_state = FlushedChainState::new(root_state);
         let root_db_res = db_ref.retrieve(&ROOT_KEY);
-        let root_block = match root_db_res {
+        let mut root_block = match root_db_res {
             Some(tip) => {
                 let block_bytes = db_ref.retrieve(&tip).unwrap();
                 B::from_bytes(&block_bytes).unwrap()
@@ -539,13 +718,36 @@ impl<B: Block> Chain<B> {
             None => B::genesis(),
         };
 
+        // The root state is flushed before the root block key so
+        // if we stopped in between, the root block is the canonical
+        // block at the height of the root state.
+        if root_state_height > root_block.height() {
+            let height_key = crypto::hash_slice(&encode_be_u64!(root_state_height));
+            let block_hash = db_ref.retrieve(&height_key.0).unwrap();
+
+            root_block = B::from_bytes(&db_ref.retrieve(&block_hash).unwrap()).unwrap();
+            db_ref.put(&ROOT_KEY, &block_hash);
+            db_ref.flush();
+        }
+
         assert!(canonical_tip.height() >= root_block.height());
 
+        // Retain the state root of the root block so that
+        // the state at its height can be read later on.
+        if archival_mode {
+            let state_root_key = Self::compute_state_root_key(root_block.height());
+
+            if db_ref.retrieve(&state_root_key.0).is_none() {
+                db_ref.put(&state_root_key.0, &root_state_root.0);
+                db_ref.flush();
+            }
+        }
+
         let mut heights_state_mapping = HashMap::with_capacity(B::MAX_CHECKPOINTS);
         let mut earliest_checkpoint_height = None;
         let mut last_checkpoint_height = None;
 
-        let canonical_tip_state = if canonical_tip.is_genesis() {
+        let canonical_tip_state = if canonical_tip.is_genesis() || canonical_tip == root_block {
             root_state.clone().modify()
         } else {
             // Calculate canonical tip state by gathering
@@ -582,7 +784,7 @@ impl<B: Block> Chain<B> {
                     let last_checkpoint = last_checkpoint_height.unwrap_or(0);
 
                     // Checkpoint state if we have reached the quota
-                    if height - last_checkpoint == B::CHECKPOINT_INTERVAL as u64 {
+                    if height - last_checkpoint == B::checkpoint_interval() {
                         if let None = earliest_checkpoint_height {
                             earliest_checkpoint_height = Some(height);
                         }
@@ -598,6 +800,7 @@ impl<B: Block> Chain<B> {
             state
         };
 
+        let reclaimed_space = Arc::new(AtomicU64::new(0));
         let height = match db_ref.retrieve(&CANONICAL_HEIGHT_KEY) {
             Some(height) => decode_be_u64!(&height).unwrap(),
             None => {
@@ -614,10 +817,16 @@ impl<B: Block> Chain<B> {
             canonical_tip,
             canonical_tip_state,
             root_block,
-            root_state,
+            flusher: Flusher::new(
+                db_ref.clone(),
+                root_state,
+                archival_mode,
+                reclaimed_space.clone(),
+            ),
             earliest_checkpoint_height,
             last_checkpoint_height,
             heights_state_mapping,
+            orphan_checkpoints: HashMap::with_capacity(B::MAX_ORPHANS),
             orphan_pool: HashMap::with_capacity(B::MAX_ORPHANS),
             short_mappings: HashMap::with_capacity(B::MAX_ORPHANS),
             heights_mapping: HashMap::with_capacity(B::MAX_ORPHANS),
@@ -629,11 +838,89 @@ impl<B: Block> Chain<B> {
             valid_tips_states: HashMap::with_capacity(B::MAX_ORPHANS),
             max_orphan_height: None,
             archival_mode,
+            reclaimed_space,
+            tx_index: false,
+            address_index: false,
+            subscribers: Vec::new(),
+            switching: false,
+            recent_blocks: VecDeque::with_capacity(B::MIN_HEIGHT as usize),
             height,
             db: db_ref,
         }
     }
 
+    /// Creates a chain starting at the given block from the state
+    /// at that block, restored from a snapshot. The ledger must be
+    /// empty. Blocks before the root block can be added afterwards
+    /// with `Chain::backfill_block`.
+    pub fn from_snapshot(
+        mut db_ref: PersistentDb,
+        root_block: Arc<B>,
+        mut root_state: B::ChainState,
+        archival_mode: bool,
+    ) -> Chain<B> {
+        assert!(db_ref.retrieve(&TIP_KEY).is_none());
+        assert_eq!(root_block.height(), root_state.height());
+
+        let block_hash = root_block.block_hash().unwrap();
+        let encoded_height = encode_be_u64!(root_block.height());
+
+        // Write the root block as the canonical tip
+        db_ref.put(&block_hash.0, &root_block.to_bytes());
+        db_ref.put(&block_hash.to_short().0, &block_hash.0);
+        db_ref.put(&Self::compute_height_key(&block_hash).0, &encoded_height);
+        db_ref.put(&crypto::hash_slice(&encoded_height).0, &block_hash.0);
+        db_ref.put(&TIP_KEY, &block_hash.0);
+        db_ref.put(&ROOT_KEY, &block_hash.0);
+        db_ref.put(&BACKFILL_KEY, &block_hash.0);
+        db_ref.put(&CANONICAL_HEIGHT_KEY, &encoded_height);
+        db_ref.flush();
+
+        root_state.flush().unwrap();
+        Self::new(db_ref, root_state, archival_mode)
+    }
+
+    /// Returns the hash and the height of the next block that can be
+    /// backfilled, or `None` if the chain has its full history.
+    pub fn next_backfill(&self) -> Option<(Hash, u64)> {
+        let earliest = self.db.retrieve(&BACKFILL_KEY)?;
+        let earliest = B::from_bytes(&self.db.retrieve(&earliest).unwrap()).unwrap();
+
+        Some((earliest.parent_hash(), earliest.height() - 1))
+    }
+
+    /// Writes the parent of the earliest block of a chain started
+    /// from a snapshot to the ledger. Backfilled blocks are only
+    /// checked against the hash of their child since the state
+    /// they apply to is not available.
+    pub fn backfill_block(&mut self, block: Arc<B>) -> Result<(), ChainErr> {
+        let (parent_hash, parent_height) = self.next_backfill().ok_or(ChainErr::NotBackfillable)?;
+        let block_hash = block.block_hash().unwrap();
+
+        if block_hash != parent_hash || block.height() != parent_height {
+            return Err(ChainErr::NotBackfillable);
+        }
+
+        let encoded_height = encode_be_u64!(block.height());
+
+        self.db.put(&block_hash.0, &block.to_bytes());
+        self.db.put(&block_hash.to_short().0, &block_hash.0);
+        self.db
+            .put(&Self::compute_height_key(&block_hash).0, &encoded_height);
+        self.db
+            .put(&crypto::hash_slice(&encoded_height).0, &block_hash.0);
+
+        // The parent of the first block is the genesis block
+        if block.height() == 1 {
+            self.db.delete(&BACKFILL_KEY);
+        } else {
+            self.db.put(&BACKFILL_KEY, &block_hash.0);
+        }
+
+        self.db.flush();
+        Ok(())
+    }
+
     /// Rewinds the canonical chain to the block with the given hash.
     ///
     /// Returns `Err(ChainErr::NoSuchBlock)` if there is no block with
@@ -655,15 +942,19 @@ impl<B: Block> Chain<B> {
 
         let canonical_state = self.canonical_tip_state.clone();
         let mut current = self.canonical_tip.clone();
+        self.pop_recent_block(&current.block_hash().unwrap());
         let mut inverse_height = 1;
 
         // Remove canonical tip from the chain
         // and mark it as a valid chain tip.
-        self.db.delete(&current.block_hash().unwrap().0);
+        self.delete_block(&current);
+        self.emit(ChainEvent::BlockDisconnected(current.clone()));
 
-        // Remove current height entry
-        let current_height_key = crypto::hash_slice(&encode_be_u64!(current.height()));
-        self.db.delete(&current_height_key.0);
+        // Keep the checkpointed state of the old tip
+        if let Some(state) = self.heights_state_mapping.remove(&current.height()) {
+            self.orphan_checkpoints
+                .insert(current.block_hash().unwrap(), state);
+        }
 
         // Add the old tip to the orphan pool
         self.orphan_pool
@@ -699,18 +990,32 @@ impl<B: Block> Chain<B> {
             if parent_hash == *block_hash {
                 break;
             } else {
-                let parent = B::from_bytes(&self.db.retrieve(&parent_hash.0).unwrap()).unwrap();
+                let parent = match self.pop_recent_block(&parent_hash) {
+                    Some(parent) => parent,
+                    None => {
+                        // Only the most recent blocks are kept along with their
+                        // transactions so these cannot be handed back to the
+                        // mempool when rewinding deeper than that.
+                        warn!(
+                            "Rewinding past the recent blocks, the transactions of block {} cannot be re-injected",
+                            parent_hash
+                        );
+
+                        B::from_bytes(&self.db.retrieve(&parent_hash.0).unwrap()).unwrap()
+                    }
+                };
                 let cur_height = parent.height();
 
                 // Remove parent from db
-                self.db.delete(&parent_hash.0);
-
-                // Remove parent state from state mappings
-                self.heights_state_mapping.remove(&parent.height());
-
-                // Remove current height entry
-                let current_height_key = crypto::hash_slice(&encode_be_u64!(cur_height));
-                self.db.delete(&current_height_key.0);
+                self.delete_block(&parent);
+                self.emit(ChainEvent::BlockDisconnected(parent.clone()));
+
+                // Move parent state from state mappings to the
+                // checkpoints of the now non-canonical chain.
+                if let Some(state) = self.heights_state_mapping.remove(&parent.height()) {
+                    self.orphan_checkpoints
+                        .insert(parent.block_hash().unwrap(), state);
+                }
 
                 // Add the parent to the orphan pool
                 self.orphan_pool
@@ -739,13 +1044,20 @@ impl<B: Block> Chain<B> {
             }
         }
 
+        // Update checkpoint heights to the remaining checkpoints
+        self.last_checkpoint_height = self.heights_state_mapping.keys().max().cloned();
+
+        if self.last_checkpoint_height.is_none() {
+            self.earliest_checkpoint_height = None;
+        }
+
         let state = self.search_fetch_next_state(new_tip.height());
         let new_tip_hash = new_tip.block_hash().unwrap();
 
         self.height = new_tip.height();
         self.write_canonical_height(new_tip.height());
         self.canonical_tip_state = state;
-        self.canonical_tip = new_tip;
+        self.canonical_tip = new_tip.clone();
 
         // Write tip key
         self.db.put(&TIP_KEY, &new_tip_hash.0);
@@ -753,15 +1065,203 @@ impl<B: Block> Chain<B> {
         // Flush changes
         self.db.flush();
 
+        if !self.switching {
+            self.emit(ChainEvent::TipChanged(new_tip));
+        }
+
         Ok(())
     }
 
+    /// Rewinds the canonical chain stored in the given ledger to the
+    /// canonical block with the given hash and height, without loading
+    /// the chain. The blocks above it are looked up by their height so
+    /// this also works when they are corrupted.
+    ///
+    /// Like `Chain::rewind`, this does not allow rewinding past the
+    /// root block. Returns the number of deleted blocks.
+    pub fn rewind_ledger(
+        db: &mut PersistentDb,
+        block_hash: &Hash,
+        height: u64,
+    ) -> Result<u64, &'static str> {
+        let root_height = match db.retrieve(&ROOT_KEY) {
+            Some(root_hash) => {
+                let root_hash = Self::decode_hash(&root_hash).ok_or("Invalid root block hash")?;
+                let root_height = db
+                    .retrieve(&Self::compute_height_key(&root_hash).0)
+                    .ok_or("Could not find the height of the root block")?;
+
+                decode_be_u64!(&root_height).map_err(|_| "Invalid root block height")?
+            }
+            None => 0,
+        };
+
+        if height < root_height {
+            return Err("Cannot rewind past the root block");
+        }
+
+        if height > 0 {
+            let height_key = crypto::hash_slice(&encode_be_u64!(height));
+            let canonical_hash = db
+                .retrieve(&height_key.0)
+                .and_then(|hash| Self::decode_hash(&hash));
+
+            if canonical_hash.as_ref() != Some(block_hash) {
+                return Err("The block is not canonical");
+            }
+        }
+
+        let canonical_height = match db.retrieve(&CANONICAL_HEIGHT_KEY) {
+            Some(canonical_height) => {
+                decode_be_u64!(&canonical_height).map_err(|_| "Invalid canonical height")?
+            }
+            None => 0,
+        };
+
+        let mut deleted = 0;
+
+        // Delete blocks starting with the tip
+        for h in ((height + 1)..=canonical_height).rev() {
+            let height_key = crypto::hash_slice(&encode_be_u64!(h));
+
+            if let Some(hash) = db.retrieve(&height_key.0) {
+                let hash = Self::decode_hash(&hash).ok_or("Invalid canonical block hash")?;
+                Self::delete_block_data(db, &hash, h);
+                deleted += 1;
+            }
+        }
+
+        // The genesis block is not stored in the ledger
+        if height == 0 {
+            db.delete(&TIP_KEY);
+        } else {
+            db.put(&TIP_KEY, &block_hash.0);
+        }
+
+        db.put(&CANONICAL_HEIGHT_KEY, &encode_be_u64!(height));
+        db.flush();
+
+        Ok(deleted)
+    }
+
+    pub(crate) fn decode_hash(bytes: &[u8]) -> Option<Hash> {
+        if bytes.len() != 32 {
+            return None;
+        }
+
+        let mut hash = [0; 32];
+        hash.copy_from_slice(bytes);
+        Some(Hash(hash))
+    }
+
     /// Returns true if there is any canonical block in the chain
     /// with the given `Hash`.
     pub fn is_canonical(&self, block_hash: &Hash) -> bool {
         self.db.retrieve(&block_hash.0).is_some()
     }
 
+    /// Enables or disables indexing the transactions of canonical
+    /// blocks by their hash. Only blocks written after the index
+    /// is enabled are indexed.
+    ///
+    /// Backfilled blocks are not indexed since the state they
+    /// apply to, and thus the outcome of their transactions,
+    /// is not available.
+    pub fn set_tx_index(&mut self, enabled: bool) {
+        self.tx_index = enabled;
+    }
+
+    /// Enables or disables indexing the transactions of canonical
+    /// blocks by the permanent addresses they touch. Only blocks
+    /// written after the index is enabled are indexed and, like
+    /// with the transaction index, backfilled blocks are not.
+    pub fn set_address_index(&mut self, enabled: bool) {
+        self.address_index = enabled;
+    }
+
+    /// Returns the number of bytes reclaimed by pruning
+    /// since the chain has been loaded.
+    pub fn reclaimed_space(&self) -> u64 {
+        self.reclaimed_space.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if the transactions of canonical
+    /// blocks are indexed by their hash.
+    pub fn tx_index(&self) -> bool {
+        self.tx_index
+    }
+
+    /// Returns true if the chain keeps the state and indexed
+    /// data of all of its blocks instead of pruning it.
+    pub fn archival_mode(&self) -> bool {
+        self.archival_mode
+    }
+
+    /// Returns a receiver of the events emitted by the chain.
+    ///
+    /// The receiver is unbounded and it stops receiving
+    /// events once it is dropped.
+    pub fn subscribe(&mut self) -> Receiver<ChainEvent<B>> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    #[inline]
+    /// Removes and returns the most recently written canonical
+    /// block if it has the given hash.
+    fn pop_recent_block(&mut self, block_hash: &Hash) -> Option<Arc<B>> {
+        match self.recent_blocks.back() {
+            Some(block) if block.block_hash().unwrap() == *block_hash => {
+                self.recent_blocks.pop_back()
+            }
+            _ => None,
+        }
+    }
+
+    #[inline]
+    /// Sends the event to all subscribers, removing
+    /// the ones which have been dropped.
+    fn emit(&mut self, event: ChainEvent<B>) {
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    #[inline]
+    /// Checkpoints the state of a block belonging to a valid
+    /// non-canonical chain if the block is at a checkpoint height.
+    ///
+    /// The earliest checkpoints of the branch are evicted once
+    /// there are more than `B::MAX_CHECKPOINTS` of them.
+    fn checkpoint_orphan(&mut self, block: &B, state: &UnflushedChainState<B::ChainState>) {
+        if block.height() % B::checkpoint_interval() != 0 {
+            return;
+        }
+
+        self.orphan_checkpoints
+            .insert(block.block_hash().unwrap(), state.clone());
+
+        let mut checkpoints = 1;
+        let mut to_evict = Vec::new();
+        let mut current = block.parent_hash();
+
+        // Traverse parents until we reach the canonical chain
+        while let Some(parent) = self.orphan_pool.get(&current) {
+            if self.orphan_checkpoints.contains_key(&current) {
+                checkpoints += 1;
+
+                if checkpoints > B::MAX_CHECKPOINTS {
+                    to_evict.push(current.clone());
+                }
+            }
+
+            current = parent.parent_hash();
+        }
+
+        for block_hash in to_evict.iter() {
+            self.orphan_checkpoints.remove(block_hash);
+        }
+    }
+
     #[inline]
     fn update_max_orphan_height(&mut self, new_height: u64) {
         if self.max_orphan_height.is_none() {
@@ -776,7 +1276,10 @@ impl<B: Block> Chain<B> {
     }
 
     #[inline]
-    fn write_block(&mut self, block: Arc<B>) {
+    /// Writes the given block on top of the canonical tip. The
+    /// given state is the one which results from appending the
+    /// block and is only needed if an index is enabled.
+    fn write_block(&mut self, block: Arc<B>, state: Option<&B::ChainState>) {
         let block_hash = block.block_hash().unwrap();
         //println!("DEBUG WRITING BLOCK: {:?}", block_hash);
         assert!(self.disconnected_heads_mapping.get(&block_hash).is_none());
@@ -824,6 +1327,18 @@ impl<B: Block> Chain<B> {
         self.db
             .put(&crypto::hash_slice(&encoded_height).0, &block_hash.0);
 
+        if self.tx_index || self.address_index {
+            let state = state.expect("The state of the block is needed to index it");
+
+            if self.tx_index {
+                self.index_transactions(&block, state.tx_statuses());
+            }
+
+            if self.address_index {
+                self.index_addresses(&block, state.touched_addresses());
+            }
+        }
+
         self.orphan_pool.remove(&block_hash);
         self.short_mappings.remove(&block_hash.to_short());
         self.validations_mapping.remove(&block_hash);
@@ -836,6 +1351,20 @@ impl<B: Block> Chain<B> {
         // Remove from valid tips
         self.valid_tips.remove(&block_hash);
         self.valid_tips_states.remove(&block_hash);
+        self.orphan_checkpoints.remove(&block_hash);
+
+        // Keep the block in memory in case it is disconnected
+        self.recent_blocks.push_back(block.clone());
+
+        if self.recent_blocks.len() > B::MIN_HEIGHT as usize {
+            self.recent_blocks.pop_front();
+        }
+
+        self.emit(ChainEvent::BlockConnected(block.clone()));
+
+        if !self.switching {
+            self.emit(ChainEvent::TipChanged(block.clone()));
+        }
 
         // Update max orphan height if this is the case
         if let Some(max_height) = self.max_orphan_height {
@@ -862,44 +1391,262 @@ impl<B: Block> Chain<B> {
 
         let root_height = self.root_block.height();
 
-        // Flush changes to root state for past blocks
-        //
-        // TODO: Make this async if possible
+        self.db.flush();
+
+        // Hand over past blocks to the flusher once
+        // they have been written to disk.
         if self.canonical_tip.height() > root_height + B::MIN_HEIGHT {
             let states_to_flush = self.canonical_tip.height() - (root_height + B::MIN_HEIGHT);
             let mut cur_block = self.root_block.clone();
-            let mut cur_state = self.root_state.clone().modify();
 
             for i in 1..=states_to_flush {
                 let cur_height = root_height + i;
                 let block = self.query_by_height(cur_height).unwrap();
-                let new_state =
-                    B::append_condition(block.clone(), cur_state.inner(), BranchType::Canonical)
-                        .unwrap();
-                let new_state = UnflushedChainState::new(new_state);
 
                 // Remove checkpointed state
                 self.heights_state_mapping.remove(&cur_height);
 
-                cur_state = new_state;
+                // Prune blocks that are too far behind the root block
+                if !self.archival_mode && cur_height > B::BLOCKS_TO_KEEP as u64 {
+                    self.prune_block(cur_height - B::BLOCKS_TO_KEEP as u64);
+                }
+
+                self.flusher.flush(block.clone());
                 cur_block = block;
             }
 
-            // Write new root block
-            self.db.put(&ROOT_KEY, &cur_block.block_hash().unwrap().0);
-
-            self.root_state = cur_state.flush().unwrap();
             self.root_block = cur_block;
+            self.db.flush();
         }
 
-        self.db.flush();
-
         // Execute after write callback
         if let Some(mut cb) = B::after_write() {
             cb(block);
         }
     }
 
+    #[inline]
+    /// Removes the given block along with its height
+    /// index entries from the ledger.
+    fn delete_block(&mut self, block: &B) {
+        Self::delete_block_data(&mut self.db, &block.block_hash().unwrap(), block.height());
+    }
+
+    /// Deletes the block with the given hash and height from the
+    /// ledger along with its index entries. The block itself is
+    /// not read so this also works for blocks which are corrupted.
+    fn delete_block_data(db: &mut PersistentDb, block_hash: &Hash, height: u64) {
+        let height_key = crypto::hash_slice(&encode_be_u64!(height));
+        let block_height_key = Self::compute_height_key(block_hash);
+
+        db.delete(&block_hash.0);
+        db.delete(&block_hash.to_short().0);
+        db.delete(&height_key.0);
+        db.delete(&block_height_key.0);
+
+        // Remove indexed transactions
+        let block_txs_key = Self::compute_block_txs_key(block_hash);
+
+        if let Some(tx_hashes) = db.retrieve(&block_txs_key.0) {
+            for tx_hash in tx_hashes.chunks(32) {
+                let mut hash = [0; 32];
+                hash.copy_from_slice(tx_hash);
+
+                db.delete(&Self::compute_tx_key(&Hash(hash)).0);
+            }
+
+            db.delete(&block_txs_key.0);
+        }
+
+        // Remove address history entries. Blocks are deleted starting
+        // with the tip so the entries of the block are always the last
+        // ones in the history of each address.
+        let block_addrs_key = Self::compute_block_addrs_key(block_hash);
+
+        if let Some(addresses) = db.retrieve(&block_addrs_key.0) {
+            for address in addresses.chunks(33).rev() {
+                let (first, count) = Self::history_bounds(db, address);
+
+                if first >= count {
+                    continue;
+                }
+
+                db.delete(&Self::compute_history_key(address, count - 1).0);
+
+                if count - 1 == first {
+                    db.delete(&Self::compute_history_count_key(address).0);
+                    db.delete(&Self::compute_history_start_key(address).0);
+                } else {
+                    db.put(
+                        &Self::compute_history_count_key(address).0,
+                        &encode_be_u64!(count - 1),
+                    );
+                }
+            }
+
+            db.delete(&block_addrs_key.0);
+        }
+    }
+
+    #[inline]
+    /// Deletes the indexed transactions and address history of the
+    /// canonical block at the given height. Blocks are stored in the
+    /// ledger only by their headers, which are kept so the chain can
+    /// still be traversed, so nothing is reclaimed without indexes.
+    fn prune_block(&mut self, height: u64) {
+        let block_hash = match self.block_hash_at_height(height) {
+            Some(block_hash) => block_hash,
+            None => return,
+        };
+        let block_txs_key = Self::compute_block_txs_key(&block_hash);
+        let mut reclaimed = 0;
+
+        if let Some(tx_hashes) = self.db.retrieve(&block_txs_key.0) {
+            for tx_hash in tx_hashes.chunks(32) {
+                let mut hash = [0; 32];
+                hash.copy_from_slice(tx_hash);
+
+                let tx_key = Self::compute_tx_key(&Hash(hash));
+
+                if let Some(entry) = self.db.retrieve(&tx_key.0) {
+                    reclaimed += entry.len() as u64;
+                    self.db.delete(&tx_key.0);
+                }
+            }
+
+            reclaimed += tx_hashes.len() as u64;
+            self.db.delete(&block_txs_key.0);
+        }
+
+        // Remove address history entries. Blocks are pruned starting
+        // with the oldest so the entries of the block are always the
+        // first retained ones in the history of each address.
+        let block_addrs_key = Self::compute_block_addrs_key(&block_hash);
+
+        if let Some(addresses) = self.db.retrieve(&block_addrs_key.0) {
+            for address in addresses.chunks(33) {
+                let (first, count) = Self::history_bounds(&self.db, address);
+
+                if first >= count {
+                    continue;
+                }
+
+                let history_key = Self::compute_history_key(address, first);
+
+                if let Some(entry) = self.db.retrieve(&history_key.0) {
+                    reclaimed += entry.len() as u64;
+                    self.db.delete(&history_key.0);
+                }
+
+                if first + 1 == count {
+                    self.db.delete(&Self::compute_history_count_key(address).0);
+                    self.db.delete(&Self::compute_history_start_key(address).0);
+                } else {
+                    self.db.put(
+                        &Self::compute_history_start_key(address).0,
+                        &encode_be_u64!(first + 1),
+                    );
+                }
+            }
+
+            reclaimed += addresses.len() as u64;
+            self.db.delete(&block_addrs_key.0);
+        }
+
+        if reclaimed > 0 {
+            debug!(
+                "Pruned {} bytes of transaction data of block at height {}",
+                reclaimed, height
+            );
+        }
+
+        self.reclaimed_space.fetch_add(reclaimed, Ordering::Relaxed);
+    }
+
+    #[inline]
+    /// Writes the transactions of the given block to the transaction index.
+    ///
+    /// Each entry has the following format:
+    /// `<block-hash><height><position><status><transaction>`
+    fn index_transactions(&mut self, block: &B, tx_statuses: &[TxStatus]) {
+        let block_hash = block.block_hash().unwrap();
+        let encoded_height = encode_be_u64!(block.height());
+        let transactions = block.transactions();
+        let mut tx_hashes = Vec::new();
+
+        assert_eq!(transactions.len(), tx_statuses.len());
+
+        for (i, (tx, status)) in transactions.iter().zip(tx_statuses.iter()).enumerate() {
+            let tx_hash = match tx.tx_hash() {
+                Some(tx_hash) => tx_hash,
+                None => continue,
+            };
+
+            let status: u8 = match status {
+                TxStatus::Success => 1,
+                TxStatus::Failed => 0,
+            };
+
+            let mut entry = Vec::new();
+            entry.extend_from_slice(&block_hash.0);
+            entry.extend_from_slice(&encoded_height);
+            entry.extend_from_slice(&encode_be_u32!(i as u32));
+            entry.push(status);
+            entry.extend_from_slice(&tx.to_bytes());
+
+            self.db.put(&Self::compute_tx_key(&tx_hash).0, &entry);
+            tx_hashes.extend_from_slice(&tx_hash.0);
+        }
+
+        if !tx_hashes.is_empty() {
+            self.db
+                .put(&Self::compute_block_txs_key(&block_hash).0, &tx_hashes);
+        }
+    }
+
+    #[inline]
+    /// Appends the transactions of the given block to the history
+    /// of each permanent address they touch.
+    ///
+    /// Each entry has the following format:
+    /// `<tx-hash><block-hash><height><asset-hash><direction>`
+    fn index_addresses(&mut self, block: &B, touched_addresses: &[TouchedAddress]) {
+        let block_hash = block.block_hash().unwrap();
+        let encoded_height = encode_be_u64!(block.height());
+        let mut addresses = Vec::new();
+
+        for touched in touched_addresses.iter() {
+            let address = touched.address.to_bytes();
+            let count_key = Self::compute_history_count_key(&address);
+            let count = match self.db.retrieve(&count_key.0) {
+                Some(count) => decode_be_u64!(&count).unwrap(),
+                None => 0,
+            };
+
+            let direction: u8 = match touched.direction {
+                TxDirection::Incoming => 0,
+                TxDirection::Outgoing => 1,
+            };
+
+            let mut entry = Vec::new();
+            entry.extend_from_slice(&touched.tx_hash.0);
+            entry.extend_from_slice(&block_hash.0);
+            entry.extend_from_slice(&encoded_height);
+            entry.extend_from_slice(&touched.asset_hash.0);
+            entry.push(direction);
+
+            self.db
+                .put(&Self::compute_history_key(&address, count).0, &entry);
+            self.db.put(&count_key.0, &encode_be_u64!(count + 1));
+            addresses.extend_from_slice(&address);
+        }
+
+        if !addresses.is_empty() {
+            self.db
+                .put(&Self::compute_block_addrs_key(&block_hash).0, &addresses);
+        }
+    }
+
     #[inline]
     fn write_canonical_height(&mut self, height: u64) {
         let encoded_height = encode_be_u64!(height);
@@ -942,11 +1689,70 @@ impl<B: Block> Chain<B> {
     }
 
     #[inline]
-    fn compute_height_key(hash: &Hash) -> Hash {
+    pub(crate) fn compute_height_key(hash: &Hash) -> Hash {
         let block_height_key = format!("{}.height", hex::encode(&hash.0));
         crypto::hash_slice(block_height_key.as_bytes())
     }
 
+    #[inline]
+    fn compute_tx_key(tx_hash: &Hash) -> Hash {
+        let tx_key = format!("{}.tx", hex::encode(&tx_hash.0));
+        crypto::hash_slice(tx_key.as_bytes())
+    }
+
+    #[inline]
+    fn compute_block_txs_key(block_hash: &Hash) -> Hash {
+        let block_txs_key = format!("{}.txs", hex::encode(&block_hash.0));
+        crypto::hash_slice(block_txs_key.as_bytes())
+    }
+
+    #[inline]
+    fn compute_block_addrs_key(block_hash: &Hash) -> Hash {
+        let block_addrs_key = format!("{}.addrs", hex::encode(&block_hash.0));
+        crypto::hash_slice(block_addrs_key.as_bytes())
+    }
+
+    #[inline]
+    fn compute_history_count_key(address: &[u8]) -> Hash {
+        let count_key = format!("{}.hist", hex::encode(address));
+        crypto::hash_slice(count_key.as_bytes())
+    }
+
+    #[inline]
+    fn compute_history_start_key(address: &[u8]) -> Hash {
+        let start_key = format!("{}.hist.start", hex::encode(address));
+        crypto::hash_slice(start_key.as_bytes())
+    }
+
+    #[inline]
+    /// Returns the index of the first retained entry in the history of
+    /// the given address along with the total number of entries ever
+    /// written to it. Entries before the first one have been pruned.
+    fn history_bounds(db: &PersistentDb, address: &[u8]) -> (u64, u64) {
+        let first = match db.retrieve(&Self::compute_history_start_key(address).0) {
+            Some(first) => decode_be_u64!(&first).unwrap(),
+            None => 0,
+        };
+        let count = match db.retrieve(&Self::compute_history_count_key(address).0) {
+            Some(count) => decode_be_u64!(&count).unwrap(),
+            None => 0,
+        };
+
+        (first, count)
+    }
+
+    #[inline]
+    fn compute_history_key(address: &[u8], index: u64) -> Hash {
+        let history_key = format!("{}.hist.{}", hex::encode(address), index);
+        crypto::hash_slice(history_key.as_bytes())
+    }
+
+    #[inline]
+    pub(crate) fn compute_state_root_key(height: u64) -> Hash {
+        let state_root_key = format!("{}.root", height);
+        crypto::hash_slice(state_root_key.as_bytes())
+    }
+
     #[inline]
     /// Attempts to attach orphans to the canonical chain
     /// starting with the given height.
@@ -987,7 +1793,10 @@ impl<B: Block> Chain<B> {
                                 if let Some(new_tip_state) = append_condition {
                                     let new_tip_state = UnflushedChainState::new(new_tip_state);
                                     self.make_valid_tips(&block_hash, new_tip_state.clone());
-                                    self.write_block(orphan.clone());
+                                    self.write_block(
+                                        orphan.clone(),
+                                        Some(new_tip_state.inner_ref()),
+                                    );
 
                                     // Perform checkpoint
                                     {
@@ -997,7 +1806,7 @@ impl<B: Block> Chain<B> {
 
                                         // Checkpoint state if we have reached the quota
                                         if height - last_checkpoint_height
-                                            == B::CHECKPOINT_INTERVAL as u64
+                                            == B::checkpoint_interval()
                                         {
                                             if let None = self.earliest_checkpoint_height {
                                                 self.earliest_checkpoint_height = Some(height);
@@ -1035,6 +1844,7 @@ impl<B: Block> Chain<B> {
                         let mut new_prev_valid_tips = prev_valid_tips.clone();
                         let mut obsolete = HashSet::with_capacity(orphans.len());
                         let mut to_cleanup = Vec::with_capacity(orphans.len());
+                        let mut to_checkpoint = Vec::with_capacity(orphans.len());
                         let mut buf: Vec<(Hash, u64, B::ChainState)> =
                             Vec::with_capacity(orphans.len());
 
@@ -1090,8 +1900,9 @@ impl<B: Block> Chain<B> {
                                     *status = OrphanType::ValidChainTip;
 
                                     obsolete.insert(orphan_parent.clone());
-                                    self.valid_tips_states
-                                        .insert(o.clone(), UnflushedChainState::new(new_tip_state));
+                                    let new_tip_state = UnflushedChainState::new(new_tip_state);
+                                    to_checkpoint.push((orphan.clone(), new_tip_state.clone()));
+                                    self.valid_tips_states.insert(o.clone(), new_tip_state);
 
                                     // Add to valid tips sets
                                     self.valid_tips.insert(o.clone());
@@ -1109,6 +1920,11 @@ impl<B: Block> Chain<B> {
                             self.valid_tips_states.remove(&b);
                         }
 
+                        // Checkpoint the states of the extended tips
+                        for (b, state) in to_checkpoint.iter() {
+                            self.checkpoint_orphan(b, state);
+                        }
+
                         // Cleanup obsolete branches
                         for b in to_cleanup.iter() {
                             self.cleanup_paths(b);
@@ -1144,8 +1960,7 @@ impl<B: Block> Chain<B> {
                                 let state = UnflushedChainState::new(state);
 
                                 // Checkpoint state if we have reached the quota
-                                if height - last_checkpoint_height == B::CHECKPOINT_INTERVAL as u64
-                                {
+                                if height - last_checkpoint_height == B::checkpoint_interval() {
                                     if let None = self.earliest_checkpoint_height {
                                         self.earliest_checkpoint_height = Some(height);
                                     }
@@ -1155,8 +1970,8 @@ impl<B: Block> Chain<B> {
                                 }
 
                                 self.make_valid_tips(&block_hash, state.clone());
+                                self.write_block(to_write.clone(), Some(state.inner_ref()));
                                 self.canonical_tip_state = state;
-                                self.write_block(to_write.clone());
                             }
                         }
 
@@ -1167,8 +1982,11 @@ impl<B: Block> Chain<B> {
                             *status = OrphanType::ValidChainTip;
                             prev_valid_tips.insert(o);
                             self.valid_tips.insert(o.clone());
-                            self.valid_tips_states
-                                .insert(o.clone(), UnflushedChainState::new(state));
+
+                            let orphan = self.orphan_pool.get(&o).unwrap().clone();
+                            let state = UnflushedChainState::new(state);
+                            self.checkpoint_orphan(&orphan, &state);
+                            self.valid_tips_states.insert(o.clone(), state);
                         }
                     }
                 }
@@ -1194,11 +2012,31 @@ impl<B: Block> Chain<B> {
             .get(&candidate_hash)
             .is_none());
 
-        if candidate_tip.height() > self.height + B::SWITCH_OFFSET as u64 {
+        if self.is_heavier(&candidate_tip) {
             self.switch(candidate_tip);
         }
     }
 
+    #[inline]
+    /// Returns `true` if the valid chain with the given tip should
+    /// replace the canonical chain.
+    ///
+    /// Chains are compared by their cumulative work. If both chains
+    /// have the same work, the longest one wins and if they are also
+    /// of the same height, the canonical chain is kept.
+    fn is_heavier(&self, candidate_tip: &B) -> bool {
+        let candidate_hash = candidate_tip.block_hash().unwrap();
+        let candidate_state = self.valid_tips_states.get(&candidate_hash).unwrap();
+        let candidate_work = B::total_work(candidate_state.inner_ref());
+        let canonical_work = B::total_work(self.canonical_tip_state.inner_ref());
+
+        if candidate_work != canonical_work {
+            candidate_work > canonical_work
+        } else {
+            candidate_tip.height() > self.height
+        }
+    }
+
     fn switch(&mut self, candidate_tip: Arc<B>) {
         let candidate_hash = candidate_tip.block_hash().unwrap();
         let mut to_write: VecDeque<Arc<B>> = VecDeque::new();
@@ -1226,8 +2064,18 @@ impl<B: Block> Chain<B> {
         };
 
         // Rewind to horizon
+        self.switching = true;
         self.rewind(&horizon).unwrap();
 
+        // Only the state of the candidate tip is kept so the
+        // candidate blocks are replayed on top of the horizon
+        // to retrieve the outcome of their transactions.
+        let mut replayed_state = if self.tx_index || self.address_index {
+            Some(self.canonical_tip_state.clone().inner())
+        } else {
+            None
+        };
+
         // Set the canonical tip state as the one belonging to the new tip
         let state = self.valid_tips_states.remove(&candidate_hash).unwrap();
         self.canonical_tip_state = state;
@@ -1244,8 +2092,38 @@ impl<B: Block> Chain<B> {
             self.disconnected_tips_mapping.remove(&block_hash);
             self.disconnected_heads_heights.remove(&block_hash);
 
-            self.write_block(block);
+            replayed_state = replayed_state.map(|state| {
+                B::append_condition(block.clone(), state, BranchType::Canonical).unwrap()
+            });
+
+            let checkpoint = self.orphan_checkpoints.remove(&block_hash);
+            self.write_block(block, replayed_state.as_ref());
+
+            // Perform checkpoint
+            {
+                let height = self.height;
+                let last_checkpoint_height = self.last_checkpoint_height.unwrap_or(0);
+
+                // Checkpoint state if we have reached the quota, reusing
+                // the state checkpointed on the candidate chain if any.
+                if height - last_checkpoint_height == B::checkpoint_interval() {
+                    let state = match checkpoint {
+                        Some(state) => state,
+                        None => self.fetch_next_state(last_checkpoint_height, height),
+                    };
+
+                    if let None = self.earliest_checkpoint_height {
+                        self.earliest_checkpoint_height = Some(height);
+                    }
+
+                    self.last_checkpoint_height = Some(height);
+                    self.heights_state_mapping.insert(height, state);
+                }
+            }
         }
+
+        self.switching = false;
+        self.emit(ChainEvent::TipChanged(candidate_tip));
     }
 
     #[inline]
@@ -1440,13 +2318,14 @@ impl<B: Block> Chain<B> {
     /// state transition.
     fn make_valid_tips(&mut self, head: &Hash, head_state: UnflushedChainState<B::ChainState>) {
         if self.disconnected_heads_mapping.remove(head).is_some() {
-            let head_block = self.orphan_pool.get(head).unwrap();
+            let head_block = self.orphan_pool.get(head).unwrap().clone();
             let mut cur_height = head_block.height() + 1;
             let mut previous: HashMap<Hash, UnflushedChainState<B::ChainState>> = HashMap::new();
 
             self.valid_tips.insert(head.clone());
             self.valid_tips_states
                 .insert(head.clone(), head_state.clone());
+            self.checkpoint_orphan(&head_block, &head_state);
 
             previous.insert(head.clone(), head_state);
             self.disconnected_heads_heights.remove(head);
@@ -1507,6 +2386,12 @@ impl<B: Block> Chain<B> {
                         }
                     }
 
+                    // Checkpoint the states of the matched blocks
+                    for (block_hash, state) in new_previous_set.iter() {
+                        let block = self.orphan_pool.get(block_hash).unwrap().clone();
+                        self.checkpoint_orphan(&block, state);
+                    }
+
                     // Cleanup obsolete branches
                     for b in to_cleanup.iter() {
                         self.cleanup_paths(b);
@@ -1651,22 +2536,279 @@ impl<B: Block> Chain<B> {
     }
 
     pub fn query_by_height(&self, height: u64) -> Option<Arc<B>> {
+        let block_hash = self.block_hash_at_height(height)?;
+        self.query(&block_hash)
+    }
+
+    /// Returns the hash of the canonical block at the given height.
+    pub fn block_hash_at_height(&self, height: u64) -> Option<Hash> {
+        if height == 0 {
+            return B::genesis().block_hash();
+        }
+
         let encoded_height = encode_be_u64!(height);
         let key = crypto::hash_slice(&encoded_height);
         let result = self.db.retrieve(&key.0)?;
         let mut block_hash = [0; 32];
         block_hash.copy_from_slice(&result);
-        let block_hash = Hash(block_hash);
 
-        self.query(&block_hash)
+        Some(Hash(block_hash))
     }
 
+    /// Returns the height of the block with the given hash, if it
+    /// is either in the canonical chain or in the orphan pool.
     pub fn block_height(&self, hash: &Hash) -> Option<u64> {
-        unimplemented!();
+        if *hash == B::genesis().block_hash().unwrap() {
+            return Some(0);
+        }
+
+        let block_height_key = Self::compute_height_key(hash);
+
+        if let Some(encoded_height) = self.db.retrieve(&block_height_key.0) {
+            return Some(decode_be_u64!(&encoded_height).unwrap());
+        }
+
+        self.orphan_pool.get(hash).map(|orphan| orphan.height())
+    }
+
+    /// Looks up a transaction of the canonical chain by its hash.
+    ///
+    /// This always returns `None` if the transaction index
+    /// has never been enabled.
+    pub fn query_tx(&self, tx_hash: &Hash) -> Option<IndexedTx> {
+        Self::read_indexed_tx(&self.db, tx_hash)
+    }
+
+    fn read_indexed_tx(db: &PersistentDb, tx_hash: &Hash) -> Option<IndexedTx> {
+        let entry = db.retrieve(&Self::compute_tx_key(tx_hash).0)?;
+        let mut block_hash = [0; 32];
+        block_hash.copy_from_slice(&entry[..32]);
+
+        let height = decode_be_u64!(&entry[32..40]).unwrap();
+        let position = decode_be_u32!(&entry[40..44]).unwrap();
+        let status = if entry[44] == 1 {
+            TxStatus::Success
+        } else {
+            TxStatus::Failed
+        };
+        let tx = Tx::from_bytes(&entry[45..]).unwrap();
+
+        Some(IndexedTx {
+            tx: Arc::new(tx),
+            block_hash: Hash(block_hash),
+            height,
+            position,
+            status,
+        })
+    }
+
+    /// Returns the transactions of the canonical block with
+    /// the given hash, in the order in which they were applied.
+    ///
+    /// This always returns an empty list if the transaction index
+    /// has never been enabled or if the block has been pruned.
+    pub fn block_transactions(&self, block_hash: &Hash) -> Vec<Arc<Tx>> {
+        Self::read_block_transactions(&self.db, block_hash)
+    }
+
+    /// Reads the indexed transactions of the block
+    /// with the given hash from the given ledger.
+    pub(crate) fn read_block_transactions(db: &PersistentDb, block_hash: &Hash) -> Vec<Arc<Tx>> {
+        let block_txs_key = Self::compute_block_txs_key(block_hash);
+        let tx_hashes = match db.retrieve(&block_txs_key.0) {
+            Some(tx_hashes) => tx_hashes,
+            None => return Vec::new(),
+        };
+
+        tx_hashes
+            .chunks(32)
+            .filter_map(|tx_hash| {
+                let mut hash = [0; 32];
+                hash.copy_from_slice(tx_hash);
+
+                Self::read_indexed_tx(db, &Hash(hash)).map(|indexed| indexed.tx)
+            })
+            .collect()
+    }
+
+    /// Builds a proof of the value stored under the given key
+    /// in the state of the canonical block with the given hash.
+    ///
+    /// See `Chain::state_at` for which states can be proven.
+    pub fn state_proof(&self, block_hash: &Hash, key: &[u8]) -> Result<StateProof, &'static str> {
+        if self.canonical_tip.block_hash().as_ref() == Some(block_hash) {
+            return self.canonical_tip_state.inner_ref().state_proof(key);
+        }
+
+        if !self.is_canonical(block_hash) {
+            return Err("The block is not canonical");
+        }
+
+        let height = self
+            .block_height(block_hash)
+            .ok_or("The block is not canonical")?;
+        self.state_at(height)?.state_proof(key)
+    }
+
+    /// Returns the state of the canonical chain at the given height.
+    ///
+    /// The states of the blocks above the root block are rebuilt
+    /// from the closest retained state. The states of older blocks
+    /// can only be read in archival mode, in which the state root
+    /// of each flushed block and the trie nodes of past states are
+    /// kept.
+    pub fn state_at(&self, height: u64) -> Result<B::ChainState, &'static str> {
+        if height > self.height {
+            return Err("The height is above the canonical tip");
+        }
+
+        if height == self.height {
+            return Ok(self.canonical_tip_state.inner_cloned());
+        }
+
+        if let Some(state) = self.heights_state_mapping.get(&height) {
+            return Ok(state.inner_cloned());
+        }
+
+        // The flusher may still be applying blocks to the root state so
+        // the blocks which are not yet flushed are re-applied on top of it.
+        let (root_state, pending) = self
+            .flusher
+            .root_state()
+            .map_err(|_| "The root state could not be flushed")?;
+        let root_state = root_state.inner();
+        let root_height = root_state.height();
+
+        if height < root_height {
+            if !self.archival_mode {
+                return Err("The state at the given height has been pruned");
+            }
+
+            let state_root = self
+                .db
+                .retrieve(&Self::compute_state_root_key(height).0)
+                .ok_or("The state root at the given height is not retained")?;
+
+            if state_root.len() != crypto::SHORT_HASH_BYTES {
+                return Err("Invalid state root stored on disk!");
+            }
+
+            let mut hash = [0; crypto::SHORT_HASH_BYTES];
+            hash.copy_from_slice(&state_root);
+
+            return Ok(root_state.at_root(height, &ShortHash(hash)));
+        }
+
+        // Start from the closest checkpoint below the height
+        let checkpoint_height = self
+            .heights_state_mapping
+            .keys()
+            .filter(|checkpoint_height| **checkpoint_height < height)
+            .max();
+        let mut state = match checkpoint_height {
+            Some(checkpoint_height) => self.heights_state_mapping[checkpoint_height].inner_cloned(),
+            None => root_state,
+        };
+
+        // Re-apply the blocks up to the height
+        for block in pending.iter().chain(self.recent_blocks.iter()) {
+            if block.height() > state.height() && block.height() <= height {
+                state = B::append_condition(block.clone(), state, BranchType::Canonical)
+                    .map_err(|_| "Could not rebuild the state at the given height")?;
+            }
+        }
+
+        if state.height() != height {
+            return Err("The blocks below the given height are not retained");
+        }
+
+        Ok(state)
+    }
+
+    /// Returns the hash of the canonical block which includes the
+    /// transaction with the given hash along with a proof that the
+    /// transaction is included in the block's transaction root.
+    ///
+    /// This always returns `None` if the transaction index has never
+    /// been enabled or if the block has been pruned.
+    pub fn tx_inclusion_proof(&self, tx_hash: &Hash) -> Option<(Hash, TxInclusionProof)> {
+        let indexed = self.query_tx(tx_hash)?;
+        let transactions = self.block_transactions(&indexed.block_hash);
+        let proof = merkle::tx_inclusion_proof(&transactions, indexed.position as usize)?;
+
+        Some((indexed.block_hash, proof))
+    }
+
+    /// Returns the number of transactions in the history
+    /// of the given permanent address.
+    ///
+    /// When not in archival mode, the entries of pruned
+    /// blocks are no longer part of the history.
+    pub fn address_history_len(&self, address: &Address) -> u64 {
+        let (first, count) = Self::history_bounds(&self.db, &address.to_bytes());
+        count - first
+    }
+
+    /// Returns at most `limit` entries of the history of the given
+    /// permanent address, skipping the `offset` most recent ones.
+    /// Entries are ordered starting with the most recent one.
+    ///
+    /// This always returns an empty history if the address
+    /// index has never been enabled.
+    pub fn address_history(&self, address: &Address, offset: u64, limit: u64) -> Vec<AddressTx> {
+        let address_bytes = address.to_bytes();
+        let (first, count) = Self::history_bounds(&self.db, &address_bytes);
+        let mut history = Vec::new();
+
+        if offset >= count - first {
+            return history;
+        }
+
+        let end = count - offset;
+        let start = end.saturating_sub(limit).max(first);
+
+        for i in (start..end).rev() {
+            let entry = self
+                .db
+                .retrieve(&Self::compute_history_key(&address_bytes, i).0)
+                .unwrap();
+
+            let mut tx_hash = [0; 32];
+            let mut block_hash = [0; 32];
+            let mut asset_hash = [0; 8];
+            tx_hash.copy_from_slice(&entry[..32]);
+            block_hash.copy_from_slice(&entry[32..64]);
+            asset_hash.copy_from_slice(&entry[72..80]);
+
+            let height = decode_be_u64!(&entry[64..72]).unwrap();
+            let direction = if entry[80] == 1 {
+                TxDirection::Outgoing
+            } else {
+                TxDirection::Incoming
+            };
+
+            history.push(AddressTx {
+                tx_hash: Hash(tx_hash),
+                block_hash: Hash(block_hash),
+                height,
+                asset_hash: ShortHash(asset_hash),
+                direction,
+            });
+        }
+
+        history
     }
 
     pub fn append_block(&mut self, block: Arc<B>) -> Result<(), ChainErr> {
         //println!("DEBUG PUSHED BLOCK HASH: {:?}", block.block_hash().unwrap());
+        if let Some(err) = self.flusher.error() {
+            error!(
+                "Refusing block, the root state could not be flushed: {:?}",
+                err
+            );
+            return Err(ChainErr::FlushFailed);
+        }
+
         let min_height = if self.height > B::MIN_HEIGHT {
             self.height - B::MIN_HEIGHT
         } else {
@@ -1705,7 +2847,7 @@ impl<B: Block> Chain<B> {
             )?;
 
             // Write block to the chain
-            self.write_block(block);
+            self.write_block(block, Some(&new_tip_state));
 
             // Perform checkpoint
             {
@@ -1713,7 +2855,7 @@ impl<B: Block> Chain<B> {
                 let last_checkpoint_height = self.last_checkpoint_height.unwrap_or(0);
 
                 // Checkpoint state if we have reached the quota
-                if height - last_checkpoint_height == B::CHECKPOINT_INTERVAL as u64 {
+                if height - last_checkpoint_height == B::checkpoint_interval() {
                     if let None = self.earliest_checkpoint_height {
                         self.earliest_checkpoint_height = Some(height);
                     }
@@ -1774,6 +2916,7 @@ impl<B: Block> Chain<B> {
                         BranchType::NonCanonical,
                     )?;
                     let tip_state = UnflushedChainState::new(tip_state);
+                    self.checkpoint_orphan(&block, &tip_state);
 
                     // Insert new state to valid tips mapping
                     self.valid_tips_states
@@ -1791,11 +2934,9 @@ impl<B: Block> Chain<B> {
                         &mut status,
                     );
 
-                    if let OrphanType::ValidChainTip = status {
-                        // Do nothing
-                    } else {
-                        self.attempt_switch(tip);
-                    }
+                    // The new tip may be heavier than the canonical
+                    // tip even if it is not higher.
+                    self.attempt_switch(tip);
 
                     Ok(())
                 }
@@ -1872,6 +3013,7 @@ impl<B: Block> Chain<B> {
 
                                 // Change status of old tip
                                 *parent_status = OrphanType::BelongsToValidChain;
+                                self.checkpoint_orphan(&block, &tip_state);
 
                                 let mut status = OrphanType::ValidChainTip;
                                 let mut tip = block.clone();
@@ -1958,39 +3100,37 @@ impl<B: Block> Chain<B> {
                                 }
                             }
                             OrphanType::BelongsToValidChain => {
-                                // TODO: Maybe cache intermediate states?
                                 let tip_state = {
                                     let mut visited_stack = Vec::new();
-                                    let head = {
-                                        let mut current = parent_hash.clone();
+                                    let mut current = parent_hash.clone();
 
-                                        // Traverse parents until we find a canonical block
-                                        loop {
-                                            let cur = self.orphan_pool.get(&current).unwrap();
-                                            let parent_hash = cur.parent_hash();
+                                    // Traverse parents until we find a checkpointed
+                                    // state or a canonical block.
+                                    let mut state = loop {
+                                        if let Some(state) = self.orphan_checkpoints.get(&current) {
+                                            break state.clone().inner();
+                                        }
 
+                                        if let Some(cur) = self.orphan_pool.get(&current) {
                                             visited_stack.push(cur.clone());
+                                            current = cur.parent_hash();
+                                            continue;
+                                        }
 
-                                            if self.db.retrieve(&parent_hash.0).is_some() {
-                                                current = parent_hash;
-                                                break;
-                                            }
+                                        let head =
+                                            B::from_bytes(&self.db.retrieve(&current.0).unwrap())
+                                                .unwrap();
 
-                                            current = parent_hash;
-                                        }
+                                        // Retrieve state associated with the head's parent
+                                        let state = self.search_fetch_next_state(head.height() - 1);
 
-                                        B::from_bytes(&self.db.retrieve(&current.0).unwrap())
-                                            .unwrap()
+                                        break B::append_condition(
+                                            head.clone(),
+                                            state.inner(),
+                                            BranchType::NonCanonical,
+                                        )?;
                                     };
 
-                                    // Retrieve state associated with the head's parent
-                                    let state = self.search_fetch_next_state(head.height() - 1);
-                                    let mut state = B::append_condition(
-                                        head.clone(),
-                                        state.inner(),
-                                        BranchType::NonCanonical,
-                                    )?;
-
                                     // Compute tip state
                                     while let Some(b) = visited_stack.pop() {
                                         state = B::append_condition(
@@ -2008,6 +3148,8 @@ impl<B: Block> Chain<B> {
                                     UnflushedChainState::new(state)
                                 };
 
+                                self.checkpoint_orphan(&block, &tip_state);
+
                                 let mut status = OrphanType::ValidChainTip;
                                 let mut tip = block.clone();
                                 let mut inverse_height = 0;
@@ -2140,7 +3282,7 @@ impl<B: Block> Chain<B> {
                     target_height
                 } else {
                     let mut current = last_checkpoint_height;
-                    let interval = B::CHECKPOINT_INTERVAL as u64;
+                    let interval = B::checkpoint_interval();
 
                     loop {
                         if current - interval < target_height {
@@ -2170,9 +3312,9 @@ impl<B: Block> Chain<B> {
         assert!(height <= target_height);
 
         let mut state = {
-            if height == 0 {
+            if target_height == 0 {
                 return UnflushedChainState::new(B::genesis_state());
-            } else if height < B::CHECKPOINT_INTERVAL as u64 {
+            } else if height < B::checkpoint_interval() {
                 height = 0;
                 B::genesis_state()
             } else {
@@ -2274,6 +3416,7 @@ impl<B: Block> Chain<B> {
         self.validations_mapping.remove(block_hash);
         self.disconnected_heads_mapping.remove(block_hash);
         self.valid_tips_states.remove(block_hash);
+        self.orphan_checkpoints.remove(block_hash);
         blocks.remove(block_hash);
     }
 }
@@ -2313,15 +3456,18 @@ pub mod tests {
     static NONCE: AtomicUsize = AtomicUsize::new(0);
 
     #[derive(Clone, PartialEq, Debug)]
-    pub struct DummyState(pub u64);
+    pub struct DummyState(pub u64, pub u128);
 
     impl DummyState {
+        /// Returns the state of a chain of the given height
+        /// made of blocks with one unit of work each.
         pub fn new(height: u64) -> DummyState {
-            DummyState(height)
+            DummyState(height, height as u128)
         }
 
-        pub fn increment(&mut self) {
+        pub fn increment(&mut self, work: u64) {
             self.0 += 1;
+            self.1 += work as u128;
         }
 
         pub fn height(&self) -> u64 {
@@ -2337,7 +3483,11 @@ pub mod tests {
 
     impl StateInterface for DummyState {
         fn state_root(&self) -> ShortHash {
-            unimplemented!();
+            crypto::hash_slice(&encode_be_u64!(self.0)).to_short()
+        }
+
+        fn height(&self) -> u64 {
+            self.0
         }
 
         fn get_account_nonce(&self, address: &Address) -> Option<u64> {
@@ -2348,9 +3498,22 @@ pub mod tests {
             unimplemented!();
         }
 
+        fn is_active(&self, feature: Feature, height: u64) -> bool {
+            true
+        }
+
         fn apply_tx(&mut self, tx: Arc<Tx>) {
             unimplemented!();
         }
+
+        fn state_proof(&self, key: &[u8]) -> Result<StateProof, &'static str> {
+            unimplemented!();
+        }
+
+        fn at_root(&self, height: u64, state_root: &ShortHash) -> Self {
+            assert_eq!(*state_root, DummyState::new(height).state_root());
+            DummyState::new(height)
+        }
     }
 
     #[derive(Clone, Debug)]
@@ -2360,10 +3523,20 @@ pub mod tests {
         parent_hash: Hash,
         height: u64,
         ip: SocketAddr,
+        work: u64,
     }
 
     impl DummyBlock {
         pub fn new(parent_hash: Option<Hash>, ip: SocketAddr, height: u64) -> DummyBlock {
+            Self::with_work(parent_hash, ip, height, 1)
+        }
+
+        pub fn with_work(
+            parent_hash: Option<Hash>,
+            ip: SocketAddr,
+            height: u64,
+            work: u64,
+        ) -> DummyBlock {
             let hash =
                 crypto::hash_slice(&format!("block-{}", NONCE.load(Ordering::Relaxed)).as_bytes());
             NONCE.fetch_add(1, Ordering::Relaxed);
@@ -2374,6 +3547,7 @@ pub mod tests {
                 parent_hash,
                 height,
                 ip,
+                work,
             }
         }
     }
@@ -2401,35 +3575,35 @@ pub mod tests {
         /// this number will be rejected.
         const MAX_HEIGHT: u64 = 10;
 
-        /// The number of blocks after which a state checkpoint will be made.
-        ///
-        /// This number **MUST** be less or equal than the minimum accepted height.
-        const CHECKPOINT_INTERVAL: usize = 5;
-
         /// Max checkpoints to keep. This number must be less or equal
-        /// than `(MAX_HEIGHT + MIN_HEIGHT) / CHECKPOINT_INTERVAL`.
+        /// than `(MAX_HEIGHT + MIN_HEIGHT) / checkpoint_interval()`.
         const MAX_CHECKPOINTS: usize = 4;
 
         /// How many blocks to keep behind the canonical
         /// chain tip when pruning is enabled. This number should
-        /// be equal to `CHECKPOINT_INTERVAL * MAX_CHECKPOINTS`.
+        /// be at least `checkpoint_interval() * MAX_CHECKPOINTS`.
         const BLOCKS_TO_KEEP: usize = 100;
 
         type ChainState = DummyState;
 
+        fn checkpoint_interval() -> u64 {
+            5
+        }
+
         fn genesis() -> Arc<Self> {
             let genesis = DummyBlock {
                 hash: Hash::NULL,
                 parent_hash: Hash::NULL,
                 ip: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 44034),
                 height: 0,
+                work: 0,
             };
 
             Arc::new(genesis)
         }
 
         fn genesis_state() -> DummyState {
-            DummyState(0)
+            DummyState::new(0)
         }
 
         fn is_genesis(&self) -> bool {
@@ -2452,6 +3626,10 @@ pub mod tests {
             self.height
         }
 
+        fn total_work(chain_state: &DummyState) -> u128 {
+            chain_state.1
+        }
+
         fn after_write() -> Option<Box<dyn FnMut(Arc<Self>)>> {
             None
         }
@@ -2464,7 +3642,7 @@ pub mod tests {
             let valid = chain_state.height() == block.height() - 1;
 
             if valid {
-                chain_state.increment();
+                chain_state.increment(block.work);
                 Ok(chain_state)
             } else {
                 Err(ChainErr::BadAppendCondition(AppendCondErr::Default))
@@ -2482,49 +3660,269 @@ pub mod tests {
             buf.extend_from_slice(&self.hash.0.to_vec());
             buf.extend_from_slice(&self.parent_hash.0.to_vec());
             buf.extend_from_slice(ip);
+            buf.extend_from_slice(&encode_be_u64!(self.work));
 
             buf
         }
 
-        fn from_bytes(bytes: &[u8]) -> Result<Arc<Self>, &'static str> {
-            let mut buf = bytes.to_vec();
-            let ip_len = buf[0];
-            let _: Vec<u8> = buf.drain(..1).collect();
-            let height_bytes: Vec<u8> = buf.drain(..8).collect();
-            let height = decode_be_u64!(&height_bytes).unwrap();
-            let hash_bytes: Vec<u8> = buf.drain(..32).collect();
-            let parent_hash_bytes: Vec<u8> = buf.drain(..32).collect();
-            let ip: Vec<u8> = buf.drain(..ip_len as usize).collect();
-            let ip = std::str::from_utf8(&ip).unwrap();
-            let ip = SocketAddr::from_str(&ip).unwrap();
-            let mut hash = [0; 32];
-            let mut parent_hash = [0; 32];
+        fn from_bytes(bytes: &[u8]) -> Result<Arc<Self>, &'static str> {
+            let mut buf = bytes.to_vec();
+            let ip_len = buf[0];
+            let _: Vec<u8> = buf.drain(..1).collect();
+            let height_bytes: Vec<u8> = buf.drain(..8).collect();
+            let height = decode_be_u64!(&height_bytes).unwrap();
+            let hash_bytes: Vec<u8> = buf.drain(..32).collect();
+            let parent_hash_bytes: Vec<u8> = buf.drain(..32).collect();
+            let ip: Vec<u8> = buf.drain(..ip_len as usize).collect();
+            let ip = std::str::from_utf8(&ip).unwrap();
+            let ip = SocketAddr::from_str(&ip).unwrap();
+            let work = decode_be_u64!(&buf).unwrap();
+            let mut hash = [0; 32];
+            let mut parent_hash = [0; 32];
+
+            hash.copy_from_slice(&hash_bytes);
+            parent_hash.copy_from_slice(&parent_hash_bytes);
+
+            let hash = Hash(hash);
+            let parent_hash = Hash(parent_hash);
+
+            Ok(Arc::new(DummyBlock {
+                height,
+                hash,
+                ip,
+                parent_hash,
+                work,
+            }))
+        }
+    }
+
+    #[test]
+    fn it_fetches_states_starting_at_the_genesis_height() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+        let mut cur_hash = Hash::NULL;
+
+        for h in 1..6 {
+            let block = Arc::new(DummyBlock::new(
+                Some(cur_hash),
+                crate::random_socket_addr(),
+                h,
+            ));
+            cur_hash = block.block_hash().unwrap();
+            chain.append_block(block).unwrap();
+        }
+
+        // Blocks are replayed on top of the genesis state
+        assert_eq!(
+            chain.fetch_next_state(0, 0).inner(),
+            DummyBlock::genesis_state()
+        );
+        assert_eq!(chain.fetch_next_state(0, 3).inner(), DummyState::new(3));
+        assert_eq!(chain.fetch_next_state(0, 5).inner(), DummyState::new(5));
+    }
+
+    #[test]
+    fn it_flushes_pending_state_to_root_state() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+
+        let mut blocks = vec![];
+        let mut cur_hash = Hash::NULL;
+
+        // Generate 15 blocks
+        for h in 1..16 {
+            let block = DummyBlock::new(Some(cur_hash), crate::random_socket_addr(), h);
+            let block = Arc::new(block);
+
+            blocks.push(block.clone());
+            cur_hash = block.block_hash().unwrap();
+        }
+
+        // Append 10 blocks to the chain
+        for _ in 0..10 {
+            chain.append_block(blocks.remove(0)).unwrap();
+        }
+
+        // The root state should still be the genesis state at this point
+        assert_eq!(chain.root_block, DummyBlock::genesis());
+        assert_eq!(
+            chain.flusher.synced_root_state().inner(),
+            FlushedChainState::new(DummyBlock::genesis_state()).inner()
+        );
+
+        // Now the state should flush with each added block
+        chain.append_block(blocks.remove(0)).unwrap();
+        assert_eq!(
+            chain.flusher.synced_root_state().inner(),
+            FlushedChainState::new(DummyState::new(1)).inner()
+        );
+        chain.append_block(blocks.remove(0)).unwrap();
+        assert_eq!(
+            chain.flusher.synced_root_state().inner(),
+            FlushedChainState::new(DummyState::new(2)).inner()
+        );
+        chain.append_block(blocks.remove(0)).unwrap();
+        assert_eq!(
+            chain.flusher.synced_root_state().inner(),
+            FlushedChainState::new(DummyState::new(3)).inner()
+        );
+        chain.append_block(blocks.remove(0)).unwrap();
+        assert_eq!(
+            chain.flusher.synced_root_state().inner(),
+            FlushedChainState::new(DummyState::new(4)).inner()
+        );
+        chain.append_block(blocks.remove(0)).unwrap();
+        assert_eq!(
+            chain.flusher.synced_root_state().inner(),
+            FlushedChainState::new(DummyState::new(5)).inner()
+        );
+    }
+
+    #[test]
+    fn it_refuses_blocks_once_the_root_state_cannot_be_flushed() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+
+        // A block which does not follow the root state cannot be applied
+        let bad_block = DummyBlock::new(Some(Hash::NULL), crate::random_socket_addr(), 5);
+        chain.flusher.flush(Arc::new(bad_block));
+
+        assert_eq!(
+            chain.flusher.synced_root_state().inner(),
+            DummyBlock::genesis_state()
+        );
+        assert_eq!(
+            chain.flusher.error(),
+            Some(ChainErr::BadAppendCondition(AppendCondErr::Default))
+        );
+        assert!(chain.flusher.root_state().is_err());
+
+        let block = DummyBlock::new(Some(Hash::NULL), crate::random_socket_addr(), 1);
+        assert_eq!(
+            chain.append_block(Arc::new(block)),
+            Err(ChainErr::FlushFailed)
+        );
+    }
+
+    #[test]
+    fn it_returns_the_state_at_past_heights() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+        let mut cur_hash = Hash::NULL;
+
+        for h in 1..16 {
+            let block = Arc::new(DummyBlock::new(
+                Some(cur_hash),
+                crate::random_socket_addr(),
+                h,
+            ));
+
+            chain.append_block(block.clone()).unwrap();
+            cur_hash = block.block_hash().unwrap();
+        }
+
+        assert_eq!(chain.root_block.height(), 5);
+
+        // Flushed, checkpointed, rebuilt and tip states
+        for height in 0..16 {
+            assert_eq!(chain.state_at(height).unwrap(), DummyState::new(height));
+        }
+
+        assert!(chain.state_at(16).is_err());
+    }
+
+    #[test]
+    fn it_does_not_return_pruned_states() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), false);
+        let mut cur_hash = Hash::NULL;
+
+        for h in 1..16 {
+            let block = Arc::new(DummyBlock::new(
+                Some(cur_hash),
+                crate::random_socket_addr(),
+                h,
+            ));
+
+            chain.append_block(block.clone()).unwrap();
+            cur_hash = block.block_hash().unwrap();
+        }
+
+        assert!(chain.state_at(4).is_err());
+        assert_eq!(chain.state_at(5).unwrap(), DummyState::new(5));
+        assert_eq!(chain.state_at(12).unwrap(), DummyState::new(12));
+    }
 
-            hash.copy_from_slice(&hash_bytes);
-            parent_hash.copy_from_slice(&parent_hash_bytes);
+    #[test]
+    fn it_prunes_transaction_data_of_old_blocks() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), false);
+        let block = DummyBlock::new(Some(Hash::NULL), crate::random_socket_addr(), 1);
+        let block = Arc::new(block);
+        let block_hash = block.block_hash().unwrap();
+        let tx_hash = crypto::hash_slice(b"test_tx");
+        let tx_key = Chain::<DummyBlock>::compute_tx_key(&tx_hash);
+        let block_txs_key = Chain::<DummyBlock>::compute_block_txs_key(&block_hash);
 
-            let hash = Hash(hash);
-            let parent_hash = Hash(parent_hash);
+        chain.append_block(block.clone()).unwrap();
+        chain.db.put(&tx_key.0, b"test_tx_data");
+        chain.db.put(&block_txs_key.0, &tx_hash.0);
+        chain.prune_block(1);
 
-            Ok(Arc::new(DummyBlock {
-                height,
-                hash,
-                ip,
-                parent_hash,
-            }))
-        }
+        assert!(chain.db.retrieve(&tx_key.0).is_none());
+        assert!(chain.db.retrieve(&block_txs_key.0).is_none());
+        assert_eq!(chain.reclaimed_space(), 12 + 32);
+
+        // The block header is kept
+        assert_eq!(chain.query(&block_hash), Some(block));
     }
 
     #[test]
-    fn it_flushes_pending_state_to_root_state() {
+    fn it_prunes_address_history_of_old_blocks() {
         let db = test_helpers::init_tempdb();
-        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), false);
+        let address = Address::Normal(account::NormalAddress::random());
+        let block1 = DummyBlock::new(Some(Hash::NULL), crate::random_socket_addr(), 1);
+        let block1 = Arc::new(block1);
+        let block2 = DummyBlock::new(block1.block_hash(), crate::random_socket_addr(), 2);
+        let block2 = Arc::new(block2);
+        let touched = |tx: &[u8]| TouchedAddress {
+            address,
+            tx_hash: crypto::hash_slice(tx),
+            asset_hash: ShortHash::NULL,
+            direction: TxDirection::Incoming,
+        };
 
+        chain.append_block(block1.clone()).unwrap();
+        chain.append_block(block2.clone()).unwrap();
+        chain.index_addresses(&block1, &[touched(b"tx1"), touched(b"tx2")]);
+        chain.index_addresses(&block2, &[touched(b"tx3")]);
+        assert_eq!(chain.address_history_len(&address), 3);
+
+        chain.prune_block(1);
+
+        let history = chain.address_history(&address, 0, 10);
+        assert_eq!(chain.address_history_len(&address), 1);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].tx_hash, crypto::hash_slice(b"tx3"));
+        assert_eq!(history[0].block_hash, block2.block_hash().unwrap());
+        assert!(chain.address_history(&address, 1, 10).is_empty());
+
+        // Rewinding removes the remaining entries along with the bounds
+        chain.rewind(&block1.block_hash().unwrap()).unwrap();
+        assert_eq!(chain.address_history_len(&address), 0);
+        assert!(chain
+            .db
+            .retrieve(&Chain::<DummyBlock>::compute_history_start_key(&address.to_bytes()).0)
+            .is_none());
+    }
+
+    #[test]
+    fn it_starts_from_a_snapshot_and_backfills_history() {
         let mut blocks = vec![];
         let mut cur_hash = Hash::NULL;
 
-        // Generate 15 blocks
-        for h in 1..16 {
+        for h in 1..6 {
             let block = DummyBlock::new(Some(cur_hash), crate::random_socket_addr(), h);
             let block = Arc::new(block);
 
@@ -2532,44 +3930,136 @@ pub mod tests {
             cur_hash = block.block_hash().unwrap();
         }
 
-        // Append 10 blocks to the chain
-        for _ in 0..10 {
-            chain.append_block(blocks.remove(0)).unwrap();
-        }
+        let db = test_helpers::init_tempdb();
+        let mut chain =
+            Chain::<DummyBlock>::from_snapshot(db, blocks[2].clone(), DummyState::new(3), true);
+
+        assert_eq!(chain.canonical_tip(), blocks[2]);
+        assert_eq!(chain.height(), 3);
+
+        // Blocks are validated normally after the snapshot
+        chain.append_block(blocks[3].clone()).unwrap();
+        chain.append_block(blocks[4].clone()).unwrap();
+        assert_eq!(chain.canonical_tip(), blocks[4]);
+        assert_eq!(chain.height(), 5);
 
-        // The root state should still be the genesis state at this point
-        assert_eq!(chain.root_block, DummyBlock::genesis());
         assert_eq!(
-            chain.root_state.clone().inner(),
-            FlushedChainState::new(DummyBlock::genesis_state()).inner()
+            chain.next_backfill(),
+            Some((blocks[1].block_hash().unwrap(), 2))
+        );
+        assert_eq!(
+            chain.backfill_block(blocks[0].clone()),
+            Err(ChainErr::NotBackfillable)
         );
 
-        // Now the state should flush with each added block
-        chain.append_block(blocks.remove(0)).unwrap();
+        chain.backfill_block(blocks[1].clone()).unwrap();
+        chain.backfill_block(blocks[0].clone()).unwrap();
+
+        assert!(chain.next_backfill().is_none());
+        assert_eq!(chain.query_by_height(1), Some(blocks[0].clone()));
         assert_eq!(
-            chain.root_state.clone().inner(),
-            FlushedChainState::new(DummyState::new(1)).inner()
+            chain.block_height(&blocks[1].block_hash().unwrap()),
+            Some(2)
         );
-        chain.append_block(blocks.remove(0)).unwrap();
+    }
+
+    #[test]
+    fn it_recovers_the_root_block_after_an_interrupted_flush() {
+        let dir = tempdir::TempDir::new("purple_test").unwrap();
+        let path = dir.path().join("database");
+        let db_ref = Arc::new(rocksdb::DB::open_default(path.to_str().unwrap()).unwrap());
+        let mut db = PersistentDb::new(db_ref, None);
+        let mut chain = Chain::<DummyBlock>::new(db.clone(), DummyBlock::genesis_state(), true);
+        let mut blocks = vec![];
+        let mut cur_hash = Hash::NULL;
+
+        for h in 1..16 {
+            let block = Arc::new(DummyBlock::new(
+                Some(cur_hash),
+                crate::random_socket_addr(),
+                h,
+            ));
+
+            chain.append_block(block.clone()).unwrap();
+            cur_hash = block.block_hash().unwrap();
+            blocks.push(block);
+        }
+
+        assert_eq!(chain.root_block, blocks[4]);
         assert_eq!(
-            chain.root_state.clone().inner(),
-            FlushedChainState::new(DummyState::new(2)).inner()
+            chain.flusher.synced_root_state().inner(),
+            DummyState::new(5)
         );
-        chain.append_block(blocks.remove(0)).unwrap();
+        drop(chain);
+
+        // Simulate stopping after the root state has
+        // been flushed but before the root block key
+        // has been written.
+        db.put(&ROOT_KEY, &blocks[2].block_hash().unwrap().0);
+        db.flush();
+
+        let chain = Chain::<DummyBlock>::new(db.clone(), DummyState::new(5), true);
+
+        assert_eq!(chain.root_block, blocks[4]);
+        assert_eq!(chain.canonical_tip, blocks[14]);
+        assert_eq!(chain.canonical_tip_state(), DummyState::new(15));
         assert_eq!(
-            chain.root_state.clone().inner(),
-            FlushedChainState::new(DummyState::new(3)).inner()
+            db.retrieve(&ROOT_KEY).unwrap(),
+            blocks[4].block_hash().unwrap().0.to_vec()
         );
-        chain.append_block(blocks.remove(0)).unwrap();
+    }
+
+    #[test]
+    fn it_indexes_block_heights() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+
+        let A = Arc::new(DummyBlock::new(
+            Some(Hash::NULL),
+            crate::random_socket_addr(),
+            1,
+        ));
+        let B = Arc::new(DummyBlock::new(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+        ));
+        let C = Arc::new(DummyBlock::new(
+            Some(B.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            3,
+        ));
+        let unknown = crypto::hash_slice(b"unknown");
+
+        chain.append_block(A.clone()).unwrap();
+        chain.append_block(B.clone()).unwrap();
+        chain.append_block(C.clone()).unwrap();
+
         assert_eq!(
-            chain.root_state.clone().inner(),
-            FlushedChainState::new(DummyState::new(4)).inner()
+            chain.block_height(&DummyBlock::genesis().block_hash().unwrap()),
+            Some(0)
         );
-        chain.append_block(blocks.remove(0)).unwrap();
+        assert_eq!(chain.block_height(&A.block_hash().unwrap()), Some(1));
+        assert_eq!(chain.block_height(&B.block_hash().unwrap()), Some(2));
+        assert_eq!(chain.block_height(&C.block_hash().unwrap()), Some(3));
+        assert_eq!(chain.block_height(&unknown), None);
         assert_eq!(
-            chain.root_state.clone().inner(),
-            FlushedChainState::new(DummyState::new(5)).inner()
+            chain.block_hash_at_height(0),
+            DummyBlock::genesis().block_hash()
         );
+        assert_eq!(chain.block_hash_at_height(2), B.block_hash());
+        assert_eq!(chain.block_hash_at_height(4), None);
+
+        chain.rewind(&A.block_hash().unwrap()).unwrap();
+
+        // The rewound blocks are now orphans
+        let B_height_key = Chain::<DummyBlock>::compute_height_key(&B.block_hash().unwrap());
+        assert!(chain.db.retrieve(&B_height_key.0).is_none());
+        assert_eq!(chain.block_height(&B.block_hash().unwrap()), Some(2));
+        assert_eq!(chain.block_height(&C.block_hash().unwrap()), Some(3));
+        assert_eq!(chain.block_hash_at_height(1), A.block_hash());
+        assert_eq!(chain.block_hash_at_height(2), None);
+        assert_eq!(chain.block_hash_at_height(3), None);
     }
 
     #[test]
@@ -9088,6 +10578,401 @@ pub mod tests {
         );
     }
 
+    #[test]
+    /// GEN -> A -> B
+    ///        |
+    ///         -> B' (2 units of work)
+    fn it_emits_chain_events() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+        let receiver = chain.subscribe();
+
+        let A = Arc::new(DummyBlock::new(
+            Some(Hash::NULL),
+            crate::random_socket_addr(),
+            1,
+        ));
+        let B = Arc::new(DummyBlock::new(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+        ));
+        let B_prime = Arc::new(DummyBlock::with_work(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+            2,
+        ));
+
+        let events = |receiver: &Receiver<ChainEvent<DummyBlock>>| -> Vec<(&'static str, Hash)> {
+            receiver
+                .try_iter()
+                .map(|event| match event {
+                    ChainEvent::BlockConnected(b) => ("connected", b.block_hash().unwrap()),
+                    ChainEvent::BlockDisconnected(b) => ("disconnected", b.block_hash().unwrap()),
+                    ChainEvent::TipChanged(b) => ("tip", b.block_hash().unwrap()),
+                })
+                .collect()
+        };
+
+        chain.append_block(A.clone()).unwrap();
+        chain.append_block(B.clone()).unwrap();
+
+        assert_eq!(
+            events(&receiver),
+            vec![
+                ("connected", A.block_hash().unwrap()),
+                ("tip", A.block_hash().unwrap()),
+                ("connected", B.block_hash().unwrap()),
+                ("tip", B.block_hash().unwrap()),
+            ]
+        );
+
+        // Switching chains only emits the final tip
+        chain.append_block(B_prime.clone()).unwrap();
+
+        assert_eq!(
+            events(&receiver),
+            vec![
+                ("disconnected", B.block_hash().unwrap()),
+                ("connected", B_prime.block_hash().unwrap()),
+                ("tip", B_prime.block_hash().unwrap()),
+            ]
+        );
+
+        chain.rewind(&A.block_hash().unwrap()).unwrap();
+
+        assert_eq!(
+            events(&receiver),
+            vec![
+                ("disconnected", B_prime.block_hash().unwrap()),
+                ("tip", A.block_hash().unwrap()),
+            ]
+        );
+
+        // Dropped receivers are unsubscribed
+        let C = Arc::new(DummyBlock::new(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+        ));
+
+        drop(receiver);
+        chain.append_block(C).unwrap();
+        assert!(chain.subscribers.is_empty());
+    }
+
+    #[test]
+    /// GEN -> A -> B -> C -> D
+    ///        |
+    ///         -> B' (5 units of work)
+    fn it_switches_to_a_shorter_chain_with_more_work() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+
+        let A = Arc::new(DummyBlock::new(
+            Some(Hash::NULL),
+            crate::random_socket_addr(),
+            1,
+        ));
+        let B = Arc::new(DummyBlock::new(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+        ));
+        let C = Arc::new(DummyBlock::new(
+            Some(B.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            3,
+        ));
+        let D = Arc::new(DummyBlock::new(
+            Some(C.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            4,
+        ));
+        let B_prime = Arc::new(DummyBlock::with_work(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+            5,
+        ));
+
+        chain.append_block(A.clone()).unwrap();
+        chain.append_block(B.clone()).unwrap();
+        chain.append_block(C.clone()).unwrap();
+        chain.append_block(D.clone()).unwrap();
+
+        assert_eq!(chain.height(), 4);
+        assert_eq!(chain.canonical_tip, D);
+
+        chain.append_block(B_prime.clone()).unwrap();
+
+        assert_eq!(chain.height(), 2);
+        assert_eq!(chain.canonical_tip, B_prime);
+        assert_eq!(chain.valid_tips, set![D.block_hash().unwrap()]);
+        assert!(!chain.is_canonical(&B.block_hash().unwrap()));
+    }
+
+    #[test]
+    /// GEN -> A -> B (10 units of work)
+    ///        |
+    ///         -> B' -> C' -> D'
+    fn it_does_not_switch_to_a_longer_chain_with_less_work() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+
+        let A = Arc::new(DummyBlock::new(
+            Some(Hash::NULL),
+            crate::random_socket_addr(),
+            1,
+        ));
+        let B = Arc::new(DummyBlock::with_work(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+            10,
+        ));
+        let B_prime = Arc::new(DummyBlock::new(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+        ));
+        let C_prime = Arc::new(DummyBlock::new(
+            Some(B_prime.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            3,
+        ));
+        let D_prime = Arc::new(DummyBlock::new(
+            Some(C_prime.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            4,
+        ));
+
+        chain.append_block(A.clone()).unwrap();
+        chain.append_block(B.clone()).unwrap();
+        chain.append_block(B_prime.clone()).unwrap();
+        chain.append_block(C_prime.clone()).unwrap();
+        chain.append_block(D_prime.clone()).unwrap();
+
+        assert_eq!(chain.height(), 2);
+        assert_eq!(chain.canonical_tip, B);
+        assert_eq!(chain.valid_tips, set![D_prime.block_hash().unwrap()]);
+    }
+
+    #[test]
+    /// GEN -> A -> B (2 units of work)
+    ///        |
+    ///         -> B' -> C'
+    ///        |
+    ///         -> B''
+    fn it_breaks_work_ties_by_height_then_by_arrival() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+
+        let A = Arc::new(DummyBlock::new(
+            Some(Hash::NULL),
+            crate::random_socket_addr(),
+            1,
+        ));
+        let B = Arc::new(DummyBlock::with_work(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+            2,
+        ));
+        let B_prime = Arc::new(DummyBlock::new(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+        ));
+        let C_prime = Arc::new(DummyBlock::new(
+            Some(B_prime.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            3,
+        ));
+        let B_second = Arc::new(DummyBlock::with_work(
+            Some(A.block_hash().unwrap()),
+            crate::random_socket_addr(),
+            2,
+            2,
+        ));
+
+        chain.append_block(A.clone()).unwrap();
+        chain.append_block(B.clone()).unwrap();
+
+        // Same work and height as the canonical tip
+        chain.append_block(B_second.clone()).unwrap();
+        assert_eq!(chain.canonical_tip, B);
+
+        // Same work as the canonical chain but longer
+        chain.append_block(B_prime.clone()).unwrap();
+        chain.append_block(C_prime.clone()).unwrap();
+
+        assert_eq!(chain.height(), 3);
+        assert_eq!(chain.canonical_tip, C_prime);
+        assert_eq!(
+            chain.valid_tips,
+            set![B.block_hash().unwrap(), B_second.block_hash().unwrap()]
+        );
+    }
+
+    #[test]
+    /// GEN -> A1 -> A2 -> ... -> A12
+    ///              |
+    ///               -> B3 -> ... -> B6 -> ... -> B11
+    ///                              |
+    ///                               -> C7
+    fn it_checkpoints_states_of_non_canonical_chains() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+        let mut canonical = vec![DummyBlock::genesis()];
+        let mut fork: Vec<Arc<DummyBlock>> = Vec::new();
+
+        for h in 1..13 {
+            let parent_hash = canonical.last().unwrap().block_hash().unwrap();
+            let block = Arc::new(DummyBlock::new(
+                Some(parent_hash),
+                crate::random_socket_addr(),
+                h,
+            ));
+
+            chain.append_block(block.clone()).unwrap();
+            canonical.push(block);
+        }
+
+        for h in 3..12 {
+            let parent_hash = match fork.last() {
+                Some(block) => block.block_hash().unwrap(),
+                None => canonical[2].block_hash().unwrap(),
+            };
+            let block = Arc::new(DummyBlock::new(
+                Some(parent_hash),
+                crate::random_socket_addr(),
+                h,
+            ));
+
+            chain.append_block(block.clone()).unwrap();
+            fork.push(block);
+        }
+
+        let B5 = fork[2].block_hash().unwrap();
+        let B6 = fork[3].block_hash().unwrap();
+        let B10 = fork[7].block_hash().unwrap();
+
+        assert_eq!(chain.canonical_tip, canonical[12]);
+        assert_eq!(chain.orphan_checkpoints.len(), 2);
+        assert_eq!(
+            chain.orphan_checkpoints.get(&B5).unwrap().inner_ref(),
+            &DummyState::new(5)
+        );
+        assert_eq!(
+            chain.orphan_checkpoints.get(&B10).unwrap().inner_ref(),
+            &DummyState::new(10)
+        );
+
+        // Fork validation must start from the checkpoint
+        chain
+            .orphan_checkpoints
+            .insert(B5, UnflushedChainState::new(DummyState(5, 100)));
+
+        let C7 = Arc::new(DummyBlock::new(Some(B6), crate::random_socket_addr(), 7));
+        chain.append_block(C7.clone()).unwrap();
+
+        assert_eq!(
+            chain
+                .valid_tips_states
+                .get(&C7.block_hash().unwrap())
+                .unwrap()
+                .inner_ref(),
+            &DummyState(7, 102)
+        );
+
+        // Cleaned up branches lose their checkpoints
+        chain.cleanup_paths(&B5);
+        assert!(chain.orphan_checkpoints.is_empty());
+    }
+
+    #[test]
+    /// GEN -> A1 -> A2 -> ... -> A12
+    ///              |
+    ///               -> B3 -> ... -> B11 -> B12 (10 units of work)
+    fn it_reuses_checkpoints_when_switching_chains() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = Chain::<DummyBlock>::new(db, DummyBlock::genesis_state(), true);
+        let mut canonical = vec![DummyBlock::genesis()];
+        let mut fork: Vec<Arc<DummyBlock>> = Vec::new();
+
+        for h in 1..13 {
+            let parent_hash = canonical.last().unwrap().block_hash().unwrap();
+            let block = Arc::new(DummyBlock::new(
+                Some(parent_hash),
+                crate::random_socket_addr(),
+                h,
+            ));
+
+            chain.append_block(block.clone()).unwrap();
+            canonical.push(block);
+        }
+
+        for h in 3..12 {
+            let parent_hash = match fork.last() {
+                Some(block) => block.block_hash().unwrap(),
+                None => canonical[2].block_hash().unwrap(),
+            };
+            let block = Arc::new(DummyBlock::new(
+                Some(parent_hash),
+                crate::random_socket_addr(),
+                h,
+            ));
+
+            chain.append_block(block.clone()).unwrap();
+            fork.push(block);
+        }
+
+        // Mark the checkpoints of the fork so we can check they are reused
+        let B5 = fork[2].block_hash().unwrap();
+        let B10 = fork[7].block_hash().unwrap();
+        chain
+            .orphan_checkpoints
+            .insert(B5, UnflushedChainState::new(DummyState(5, 50)));
+        chain
+            .orphan_checkpoints
+            .insert(B10, UnflushedChainState::new(DummyState(10, 100)));
+
+        let B12 = Arc::new(DummyBlock::with_work(
+            Some(fork[8].block_hash().unwrap()),
+            crate::random_socket_addr(),
+            12,
+            10,
+        ));
+        chain.append_block(B12.clone()).unwrap();
+
+        assert_eq!(chain.canonical_tip, B12);
+        assert_eq!(chain.last_checkpoint_height, Some(10));
+        assert_eq!(
+            chain.heights_state_mapping.get(&5).unwrap().inner_ref(),
+            &DummyState(5, 50)
+        );
+        assert_eq!(
+            chain.heights_state_mapping.get(&10).unwrap().inner_ref(),
+            &DummyState(10, 100)
+        );
+
+        // The old canonical chain keeps its checkpoints
+        let A5 = canonical[5].block_hash().unwrap();
+        let A10 = canonical[10].block_hash().unwrap();
+
+        assert_eq!(chain.orphan_checkpoints.len(), 2);
+        assert_eq!(
+            chain.orphan_checkpoints.get(&A5).unwrap().inner_ref(),
+            &DummyState::new(5)
+        );
+        assert_eq!(
+            chain.orphan_checkpoints.get(&A10).unwrap().inner_ref(),
+            &DummyState::new(10)
+        );
+    }
+
     quickcheck! {
         /// Stress test of chain append.
         ///
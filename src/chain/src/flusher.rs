@@ -0,0 +1,259 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::chain::{Chain, ChainErr, ROOT_KEY};
+use crate::types::*;
+use crossbeam_channel::{Receiver, Sender};
+use parking_lot::RwLock;
+use persistence::PersistentDb;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use triomphe::Arc;
+
+enum FlushJob<B: Block> {
+    /// Apply the block to the root state.
+    Block(Arc<B>),
+
+    /// Notify once all the previous jobs are done.
+    #[cfg(test)]
+    Sync(Sender<()>),
+}
+
+/// State shared between the chain and the flusher thread.
+struct Shared<B: Block> {
+    /// The last flushed root state.
+    root_state: FlushedChainState<B::ChainState>,
+
+    /// Scheduled blocks which are not yet applied to the root state.
+    pending: VecDeque<Arc<B>>,
+
+    /// Set if the scheduled blocks could not be flushed. No
+    /// blocks are flushed afterwards.
+    error: Option<ChainErr>,
+}
+
+/// Background worker which applies finalised blocks to the
+/// root state and commits them to disk.
+///
+/// The root state is always flushed before the root block key is
+/// written. If the node stops in between, the root state on disk is
+/// ahead of the root block, which the chain detects when it is loaded.
+///
+/// When not in archival mode, the state data which dies with each
+/// flushed block is journaled and only pruned once the block is
+/// `B::BLOCKS_TO_KEEP` blocks behind the root block, so that the
+/// states which are still in memory can read it. Otherwise the state
+/// root of each flushed block is retained.
+pub(crate) struct Flusher<B: Block> {
+    /// Sender of flush jobs. This is only `None` when dropped.
+    sender: Option<Sender<FlushJob<B>>>,
+
+    /// The root state along with the blocks scheduled on top of it.
+    shared: Arc<RwLock<Shared<B>>>,
+
+    /// Handle of the flusher thread.
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<B: Block> Flusher<B> {
+    pub fn new(
+        db: PersistentDb,
+        root_state: FlushedChainState<B::ChainState>,
+        archival_mode: bool,
+        reclaimed_space: Arc<AtomicU64>,
+    ) -> Flusher<B> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let shared = Arc::new(RwLock::new(Shared {
+            root_state,
+            pending: VecDeque::new(),
+            error: None,
+        }));
+        let shared_clone = shared.clone();
+        let builder = thread::Builder::new().name("Chain flusher thread".to_owned());
+        let handle = builder
+            .spawn(move || Self::run(db, shared_clone, receiver, archival_mode, reclaimed_space))
+            .expect("Could not spawn chain flusher thread");
+
+        Flusher {
+            sender: Some(sender),
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    /// Schedules the given finalised block to be applied to the root
+    /// state. Blocks must be scheduled in ascending order of height.
+    pub fn flush(&self, block: Arc<B>) {
+        self.shared.write().pending.push_back(block.clone());
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(FlushJob::Block(block))
+            .unwrap();
+    }
+
+    /// Returns the error which stopped the flusher, if any.
+    pub fn error(&self) -> Option<ChainErr> {
+        self.shared.read().error.clone()
+    }
+
+    /// Returns the last flushed root state along with the scheduled
+    /// blocks which are not yet applied to it, in ascending order of
+    /// height. This does not wait for the scheduled blocks.
+    pub fn root_state(&self) -> Result<(FlushedChainState<B::ChainState>, Vec<Arc<B>>), ChainErr> {
+        let shared = self.shared.read();
+
+        if let Some(ref err) = shared.error {
+            return Err(err.clone());
+        }
+
+        Ok((
+            shared.root_state.clone(),
+            shared.pending.iter().cloned().collect(),
+        ))
+    }
+
+    #[cfg(test)]
+    /// Waits for the scheduled blocks to be flushed
+    /// and then returns the root state.
+    pub fn synced_root_state(&self) -> FlushedChainState<B::ChainState> {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(FlushJob::Sync(tx))
+            .unwrap();
+        rx.recv().unwrap();
+        self.shared.read().root_state.clone()
+    }
+
+    fn run(
+        mut db: PersistentDb,
+        shared: Arc<RwLock<Shared<B>>>,
+        receiver: Receiver<FlushJob<B>>,
+        archival_mode: bool,
+        reclaimed_space: Arc<AtomicU64>,
+    ) {
+        let mut state = shared.read().root_state.clone();
+
+        while let Ok(job) = receiver.recv() {
+            let mut blocks = Vec::new();
+
+            #[cfg(test)]
+            let mut waiting = Vec::new();
+
+            // Apply all the scheduled blocks and flush them at once
+            for job in std::iter::once(job).chain(receiver.try_iter()) {
+                match job {
+                    FlushJob::Block(block) => blocks.push(block),
+
+                    #[cfg(test)]
+                    FlushJob::Sync(tx) => waiting.push(tx),
+                }
+            }
+
+            if !blocks.is_empty() && shared.read().error.is_none() {
+                match Self::flush_blocks(&mut db, state.clone(), &blocks, archival_mode) {
+                    Ok(flushed) => {
+                        state = flushed;
+
+                        let root_height = blocks.last().unwrap().height();
+
+                        if !archival_mode && root_height > B::BLOCKS_TO_KEEP as u64 {
+                            let mut inner = state.inner();
+                            let reclaimed = inner.prune(root_height - B::BLOCKS_TO_KEEP as u64);
+
+                            if reclaimed > 0 {
+                                debug!("Pruned state, reclaimed {} bytes", reclaimed);
+                                reclaimed_space.fetch_add(reclaimed, Ordering::Relaxed);
+                            }
+
+                            state = FlushedChainState::new(inner);
+                        }
+
+                        let mut shared = shared.write();
+                        shared.root_state = state.clone();
+                        shared.pending.drain(..blocks.len());
+                    }
+                    Err(err) => {
+                        error!("Could not flush the root state: {:?}", err);
+                        shared.write().error = Some(err);
+                    }
+                }
+            }
+
+            #[cfg(test)]
+            for tx in waiting {
+                let _ = tx.send(());
+            }
+        }
+    }
+
+    /// Applies the given blocks to the root state and commits
+    /// the resulting state along with the new root block.
+    fn flush_blocks(
+        db: &mut PersistentDb,
+        state: FlushedChainState<B::ChainState>,
+        blocks: &[Arc<B>],
+        archival_mode: bool,
+    ) -> Result<FlushedChainState<B::ChainState>, ChainErr> {
+        let mut unflushed = state.modify();
+        let mut state_roots = Vec::new();
+
+        for block in blocks.iter() {
+            let mut new_state =
+                B::append_condition(block.clone(), unflushed.inner(), BranchType::Canonical)?;
+
+            if archival_mode {
+                state_roots.push((block.height(), new_state.state_root()));
+            } else {
+                new_state.journal(block.height());
+            }
+
+            unflushed = UnflushedChainState::new(new_state);
+        }
+
+        let state = unflushed.flush()?;
+
+        for (height, state_root) in state_roots.iter() {
+            let state_root_key = Chain::<B>::compute_state_root_key(*height);
+            db.put(&state_root_key.0, &state_root.0);
+        }
+
+        // Write the root block only after its state is on disk
+        let root_block = blocks.last().unwrap();
+        db.put(&ROOT_KEY, &root_block.block_hash().unwrap().0);
+        db.flush();
+
+        Ok(state)
+    }
+}
+
+impl<B: Block> Drop for Flusher<B> {
+    fn drop(&mut self) {
+        // Disconnect the channel and wait for the pending flushes
+        self.sender.take();
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
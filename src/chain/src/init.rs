@@ -18,6 +18,7 @@
 
 use crate::pow_chain::chain::*;
 use crate::pow_chain::PowChainState;
+use crate::spec::chain_spec;
 use lazy_static::*;
 use parking_lot::RwLock;
 use persistence::PersistentDb;
@@ -41,10 +42,17 @@ thread_local! {
 #[cfg(not(feature = "test"))]
 /// Init chain module. Call this before any other function.
 pub fn init(
-    pow_chain_db: PersistentDb,
-    state_db: PersistentDb,
+    mut pow_chain_db: PersistentDb,
+    mut state_db: PersistentDb,
     archival_mode: bool,
 ) -> PowChainRef {
+    // Keep the trie nodes of past states in archival mode. Otherwise
+    // they are reference counted so the dead ones can be pruned.
+    state_db.set_archival(archival_mode);
+    state_db.set_journaling(!archival_mode);
+
+    check_genesis_hash(&mut pow_chain_db);
+
     let root_state = if state_db.retrieve(PersistentDb::RELOAD_FLAG).is_some() {
         PowChainState::reload(state_db.clone()).unwrap()
     } else {
@@ -74,10 +82,17 @@ pub fn init(
 
 #[cfg(feature = "test")]
 pub fn init(
-    pow_chain_db: PersistentDb,
-    state_db: PersistentDb,
+    mut pow_chain_db: PersistentDb,
+    mut state_db: PersistentDb,
     archival_mode: bool,
 ) -> PowChainRef {
+    // Keep the trie nodes of past states in archival mode. Otherwise
+    // they are reference counted so the dead ones can be pruned.
+    state_db.set_archival(archival_mode);
+    state_db.set_journaling(!archival_mode);
+
+    check_genesis_hash(&mut pow_chain_db);
+
     let root_state = if state_db.retrieve(PersistentDb::RELOAD_FLAG).is_some() {
         PowChainState::reload(state_db.clone()).unwrap()
     } else {
@@ -105,6 +120,28 @@ pub fn init(
     pow_chain
 }
 
+/// The key of the genesis hash of the chain stored in the database
+const GENESIS_KEY: &'static [u8] = b"genesis_hash";
+
+/// Stores the genesis hash of the chain spec in a fresh chain database.
+///
+/// Panics if the database belongs to a chain with a different genesis.
+fn check_genesis_hash(pow_chain_db: &mut PersistentDb) {
+    let genesis_hash = chain_spec().genesis_hash();
+
+    match pow_chain_db.retrieve(GENESIS_KEY) {
+        Some(stored) => {
+            if stored != &genesis_hash.0[..] {
+                panic!("The database belongs to a chain with a different chain spec!");
+            }
+        }
+        None => {
+            pow_chain_db.put(GENESIS_KEY, &genesis_hash.0);
+            pow_chain_db.flush();
+        }
+    }
+}
+
 #[cfg(not(feature = "test"))]
 pub fn state_db_ref() -> PersistentDb {
     let state_db_ref = STATE_DB_REF.read();
@@ -24,10 +24,16 @@ extern crate log;
 #[macro_use]
 extern crate bin_tools;
 
+#[macro_use]
+extern crate serde_derive;
+
 mod block;
 mod chain;
+mod flusher;
 mod init;
 mod pow_chain;
+mod spec;
+mod state_proof;
 pub mod types;
 
 #[cfg(any(test, feature = "test"))]
@@ -39,6 +45,8 @@ pub use crate::init::*;
 pub use crate::pow_chain::block::*;
 pub use crate::pow_chain::chain::*;
 pub use crate::pow_chain::*;
+pub use crate::spec::*;
+pub use crate::state_proof::*;
 
 #[cfg(any(test, feature = "test"))]
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
@@ -21,11 +21,13 @@ use crate::chain::*;
 use crate::pow_chain::checkpoint_block::CheckpointBlock;
 use crate::pow_chain::transaction_block::TransactionBlock;
 use crate::pow_chain::PowChainState;
+use crate::spec::chain_spec;
 use crate::types::*;
 use chrono::prelude::*;
 use crypto::Hash;
 use std::hash::Hash as HashTrait;
 use std::hash::Hasher;
+use transactions::Tx;
 use triomphe::Arc;
 
 pub const GENESIS_HASH_KEY: &[u8] = b"GENESIS";
@@ -58,6 +60,10 @@ impl HashTrait for PowBlock {
 impl Block for PowBlock {
     type ChainState = PowChainState;
 
+    fn checkpoint_interval() -> u64 {
+        chain_spec().consensus.checkpoint_interval
+    }
+
     fn genesis() -> Arc<PowBlock> {
         Arc::new(PowBlock::Genesis)
     }
@@ -73,7 +79,7 @@ impl Block for PowBlock {
 
     fn block_hash(&self) -> Option<Hash> {
         match *self {
-            PowBlock::Genesis => Some(crypto::hash_slice(GENESIS_HASH_KEY)),
+            PowBlock::Genesis => Some(chain_spec().genesis_hash()),
 
             PowBlock::Checkpoint(ref block) => block.block_hash(),
 
@@ -115,6 +121,17 @@ impl Block for PowBlock {
         }
     }
 
+    fn transactions(&self) -> Vec<Arc<Tx>> {
+        match *self {
+            PowBlock::Transaction(ref block) => block.transactions(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn total_work(chain_state: &PowChainState) -> u128 {
+        chain_state.total_work
+    }
+
     fn after_write() -> Option<Box<dyn FnMut(Arc<PowBlock>)>> {
         let fun = |block: Arc<PowBlock>| match *block {
             PowBlock::Genesis => {}
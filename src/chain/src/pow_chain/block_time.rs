@@ -0,0 +1,315 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::chain::AppendCondErr;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chrono::prelude::*;
+use chrono::{Duration, LocalResult};
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+/// The number of recent blocks whose median timestamp the
+/// timestamp of a new block must be strictly greater than.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// How many seconds the timestamp of a block is allowed
+/// to be ahead of the local clock.
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 2 * 60;
+
+/// The targeted number of seconds between two checkpoint blocks.
+pub const TARGET_CHECKPOINT_TIME: i64 = 2 * 60;
+
+#[derive(Clone, Debug, PartialEq)]
+/// Timestamps of the latest blocks of a chain which are
+/// used to validate the timestamps of new blocks and to
+/// adjust the difficulty.
+pub struct BlockTimes {
+    /// The timestamps of the last `MEDIAN_TIME_SPAN` blocks,
+    /// ordered by height.
+    recent: VecDeque<DateTime<Utc>>,
+
+    /// The timestamp of the last checkpoint block.
+    last_checkpoint: Option<DateTime<Utc>>,
+}
+
+impl BlockTimes {
+    pub fn new() -> BlockTimes {
+        BlockTimes {
+            recent: VecDeque::with_capacity(MEDIAN_TIME_SPAN),
+            last_checkpoint: None,
+        }
+    }
+
+    /// Returns the median timestamp of the recent blocks
+    /// or `None` if no block has been tracked yet.
+    pub fn median(&self) -> Option<DateTime<Utc>> {
+        if self.recent.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<&DateTime<Utc>> = self.recent.iter().collect();
+        sorted.sort();
+
+        Some(*sorted[sorted.len() / 2])
+    }
+
+    /// Validates the timestamp of a block that is appended after
+    /// the tracked blocks against the given local time.
+    ///
+    /// The timestamp must be strictly greater than the median of
+    /// the recent blocks and at most `MAX_FUTURE_BLOCK_TIME` seconds
+    /// ahead of the local time. A block which is rejected for being
+    /// in the future may become valid later on.
+    pub fn validate(
+        &self,
+        timestamp: &DateTime<Utc>,
+        now: &DateTime<Utc>,
+    ) -> Result<(), AppendCondErr> {
+        if let Some(median) = self.median() {
+            if *timestamp <= median {
+                return Err(AppendCondErr::TimestampTooOld);
+            }
+        }
+
+        if *timestamp > *now + Duration::seconds(MAX_FUTURE_BLOCK_TIME) {
+            return Err(AppendCondErr::TimestampInFuture);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the timestamp of the last tracked block.
+    pub fn last(&self) -> Option<DateTime<Utc>> {
+        self.recent.back().cloned()
+    }
+
+    /// Tracks the timestamp of a newly appended block.
+    pub fn push(&mut self, timestamp: DateTime<Utc>) {
+        if self.recent.len() == MEDIAN_TIME_SPAN {
+            self.recent.pop_front();
+        }
+
+        self.recent.push_back(timestamp);
+    }
+
+    /// Tracks the timestamp of a newly appended checkpoint block.
+    pub fn push_checkpoint(&mut self, timestamp: DateTime<Utc>) {
+        self.last_checkpoint = Some(timestamp);
+        self.push(timestamp);
+    }
+
+    /// Returns the difficulty of the checkpoint block following the
+    /// one with the given timestamp, which has been mined with the
+    /// given difficulty.
+    ///
+    /// The difficulty is raised when the checkpoint block is mined
+    /// in less than half of `TARGET_CHECKPOINT_TIME` after the previous
+    /// one and lowered when it takes more than twice as long.
+    pub fn next_difficulty(&self, difficulty: u64, timestamp: &DateTime<Utc>) -> u64 {
+        let last_checkpoint = if let Some(last_checkpoint) = self.last_checkpoint {
+            last_checkpoint
+        } else {
+            return difficulty;
+        };
+
+        let elapsed = timestamp
+            .signed_duration_since(last_checkpoint)
+            .num_seconds();
+
+        if elapsed < TARGET_CHECKPOINT_TIME / 2 {
+            // Proofs cannot have a difficulty greater than `u8::MAX`
+            (difficulty + 1).min(u8::max_value() as u64)
+        } else if elapsed > TARGET_CHECKPOINT_TIME * 2 {
+            difficulty.saturating_sub(1)
+        } else {
+            difficulty
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::new();
+
+        // Structure:
+        // 1) Has last checkpoint   - 8bits
+        // 2) Last checkpoint       - 96bits (optional)
+        // 3) Timestamps            - 96bits each
+        //
+        // Each timestamp is encoded as seconds since the
+        // unix epoch followed by the sub-second nanoseconds.
+        if let Some(ref last_checkpoint) = self.last_checkpoint {
+            buf.write_u8(1).unwrap();
+            Self::write_timestamp(&mut buf, last_checkpoint);
+        } else {
+            buf.write_u8(0).unwrap();
+        }
+
+        for timestamp in self.recent.iter() {
+            Self::write_timestamp(&mut buf, timestamp);
+        }
+
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<BlockTimes, &'static str> {
+        let mut rdr = Cursor::new(bytes);
+        let mut block_times = BlockTimes::new();
+
+        let has_last_checkpoint = if let Ok(result) = rdr.read_u8() {
+            result
+        } else {
+            return Err("Bad last checkpoint flag");
+        };
+
+        match has_last_checkpoint {
+            0 => {}
+            1 => block_times.last_checkpoint = Some(Self::read_timestamp(&mut rdr)?),
+            _ => return Err("Bad last checkpoint flag"),
+        }
+
+        while (rdr.position() as usize) < bytes.len() {
+            if block_times.recent.len() == MEDIAN_TIME_SPAN {
+                return Err("Too many timestamps");
+            }
+
+            let timestamp = Self::read_timestamp(&mut rdr)?;
+            block_times.recent.push_back(timestamp);
+        }
+
+        Ok(block_times)
+    }
+
+    fn write_timestamp(buf: &mut Vec<u8>, timestamp: &DateTime<Utc>) {
+        buf.write_i64::<BigEndian>(timestamp.timestamp()).unwrap();
+        buf.write_u32::<BigEndian>(timestamp.timestamp_subsec_nanos())
+            .unwrap();
+    }
+
+    fn read_timestamp(rdr: &mut Cursor<&[u8]>) -> Result<DateTime<Utc>, &'static str> {
+        let secs = rdr.read_i64::<BigEndian>().map_err(|_| "Bad timestamp")?;
+        let nanos = rdr.read_u32::<BigEndian>().map_err(|_| "Bad timestamp")?;
+
+        match Utc.timestamp_opt(secs, nanos) {
+            LocalResult::Single(timestamp) => Ok(timestamp),
+            _ => Err("Bad timestamp"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(1_600_000_000 + secs, 0)
+    }
+
+    #[test]
+    fn it_rejects_timestamps_not_greater_than_the_median() {
+        let mut block_times = BlockTimes::new();
+        let now = at(1000);
+
+        for secs in &[10, 50, 20, 40, 30] {
+            block_times.push(at(*secs));
+        }
+
+        assert_eq!(block_times.median(), Some(at(30)));
+        assert_eq!(
+            block_times.validate(&at(30), &now),
+            Err(AppendCondErr::TimestampTooOld)
+        );
+        assert_eq!(
+            block_times.validate(&at(25), &now),
+            Err(AppendCondErr::TimestampTooOld)
+        );
+
+        // Timestamps lower than the latest one are valid
+        // as long as they are greater than the median.
+        assert_eq!(block_times.validate(&at(31), &now), Ok(()));
+    }
+
+    #[test]
+    fn it_rejects_timestamps_in_the_future() {
+        let block_times = BlockTimes::new();
+        let now = at(1000);
+
+        assert_eq!(
+            block_times.validate(&at(1000 + MAX_FUTURE_BLOCK_TIME), &now),
+            Ok(())
+        );
+        assert_eq!(
+            block_times.validate(&at(1001 + MAX_FUTURE_BLOCK_TIME), &now),
+            Err(AppendCondErr::TimestampInFuture)
+        );
+    }
+
+    #[test]
+    fn it_only_keeps_the_recent_timestamps() {
+        let mut block_times = BlockTimes::new();
+
+        for secs in 0..(MEDIAN_TIME_SPAN as i64 * 2) {
+            block_times.push(at(secs));
+        }
+
+        assert_eq!(block_times.recent.len(), MEDIAN_TIME_SPAN);
+        assert_eq!(
+            block_times.median(),
+            Some(at(MEDIAN_TIME_SPAN as i64 * 3 / 2))
+        );
+    }
+
+    #[test]
+    fn it_adjusts_the_difficulty() {
+        let mut block_times = BlockTimes::new();
+
+        // The first checkpoint keeps the initial difficulty
+        assert_eq!(block_times.next_difficulty(5, &at(0)), 5);
+        block_times.push_checkpoint(at(0));
+
+        assert_eq!(block_times.next_difficulty(5, &at(1)), 6);
+        assert_eq!(
+            block_times.next_difficulty(5, &at(TARGET_CHECKPOINT_TIME)),
+            5
+        );
+        assert_eq!(
+            block_times.next_difficulty(5, &at(TARGET_CHECKPOINT_TIME * 3)),
+            4
+        );
+        assert_eq!(
+            block_times.next_difficulty(0, &at(TARGET_CHECKPOINT_TIME * 3)),
+            0
+        );
+        assert_eq!(block_times.next_difficulty(255, &at(1)), 255);
+    }
+
+    #[test]
+    fn it_serializes_and_deserializes() {
+        let mut block_times = BlockTimes::new();
+        assert_eq!(
+            BlockTimes::from_bytes(&block_times.to_bytes()).unwrap(),
+            block_times
+        );
+
+        block_times.push(Utc::now());
+        block_times.push_checkpoint(Utc::now());
+
+        assert_eq!(
+            BlockTimes::from_bytes(&block_times.to_bytes()).unwrap(),
+            block_times
+        );
+    }
+}
@@ -16,8 +16,11 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
-use crate::chain::{Chain, ChainRef};
+use crate::block::Block;
+use crate::chain::{AppendCondErr, Chain, ChainErr, ChainRef};
 use crate::pow_chain::block::PowBlock;
+use chrono::prelude::*;
+use triomphe::Arc;
 
 pub type PowChainRef = ChainRef<PowBlock>;
 
@@ -44,3 +47,34 @@ pub type PowChainRef = ChainRef<PowBlock>;
 /// their allocated events or until the pool is deemed to be
 /// corrupt.
 pub type PowChain = Chain<PowBlock>;
+
+impl PowChainRef {
+    /// Appends a block received from the network.
+    ///
+    /// Unlike `append_block`, a transaction block which takes over a slot
+    /// of the canonical chain is rejected until the slot has also timed
+    /// out on the local clock. This is a local acceptance policy rather
+    /// than a consensus rule, so the block is accepted if received again
+    /// later, and blocks which are already stored are always replayed.
+    pub fn append_received_block(&self, block: Arc<PowBlock>) -> Result<(), ChainErr> {
+        let mut chain = self.chain.write();
+
+        if let PowBlock::Transaction(ref tx_block) = *block {
+            let extends_tip = chain.canonical_tip().block_hash() == Some(tx_block.parent_hash);
+
+            if extends_tip
+                && chain.canonical_tip_state().is_early_handoff(
+                    tx_block.height,
+                    &tx_block.timestamp,
+                    &Utc::now(),
+                )
+            {
+                return Err(ChainErr::BadAppendCondition(
+                    AppendCondErr::TimestampInFuture,
+                ));
+            }
+        }
+
+        chain.append_block(block)
+    }
+}
@@ -17,17 +17,20 @@
 */
 
 use crate::chain::ChainErr;
-use crate::pow_chain::block::GENESIS_HASH_KEY;
+use crate::pow_chain::block_time::BlockTimes;
+use crate::pow_chain::validator_set::{is_early_handoff, slot_handoffs, ValidatorSet};
+use crate::spec::{chain_spec, ChainSpec, Upgrades};
+use crate::state_proof::StateProof;
 use crate::types::*;
 use account::Address;
-use crypto::{Hash, NodeId, ShortHash};
+use chrono::prelude::*;
+use crypto::{Hash, NodeId, ShortHash, SHORT_HASH_BYTES};
 use hashbrown::{HashMap, HashSet};
-use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use patricia_trie::{Recorder, Trie, TrieDB, TrieDBMut, TrieMut};
 use persistence::{Codec, DbHasher, PersistentDb};
 use std::collections::VecDeque;
 use std::net::SocketAddr;
-use transactions::Genesis;
-use transactions::Tx;
+use transactions::{Feature, Tx};
 use triomphe::Arc;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -53,8 +56,8 @@ pub struct PowChainState {
     /// Which block type is accepted next
     pub(crate) accepts: BlockType,
 
-    /// The current validator's node id. This field
-    /// is `None` if we accept checkpoint blocks.
+    /// The node id of the miner of the last checkpoint block.
+    /// This field is `None` if we accept checkpoint blocks.
     pub(crate) current_validator: Option<NodeId>,
 
     /// Number of transaction blocks left that the
@@ -67,6 +70,33 @@ pub struct PowChainState {
 
     /// Hash of the last checkpoint block.
     pub last_checkpoint: Hash,
+
+    /// Total proof of work of the checkpoint blocks
+    /// included in the chain.
+    pub(crate) total_work: u128,
+
+    /// Timestamps of the latest blocks included in the chain.
+    pub(crate) block_times: BlockTimes,
+
+    /// The miners of the latest checkpoint blocks which
+    /// take turns appending transaction blocks.
+    pub(crate) validators: ValidatorSet,
+
+    /// Activation heights of protocol features. These are
+    /// taken from the chain spec rather than stored on disk
+    /// so that upgrades can be scheduled on a running chain.
+    pub(crate) upgrades: Upgrades,
+
+    /// Execution statuses of the transactions applied by the
+    /// block which produced this state. These are only kept
+    /// in memory since they are consumed when the block is
+    /// written to the transaction index.
+    pub(crate) tx_statuses: Vec<TxStatus>,
+
+    /// Permanent addresses touched by the transactions applied
+    /// by the block which produced this state. Like the statuses,
+    /// these are consumed when the block is written.
+    pub(crate) touched_addresses: Vec<TouchedAddress>,
 }
 
 impl PowChainState {
@@ -76,12 +106,24 @@ impl PowChainState {
     const LAST_CHECKPOINT_KEY: &'static [u8] = b"CHAIN_LAST_CHECKPOINT";
     const CURRENT_VALIDATOR_KEY: &'static [u8] = b"CHAIN_CURRENT_VALIDATOR";
     const TXS_BLOCKS_LEFT_KEY: &'static [u8] = b"CHAIN_REMAINING_BLOCKS";
+    const TOTAL_WORK_KEY: &'static [u8] = b"CHAIN_TOTAL_WORK";
+    const BLOCK_TIMES_KEY: &'static [u8] = b"CHAIN_BLOCK_TIMES";
+    const VALIDATORS_KEY: &'static [u8] = b"CHAIN_VALIDATORS";
+    const PRUNED_HEIGHT_KEY: &'static [u8] = b"CHAIN_PRUNED_HEIGHT";
+    const DEAD_NODES_PREFIX: &'static [u8] = b"CHAIN_DEAD_NODES";
+
+    /// Creates the genesis state of the network described
+    /// by the chain spec that has been set at startup.
+    pub fn genesis(db: PersistentDb) -> Self {
+        Self::genesis_with_spec(db, &chain_spec())
+    }
 
-    pub fn genesis(mut db: PersistentDb) -> Self {
+    /// Creates the genesis state of the network described by the given chain spec.
+    pub fn genesis_with_spec(mut db: PersistentDb, spec: &ChainSpec) -> Self {
         // Apply genesis transactions to the state
         debug!("Applying genesis transaction...");
 
-        let genesis = Genesis::default();
+        let genesis = &spec.genesis;
         let mut state_root = ShortHash::NULL_RLP;
 
         {
@@ -96,13 +138,19 @@ impl PowChainState {
         PowChainState {
             db,
             height: 0,
-            difficulty: 0,
-            edge_bits: miner::MIN_EDGE_BITS,
+            difficulty: spec.consensus.difficulty,
+            edge_bits: spec.consensus.edge_bits,
             accepts: BlockType::Checkpoint,
             current_validator: None,
             txs_blocks_left: None,
             state_root,
-            last_checkpoint: crypto::hash_slice(GENESIS_HASH_KEY),
+            last_checkpoint: spec.genesis_hash(),
+            total_work: 0,
+            block_times: BlockTimes::new(),
+            validators: ValidatorSet::new(),
+            upgrades: spec.upgrades.clone(),
+            tx_statuses: Vec::new(),
+            touched_addresses: Vec::new(),
         }
     }
 
@@ -163,11 +211,52 @@ impl PowChainState {
             None
         };
 
+        // States written before the total work was tracked
+        // do not have it stored.
+        let total_work = if let Some(total_work) = db.retrieve(Self::TOTAL_WORK_KEY) {
+            if total_work.len() != 16 {
+                return Err("Invalid total work stored on disk!");
+            }
+
+            let mut buf = [0; 16];
+            buf.copy_from_slice(&total_work);
+            u128::from_be_bytes(buf)
+        } else {
+            0
+        };
+
+        // States written before block timestamps were
+        // validated do not have them stored.
+        let block_times = if let Some(block_times) = db.retrieve(Self::BLOCK_TIMES_KEY) {
+            BlockTimes::from_bytes(&block_times)
+                .map_err(|_| "Invalid block timestamps stored on disk!")?
+        } else {
+            BlockTimes::new()
+        };
+
+        // States written before validators were rotated
+        // only know about the current validator.
+        let validators = if let Some(validators) = db.retrieve(Self::VALIDATORS_KEY) {
+            ValidatorSet::from_bytes(&validators)
+                .map_err(|_| "Invalid validator set stored on disk!")?
+        } else {
+            let mut validators = ValidatorSet::new();
+
+            if let Some(ref current_validator) = current_validator {
+                validators.push(current_validator.clone());
+            }
+
+            validators
+        };
+
         chain_state.height = height;
         chain_state.difficulty = difficulty;
         chain_state.edge_bits = edge_bits;
         chain_state.last_checkpoint = last_checkpoint;
         chain_state.state_root = state_root;
+        chain_state.total_work = total_work;
+        chain_state.block_times = block_times;
+        chain_state.validators = validators;
 
         if let Some(current_validator) = current_validator {
             chain_state.current_validator = Some(current_validator);
@@ -189,6 +278,71 @@ impl PowChainState {
     pub fn accepts_tx(&self) -> bool {
         self.accepts == BlockType::Transaction
     }
+
+    /// Returns the validator allowed to append the transaction block
+    /// at the given height with the given timestamp, or `None` if we
+    /// accept checkpoint blocks.
+    pub fn slot_validator(&self, height: u64, timestamp: &DateTime<Utc>) -> Option<NodeId> {
+        let current_validator = self.current_validator.as_ref()?;
+
+        if !self.is_active(Feature::ValidatorRotation, height) {
+            return Some(current_validator.clone());
+        }
+
+        let slot = chain_spec()
+            .consensus
+            .allowed_txs_blocks
+            .saturating_sub(self.txs_blocks_left?);
+
+        // Slots can only time out if block timestamps are validated
+        let handoffs = match self.block_times.last() {
+            Some(previous) if self.is_active(Feature::BlockTimestamps, height) => {
+                slot_handoffs(&previous, timestamp)
+            }
+            _ => 0,
+        };
+
+        self.validators.slot_validator(slot, handoffs).cloned()
+    }
+
+    /// Returns the difficulty which the checkpoint block at the given
+    /// height must be mined with. Difficulty is only enforced once block
+    /// timestamps, which it is adjusted from, are validated.
+    pub fn checkpoint_difficulty(&self, height: u64) -> u64 {
+        if self.is_active(Feature::BlockTimestamps, height) {
+            self.difficulty
+        } else {
+            0
+        }
+    }
+
+    /// Returns `true` if the transaction block at the given height with
+    /// the given timestamp takes over a slot which has not timed out yet
+    /// at the given local time.
+    ///
+    /// This depends on the local clock so it is not checked when blocks
+    /// are applied, only when they are received from the network.
+    pub fn is_early_handoff(
+        &self,
+        height: u64,
+        timestamp: &DateTime<Utc>,
+        now: &DateTime<Utc>,
+    ) -> bool {
+        if !self.is_active(Feature::ValidatorRotation, height)
+            || !self.is_active(Feature::BlockTimestamps, height)
+        {
+            return false;
+        }
+
+        match self.block_times.last() {
+            Some(previous) => is_early_handoff(&previous, timestamp, now),
+            None => false,
+        }
+    }
+
+    fn compute_dead_nodes_key(height: u64) -> Vec<u8> {
+        [Self::DEAD_NODES_PREFIX, &encode_be_u64!(height)].concat()
+    }
 }
 
 impl Flushable for PowChainState {
@@ -201,6 +355,12 @@ impl Flushable for PowChainState {
         self.db
             .put(Self::LAST_CHECKPOINT_KEY, &self.last_checkpoint.0);
         self.db.put(PersistentDb::ROOT_HASH_KEY, &self.state_root.0);
+        self.db
+            .put(Self::TOTAL_WORK_KEY, &self.total_work.to_be_bytes());
+        self.db
+            .put(Self::BLOCK_TIMES_KEY, &self.block_times.to_bytes());
+        self.db
+            .put(Self::VALIDATORS_KEY, &self.validators.to_bytes());
 
         if let Some(current_validator) = &self.current_validator {
             assert_eq!(self.accepts, BlockType::Transaction);
@@ -226,6 +386,57 @@ impl Flushable for PowChainState {
 
         Ok(())
     }
+
+    fn journal(&mut self, height: u64) {
+        let dead_nodes = self.db.take_dead_nodes();
+
+        // Nothing journaled below the first journaled height is left to prune
+        if self.db.retrieve(Self::PRUNED_HEIGHT_KEY).is_none() {
+            self.db
+                .put(Self::PRUNED_HEIGHT_KEY, &encode_be_u64!(height - 1));
+        }
+
+        if dead_nodes.is_empty() {
+            return;
+        }
+
+        let journal: Vec<u8> = dead_nodes.iter().flat_map(|node| node.0.to_vec()).collect();
+        self.db.put(&Self::compute_dead_nodes_key(height), &journal);
+    }
+
+    fn prune(&mut self, height: u64) -> u64 {
+        let pruned_height = match self.db.retrieve(Self::PRUNED_HEIGHT_KEY) {
+            Some(pruned_height) => decode_be_u64!(pruned_height).unwrap(),
+            None => return 0,
+        };
+
+        if height <= pruned_height {
+            return 0;
+        }
+
+        let mut reclaimed = 0;
+
+        for journaled_height in pruned_height + 1..=height {
+            let dead_nodes_key = Self::compute_dead_nodes_key(journaled_height);
+
+            if let Some(journal) = self.db.retrieve(&dead_nodes_key) {
+                // Nodes referenced again since they died are kept
+                for node in journal.chunks(SHORT_HASH_BYTES) {
+                    let mut hash = [0; SHORT_HASH_BYTES];
+                    hash.copy_from_slice(node);
+                    reclaimed += self.db.prune_node(&ShortHash(hash));
+                }
+
+                reclaimed += journal.len() as u64;
+                self.db.delete(&dead_nodes_key);
+            }
+        }
+
+        self.db
+            .put(Self::PRUNED_HEIGHT_KEY, &encode_be_u64!(height));
+        self.db.flush();
+        reclaimed
+    }
 }
 
 impl StateInterface for PowChainState {
@@ -233,6 +444,10 @@ impl StateInterface for PowChainState {
         self.state_root.clone()
     }
 
+    fn height(&self) -> u64 {
+        self.height
+    }
+
     fn get_account_nonce(&self, address: &Address) -> Option<u64> {
         let trie = TrieDB::<DbHasher, Codec>::new(&self.db, &self.state_root).unwrap();
 
@@ -248,7 +463,14 @@ impl StateInterface for PowChainState {
 
     fn validate_tx(&self, tx: Arc<Tx>) -> bool {
         let trie = TrieDB::<DbHasher, Codec>::new(&self.db, &self.state_root).unwrap();
-        tx.validate(&trie)
+
+        // Transactions are validated for inclusion in the next block
+        let height = self.height + 1;
+        tx.validate(&trie, |feature| self.is_active(feature, height))
+    }
+
+    fn is_active(&self, feature: Feature, height: u64) -> bool {
+        self.upgrades.is_active(feature, height)
     }
 
     fn apply_tx(&mut self, tx: Arc<Tx>) {
@@ -257,11 +479,50 @@ impl StateInterface for PowChainState {
                 .unwrap();
         tx.apply(&mut trie);
     }
+
+    fn tx_statuses(&self) -> &[TxStatus] {
+        &self.tx_statuses
+    }
+
+    fn touched_addresses(&self) -> &[TouchedAddress] {
+        &self.touched_addresses
+    }
+
+    fn state_proof(&self, key: &[u8]) -> Result<StateProof, &'static str> {
+        let trie = TrieDB::<DbHasher, Codec>::new(&self.db, &self.state_root)
+            .map_err(|_| "Could not find the state root")?;
+        let mut recorder = Recorder::new();
+
+        trie.get_with(key, &mut recorder)
+            .map_err(|_| "Could not look up the key in the state")?;
+
+        let nodes = recorder
+            .drain()
+            .into_iter()
+            .map(|record| record.data)
+            .collect();
+
+        Ok(StateProof {
+            key: key.to_vec(),
+            nodes,
+        })
+    }
+
+    fn at_root(&self, height: u64, state_root: &ShortHash) -> Self {
+        let mut state = self.clone();
+
+        // Only the trie is read so the consensus
+        // fields are left as they are.
+        state.height = height;
+        state.state_root = state_root.clone();
+        state
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::prelude::*;
     use crypto::Identity;
 
     #[test]
@@ -273,6 +534,7 @@ mod tests {
         chain_state.height = 10;
         chain_state.difficulty = 6;
         chain_state.edge_bits = 29;
+        chain_state.total_work = 1024;
         chain_state.state_root = crypto::hash_slice(b"random_state_root").to_short();
         chain_state.last_checkpoint = crypto::hash_slice(b"random_checkpoint");
 
@@ -295,8 +557,11 @@ mod tests {
         chain_state.height = 10;
         chain_state.difficulty = 6;
         chain_state.edge_bits = 29;
+        chain_state.total_work = 1024;
         chain_state.state_root = crypto::hash_slice(b"random_state_root").to_short();
         chain_state.last_checkpoint = crypto::hash_slice(b"random_checkpoint");
+        chain_state.block_times.push_checkpoint(Utc::now());
+        chain_state.block_times.push(Utc::now());
         chain_state.current_validator = Some(node_id);
         chain_state.txs_blocks_left = Some(7);
         chain_state.accepts = BlockType::Transaction;
@@ -308,4 +573,97 @@ mod tests {
         let reloaded_state = PowChainState::reload(chain_state.db.clone()).unwrap();
         assert_eq!(reloaded_state, chain_state);
     }
+
+    #[test]
+    fn it_only_enforces_difficulty_once_timestamps_are_validated() {
+        let mut spec = ChainSpec::default();
+        spec.upgrades.schedule(Feature::BlockTimestamps, 3);
+
+        let mut chain_state = PowChainState::genesis_with_spec(test_helpers::init_tempdb(), &spec);
+        chain_state.difficulty = 6;
+
+        assert_eq!(chain_state.checkpoint_difficulty(2), 0);
+        assert_eq!(chain_state.checkpoint_difficulty(3), 6);
+    }
+
+    #[test]
+    fn it_prunes_nodes_journaled_as_dead() {
+        let mut db = test_helpers::init_tempdb();
+        db.set_journaling(true);
+
+        let mut chain_state = PowChainState::genesis(db);
+        let sender = Address::Normal(transactions::TestAccount::A.to_perm_address());
+        let send = |nonce| {
+            Arc::new(transactions::send_coins(
+                transactions::TestAccount::A,
+                transactions::TestAccount::B,
+                100,
+                10,
+                nonce,
+            ))
+        };
+
+        for height in 1..=2 {
+            chain_state.apply_tx(send(height));
+            chain_state.height = height;
+            chain_state.journal(height);
+            chain_state.flush().unwrap();
+        }
+
+        let past_root = chain_state.state_root();
+
+        chain_state.apply_tx(send(3));
+        chain_state.height = 3;
+        chain_state.journal(3);
+        chain_state.flush().unwrap();
+
+        // The state at the kept height is left intact
+        assert!(chain_state.prune(2) > 0);
+        assert_eq!(
+            chain_state
+                .at_root(2, &past_root)
+                .get_account_nonce(&sender),
+            Some(2)
+        );
+
+        assert!(chain_state.prune(3) > 0);
+        assert!(chain_state.db.retrieve(&past_root.0).is_none());
+        assert_eq!(chain_state.get_account_nonce(&sender), Some(3));
+        assert_eq!(chain_state.prune(3), 0);
+    }
+
+    #[test]
+    fn it_reads_past_states_in_archival_mode() {
+        let mut db = test_helpers::init_tempdb();
+        db.set_archival(true);
+
+        let mut chain_state = PowChainState::genesis(db);
+        let sender = Address::Normal(transactions::TestAccount::A.to_perm_address());
+        let send = |nonce| {
+            Arc::new(transactions::send_coins(
+                transactions::TestAccount::A,
+                transactions::TestAccount::B,
+                100,
+                10,
+                nonce,
+            ))
+        };
+
+        chain_state.apply_tx(send(1));
+        chain_state.height = 1;
+        chain_state.flush().unwrap();
+
+        let past_root = chain_state.state_root();
+        let past_nonce = chain_state.get_account_nonce(&sender);
+
+        chain_state.apply_tx(send(2));
+        chain_state.height = 2;
+        chain_state.flush().unwrap();
+
+        let past_state = chain_state.at_root(1, &past_root);
+
+        assert_eq!(past_state.height(), 1);
+        assert_eq!(past_state.get_account_nonce(&sender), past_nonce);
+        assert_ne!(chain_state.get_account_nonce(&sender), past_nonce);
+    }
 }
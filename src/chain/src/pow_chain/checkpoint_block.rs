@@ -20,6 +20,7 @@ use crate::block::Block;
 use crate::chain::*;
 use crate::pow_chain::chain_state::BlockType;
 use crate::pow_chain::PowChainState;
+use crate::spec::chain_spec;
 use crate::types::*;
 use account::NormalAddress;
 use bin_tools::*;
@@ -38,11 +39,13 @@ use std::io::Cursor;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::str;
 use std::str::FromStr;
+use transactions::Feature;
 use triomphe::Arc;
 
 #[cfg(not(test))]
 /// How many transaction blocks the validator is allowed to create
-/// for a successfully appended checkpoint block.
+/// for a successfully appended checkpoint block, unless specified
+/// otherwise by the chain spec.
 pub const ALLOWED_TXS_BLOCKS: u32 = 25;
 
 #[cfg(test)]
@@ -74,7 +77,7 @@ pub struct CheckpointBlock {
     hash: Option<Hash>,
 
     /// The timestamp of the block.
-    timestamp: DateTime<Utc>,
+    pub(crate) timestamp: DateTime<Utc>,
 }
 
 impl PartialEq for CheckpointBlock {
@@ -98,6 +101,10 @@ impl HashTrait for CheckpointBlock {
 impl Block for CheckpointBlock {
     type ChainState = PowChainState;
 
+    fn checkpoint_interval() -> u64 {
+        unimplemented!();
+    }
+
     fn genesis() -> Arc<CheckpointBlock> {
         unimplemented!();
     }
@@ -126,6 +133,10 @@ impl Block for CheckpointBlock {
         self.timestamp.clone()
     }
 
+    fn total_work(chain_state: &PowChainState) -> u128 {
+        chain_state.total_work
+    }
+
     fn after_write() -> Option<Box<dyn FnMut(Arc<CheckpointBlock>)>> {
         let fun = |block| {};
         Some(Box::new(fun))
@@ -158,9 +169,21 @@ impl Block for CheckpointBlock {
         assert!(chain_state.current_validator.is_none());
         assert!(chain_state.txs_blocks_left.is_none());
 
-        // TODO: Validate difficulty. Issue #118
+        let timestamps_active = chain_state.is_active(Feature::BlockTimestamps, block.height());
+
+        if timestamps_active {
+            chain_state
+                .block_times
+                .validate(&block.timestamp, &Utc::now())
+                .map_err(ChainErr::BadAppendCondition)?;
+        }
+
+        #[cfg(test)]
         let difficulty = 0;
 
+        #[cfg(not(test))]
+        let difficulty = chain_state.checkpoint_difficulty(block.height());
+
         #[cfg(test)]
         let edge_bits = 0;
 
@@ -168,22 +191,30 @@ impl Block for CheckpointBlock {
         let edge_bits = chain_state.edge_bits;
 
         // Validate proof of work
-        if let Err(_) = miner::verify(
-            &chain_state.last_checkpoint.0,
-            block.proof.nonce,
-            difficulty,
-            edge_bits,
-            &block.proof,
-        ) {
+        if !block.verify_proof(&chain_state.last_checkpoint, difficulty, edge_bits) {
             return Err(ChainErr::BadAppendCondition(AppendCondErr::BadProof));
         }
 
         // Commit
+        chain_state.tx_statuses = Vec::new();
+        chain_state.touched_addresses = Vec::new();
         chain_state.current_validator = Some(block.miner_id.clone());
-        chain_state.txs_blocks_left = Some(ALLOWED_TXS_BLOCKS);
+        chain_state.validators.push(block.miner_id.clone());
+        chain_state.txs_blocks_left = Some(chain_spec().consensus.allowed_txs_blocks);
         chain_state.accepts = BlockType::Transaction;
         chain_state.height = block.height();
         chain_state.last_checkpoint = block_hash;
+        chain_state.total_work += Self::work(difficulty, edge_bits);
+
+        // Difficulty is only adjusted once the timestamps
+        // it is computed from are validated.
+        if timestamps_active {
+            chain_state.difficulty = chain_state
+                .block_times
+                .next_difficulty(chain_state.difficulty, &block.timestamp);
+        }
+
+        chain_state.block_times.push_checkpoint(block.timestamp);
 
         Ok(chain_state)
     }
@@ -324,6 +355,13 @@ impl Block for CheckpointBlock {
 impl CheckpointBlock {
     pub const BLOCK_TYPE: u8 = 1;
 
+    /// Returns the work represented by a proof found with the given
+    /// difficulty and edge bits. Each edge bit doubles the size of
+    /// the graph that is searched for a proof.
+    pub fn work(difficulty: u64, edge_bits: u8) -> u128 {
+        (difficulty as u128 + 1) << edge_bits
+    }
+
     pub fn new(
         parent_hash: Hash,
         collector_address: NormalAddress,
@@ -344,6 +382,29 @@ impl CheckpointBlock {
         }
     }
 
+    /// Returns the `NodeId` of the miner of the block.
+    pub fn miner_id(&self) -> &NodeId {
+        &self.miner_id
+    }
+
+    /// Verifies the proof of work of the block against
+    /// the hash of the previous checkpoint block.
+    pub fn verify_proof(&self, last_checkpoint: &Hash, difficulty: u64, edge_bits: u8) -> bool {
+        // Proofs cannot have a difficulty greater than `u8::MAX`
+        if difficulty > u8::max_value() as u64 {
+            return false;
+        }
+
+        miner::verify(
+            &last_checkpoint.0,
+            self.proof.nonce,
+            difficulty as u8,
+            edge_bits,
+            &self.proof,
+        )
+        .is_ok()
+    }
+
     pub fn sign_miner(&mut self, sk: &Sk) {
         let message = self.compute_message();
         let sig = crypto::sign(&message, sk);
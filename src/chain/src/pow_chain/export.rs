@@ -0,0 +1,374 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Export and import of the canonical pow chain.
+//!
+//! An export file starts with `EXPORT_MAGIC` followed by the version
+//! of the format. After the header, there is one record for each
+//! exported block, in ascending order of height. A record has the
+//! following format:
+//! `<record-len><checksum><block-len><block><txs-count>[<tx-len><tx>]*`
+//!
+//! All lengths and counts are big-endian `u32`s and the checksum is
+//! the `ShortHash` of everything following it in the record.
+
+use crate::block::Block;
+use crate::pow_chain::block::PowBlock;
+use crate::pow_chain::chain::PowChainRef;
+use crate::pow_chain::merkle::compute_tx_root;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use constants::MAX_TX_SET_SIZE;
+use crypto::{ShortHash, SHORT_HASH_BYTES};
+use parking_lot::RwLock;
+use std::io::{Cursor, ErrorKind, Read, Write};
+use transactions::Tx;
+use triomphe::Arc;
+
+/// Bytes identifying an export file.
+pub const EXPORT_MAGIC: &[u8] = b"PURPLECHAIN";
+
+/// The current version of the export format.
+pub const EXPORT_VERSION: u8 = 1;
+
+/// The maximum allowed size of a record.
+const MAX_RECORD_SIZE: usize = 2 * MAX_TX_SET_SIZE;
+
+/// Writes the canonical blocks starting at height `from` to the
+/// given writer along with their transaction sets. `progress` is
+/// called with the height of each exported block.
+///
+/// Transaction sets are read from the transaction index, so the
+/// chain must be in archival mode and must have been synced with
+/// the transaction index enabled.
+///
+/// Returns the number of exported blocks.
+pub fn export_chain<W: Write>(
+    chain: &PowChainRef,
+    writer: &mut W,
+    from: u64,
+    mut progress: impl FnMut(u64),
+) -> Result<u64, &'static str> {
+    if !chain.archival_mode() {
+        return Err("Cannot export a pruned chain");
+    }
+
+    if !chain.tx_index() {
+        return Err("Cannot export a chain without the transaction index");
+    }
+
+    let tip_height = chain.canonical_tip().height();
+    let mut exported = 0;
+
+    writer
+        .write_all(EXPORT_MAGIC)
+        .map_err(|_| "Could not write header")?;
+    writer
+        .write_u8(EXPORT_VERSION)
+        .map_err(|_| "Could not write header")?;
+
+    // The genesis block is never exported
+    for height in from.max(1)..=tip_height {
+        let block = chain
+            .query_by_height(height)
+            .ok_or("Could not find canonical block")?;
+        let transactions = chain.block_transactions(&block.block_hash().unwrap());
+
+        // The index misses the transactions of blocks written
+        // before it has been enabled.
+        if let PowBlock::Transaction(ref tx_block) = *block {
+            if tx_block.tx_root != Some(compute_tx_root(&transactions)) {
+                return Err("The indexed transactions do not match the transaction root");
+            }
+        }
+
+        let block_bytes = block.to_bytes();
+        let mut record = Vec::new();
+
+        record
+            .write_u32::<BigEndian>(block_bytes.len() as u32)
+            .unwrap();
+        record.extend_from_slice(&block_bytes);
+        record
+            .write_u32::<BigEndian>(transactions.len() as u32)
+            .unwrap();
+
+        for tx in transactions.iter() {
+            let tx_bytes = tx.to_bytes();
+
+            record
+                .write_u32::<BigEndian>(tx_bytes.len() as u32)
+                .unwrap();
+            record.extend_from_slice(&tx_bytes);
+        }
+
+        let checksum = crypto::hash_slice(&record).to_short();
+
+        writer
+            .write_u32::<BigEndian>(record.len() as u32)
+            .map_err(|_| "Could not write record")?;
+        writer
+            .write_all(&checksum.0)
+            .map_err(|_| "Could not write record")?;
+        writer
+            .write_all(&record)
+            .map_err(|_| "Could not write record")?;
+
+        exported += 1;
+        progress(height);
+    }
+
+    writer.flush().map_err(|_| "Could not write record")?;
+    Ok(exported)
+}
+
+/// Reads the blocks written by `export_chain` and appends each of
+/// them to the chain, which fully validates them. `progress` is
+/// called with the height of each read block.
+///
+/// Blocks which are already in the canonical chain are skipped so
+/// an interrupted import can be resumed by importing the same file.
+///
+/// Returns the number of imported blocks.
+pub fn import_chain<R: Read>(
+    chain: &PowChainRef,
+    reader: &mut R,
+    mut progress: impl FnMut(u64),
+) -> Result<u64, &'static str> {
+    let mut header = vec![0; EXPORT_MAGIC.len() + 1];
+    let mut imported = 0;
+
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| "Could not read header")?;
+
+    if &header[..EXPORT_MAGIC.len()] != EXPORT_MAGIC {
+        return Err("Invalid header");
+    }
+
+    if header[EXPORT_MAGIC.len()] != EXPORT_VERSION {
+        return Err("Unsupported export version");
+    }
+
+    while let Some(record) = read_record(reader)? {
+        let block = decode_record(&record)?;
+        let block_hash = block.block_hash().unwrap();
+        let height = block.height();
+
+        if !chain.is_canonical(&block_hash) {
+            if let Err(err) = chain.append_block(block) {
+                debug!("Could not import block at height {}: {:?}", height, err);
+                return Err("Invalid block");
+            }
+
+            // The parent of the block is not in the chain
+            if !chain.is_canonical(&block_hash) {
+                return Err("Block is not part of the canonical chain");
+            }
+
+            imported += 1;
+        }
+
+        progress(height);
+    }
+
+    Ok(imported)
+}
+
+/// Reads the next record and verifies its checksum. Returns
+/// `None` if the reader is at the end of the file.
+fn read_record<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>, &'static str> {
+    let mut len_buf = [0; 4];
+    let mut read = 0;
+
+    // Read the length of the record, stopping on a clean end of file
+    while read < len_buf.len() {
+        match reader.read(&mut len_buf[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err("Truncated record"),
+            Ok(n) => read += n,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return Err("Could not read record"),
+        }
+    }
+
+    let record_len = decode_be_u32!(&len_buf).unwrap() as usize;
+
+    if record_len > MAX_RECORD_SIZE {
+        return Err("Record is too large");
+    }
+
+    let mut checksum = [0; SHORT_HASH_BYTES];
+    let mut record = vec![0; record_len];
+
+    reader
+        .read_exact(&mut checksum)
+        .map_err(|_| "Truncated record")?;
+    reader
+        .read_exact(&mut record)
+        .map_err(|_| "Truncated record")?;
+
+    if crypto::hash_slice(&record).to_short() != ShortHash(checksum) {
+        return Err("Invalid checksum");
+    }
+
+    Ok(Some(record))
+}
+
+/// Decodes a record into a block along with its transaction set.
+fn decode_record(record: &[u8]) -> Result<Arc<PowBlock>, &'static str> {
+    let mut rdr = Cursor::new(record);
+    let block_len = rdr
+        .read_u32::<BigEndian>()
+        .map_err(|_| "Bad block length")? as usize;
+    let block_start = rdr.position() as usize;
+
+    if record.len() < block_start + block_len {
+        return Err("Bad block length");
+    }
+
+    let block = PowBlock::from_bytes(&record[block_start..(block_start + block_len)])?;
+    rdr.set_position((block_start + block_len) as u64);
+
+    let txs_count = rdr
+        .read_u32::<BigEndian>()
+        .map_err(|_| "Bad transactions count")?;
+    let mut transactions = Vec::new();
+
+    for _ in 0..txs_count {
+        let tx_len = rdr
+            .read_u32::<BigEndian>()
+            .map_err(|_| "Bad transaction length")? as usize;
+        let tx_start = rdr.position() as usize;
+
+        if record.len() < tx_start + tx_len {
+            return Err("Bad transaction length");
+        }
+
+        let tx = Tx::from_bytes(&record[tx_start..(tx_start + tx_len)])?;
+        transactions.push(Arc::new(tx));
+        rdr.set_position((tx_start + tx_len) as u64);
+    }
+
+    if rdr.position() as usize != record.len() {
+        return Err("Invalid record length");
+    }
+
+    if let PowBlock::Transaction(ref tx_block) = *block {
+        let mut tx_block = (**tx_block).clone();
+        tx_block.transactions = Some(Arc::new(RwLock::new(transactions)));
+
+        return Ok(Arc::new(PowBlock::Transaction(Arc::new(tx_block))));
+    }
+
+    if !transactions.is_empty() {
+        return Err("Only transaction blocks can have transactions");
+    }
+
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::init_chain_with_blocks;
+
+    #[test]
+    fn it_exports_and_imports_the_chain() {
+        let (chain, blocks) = init_chain_with_blocks();
+        let target = crate::test_helpers::init_test_chains();
+        let mut exported_heights = Vec::new();
+        let mut imported_heights = Vec::new();
+        let mut buf = Vec::new();
+
+        let exported = export_chain(&chain, &mut buf, 0, |h| exported_heights.push(h)).unwrap();
+        assert_eq!(exported, 2);
+        assert_eq!(exported_heights, vec![1, 2]);
+
+        let imported =
+            import_chain(&target, &mut buf.as_slice(), |h| imported_heights.push(h)).unwrap();
+        assert_eq!(imported, 2);
+        assert_eq!(imported_heights, vec![1, 2]);
+        assert_eq!(target.canonical_tip(), blocks[1]);
+        assert_eq!(
+            target.canonical_tip_state().state_root(),
+            chain.canonical_tip_state().state_root()
+        );
+    }
+
+    #[test]
+    fn it_does_not_export_blocks_with_missing_transactions() {
+        let (_, blocks) = init_chain_with_blocks();
+        let chain = crate::test_helpers::init_test_chains();
+        let mut buf = Vec::new();
+
+        // The transactions are not indexed before the index is enabled
+        chain.append_block(blocks[0].clone()).unwrap();
+        chain.append_block(blocks[1].clone()).unwrap();
+        chain.chain.write().set_tx_index(true);
+
+        assert_eq!(
+            export_chain(&chain, &mut buf, 0, |_| {}),
+            Err("The indexed transactions do not match the transaction root")
+        );
+    }
+
+    #[test]
+    fn it_resumes_interrupted_imports() {
+        let (chain, blocks) = init_chain_with_blocks();
+        let target = crate::test_helpers::init_test_chains();
+        let mut buf = Vec::new();
+
+        export_chain(&chain, &mut buf, 0, |_| {}).unwrap();
+        target.append_block(blocks[0].clone()).unwrap();
+
+        let imported = import_chain(&target, &mut buf.as_slice(), |_| {}).unwrap();
+        assert_eq!(imported, 1);
+        assert_eq!(target.canonical_tip(), blocks[1]);
+
+        // Importing the same file again is a no-op
+        let imported = import_chain(&target, &mut buf.as_slice(), |_| {}).unwrap();
+        assert_eq!(imported, 0);
+    }
+
+    #[test]
+    fn it_rejects_corrupted_records() {
+        let (chain, _) = init_chain_with_blocks();
+        let target = crate::test_helpers::init_test_chains();
+        let mut buf = Vec::new();
+
+        export_chain(&chain, &mut buf, 0, |_| {}).unwrap();
+
+        let last = buf.len() - 1;
+        buf[last] ^= 1;
+
+        assert_eq!(
+            import_chain(&target, &mut buf.as_slice(), |_| {}),
+            Err("Invalid checksum")
+        );
+
+        // The blocks before the corrupted record are imported
+        assert_eq!(target.canonical_tip().height(), 1);
+
+        buf.truncate(last);
+
+        assert_eq!(
+            import_chain(&target, &mut buf.as_slice(), |_| {}),
+            Err("Truncated record")
+        );
+    }
+}
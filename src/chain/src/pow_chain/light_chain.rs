@@ -0,0 +1,894 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+//! Header chain followed by nodes running in light client mode.
+//!
+//! A light client only stores block headers. Checkpoint headers are
+//! validated by their proof of work and miner signature, transaction
+//! block headers by their miner signature and by the validator rights
+//! granted by the latest checkpoints. Transactions are never applied so
+//! no state is stored. Instead, balances and transactions are queried
+//! from full peers which answer with state and inclusion proofs that
+//! are checked against the state and transaction roots of the headers.
+//!
+//! Like the full chain, the light chain follows the valid chain with
+//! the most work. Competing chains are tracked as long as they fork
+//! from one of the latest `PowBlock::MIN_HEIGHT` headers.
+
+use crate::block::Block;
+use crate::chain::{AppendCondErr, ChainErr};
+use crate::pow_chain::block::PowBlock;
+use crate::pow_chain::block_time::BlockTimes;
+use crate::pow_chain::chain_state::PowChainState;
+use crate::pow_chain::checkpoint_block::CheckpointBlock;
+use crate::pow_chain::merkle::TxInclusionProof;
+use crate::pow_chain::validator_set::{is_early_handoff, slot_handoffs, ValidatorSet};
+use crate::spec::chain_spec;
+use crate::state_proof::StateProof;
+use crate::types::StateInterface;
+use chrono::prelude::*;
+use crypto::{Hash, NodeId, ShortHash};
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use persistence::PersistentDb;
+use transactions::{Feature, Tx};
+use triomphe::Arc;
+
+/// The key of the height of the header chain
+const HEIGHT_KEY: &'static [u8] = b"light_chain_height";
+
+#[derive(Clone)]
+/// Thread-safe reference to a light chain.
+pub struct LightChainRef {
+    /// Atomic reference to the light chain.
+    pub chain: Arc<RwLock<LightChain>>,
+}
+
+impl LightChainRef {
+    pub fn new(chain: Arc<RwLock<LightChain>>) -> LightChainRef {
+        LightChainRef { chain }
+    }
+
+    /// Validates the given header and appends it to the chain.
+    pub fn append_header(&self, header: Arc<PowBlock>) -> Result<(), ChainErr> {
+        let mut chain = self.chain.write();
+        chain.append_header(header)
+    }
+
+    /// Returns the header with the given hash.
+    pub fn query(&self, hash: &Hash) -> Option<Arc<PowBlock>> {
+        let chain = self.chain.read();
+        chain.query(hash)
+    }
+
+    /// Returns the tip of the header chain.
+    pub fn tip(&self) -> Arc<PowBlock> {
+        let chain = self.chain.read();
+        chain.tip()
+    }
+
+    /// Returns the height of the tip of the header chain.
+    pub fn height(&self) -> u64 {
+        let chain = self.chain.read();
+        chain.height()
+    }
+
+    /// Returns the hash of the latest transaction block
+    /// header along with the state root committed in it.
+    pub fn latest_state_root(&self) -> Option<(Hash, ShortHash)> {
+        let chain = self.chain.read();
+        chain.latest_state_root()
+    }
+
+    /// Verifies the given state proof against the header with the
+    /// given hash and caches the proven value if the header is the
+    /// latest transaction block header.
+    pub fn verify_state_proof(
+        &self,
+        block_hash: &Hash,
+        proof: &StateProof,
+    ) -> Result<Option<Vec<u8>>, &'static str> {
+        let mut chain = self.chain.write();
+        chain.verify_state_proof(block_hash, proof)
+    }
+
+    /// Verifies that the given transaction is included in the
+    /// header with the given hash and caches the result if so.
+    pub fn verify_tx_inclusion(
+        &self,
+        block_hash: &Hash,
+        tx: &Tx,
+        proof: &TxInclusionProof,
+    ) -> bool {
+        let mut chain = self.chain.write();
+        chain.verify_tx_inclusion(block_hash, tx, proof)
+    }
+
+    /// Returns the verified value of the given state key along with
+    /// the latest transaction block header hash and state root it
+    /// has been proven against.
+    pub fn verified_state(&self, key: &[u8]) -> Option<(Hash, ShortHash, Option<Vec<u8>>)> {
+        let chain = self.chain.read();
+        let (block_hash, state_root) = chain.latest_state_root()?;
+        let value = chain.verified_state(key)?;
+
+        Some((block_hash, state_root, value))
+    }
+
+    /// Returns the hash of the header which has been verified
+    /// to include the transaction with the given hash.
+    pub fn verified_tx(&self, tx_hash: &Hash) -> Option<Hash> {
+        let chain = self.chain.read();
+        chain.verified_tx(tx_hash)
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Validation state of the header chain ending with a header.
+pub(crate) struct HeaderState {
+    /// The last header of the chain.
+    pub(crate) tip: Arc<PowBlock>,
+
+    /// Hash of the last checkpoint header.
+    pub(crate) last_checkpoint: Hash,
+
+    /// The current edge bits.
+    pub(crate) edge_bits: u8,
+
+    /// The difficulty of the next checkpoint header.
+    pub(crate) difficulty: u64,
+
+    /// Total proof of work of the checkpoint headers.
+    pub(crate) total_work: u128,
+
+    /// Timestamps of the latest headers.
+    pub(crate) block_times: BlockTimes,
+
+    /// The miner of the last checkpoint header. This is `None`
+    /// if the next header must be a checkpoint header.
+    pub(crate) current_validator: Option<NodeId>,
+
+    /// Number of transaction block headers that can
+    /// be appended until the next checkpoint header.
+    pub(crate) txs_blocks_left: Option<u32>,
+
+    /// The miners of the latest checkpoint headers.
+    pub(crate) validators: ValidatorSet,
+
+    /// Hash of the latest transaction block header.
+    pub(crate) latest_tx_header: Option<Hash>,
+
+    /// State root of the latest transaction block header.
+    pub(crate) state_root: ShortHash,
+}
+
+impl HeaderState {
+    fn genesis() -> HeaderState {
+        let genesis_state = PowChainState::genesis(PersistentDb::new_in_memory());
+        HeaderState::from_chain_state(PowBlock::genesis(), &genesis_state)
+    }
+
+    /// Returns the validation state of the header chain ending
+    /// with the given block, whose chain state is the given one.
+    pub(crate) fn from_chain_state(tip: Arc<PowBlock>, chain_state: &PowChainState) -> HeaderState {
+        let latest_tx_header = match *tip {
+            PowBlock::Transaction(_) => tip.block_hash(),
+            _ => None,
+        };
+
+        HeaderState {
+            tip,
+            last_checkpoint: chain_state.last_checkpoint,
+            edge_bits: chain_state.edge_bits,
+            difficulty: chain_state.difficulty,
+            total_work: chain_state.total_work,
+            block_times: chain_state.block_times.clone(),
+            current_validator: chain_state.current_validator.clone(),
+            txs_blocks_left: chain_state.txs_blocks_left,
+            validators: chain_state.validators.clone(),
+            latest_tx_header,
+            state_root: chain_state.state_root(),
+        }
+    }
+
+    /// Returns `true` if the chain ending with this state should
+    /// replace the chain ending with the given state.
+    ///
+    /// As with the full chain, chains are compared by their work and
+    /// then by their height. Otherwise the current chain is kept.
+    fn is_heavier(&self, other: &HeaderState) -> bool {
+        if self.total_work != other.total_work {
+            self.total_work > other.total_work
+        } else {
+            self.tip.height() > other.tip.height()
+        }
+    }
+
+    /// Validates the given header against the state and
+    /// returns the state of the chain ending with it.
+    pub(crate) fn apply(mut self, header: Arc<PowBlock>) -> Result<HeaderState, ChainErr> {
+        if Some(header.parent_hash()) != self.tip.block_hash() {
+            return Err(ChainErr::InvalidParent);
+        }
+
+        if header.height() != self.tip.height() + 1 {
+            return Err(ChainErr::BadHeight);
+        }
+
+        if chain_spec().is_active(Feature::BlockTimestamps, header.height()) {
+            self.block_times
+                .validate(&header.timestamp(), &Utc::now())
+                .map_err(ChainErr::BadAppendCondition)?;
+        }
+
+        match *header {
+            PowBlock::Genesis => return Err(ChainErr::AlreadyInChain),
+
+            PowBlock::Checkpoint(ref block) => {
+                if !block.verify_miner_sig() {
+                    return Err(ChainErr::BadAppendCondition(AppendCondErr::BadMinerSig));
+                }
+
+                if self.current_validator.is_some() {
+                    return Err(ChainErr::BadAppendCondition(
+                        AppendCondErr::DoesntAcceptBlockType,
+                    ));
+                }
+
+                let timestamps_active =
+                    chain_spec().is_active(Feature::BlockTimestamps, block.height());
+
+                #[cfg(test)]
+                let difficulty = 0;
+
+                #[cfg(not(test))]
+                let difficulty = if timestamps_active {
+                    self.difficulty
+                } else {
+                    0
+                };
+
+                #[cfg(test)]
+                let edge_bits = 0;
+
+                #[cfg(not(test))]
+                let edge_bits = self.edge_bits;
+
+                if !block.verify_proof(&self.last_checkpoint, difficulty, edge_bits) {
+                    return Err(ChainErr::BadAppendCondition(AppendCondErr::BadProof));
+                }
+
+                self.current_validator = Some(block.miner_id().clone());
+                self.validators.push(block.miner_id().clone());
+                self.total_work += CheckpointBlock::work(difficulty, edge_bits);
+                self.txs_blocks_left = Some(chain_spec().consensus.allowed_txs_blocks);
+                self.last_checkpoint = block.block_hash().unwrap();
+
+                if timestamps_active {
+                    self.difficulty = self
+                        .block_times
+                        .next_difficulty(self.difficulty, &block.timestamp());
+                }
+
+                self.block_times.push_checkpoint(block.timestamp());
+            }
+
+            PowBlock::Transaction(ref block) => {
+                if !block.verify_miner_sig() {
+                    return Err(ChainErr::BadAppendCondition(AppendCondErr::BadMinerSig));
+                }
+
+                if self.current_validator.is_none() {
+                    return Err(ChainErr::BadAppendCondition(
+                        AppendCondErr::DoesntAcceptBlockType,
+                    ));
+                }
+
+                let slot_validator = self.slot_validator(block.height(), &block.timestamp);
+
+                if slot_validator.as_ref() != Some(&block.miner_id) {
+                    return Err(ChainErr::BadAppendCondition(AppendCondErr::InvalidMiner));
+                }
+
+                let txs_blocks_left = self.txs_blocks_left.unwrap() - 1;
+
+                if txs_blocks_left == 0 {
+                    self.current_validator = None;
+                    self.txs_blocks_left = None;
+                } else {
+                    self.txs_blocks_left = Some(txs_blocks_left);
+                }
+
+                self.latest_tx_header = block.block_hash();
+                self.state_root = block.state_root.unwrap();
+                self.block_times.push(block.timestamp);
+            }
+        }
+
+        self.tip = header;
+        Ok(self)
+    }
+
+    /// Returns the validator allowed to append the transaction block
+    /// header at the given height with the given timestamp.
+    fn slot_validator(&self, height: u64, timestamp: &DateTime<Utc>) -> Option<NodeId> {
+        let current_validator = self.current_validator.as_ref()?;
+
+        if !chain_spec().is_active(Feature::ValidatorRotation, height) {
+            return Some(current_validator.clone());
+        }
+
+        let slot = chain_spec()
+            .consensus
+            .allowed_txs_blocks
+            .saturating_sub(self.txs_blocks_left?);
+
+        let handoffs = match self.block_times.last() {
+            Some(previous) if chain_spec().is_active(Feature::BlockTimestamps, height) => {
+                slot_handoffs(&previous, timestamp)
+            }
+            _ => 0,
+        };
+
+        self.validators.slot_validator(slot, handoffs).cloned()
+    }
+
+    /// Returns `true` if the given header takes over a slot
+    /// which has not timed out yet at the given local time.
+    fn is_early_handoff(&self, header: &PowBlock, now: &DateTime<Utc>) -> bool {
+        let height = header.height();
+
+        match (header, self.block_times.last()) {
+            (PowBlock::Transaction(_), Some(previous)) => {
+                chain_spec().is_active(Feature::ValidatorRotation, height)
+                    && chain_spec().is_active(Feature::BlockTimestamps, height)
+                    && is_early_handoff(&previous, &header.timestamp(), now)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Chain of block headers which stores no state.
+pub struct LightChain {
+    /// Database storing the headers.
+    db: PersistentDb,
+
+    /// The state of the canonical header chain.
+    canonical: HeaderState,
+
+    /// The states of the chains ending with the latest headers,
+    /// canonical or not. Headers can only be appended to these.
+    states: HashMap<Hash, HeaderState>,
+
+    /// Proven values of state keys as of the latest
+    /// transaction block header.
+    verified_state: HashMap<Vec<u8>, Option<Vec<u8>>>,
+
+    /// Mapping between proven transactions
+    /// and the headers that include them.
+    verified_txs: HashMap<Hash, Hash>,
+}
+
+impl LightChain {
+    /// Creates a new light chain, reloading and re-validating
+    /// the canonical headers that are stored in the given database.
+    pub fn new(db: PersistentDb) -> LightChain {
+        let genesis = HeaderState::genesis();
+        let mut states = HashMap::new();
+        states.insert(genesis.tip.block_hash().unwrap(), genesis.clone());
+
+        let mut chain = LightChain {
+            db,
+            canonical: genesis,
+            states,
+            verified_state: HashMap::new(),
+            verified_txs: HashMap::new(),
+        };
+
+        let stored_height = match chain.db.retrieve(HEIGHT_KEY) {
+            Some(height) => decode_be_u64!(&height).unwrap(),
+            None => 0,
+        };
+
+        for height in 1..=stored_height {
+            let header = chain
+                .query_by_height(height)
+                .expect("Could not reload header from disk!");
+            let state = chain
+                .canonical
+                .clone()
+                .apply(header.clone())
+                .expect("Invalid header stored on disk!");
+
+            chain
+                .states
+                .insert(header.block_hash().unwrap(), state.clone());
+            chain.canonical = state;
+            chain.prune_states();
+        }
+
+        chain
+    }
+
+    /// Validates the given header and appends it to the chain it
+    /// extends. The chain with the most work becomes canonical.
+    pub fn append_header(&mut self, header: Arc<PowBlock>) -> Result<(), ChainErr> {
+        let block_hash = header.block_hash().unwrap();
+
+        if self.states.contains_key(&block_hash) || self.db.retrieve(&block_hash.0).is_some() {
+            return Err(ChainErr::AlreadyInChain);
+        }
+
+        let parent_state = self
+            .states
+            .get(&header.parent_hash())
+            .cloned()
+            .ok_or(ChainErr::InvalidParent)?;
+
+        // Received headers cannot take over a slot before it has timed
+        // out on the local clock. This is not checked when reloading.
+        if parent_state.is_early_handoff(&header, &Utc::now()) {
+            return Err(ChainErr::BadAppendCondition(
+                AppendCondErr::TimestampInFuture,
+            ));
+        }
+
+        let state = parent_state.apply(header.clone())?;
+
+        // Write header
+        self.db.put(&block_hash.0, &header.to_bytes());
+        self.states.insert(block_hash, state.clone());
+
+        if state.is_heavier(&self.canonical) {
+            self.switch(state);
+        }
+
+        self.prune_states();
+        self.db.flush();
+
+        Ok(())
+    }
+
+    /// Returns the header with the given hash.
+    pub fn query(&self, hash: &Hash) -> Option<Arc<PowBlock>> {
+        if self.canonical.tip.block_hash().as_ref() == Some(hash) {
+            return Some(self.canonical.tip.clone());
+        }
+
+        let stored = self.db.retrieve(&hash.0)?;
+        PowBlock::from_bytes(&stored).ok()
+    }
+
+    /// Returns the canonical header at the given height.
+    pub fn query_by_height(&self, height: u64) -> Option<Arc<PowBlock>> {
+        if height == 0 {
+            return Some(PowBlock::genesis());
+        }
+
+        let hash = self.db.retrieve(&Self::compute_height_key(height).0)?;
+        let stored = self.db.retrieve(&hash)?;
+        PowBlock::from_bytes(&stored).ok()
+    }
+
+    /// Returns `true` if the header with the given
+    /// hash is part of the canonical chain.
+    pub fn is_canonical(&self, hash: &Hash) -> bool {
+        match self.query(hash) {
+            Some(header) => {
+                self.query_by_height(header.height())
+                    .and_then(|canonical| canonical.block_hash())
+                    == Some(*hash)
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the tip of the header chain.
+    pub fn tip(&self) -> Arc<PowBlock> {
+        self.canonical.tip.clone()
+    }
+
+    /// Returns the height of the tip of the header chain.
+    pub fn height(&self) -> u64 {
+        self.canonical.tip.height()
+    }
+
+    /// Returns the hash of the latest transaction block
+    /// header along with the state root committed in it.
+    pub fn latest_state_root(&self) -> Option<(Hash, ShortHash)> {
+        self.canonical
+            .latest_tx_header
+            .map(|block_hash| (block_hash, self.canonical.state_root))
+    }
+
+    /// Returns the validation state of the canonical chain.
+    pub(crate) fn tip_state(&self) -> &HeaderState {
+        &self.canonical
+    }
+
+    /// Verifies the given state proof against the state root of the
+    /// transaction block header with the given hash. The proven value
+    /// is cached if the header is the latest transaction block header.
+    pub fn verify_state_proof(
+        &mut self,
+        block_hash: &Hash,
+        proof: &StateProof,
+    ) -> Result<Option<Vec<u8>>, &'static str> {
+        let state_root = match *self.query(block_hash).ok_or("Unknown header")? {
+            PowBlock::Transaction(ref header) => header.state_root.ok_or("Invalid header")?,
+            _ => return Err("The header does not commit to a state root"),
+        };
+
+        let value = proof.verify(&state_root)?;
+
+        if self.canonical.latest_tx_header.as_ref() == Some(block_hash) {
+            self.verified_state.insert(proof.key.clone(), value.clone());
+        }
+
+        Ok(value)
+    }
+
+    /// Verifies that the given transaction is included in the transaction
+    /// block header with the given hash and caches the result if the
+    /// header is canonical.
+    pub fn verify_tx_inclusion(
+        &mut self,
+        block_hash: &Hash,
+        tx: &Tx,
+        proof: &TxInclusionProof,
+    ) -> bool {
+        let is_included = match self.query(block_hash).as_ref().map(|header| &**header) {
+            Some(PowBlock::Transaction(header)) => header.verify_tx_inclusion(tx, proof),
+            _ => false,
+        };
+
+        // Transactions of competing chains are not cached
+        if is_included && self.is_canonical(block_hash) {
+            if let Some(tx_hash) = tx.tx_hash() {
+                self.verified_txs.insert(tx_hash, *block_hash);
+            }
+        }
+
+        is_included
+    }
+
+    /// Returns the verified value of the given state key
+    /// as of the latest transaction block header.
+    pub fn verified_state(&self, key: &[u8]) -> Option<Option<Vec<u8>>> {
+        self.verified_state.get(key).cloned()
+    }
+
+    /// Returns the hash of the header which has been verified
+    /// to include the transaction with the given hash.
+    pub fn verified_tx(&self, tx_hash: &Hash) -> Option<Hash> {
+        self.verified_txs.get(tx_hash).cloned()
+    }
+
+    /// Makes the chain ending with the given state canonical.
+    fn switch(&mut self, state: HeaderState) {
+        let old_height = self.canonical.tip.height();
+        let new_height = state.tip.height();
+        let extends_tip = Some(state.tip.parent_hash()) == self.canonical.tip.block_hash();
+        let mut current = state.tip.clone();
+
+        // Point the heights to the headers of the new chain
+        // until reaching a header of the old chain.
+        while current.height() > 0 && !self.is_canonical(&current.block_hash().unwrap()) {
+            let height_key = Self::compute_height_key(current.height());
+
+            self.db.put(&height_key.0, &current.block_hash().unwrap().0);
+            current = self
+                .query(&current.parent_hash())
+                .expect("Could not find parent header!");
+        }
+
+        for height in (new_height + 1)..=old_height {
+            self.db.delete(&Self::compute_height_key(height).0);
+        }
+
+        self.db.put(HEIGHT_KEY, &encode_be_u64!(new_height));
+
+        // Proven values only hold for the state they were proven against
+        if state.latest_tx_header != self.canonical.latest_tx_header {
+            self.verified_state.clear();
+        }
+
+        self.canonical = state;
+
+        // The transactions of the old chain may not be included anymore
+        if !extends_tip {
+            let verified_txs = std::mem::replace(&mut self.verified_txs, HashMap::new());
+
+            self.verified_txs = verified_txs
+                .into_iter()
+                .filter(|(_, block_hash)| self.is_canonical(block_hash))
+                .collect();
+        }
+    }
+
+    /// Stops tracking the chains which fork from a header
+    /// which is too far behind the canonical tip.
+    fn prune_states(&mut self) {
+        let min_height = self.height().saturating_sub(PowBlock::MIN_HEIGHT);
+        self.states
+            .retain(|_, state| state.tip.height() >= min_height);
+    }
+
+    fn compute_height_key(height: u64) -> Hash {
+        crypto::hash_slice(&[&b"light_height"[..], &encode_be_u64!(height)].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pow_chain::chain::PowChain;
+    use crate::pow_chain::checkpoint_block::ALLOWED_TXS_BLOCKS;
+    use crate::pow_chain::merkle::compute_tx_root;
+    use crate::pow_chain::transaction_block::TransactionBlock;
+    use crate::state_proof::nonce_key;
+    use crate::test_helpers;
+    use account::{Address, NormalAddress};
+    use crypto::Identity;
+    use miner::Proof;
+    use transactions::TestAccount;
+
+    fn checkpoint(parent_hash: Hash, height: u64, identity: &Identity) -> Arc<PowBlock> {
+        let mut block = CheckpointBlock::new(
+            parent_hash,
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            height,
+            Proof::test_proof(42),
+            NodeId(*identity.pkey()),
+        );
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        Arc::new(PowBlock::Checkpoint(Arc::new(block)))
+    }
+
+    fn transaction_block(
+        parent_hash: Hash,
+        height: u64,
+        identity: &Identity,
+        txs: Vec<Arc<Tx>>,
+        state_root: ShortHash,
+    ) -> Arc<PowBlock> {
+        let mut block = TransactionBlock::new(
+            parent_hash,
+            crate::random_socket_addr(),
+            height,
+            Proof::test_proof(42),
+            NodeId(*identity.pkey()),
+        );
+        block.tx_checksums = Some(Vec::new());
+        block.pieces_sizes = Some(Vec::new());
+        block.tx_root = Some(compute_tx_root(&txs));
+        block.state_root = Some(state_root);
+        block.transactions = Some(Arc::new(RwLock::new(txs)));
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        Arc::new(PowBlock::Transaction(Arc::new(block)))
+    }
+
+    #[test]
+    fn it_validates_headers() {
+        let mut chain = LightChain::new(test_helpers::init_tempdb());
+        let identity = Identity::new();
+        let other = Identity::new();
+        let genesis_hash = PowBlock::genesis().block_hash().unwrap();
+        let state_root = chain.tip_state().state_root;
+
+        // Transaction blocks need a checkpoint first
+        let early = transaction_block(genesis_hash, 1, &identity, Vec::new(), state_root);
+        assert_eq!(
+            chain.append_header(early),
+            Err(ChainErr::BadAppendCondition(
+                AppendCondErr::DoesntAcceptBlockType
+            ))
+        );
+
+        let checkpoint = checkpoint(genesis_hash, 1, &identity);
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+        chain.append_header(checkpoint).unwrap();
+
+        // Only the miner of the checkpoint can append transaction blocks
+        let invalid_miner = transaction_block(checkpoint_hash, 2, &other, Vec::new(), state_root);
+        assert_eq!(
+            chain.append_header(invalid_miner),
+            Err(ChainErr::BadAppendCondition(AppendCondErr::InvalidMiner))
+        );
+
+        let bad_height = transaction_block(checkpoint_hash, 3, &identity, Vec::new(), state_root);
+        assert_eq!(chain.append_header(bad_height), Err(ChainErr::BadHeight));
+
+        let mut parent_hash = checkpoint_hash;
+
+        for height in 2..(2 + ALLOWED_TXS_BLOCKS as u64) {
+            let header = transaction_block(parent_hash, height, &identity, Vec::new(), state_root);
+            parent_hash = header.block_hash().unwrap();
+            chain.append_header(header).unwrap();
+        }
+
+        // The validator has no transaction blocks left
+        let height = 2 + ALLOWED_TXS_BLOCKS as u64;
+        let extra = transaction_block(parent_hash, height, &identity, Vec::new(), state_root);
+        assert_eq!(
+            chain.append_header(extra),
+            Err(ChainErr::BadAppendCondition(
+                AppendCondErr::DoesntAcceptBlockType
+            ))
+        );
+
+        let unknown_parent = checkpoint(crypto::hash_slice(b"unknown"), height, &identity);
+        assert_eq!(
+            chain.append_header(unknown_parent),
+            Err(ChainErr::InvalidParent)
+        );
+        assert_eq!(chain.height(), 1 + ALLOWED_TXS_BLOCKS as u64);
+        assert_eq!(chain.latest_state_root(), Some((parent_hash, state_root)));
+    }
+
+    #[test]
+    fn it_reloads_headers_from_disk() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = LightChain::new(db.clone());
+        let identity = Identity::new();
+        let genesis_hash = PowBlock::genesis().block_hash().unwrap();
+        let checkpoint = checkpoint(genesis_hash, 1, &identity);
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+        let state_root = chain.tip_state().state_root;
+        let header = transaction_block(checkpoint_hash, 2, &identity, Vec::new(), state_root);
+        let header_hash = header.block_hash().unwrap();
+
+        chain.append_header(checkpoint).unwrap();
+        chain.append_header(header).unwrap();
+
+        let reloaded = LightChain::new(db);
+
+        assert_eq!(reloaded.height(), 2);
+        assert_eq!(reloaded.tip().block_hash(), Some(header_hash));
+        assert_eq!(
+            reloaded.tip_state().current_validator,
+            chain.tip_state().current_validator
+        );
+        assert_eq!(
+            reloaded.tip_state().txs_blocks_left,
+            chain.tip_state().txs_blocks_left
+        );
+        assert_eq!(reloaded.latest_state_root(), chain.latest_state_root());
+    }
+
+    #[test]
+    fn it_switches_to_the_chain_with_the_most_work() {
+        let db = test_helpers::init_tempdb();
+        let mut chain = LightChain::new(db.clone());
+        let identity = Identity::new();
+        let other = Identity::new();
+        let genesis_hash = PowBlock::genesis().block_hash().unwrap();
+        let state_root = chain.tip_state().state_root;
+
+        let canonical = checkpoint(genesis_hash, 1, &identity);
+        let fork = checkpoint(genesis_hash, 1, &other);
+        let mut canonical_hash = canonical.block_hash().unwrap();
+        let mut fork_hash = fork.block_hash().unwrap();
+
+        chain.append_header(canonical).unwrap();
+        chain.append_header(fork).unwrap();
+
+        for height in 2..(2 + ALLOWED_TXS_BLOCKS as u64) {
+            let header =
+                transaction_block(canonical_hash, height, &identity, Vec::new(), state_root);
+            canonical_hash = header.block_hash().unwrap();
+            chain.append_header(header).unwrap();
+
+            let header = transaction_block(fork_hash, height, &other, Vec::new(), state_root);
+            fork_hash = header.block_hash().unwrap();
+            chain.append_header(header).unwrap();
+        }
+
+        // Chains with as much work do not replace the canonical chain
+        assert_eq!(chain.tip().block_hash(), Some(canonical_hash));
+        assert!(chain.is_canonical(&canonical_hash));
+        assert!(!chain.is_canonical(&fork_hash));
+
+        let height = 2 + ALLOWED_TXS_BLOCKS as u64;
+        let fork_checkpoint = checkpoint(fork_hash, height, &other);
+        let fork_checkpoint_hash = fork_checkpoint.block_hash().unwrap();
+        chain.append_header(fork_checkpoint).unwrap();
+
+        assert_eq!(chain.height(), height);
+        assert_eq!(chain.tip().block_hash(), Some(fork_checkpoint_hash));
+        assert!(chain.is_canonical(&fork_hash));
+        assert!(!chain.is_canonical(&canonical_hash));
+        assert_eq!(chain.latest_state_root(), Some((fork_hash, state_root)));
+
+        // The canonical chain is reloaded from disk
+        let reloaded = LightChain::new(db);
+
+        assert_eq!(reloaded.tip().block_hash(), Some(fork_checkpoint_hash));
+        assert_eq!(reloaded.latest_state_root(), Some((fork_hash, state_root)));
+    }
+
+    #[test]
+    fn it_verifies_proofs_from_full_nodes() {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+        let mut full_chain = PowChain::new(test_helpers::init_tempdb(), state, true);
+        let mut light_chain = LightChain::new(test_helpers::init_tempdb());
+        let identity = Identity::new();
+        let sender = Address::Normal(TestAccount::A.to_perm_address());
+
+        full_chain.set_tx_index(true);
+
+        let genesis_hash = PowBlock::genesis().block_hash().unwrap();
+        let checkpoint = checkpoint(genesis_hash, 1, &identity);
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+
+        full_chain.append_block(checkpoint.clone()).unwrap();
+        light_chain.append_header(checkpoint).unwrap();
+
+        let tx = Arc::new(transactions::send_coins(
+            TestAccount::A,
+            TestAccount::B,
+            100,
+            10,
+            1,
+        ));
+        let tx_hash = tx.tx_hash().unwrap();
+        let mut state = full_chain.canonical_tip_state();
+        state.apply_tx(tx.clone());
+
+        let block = transaction_block(
+            checkpoint_hash,
+            2,
+            &identity,
+            vec![tx.clone()],
+            state.state_root(),
+        );
+        let block_hash = block.block_hash().unwrap();
+        full_chain.append_block(block.clone()).unwrap();
+
+        // The light client only receives the header
+        let header = PowBlock::from_bytes(&block.to_bytes()).unwrap();
+        light_chain.append_header(header).unwrap();
+
+        let key = nonce_key(&sender);
+        let state_proof = full_chain.state_proof(&block_hash, &key).unwrap();
+        let nonce = light_chain
+            .verify_state_proof(&block_hash, &state_proof)
+            .unwrap();
+
+        assert_eq!(light_chain.verified_state(&key), Some(nonce.clone()));
+        assert_eq!(
+            nonce.map(|nonce| decode_be_u64!(&nonce).unwrap()),
+            state.get_account_nonce(&sender)
+        );
+        assert!(light_chain
+            .verify_state_proof(&checkpoint_hash, &state_proof)
+            .is_err());
+
+        let (proof_block_hash, tx_proof) = full_chain.tx_inclusion_proof(&tx_hash).unwrap();
+
+        assert_eq!(proof_block_hash, block_hash);
+        assert!(light_chain.verify_tx_inclusion(&block_hash, &tx, &tx_proof));
+        assert_eq!(light_chain.verified_tx(&tx_hash), Some(block_hash));
+    }
+}
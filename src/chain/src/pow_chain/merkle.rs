@@ -0,0 +1,298 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Merkle trees over the ordered transaction set of a `TransactionBlock`.
+//!
+//! Leaves are computed as `hash(0x00 || tx-bytes)` and inner nodes as
+//! `hash(0x01 || left || right)` so that a leaf can never be passed off
+//! as an inner node. When a level has an odd number of nodes, the last
+//! node is promoted to the next level unchanged. The transaction root
+//! written in the block header is the short form of the top node and
+//! the root of an empty transaction set is `ShortHash::NULL_RLP`.
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use crypto::{Hash, ShortHash};
+use std::io::Cursor;
+use transactions::Tx;
+use triomphe::Arc;
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Computes the Merkle root of the given ordered transaction set.
+pub fn compute_tx_root(txs: &[Arc<Tx>]) -> ShortHash {
+    if txs.is_empty() {
+        return ShortHash::NULL_RLP;
+    }
+
+    let mut level: Vec<Hash> = txs.iter().map(|tx| leaf_hash(tx)).collect();
+
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+
+    level[0].to_short()
+}
+
+/// Builds a proof that the transaction at the given position
+/// is included in the given ordered transaction set.
+///
+/// Returns `None` if the position is out of bounds.
+pub fn tx_inclusion_proof(txs: &[Arc<Tx>], position: usize) -> Option<TxInclusionProof> {
+    if position >= txs.len() {
+        return None;
+    }
+
+    let mut level: Vec<Hash> = txs.iter().map(|tx| leaf_hash(tx)).collect();
+    let mut index = position;
+    let mut siblings = Vec::new();
+
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+
+        // The last node of an odd level is promoted and has no sibling
+        if sibling < level.len() {
+            siblings.push(level[sibling]);
+        }
+
+        level = next_level(&level);
+        index /= 2;
+    }
+
+    Some(TxInclusionProof {
+        position: position as u32,
+        tx_count: txs.len() as u32,
+        siblings,
+    })
+}
+
+/// Proof that a transaction is included in the
+/// transaction set of a `TransactionBlock`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxInclusionProof {
+    /// The position of the transaction in the transaction set.
+    pub position: u32,
+
+    /// The number of transactions in the transaction set.
+    pub tx_count: u32,
+
+    /// The sibling nodes on the path from the leaf to the root.
+    pub siblings: Vec<Hash>,
+}
+
+impl TxInclusionProof {
+    /// Verifies that the given transaction is included at the
+    /// position of the proof in a transaction set with the given root.
+    pub fn verify(&self, tx: &Tx, tx_root: &ShortHash) -> bool {
+        if self.position >= self.tx_count {
+            return false;
+        }
+
+        let mut hash = leaf_hash(tx);
+        let mut index = self.position as usize;
+        let mut len = self.tx_count as usize;
+        let mut siblings = self.siblings.iter();
+
+        while len > 1 {
+            if index % 2 == 1 {
+                match siblings.next() {
+                    Some(sibling) => hash = node_hash(sibling, &hash),
+                    None => return false,
+                }
+            } else if index + 1 < len {
+                match siblings.next() {
+                    Some(sibling) => hash = node_hash(&hash, sibling),
+                    None => return false,
+                }
+            }
+
+            index /= 2;
+            len = (len + 1) / 2;
+        }
+
+        // All siblings must have been used
+        if siblings.next().is_some() {
+            return false;
+        }
+
+        hash.to_short() == *tx_root
+    }
+
+    /// Serializes the proof. The format is the following:
+    /// `<position><tx-count>[<sibling>]*`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(8 + self.siblings.len() * crypto::HASH_BYTES);
+
+        buf.write_u32::<BigEndian>(self.position).unwrap();
+        buf.write_u32::<BigEndian>(self.tx_count).unwrap();
+
+        for sibling in self.siblings.iter() {
+            buf.extend_from_slice(&sibling.0);
+        }
+
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TxInclusionProof, &'static str> {
+        if bytes.len() < 8 || (bytes.len() - 8) % crypto::HASH_BYTES != 0 {
+            return Err("Invalid proof length");
+        }
+
+        let mut rdr = Cursor::new(bytes);
+        let position = rdr.read_u32::<BigEndian>().map_err(|_| "Bad position")?;
+        let tx_count = rdr.read_u32::<BigEndian>().map_err(|_| "Bad tx count")?;
+
+        if position >= tx_count {
+            return Err("Invalid position");
+        }
+
+        let siblings = bytes[8..]
+            .chunks(crypto::HASH_BYTES)
+            .map(|sibling| {
+                let mut hash = [0; crypto::HASH_BYTES];
+                hash.copy_from_slice(sibling);
+                Hash(hash)
+            })
+            .collect();
+
+        Ok(TxInclusionProof {
+            position,
+            tx_count,
+            siblings,
+        })
+    }
+}
+
+fn leaf_hash(tx: &Tx) -> Hash {
+    let tx_bytes = tx.to_bytes();
+    let mut buf = Vec::with_capacity(1 + tx_bytes.len());
+
+    buf.push(LEAF_PREFIX);
+    buf.extend_from_slice(&tx_bytes);
+    crypto::hash_slice(&buf)
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut buf = Vec::with_capacity(1 + 2 * crypto::HASH_BYTES);
+
+    buf.push(NODE_PREFIX);
+    buf.extend_from_slice(&left.0);
+    buf.extend_from_slice(&right.0);
+    crypto::hash_slice(&buf)
+}
+
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() == 2 {
+                node_hash(&pair[0], &pair[1])
+            } else {
+                pair[0]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transactions::TestAccount;
+
+    fn test_txs(count: u64) -> Vec<Arc<Tx>> {
+        (1..=count)
+            .map(|nonce| {
+                Arc::new(transactions::send_coins(
+                    TestAccount::A,
+                    TestAccount::B,
+                    100,
+                    10,
+                    nonce,
+                ))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_computes_the_root_of_an_empty_set() {
+        assert_eq!(compute_tx_root(&[]), ShortHash::NULL_RLP);
+        assert!(tx_inclusion_proof(&[], 0).is_none());
+    }
+
+    #[test]
+    fn it_commits_to_the_order_of_transactions() {
+        let txs = test_txs(3);
+        let mut reversed = txs.clone();
+        reversed.reverse();
+
+        assert_ne!(compute_tx_root(&txs), compute_tx_root(&reversed));
+        assert_ne!(compute_tx_root(&txs), compute_tx_root(&txs[..2]));
+    }
+
+    #[test]
+    fn it_verifies_inclusion_proofs() {
+        for count in 1..=9 {
+            let txs = test_txs(count);
+            let root = compute_tx_root(&txs);
+
+            for (position, tx) in txs.iter().enumerate() {
+                let proof = tx_inclusion_proof(&txs, position).unwrap();
+                let decoded = TxInclusionProof::from_bytes(&proof.to_bytes()).unwrap();
+
+                assert_eq!(decoded, proof);
+                assert!(proof.verify(tx, &root));
+            }
+
+            assert!(tx_inclusion_proof(&txs, count as usize).is_none());
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_inclusion_proofs() {
+        let txs = test_txs(5);
+        let root = compute_tx_root(&txs);
+        let proof = tx_inclusion_proof(&txs, 2).unwrap();
+
+        // Wrong transaction
+        assert!(!proof.verify(&txs[3], &root));
+
+        // Wrong position
+        let mut moved = proof.clone();
+        moved.position = 3;
+        assert!(!moved.verify(&txs[2], &root));
+
+        // Tampered sibling
+        let mut tampered = proof.clone();
+        tampered.siblings[0] = crypto::hash_slice(b"tampered");
+        assert!(!tampered.verify(&txs[2], &root));
+
+        // Extra sibling
+        let mut extended = proof.clone();
+        extended.siblings.push(crypto::hash_slice(b"extra"));
+        assert!(!extended.verify(&txs[2], &root));
+
+        // Truncated proof
+        assert!(TxInclusionProof::from_bytes(&proof.to_bytes()[..7]).is_err());
+
+        // Out of bounds position
+        let mut out_of_bounds = proof;
+        out_of_bounds.position = 5;
+        assert!(!out_of_bounds.verify(&txs[2], &root));
+        assert!(TxInclusionProof::from_bytes(&out_of_bounds.to_bytes()).is_err());
+    }
+}
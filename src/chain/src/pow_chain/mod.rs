@@ -17,11 +17,27 @@
 */
 
 pub mod block;
+pub mod block_time;
 pub mod chain;
 pub mod chain_state;
 pub mod checkpoint_block;
+pub mod export;
+pub mod light_chain;
+pub mod merkle;
+pub mod simulation;
+pub mod snapshot;
 pub mod transaction_block;
+pub mod validator_set;
+pub mod verify;
 
+pub use self::block_time::*;
 pub use self::chain_state::*;
 pub use self::checkpoint_block::*;
+pub use self::export::*;
+pub use self::light_chain::*;
+pub use self::merkle::*;
+pub use self::simulation::*;
+pub use self::snapshot::*;
 pub use self::transaction_block::*;
+pub use self::validator_set::*;
+pub use self::verify::*;
@@ -0,0 +1,229 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Dry-run execution of transactions against a chain state.
+//!
+//! A simulation applies a transaction to a clone of a `PowChainState`.
+//! Writes to a `PersistentDb` are buffered in memory until it is flushed
+//! and the clone is never flushed, so the simulated state is discarded
+//! once the simulation ends.
+//!
+//! The state diff is built from the journal of the trie writes made
+//! by the transaction so only the written entries are read.
+
+use crate::pow_chain::chain_state::PowChainState;
+use chrono::Utc;
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
+use persistence::{Codec, DbHasher};
+use purple_vm::Gas;
+use transactions::Tx;
+
+/// The outcome of a simulated transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Simulation {
+    /// `true` if the transaction is valid and its
+    /// execution did not fail.
+    pub success: bool,
+
+    /// The amount of gas consumed by contract execution.
+    pub gas_used: Gas,
+
+    /// Messages describing why the simulation failed.
+    pub logs: Vec<String>,
+
+    /// The trie entries changed by the transaction,
+    /// ordered by key.
+    pub state_diff: Vec<StateChange>,
+}
+
+/// A change of a single trie entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateChange {
+    /// The key of the changed entry.
+    pub key: Vec<u8>,
+
+    /// The value before the transaction was applied. This
+    /// is `None` if the entry was created by the transaction.
+    pub before: Option<Vec<u8>>,
+
+    /// The value after the transaction was applied. This
+    /// is `None` if the entry was removed by the transaction.
+    pub after: Option<Vec<u8>>,
+}
+
+impl PowChainState {
+    /// Applies the given transaction to a copy of the state and
+    /// returns its outcome, leaving the state itself untouched.
+    ///
+    /// If `verify_signature` is `false`, the transaction is validated
+    /// without verifying its signature so that unsigned transactions
+    /// can be simulated as well.
+    pub fn simulate_tx(&self, tx: &Tx, verify_signature: bool) -> Simulation {
+        let zero = Gas::from_bytes(b"0.0").unwrap();
+        let mut state = self.clone();
+
+        let is_valid = {
+            let trie = TrieDB::<DbHasher, Codec>::new(&state.db, &state.state_root).unwrap();
+
+            // The transaction is simulated as if included in the next block
+            let height = state.height + 1;
+            let is_active = |feature| state.upgrades.is_active(feature, height);
+
+            if verify_signature {
+                tx.validate(&trie, is_active)
+            } else {
+                tx.validate_unsigned(&trie, is_active)
+            }
+        };
+
+        if !is_valid {
+            return Simulation {
+                success: false,
+                gas_used: zero,
+                logs: vec!["The transaction is invalid against the current state".to_owned()],
+                state_diff: Vec::new(),
+            };
+        }
+
+        let (result, journal) = {
+            let mut trie =
+                TrieDBMut::<DbHasher, Codec>::from_existing(&mut state.db, &mut state.state_root)
+                    .unwrap();
+
+            // The transaction would be included in a block mined around now
+            transactions::record_writes(|| tx.apply_metered(&mut trie, Utc::now().timestamp()))
+        };
+
+        let (success, gas_used, logs) = match result {
+            Ok(gas_used) => (true, gas_used, Vec::new()),
+            Err(err) => (
+                false,
+                zero,
+                vec![format!("Contract execution failed: {:?}", err)],
+            ),
+        };
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&state.db, &state.state_root).unwrap();
+
+        // Entries which were written back with
+        // their old value are left out.
+        let state_diff = journal
+            .changes()
+            .into_iter()
+            .filter_map(|(key, before)| {
+                let after = trie.get(&key).unwrap().map(|value| value.to_vec());
+
+                if before == after {
+                    None
+                } else {
+                    Some(StateChange { key, before, after })
+                }
+            })
+            .collect();
+
+        Simulation {
+            success,
+            gas_used,
+            logs,
+            state_diff,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::StateInterface;
+    use account::{Address, Balance};
+    use transactions::TestAccount;
+    use triomphe::Arc;
+
+    const RETURN_42: &'static str = "
+        Begin 0x00
+        PushLocal 0x01 0x00
+        i32Const 0x00 0x00 0x00 0x2a
+        Return 0x01
+        End
+    ";
+
+    #[test]
+    fn it_simulates_transactions_without_changing_the_state() {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+        let tx = transactions::send_coins(TestAccount::A, TestAccount::B, 100, 10, 1);
+        let simulation = state.simulate_tx(&tx, true);
+        let asset_hash = crypto::hash_slice(transactions::MAIN_CUR_NAME).to_short();
+        let receiver_cur_key = [
+            TestAccount::B.to_perm_address().as_bytes(),
+            &b"."[..],
+            &asset_hash.0,
+        ]
+        .concat();
+        let receiver_change = simulation
+            .state_diff
+            .iter()
+            .find(|change| change.key == receiver_cur_key)
+            .unwrap();
+
+        assert!(simulation.success);
+        assert_eq!(simulation.gas_used, Gas::from_bytes(b"0.0").unwrap());
+        assert_eq!(
+            Balance::from_bytes(receiver_change.after.as_ref().unwrap()).unwrap(),
+            Balance::from_u64(100100)
+        );
+
+        // The simulated transaction can be applied for real
+        assert!(state.validate_tx(Arc::new(tx)));
+        assert_eq!(
+            state.get_account_nonce(&Address::Normal(TestAccount::A.to_perm_address())),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn it_simulates_invalid_transactions() {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+
+        // Wrong nonce
+        let tx = transactions::send_coins(TestAccount::A, TestAccount::B, 100, 10, 5);
+        let simulation = state.simulate_tx(&tx, false);
+
+        assert!(!simulation.success);
+        assert!(simulation.state_diff.is_empty());
+        assert_eq!(simulation.logs.len(), 1);
+    }
+
+    #[test]
+    fn it_reports_gas_used_by_calls() {
+        let mut state = PowChainState::genesis(test_helpers::init_tempdb());
+        let code = purple_vm::assemble(RETURN_42).unwrap();
+        let open_contract = transactions::open_contract(TestAccount::A, &code, &[], 100, 1, 1);
+        let contract = match open_contract {
+            Tx::OpenContract(ref tx, _) => tx.address().unwrap(),
+            _ => unreachable!(),
+        };
+
+        state.apply_tx(Arc::new(open_contract));
+
+        let tx = transactions::call_contract(TestAccount::A, contract, 10, 1, 2);
+        let simulation = state.simulate_tx(&tx, true);
+
+        assert!(simulation.success);
+        assert!(simulation.gas_used > Gas::from_bytes(b"0.0").unwrap());
+        assert!(!simulation.state_diff.is_empty());
+    }
+}
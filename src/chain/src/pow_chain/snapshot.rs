@@ -0,0 +1,450 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Snapshots of the state trie used for fast syncing.
+//!
+//! A snapshot is taken right after a checkpoint block has been applied
+//! and is made of a `SnapshotManifest` and of chunks holding the entries
+//! of the state trie. Each chunk has the size of at most one downloader
+//! piece and it is addressed by the same checksum as a piece with the
+//! same data, so chunks can be fetched with the piece downloader.
+//!
+//! Each entry of a chunk has the following format:
+//! `<key-len><key><value-len><value>`
+//!
+//! The key length is a big-endian `u16` and the value
+//! length is a big-endian `u32`.
+//!
+//! A snapshot is restored on top of the header chain ending with its
+//! checkpoint block, which provides everything but the state trie.
+//! Snapshot manifests are not exchanged over the network yet, so the
+//! node does not start from snapshots on its own. Fast syncing is up
+//! to the embedder, which fetches the manifest out of band, schedules
+//! the chunks with `Downloader::from_snapshot` and then starts the
+//! chain with `Chain::from_snapshot`.
+
+use crate::block::Block;
+use crate::pow_chain::block::PowBlock;
+use crate::pow_chain::chain_state::{BlockType, PowChainState};
+use crate::pow_chain::light_chain::LightChain;
+use crate::spec::chain_spec;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use constants::*;
+use crypto::{BlakeHasher, Hash, ShortHash};
+use hashbrown::HashMap;
+use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use persistence::{Codec, DbHasher, PersistentDb};
+use std::hash::Hasher;
+use std::io::Cursor;
+
+/// Describes a snapshot of the state taken at a checkpoint block.
+///
+/// The manifest only describes the state trie. The rest of the
+/// chain state is derived from the verified header chain when
+/// the snapshot is restored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotManifest {
+    /// The hash of the checkpoint block.
+    pub block_hash: Hash,
+
+    /// The height of the checkpoint block.
+    pub height: u64,
+
+    /// The root hash of the state trie.
+    pub state_root: ShortHash,
+
+    /// Checksums and sizes of the chunks, in the order
+    /// in which their entries are applied.
+    pub chunks: Vec<(ShortHash, u64)>,
+}
+
+impl SnapshotManifest {
+    /// Serializes the manifest. The format is the following:
+    /// `<height><block-hash><state-root><chunks-count>[<checksum><size>]*`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        buf.write_u64::<BigEndian>(self.height).unwrap();
+        buf.extend_from_slice(&self.block_hash.0);
+        buf.extend_from_slice(&self.state_root.0);
+        buf.write_u32::<BigEndian>(self.chunks.len() as u32)
+            .unwrap();
+
+        for (checksum, size) in self.chunks.iter() {
+            buf.extend_from_slice(&checksum.0);
+            buf.write_u32::<BigEndian>(*size as u32).unwrap();
+        }
+
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SnapshotManifest, &'static str> {
+        let mut rdr = Cursor::new(bytes);
+
+        if bytes.len() < 52 {
+            return Err("Invalid manifest length");
+        }
+
+        let height = rdr.read_u64::<BigEndian>().map_err(|_| "Bad height")?;
+        let mut block_hash = [0; crypto::HASH_BYTES];
+        let mut state_root = [0; crypto::SHORT_HASH_BYTES];
+
+        block_hash.copy_from_slice(&bytes[8..40]);
+        state_root.copy_from_slice(&bytes[40..48]);
+        rdr.set_position(48);
+
+        let chunks_count = rdr
+            .read_u32::<BigEndian>()
+            .map_err(|_| "Bad chunks count")? as usize;
+
+        if bytes.len() != 52 + chunks_count * 12 {
+            return Err("Invalid manifest length");
+        }
+
+        let mut chunks = Vec::with_capacity(chunks_count);
+
+        for _ in 0..chunks_count {
+            let start = rdr.position() as usize;
+            let mut checksum = [0; crypto::SHORT_HASH_BYTES];
+            checksum.copy_from_slice(&bytes[start..(start + crypto::SHORT_HASH_BYTES)]);
+            rdr.set_position((start + crypto::SHORT_HASH_BYTES) as u64);
+
+            let size = rdr.read_u32::<BigEndian>().map_err(|_| "Bad chunk size")? as u64;
+
+            if size == 0 || size > MAX_PIECE_SIZE as u64 {
+                return Err("Invalid chunk size");
+            }
+
+            chunks.push((ShortHash(checksum), size));
+        }
+
+        Ok(SnapshotManifest {
+            block_hash: Hash(block_hash),
+            height,
+            state_root: ShortHash(state_root),
+            chunks,
+        })
+    }
+}
+
+/// A snapshot of the state along with its chunks.
+#[derive(Clone, Debug)]
+pub struct StateSnapshot {
+    /// The manifest of the snapshot.
+    pub manifest: SnapshotManifest,
+
+    /// The chunks of the snapshot addressed by their checksum.
+    chunks: HashMap<ShortHash, Vec<u8>>,
+}
+
+impl StateSnapshot {
+    /// Returns the chunk with the given checksum.
+    pub fn chunk(&self, checksum: &ShortHash) -> Option<&[u8]> {
+        self.chunks.get(checksum).map(|chunk| chunk.as_slice())
+    }
+}
+
+impl PowChainState {
+    /// Serializes the state trie into a snapshot.
+    ///
+    /// Returns `Err(_)` if the last applied block is not a checkpoint
+    /// block or if a trie entry doesn't fit into a chunk.
+    pub fn snapshot(&self) -> Result<StateSnapshot, &'static str> {
+        if self.accepts != BlockType::Transaction
+            || self.txs_blocks_left != Some(chain_spec().consensus.allowed_txs_blocks)
+        {
+            return Err("Snapshots can only be taken at checkpoint blocks");
+        }
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&self.db, &self.state_root)
+            .map_err(|_| "Could not open state trie")?;
+        let mut manifest_chunks = Vec::new();
+        let mut chunks = HashMap::new();
+        let mut chunk = Vec::new();
+
+        for entry in trie.iter().map_err(|_| "Could not iterate state trie")? {
+            let (key, value) = entry.map_err(|_| "Could not iterate state trie")?;
+            let entry_size = 6 + key.len() + value.len();
+
+            if entry_size > MAX_PIECE_SIZE {
+                return Err("Trie entry is too large for a chunk");
+            }
+
+            if chunk.len() + entry_size > MAX_PIECE_SIZE {
+                let checksum = chunk_checksum(&chunk);
+                manifest_chunks.push((checksum, chunk.len() as u64));
+                chunks.insert(checksum, chunk);
+                chunk = Vec::new();
+            }
+
+            chunk.write_u16::<BigEndian>(key.len() as u16).unwrap();
+            chunk.extend_from_slice(&key);
+            chunk.write_u32::<BigEndian>(value.len() as u32).unwrap();
+            chunk.extend_from_slice(&value);
+        }
+
+        if !chunk.is_empty() {
+            let checksum = chunk_checksum(&chunk);
+            manifest_chunks.push((checksum, chunk.len() as u64));
+            chunks.insert(checksum, chunk);
+        }
+
+        let manifest = SnapshotManifest {
+            block_hash: self.last_checkpoint.clone(),
+            height: self.height,
+            state_root: self.state_root.clone(),
+            chunks: manifest_chunks,
+        };
+
+        Ok(StateSnapshot { manifest, chunks })
+    }
+
+    /// Rebuilds the state at the checkpoint block at the tip of the
+    /// given header chain from the chunks of a snapshot, in the order
+    /// listed in the manifest.
+    ///
+    /// The rebuilt trie must match the state root of the last
+    /// transaction block header before the checkpoint block. The
+    /// difficulty, edge bits, total work, block times and validator
+    /// set are taken from the header chain which has verified them.
+    pub fn from_snapshot(
+        mut db: PersistentDb,
+        headers: &LightChain,
+        manifest: &SnapshotManifest,
+        chunks: &[Vec<u8>],
+    ) -> Result<PowChainState, &'static str> {
+        let checkpoint = match *headers.tip() {
+            PowBlock::Checkpoint(ref checkpoint) => checkpoint.clone(),
+            _ => return Err("The tip of the header chain is not a checkpoint block"),
+        };
+
+        if manifest.block_hash != checkpoint.block_hash().unwrap()
+            || manifest.height != checkpoint.height()
+        {
+            return Err("The snapshot does not belong to the checkpoint block");
+        }
+
+        let headers = headers.tip_state();
+        let state_root = &headers.state_root;
+
+        if manifest.state_root != *state_root {
+            return Err("Invalid state root");
+        }
+
+        if chunks.len() != manifest.chunks.len() {
+            return Err("Invalid chunks count");
+        }
+
+        let mut root = ShortHash::NULL_RLP;
+
+        {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            for (chunk, (checksum, size)) in chunks.iter().zip(manifest.chunks.iter()) {
+                if chunk.len() as u64 != *size || chunk_checksum(chunk) != *checksum {
+                    return Err("Invalid chunk");
+                }
+
+                let mut rdr = Cursor::new(chunk.as_slice());
+
+                while (rdr.position() as usize) < chunk.len() {
+                    let key_len =
+                        rdr.read_u16::<BigEndian>().map_err(|_| "Bad key length")? as usize;
+                    let key_start = rdr.position() as usize;
+
+                    if chunk.len() < key_start + key_len + 4 {
+                        return Err("Bad key length");
+                    }
+
+                    rdr.set_position((key_start + key_len) as u64);
+
+                    let value_len =
+                        rdr.read_u32::<BigEndian>()
+                            .map_err(|_| "Bad value length")? as usize;
+                    let value_start = rdr.position() as usize;
+
+                    if chunk.len() < value_start + value_len {
+                        return Err("Bad value length");
+                    }
+
+                    trie.insert(
+                        &chunk[key_start..(key_start + key_len)],
+                        &chunk[value_start..(value_start + value_len)],
+                    )
+                    .map_err(|_| "Could not write state trie")?;
+                    rdr.set_position((value_start + value_len) as u64);
+                }
+            }
+        }
+
+        if root != *state_root {
+            return Err("Invalid state root");
+        }
+
+        // Apply the checkpoint block to the metadata
+        Ok(PowChainState {
+            db,
+            height: checkpoint.height(),
+            difficulty: headers.difficulty,
+            edge_bits: headers.edge_bits,
+            accepts: BlockType::Transaction,
+            current_validator: Some(checkpoint.miner_id().clone()),
+            txs_blocks_left: Some(chain_spec().consensus.allowed_txs_blocks),
+            state_root: root,
+            last_checkpoint: headers.last_checkpoint.clone(),
+            total_work: headers.total_work,
+            block_times: headers.block_times.clone(),
+            validators: headers.validators.clone(),
+            upgrades: chain_spec().upgrades.clone(),
+            tx_statuses: Vec::new(),
+            touched_addresses: Vec::new(),
+        })
+    }
+}
+
+/// Computes the checksum of a chunk. This is the same as the
+/// checksum of a downloader piece with the same data, which
+/// hashes the checksums of its sub-pieces.
+pub fn chunk_checksum(chunk: &[u8]) -> ShortHash {
+    let mut hasher = BlakeHasher::new();
+
+    for sub_piece in chunk.chunks(MAX_SUB_PIECE_SIZE) {
+        hasher.write(&crypto::hash_slice(sub_piece).to_short().0);
+    }
+
+    let hash = encode_le_u64!(hasher.finish());
+    let mut hash_bytes = [0; crypto::SHORT_HASH_BYTES];
+    hash_bytes.copy_from_slice(&hash);
+
+    ShortHash(hash_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pow_chain::checkpoint_block::CheckpointBlock;
+    use crate::types::StateInterface;
+    use account::NormalAddress;
+    use crypto::{Identity, NodeId};
+    use miner::Proof;
+    use triomphe::Arc;
+
+    /// Returns the state after the first checkpoint block along
+    /// with a header chain whose tip is the checkpoint block.
+    fn checkpoint_state() -> (PowChainState, LightChain) {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+        let mut headers = LightChain::new(test_helpers::init_tempdb());
+        let identity = Identity::new();
+        let mut checkpoint = CheckpointBlock::new(
+            PowBlock::genesis().block_hash().unwrap(),
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            1,
+            Proof::test_proof(42),
+            NodeId(*identity.pkey()),
+        );
+        checkpoint.sign_miner(identity.skey());
+        checkpoint.compute_hash();
+
+        let checkpoint = Arc::new(checkpoint);
+        let state = CheckpointBlock::append_condition(
+            checkpoint.clone(),
+            state,
+            crate::types::BranchType::Canonical,
+        )
+        .unwrap();
+
+        headers
+            .append_header(Arc::new(PowBlock::Checkpoint(checkpoint)))
+            .unwrap();
+
+        (state, headers)
+    }
+
+    fn snapshot_chunks(snapshot: &StateSnapshot) -> Vec<Vec<u8>> {
+        snapshot
+            .manifest
+            .chunks
+            .iter()
+            .map(|(checksum, _)| snapshot.chunk(checksum).unwrap().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn it_restores_the_state_from_a_snapshot() {
+        let (state, headers) = checkpoint_state();
+        let snapshot = state.snapshot().unwrap();
+        let manifest = SnapshotManifest::from_bytes(&snapshot.manifest.to_bytes()).unwrap();
+        let chunks = snapshot_chunks(&snapshot);
+
+        assert_eq!(manifest, snapshot.manifest);
+
+        let restored =
+            PowChainState::from_snapshot(test_helpers::init_tempdb(), &headers, &manifest, &chunks)
+                .unwrap();
+
+        assert_eq!(restored.state_root(), state.state_root());
+        assert_eq!(restored.height(), 1);
+        assert_eq!(restored.current_validator, state.current_validator);
+        assert_eq!(restored.txs_blocks_left, state.txs_blocks_left);
+        assert_eq!(restored.last_checkpoint, state.last_checkpoint);
+        assert_eq!(restored.difficulty, state.difficulty);
+        assert_eq!(restored.edge_bits, state.edge_bits);
+        assert_eq!(restored.total_work, state.total_work);
+        assert_eq!(restored.validators, state.validators);
+    }
+
+    #[test]
+    fn it_rejects_invalid_snapshots() {
+        let (state, headers) = checkpoint_state();
+        let snapshot = state.snapshot().unwrap();
+        let mut manifest = snapshot.manifest.clone();
+        let mut chunks = snapshot_chunks(&snapshot);
+
+        // The header chain must end with the checkpoint block
+        assert_eq!(
+            PowChainState::from_snapshot(
+                test_helpers::init_tempdb(),
+                &LightChain::new(test_helpers::init_tempdb()),
+                &manifest,
+                &chunks,
+            ),
+            Err("The tip of the header chain is not a checkpoint block")
+        );
+
+        chunks[0][0] ^= 1;
+
+        assert_eq!(
+            PowChainState::from_snapshot(test_helpers::init_tempdb(), &headers, &manifest, &chunks,),
+            Err("Invalid chunk")
+        );
+
+        manifest.state_root = crypto::hash_slice(b"bad_root").to_short();
+
+        assert_eq!(
+            PowChainState::from_snapshot(test_helpers::init_tempdb(), &headers, &manifest, &chunks,),
+            Err("Invalid state root")
+        );
+    }
+
+    #[test]
+    fn it_only_takes_snapshots_at_checkpoint_blocks() {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+        assert!(state.snapshot().is_err());
+    }
+}
@@ -19,9 +19,10 @@
 use crate::block::Block;
 use crate::chain::*;
 use crate::pow_chain::chain_state::BlockType;
+use crate::pow_chain::merkle::{self, compute_tx_root, TxInclusionProof};
 use crate::pow_chain::PowChainState;
 use crate::types::*;
-use account::NormalAddress;
+use account::{Address, NormalAddress};
 use bin_tools::*;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use chrono::prelude::*;
@@ -42,7 +43,7 @@ use std::io::Cursor;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::str;
 use std::str::FromStr;
-use transactions::Tx;
+use transactions::{Feature, Tx};
 use triomphe::Arc;
 
 #[derive(Clone, Debug)]
@@ -104,6 +105,10 @@ impl HashTrait for TransactionBlock {
 impl Block for TransactionBlock {
     type ChainState = PowChainState;
 
+    fn checkpoint_interval() -> u64 {
+        unimplemented!();
+    }
+
     fn genesis() -> Arc<TransactionBlock> {
         unimplemented!();
     }
@@ -132,6 +137,17 @@ impl Block for TransactionBlock {
         self.timestamp.clone()
     }
 
+    fn transactions(&self) -> Vec<Arc<Tx>> {
+        match self.transactions {
+            Some(ref transactions) => transactions.read().clone(),
+            None => Vec::new(),
+        }
+    }
+
+    fn total_work(chain_state: &PowChainState) -> u128 {
+        chain_state.total_work
+    }
+
     fn after_write() -> Option<Box<dyn FnMut(Arc<TransactionBlock>)>> {
         let fun = |block| {};
         Some(Box::new(fun))
@@ -164,10 +180,17 @@ impl Block for TransactionBlock {
         assert!(chain_state.current_validator.is_some());
         assert!(chain_state.txs_blocks_left.is_some());
 
-        let current_validator = chain_state.current_validator.as_ref().unwrap().clone();
+        if chain_state.is_active(Feature::BlockTimestamps, block.height()) {
+            chain_state
+                .block_times
+                .validate(&block.timestamp, &Utc::now())
+                .map_err(ChainErr::BadAppendCondition)?;
+        }
+
         let mut txs_blocks_left = chain_state.txs_blocks_left.as_ref().unwrap().clone();
+        let slot_validator = chain_state.slot_validator(block.height(), &block.timestamp);
 
-        if current_validator != block.miner_id {
+        if slot_validator.as_ref() != Some(&block.miner_id) {
             return Err(ChainErr::BadAppendCondition(AppendCondErr::InvalidMiner));
         }
 
@@ -176,15 +199,35 @@ impl Block for TransactionBlock {
         }
 
         // Apply transactions to state
+        let mut tx_statuses = Vec::new();
+        let mut touched_addresses = Vec::new();
+
         if let Some(transaction_set) = &block.transactions {
             let transaction_set = transaction_set.read();
 
+            // Verify that the transaction set matches the root in the block header
+            if compute_tx_root(&transaction_set) != block.tx_root.unwrap() {
+                return Err(ChainErr::BadAppendCondition(AppendCondErr::BadTxRoot));
+            }
+
+            tx_statuses.reserve(transaction_set.len());
+
             for tx in transaction_set.iter() {
                 let validation_result = {
                     let trie =
                         TrieDB::<DbHasher, Codec>::new(&chain_state.db, &chain_state.state_root)
                             .unwrap();
-                    tx.validate(&trie)
+
+                    let is_active = |feature| chain_state.is_active(feature, block.height());
+
+                    if tx.validate(&trie, is_active) {
+                        // The sender must be resolved before the transaction
+                        // rotates its signing address.
+                        touched_addresses.extend(tx_touched_addresses(tx, &trie));
+                        true
+                    } else {
+                        false
+                    }
                 };
 
                 if validation_result {
@@ -193,7 +236,11 @@ impl Block for TransactionBlock {
                         &mut chain_state.state_root,
                     )
                     .unwrap();
-                    tx.apply(&mut trie);
+
+                    match tx.apply_metered(&mut trie, block.timestamp.timestamp()) {
+                        Ok(_) => tx_statuses.push(TxStatus::Success),
+                        Err(_) => tx_statuses.push(TxStatus::Failed),
+                    }
                 } else {
                     return Err(ChainErr::BadAppendCondition(AppendCondErr::BadTx));
                 }
@@ -208,6 +255,8 @@ impl Block for TransactionBlock {
         }
 
         // Commit
+        chain_state.tx_statuses = tx_statuses;
+        chain_state.touched_addresses = touched_addresses;
         txs_blocks_left -= 1;
 
         if txs_blocks_left == 0 {
@@ -219,6 +268,7 @@ impl Block for TransactionBlock {
         }
 
         chain_state.height = block.height();
+        chain_state.block_times.push(block.timestamp);
         Ok(chain_state)
     }
 
@@ -401,6 +451,27 @@ impl TransactionBlock {
         }
     }
 
+    /// Builds a proof that the transaction with the given hash is
+    /// included in the block. Returns `None` if the block doesn't
+    /// carry its transaction set or if the transaction is not in it.
+    pub fn tx_inclusion_proof(&self, tx_hash: &Hash) -> Option<TxInclusionProof> {
+        let transaction_set = self.transactions.as_ref()?.read();
+        let position = transaction_set
+            .iter()
+            .position(|tx| tx.tx_hash().as_ref() == Some(tx_hash))?;
+
+        merkle::tx_inclusion_proof(&transaction_set, position)
+    }
+
+    /// Verifies that the given transaction is included in
+    /// the block against the transaction root in its header.
+    pub fn verify_tx_inclusion(&self, tx: &Tx, proof: &TxInclusionProof) -> bool {
+        match self.tx_root {
+            Some(ref tx_root) => proof.verify(tx, tx_root),
+            None => false,
+        }
+    }
+
     pub fn sign_miner(&mut self, sk: &Sk) {
         let message = self.compute_sign_message();
         let sig = crypto::sign(&message, sk);
@@ -476,6 +547,59 @@ impl TransactionBlock {
     }
 }
 
+/// Returns the permanent addresses touched by the given transaction.
+///
+/// The permanent address of the sender is looked up through the
+/// mapping of its current signing address so this must be called
+/// before the transaction is applied.
+fn tx_touched_addresses(tx: &Tx, trie: &TrieDB<DbHasher, Codec>) -> Vec<TouchedAddress> {
+    let mut touched = Vec::new();
+    let tx_hash = match tx.tx_hash() {
+        Some(tx_hash) => tx_hash,
+        None => return touched,
+    };
+
+    // `<signing-address>.am`
+    let addr_mapping_key = [tx.creator_signing_address().as_bytes(), &b".am"[..]].concat();
+    let sender = match trie.get(&addr_mapping_key) {
+        Ok(Some(perm_addr)) => Address::from_bytes(&perm_addr).ok(),
+        _ => None,
+    };
+
+    let fee_hash = tx.fee_hash();
+    let asset_hash = tx.asset_hash().unwrap_or(fee_hash);
+
+    if let Some(sender) = sender {
+        touched.push(TouchedAddress {
+            address: sender,
+            tx_hash,
+            asset_hash,
+            direction: TxDirection::Outgoing,
+        });
+
+        // The fee is paid in a different asset
+        if asset_hash != fee_hash {
+            touched.push(TouchedAddress {
+                address: sender,
+                tx_hash,
+                asset_hash: fee_hash,
+                direction: TxDirection::Outgoing,
+            });
+        }
+    }
+
+    if let Some(receiver) = tx.receiver() {
+        touched.push(TouchedAddress {
+            address: receiver,
+            tx_hash,
+            asset_hash,
+            direction: TxDirection::Incoming,
+        });
+    }
+
+    touched
+}
+
 use quickcheck::*;
 use rand::prelude::*;
 
@@ -516,6 +640,546 @@ impl Arbitrary for TransactionBlock {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pow_chain::block::PowBlock;
+    use crate::pow_chain::block_time::MAX_FUTURE_BLOCK_TIME;
+    use crate::pow_chain::chain::PowChain;
+    use crate::pow_chain::checkpoint_block::{CheckpointBlock, ALLOWED_TXS_BLOCKS};
+    use crate::pow_chain::validator_set::SLOT_TIMEOUT;
+    use crate::spec::{ChainSpec, Upgrades};
+    use crypto::Identity;
+    use transactions::TestAccount;
+
+    #[test]
+    fn it_indexes_transactions_of_canonical_blocks() {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+        let mut chain = PowChain::new(test_helpers::init_tempdb(), state, true);
+        let identity = Identity::new();
+        let node_id = NodeId(*identity.pkey());
+
+        chain.set_tx_index(true);
+
+        let mut checkpoint = CheckpointBlock::new(
+            PowBlock::genesis().block_hash().unwrap(),
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            1,
+            Proof::test_proof(42),
+            node_id.clone(),
+        );
+        checkpoint.sign_miner(identity.skey());
+        checkpoint.compute_hash();
+
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+        chain
+            .append_block(Arc::new(PowBlock::Checkpoint(Arc::new(checkpoint))))
+            .unwrap();
+
+        let tx = Arc::new(transactions::send_coins(
+            TestAccount::A,
+            TestAccount::B,
+            100,
+            10,
+            1,
+        ));
+        let tx_hash = tx.tx_hash().unwrap();
+        let mut state = chain.canonical_tip_state();
+        state.apply_tx(tx.clone());
+
+        let mut block = TransactionBlock::new(
+            checkpoint_hash,
+            crate::random_socket_addr(),
+            2,
+            Proof::test_proof(42),
+            node_id,
+        );
+        block.tx_checksums = Some(Vec::new());
+        block.pieces_sizes = Some(Vec::new());
+        block.tx_root = Some(compute_tx_root(&[tx.clone()]));
+        block.state_root = Some(state.state_root());
+        block.transactions = Some(Arc::new(RwLock::new(vec![tx.clone()])));
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        let block_hash = block.block_hash().unwrap();
+        let block = Arc::new(block);
+
+        // The statuses are returned by the state transition
+        // so they do not change when the block is replayed.
+        for _ in 0..2 {
+            let new_state = TransactionBlock::append_condition(
+                block.clone(),
+                chain.canonical_tip_state(),
+                BranchType::Canonical,
+            )
+            .unwrap();
+
+            assert_eq!(new_state.tx_statuses(), &[TxStatus::Success][..]);
+        }
+
+        chain
+            .append_block(Arc::new(PowBlock::Transaction(block.clone())))
+            .unwrap();
+
+        let indexed = chain.query_tx(&tx_hash).unwrap();
+
+        assert_eq!(*indexed.tx, *tx);
+        assert_eq!(indexed.block_hash, block_hash);
+        assert_eq!(indexed.height, 2);
+        assert_eq!(indexed.position, 0);
+        assert_eq!(indexed.status, TxStatus::Success);
+
+        let (proof_block_hash, proof) = chain.tx_inclusion_proof(&tx_hash).unwrap();
+        assert_eq!(proof_block_hash, block_hash);
+        assert_eq!(block.tx_inclusion_proof(&tx_hash).unwrap(), proof);
+
+        // The proof verifies against the stored header which has no transactions
+        let header = match *chain.query(&block_hash).unwrap() {
+            PowBlock::Transaction(ref header) => header.clone(),
+            _ => panic!("Expected a transaction block"),
+        };
+
+        assert!(header.transactions.is_none());
+        assert!(header.verify_tx_inclusion(&tx, &proof));
+
+        // Rewinding removes the block's transactions from the index
+        chain.rewind(&checkpoint_hash).unwrap();
+        assert!(chain.query_tx(&tx_hash).is_none());
+    }
+
+    #[test]
+    fn it_rejects_blocks_with_a_bad_tx_root() {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+        let mut chain = PowChain::new(test_helpers::init_tempdb(), state, true);
+        let identity = Identity::new();
+        let node_id = NodeId(*identity.pkey());
+
+        let mut checkpoint = CheckpointBlock::new(
+            PowBlock::genesis().block_hash().unwrap(),
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            1,
+            Proof::test_proof(42),
+            node_id.clone(),
+        );
+        checkpoint.sign_miner(identity.skey());
+        checkpoint.compute_hash();
+
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+        chain
+            .append_block(Arc::new(PowBlock::Checkpoint(Arc::new(checkpoint))))
+            .unwrap();
+
+        let tx1 = Arc::new(transactions::send_coins(
+            TestAccount::A,
+            TestAccount::B,
+            100,
+            10,
+            1,
+        ));
+        let tx2 = Arc::new(transactions::send_coins(
+            TestAccount::A,
+            TestAccount::B,
+            100,
+            10,
+            2,
+        ));
+        let mut state = chain.canonical_tip_state();
+        state.apply_tx(tx1.clone());
+        state.apply_tx(tx2.clone());
+
+        // The root commits to the transactions in a different order
+        let mut block = TransactionBlock::new(
+            checkpoint_hash,
+            crate::random_socket_addr(),
+            2,
+            Proof::test_proof(42),
+            node_id,
+        );
+        block.tx_checksums = Some(Vec::new());
+        block.pieces_sizes = Some(Vec::new());
+        block.tx_root = Some(compute_tx_root(&[tx2.clone(), tx1.clone()]));
+        block.state_root = Some(state.state_root());
+        block.transactions = Some(Arc::new(RwLock::new(vec![tx1, tx2])));
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        assert_eq!(
+            chain.append_block(Arc::new(PowBlock::Transaction(Arc::new(block)))),
+            Err(ChainErr::BadAppendCondition(AppendCondErr::BadTxRoot))
+        );
+        assert_eq!(chain.canonical_tip_height(), 1);
+    }
+
+    #[test]
+    fn it_rejects_blocks_with_invalid_timestamps() {
+        let mut spec = ChainSpec::default();
+        spec.upgrades = Upgrades::all();
+
+        let state = PowChainState::genesis_with_spec(test_helpers::init_tempdb(), &spec);
+        let mut chain = PowChain::new(test_helpers::init_tempdb(), state, true);
+        let identity = Identity::new();
+        let node_id = NodeId(*identity.pkey());
+
+        let mut checkpoint = CheckpointBlock::new(
+            PowBlock::genesis().block_hash().unwrap(),
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            1,
+            Proof::test_proof(42),
+            node_id.clone(),
+        );
+        checkpoint.sign_miner(identity.skey());
+        checkpoint.compute_hash();
+
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+        let checkpoint_timestamp = checkpoint.timestamp();
+        chain
+            .append_block(Arc::new(PowBlock::Checkpoint(Arc::new(checkpoint))))
+            .unwrap();
+
+        let state_root = chain.canonical_tip_state().state_root();
+        let block_at = |timestamp: DateTime<Utc>| {
+            let mut block = TransactionBlock::new(
+                checkpoint_hash,
+                crate::random_socket_addr(),
+                2,
+                Proof::test_proof(42),
+                node_id.clone(),
+            );
+            block.timestamp = timestamp;
+            block.tx_checksums = Some(Vec::new());
+            block.pieces_sizes = Some(Vec::new());
+            block.tx_root = Some(compute_tx_root(&[]));
+            block.state_root = Some(state_root);
+            block.transactions = Some(Arc::new(RwLock::new(Vec::new())));
+            block.sign_miner(identity.skey());
+            block.compute_hash();
+
+            Arc::new(PowBlock::Transaction(Arc::new(block)))
+        };
+
+        assert_eq!(
+            chain.append_block(block_at(checkpoint_timestamp)),
+            Err(ChainErr::BadAppendCondition(AppendCondErr::TimestampTooOld))
+        );
+        assert_eq!(
+            chain.append_block(block_at(Utc::now() + chrono::Duration::hours(1))),
+            Err(ChainErr::BadAppendCondition(
+                AppendCondErr::TimestampInFuture
+            ))
+        );
+        assert_eq!(chain.canonical_tip_height(), 1);
+
+        chain.append_block(block_at(Utc::now())).unwrap();
+        assert_eq!(chain.canonical_tip_height(), 2);
+    }
+
+    #[test]
+    fn it_validates_timestamps_after_the_activation_height() {
+        let mut spec = ChainSpec::default();
+        spec.upgrades.schedule(Feature::BlockTimestamps, 3);
+
+        let state = PowChainState::genesis_with_spec(test_helpers::init_tempdb(), &spec);
+        let mut chain = PowChain::new(test_helpers::init_tempdb(), state, true);
+        let identity = Identity::new();
+        let node_id = NodeId(*identity.pkey());
+
+        let mut checkpoint = CheckpointBlock::new(
+            PowBlock::genesis().block_hash().unwrap(),
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            1,
+            Proof::test_proof(42),
+            node_id.clone(),
+        );
+        checkpoint.sign_miner(identity.skey());
+        checkpoint.compute_hash();
+
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+        let checkpoint_timestamp = checkpoint.timestamp();
+        chain
+            .append_block(Arc::new(PowBlock::Checkpoint(Arc::new(checkpoint))))
+            .unwrap();
+
+        let state_root = chain.canonical_tip_state().state_root();
+        let block_at = |parent_hash: Hash, height: u64, timestamp: DateTime<Utc>| {
+            let mut block = TransactionBlock::new(
+                parent_hash,
+                crate::random_socket_addr(),
+                height,
+                Proof::test_proof(42),
+                node_id.clone(),
+            );
+            block.timestamp = timestamp;
+            block.tx_checksums = Some(Vec::new());
+            block.pieces_sizes = Some(Vec::new());
+            block.tx_root = Some(compute_tx_root(&[]));
+            block.state_root = Some(state_root);
+            block.transactions = Some(Arc::new(RwLock::new(Vec::new())));
+            block.sign_miner(identity.skey());
+            block.compute_hash();
+
+            Arc::new(PowBlock::Transaction(Arc::new(block)))
+        };
+
+        // Timestamps are not validated before the activation height
+        let block = block_at(checkpoint_hash, 2, checkpoint_timestamp);
+        let block_hash = block.block_hash().unwrap();
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.canonical_tip_height(), 2);
+
+        let block = block_at(block_hash, 3, checkpoint_timestamp);
+        assert_eq!(
+            chain.append_block(block),
+            Err(ChainErr::BadAppendCondition(AppendCondErr::TimestampTooOld))
+        );
+        assert_eq!(chain.canonical_tip_height(), 2);
+
+        let block = block_at(block_hash, 3, Utc::now());
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.canonical_tip_height(), 3);
+    }
+
+    fn checkpoint_by(
+        parent_hash: Hash,
+        height: u64,
+        identity: &Identity,
+        timestamp: DateTime<Utc>,
+    ) -> Arc<PowBlock> {
+        let mut checkpoint = CheckpointBlock::new(
+            parent_hash,
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            height,
+            Proof::test_proof(42),
+            NodeId(*identity.pkey()),
+        );
+        checkpoint.timestamp = timestamp;
+        checkpoint.sign_miner(identity.skey());
+        checkpoint.compute_hash();
+
+        Arc::new(PowBlock::Checkpoint(Arc::new(checkpoint)))
+    }
+
+    fn empty_block_by(
+        parent_hash: Hash,
+        height: u64,
+        identity: &Identity,
+        timestamp: DateTime<Utc>,
+        state_root: ShortHash,
+    ) -> Arc<PowBlock> {
+        let mut block = TransactionBlock::new(
+            parent_hash,
+            crate::random_socket_addr(),
+            height,
+            Proof::test_proof(42),
+            NodeId(*identity.pkey()),
+        );
+        block.timestamp = timestamp;
+        block.tx_checksums = Some(Vec::new());
+        block.pieces_sizes = Some(Vec::new());
+        block.tx_root = Some(compute_tx_root(&[]));
+        block.state_root = Some(state_root);
+        block.transactions = Some(Arc::new(RwLock::new(Vec::new())));
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        Arc::new(PowBlock::Transaction(Arc::new(block)))
+    }
+
+    /// Initializes a chain whose last checkpoint block is mined by
+    /// `other` and the previous one by `miner`. Blocks are dated one
+    /// second apart, starting at the given time.
+    fn init_rotation_chain(miner: &Identity, other: &Identity, start: DateTime<Utc>) -> PowChain {
+        let mut spec = ChainSpec::default();
+        spec.upgrades = Upgrades::all();
+
+        let state = PowChainState::genesis_with_spec(test_helpers::init_tempdb(), &spec);
+        let mut chain = PowChain::new(test_helpers::init_tempdb(), state, true);
+        let state_root = chain.canonical_tip_state().state_root();
+        let last_height = ALLOWED_TXS_BLOCKS as u64 + 2;
+        let mut parent_hash = PowBlock::genesis().block_hash().unwrap();
+
+        for height in 1..=last_height {
+            let timestamp = start + chrono::Duration::seconds(height as i64);
+            let block = if height == 1 {
+                checkpoint_by(parent_hash, height, miner, timestamp)
+            } else if height == last_height {
+                checkpoint_by(parent_hash, height, other, timestamp)
+            } else {
+                empty_block_by(parent_hash, height, miner, timestamp, state_root)
+            };
+
+            parent_hash = block.block_hash().unwrap();
+            chain.append_block(block).unwrap();
+        }
+
+        chain
+    }
+
+    #[test]
+    fn it_rotates_slots_between_the_latest_checkpoint_miners() {
+        let miner = Identity::new();
+        let other = Identity::new();
+        let start = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let mut chain = init_rotation_chain(&miner, &other, start);
+        let state_root = chain.canonical_tip_state().state_root();
+        let height = chain.canonical_tip_height();
+        let mut parent_hash = chain.canonical_tip().block_hash().unwrap();
+        let timestamp = start + chrono::Duration::seconds(height as i64 + 1);
+
+        // The first slot belongs to the miner of the last checkpoint
+        let block = empty_block_by(parent_hash, height + 1, &miner, timestamp, state_root);
+        assert_eq!(
+            chain.append_block(block),
+            Err(ChainErr::BadAppendCondition(AppendCondErr::InvalidMiner))
+        );
+
+        let block = empty_block_by(parent_hash, height + 1, &other, timestamp, state_root);
+        parent_hash = block.block_hash().unwrap();
+        chain.append_block(block).unwrap();
+
+        // The next slot belongs to the miner of the previous checkpoint
+        let next = timestamp + chrono::Duration::seconds(1);
+
+        assert_eq!(
+            chain
+                .canonical_tip_state()
+                .slot_validator(height + 2, &next),
+            Some(NodeId(*miner.pkey()))
+        );
+
+        let block = empty_block_by(parent_hash, height + 2, &other, next, state_root);
+        assert_eq!(
+            chain.append_block(block),
+            Err(ChainErr::BadAppendCondition(AppendCondErr::InvalidMiner))
+        );
+
+        // Until it times out
+        let timed_out = timestamp + chrono::Duration::seconds(SLOT_TIMEOUT);
+        let block = empty_block_by(parent_hash, height + 2, &other, timed_out, state_root);
+        chain.append_block(block).unwrap();
+        assert_eq!(chain.canonical_tip_height(), height + 2);
+    }
+
+    #[test]
+    fn it_detects_handoffs_before_the_local_timeout() {
+        let miner = Identity::new();
+        let other = Identity::new();
+        let start = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let mut chain = init_rotation_chain(&miner, &other, start);
+        let state_root = chain.canonical_tip_state().state_root();
+        let height = chain.canonical_tip_height();
+        let parent_hash = chain.canonical_tip().block_hash().unwrap();
+        let timestamp = start + chrono::Duration::seconds(height as i64 + 1);
+
+        let block = empty_block_by(parent_hash, height + 1, &other, timestamp, state_root);
+        chain.append_block(block).unwrap();
+
+        // The next slot belongs to `miner`, which may still be online
+        // when a block dated after the timeout is received.
+        let state = chain.canonical_tip_state();
+        let timed_out = timestamp + chrono::Duration::seconds(SLOT_TIMEOUT);
+        let received_at = timed_out - chrono::Duration::seconds(MAX_FUTURE_BLOCK_TIME);
+
+        assert_eq!(
+            state.slot_validator(height + 2, &timed_out),
+            Some(NodeId(*other.pkey()))
+        );
+        assert!(state.is_early_handoff(height + 2, &timed_out, &received_at));
+        assert!(!state.is_early_handoff(height + 2, &timed_out, &timed_out));
+        assert!(!state.is_early_handoff(height + 2, &timestamp, &received_at));
+    }
+
+    #[test]
+    fn it_indexes_address_histories() {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+        let mut chain = PowChain::new(test_helpers::init_tempdb(), state, true);
+        let identity = Identity::new();
+        let node_id = NodeId(*identity.pkey());
+        let sender = Address::Normal(TestAccount::A.to_perm_address());
+        let receiver = Address::Normal(TestAccount::B.to_perm_address());
+
+        chain.set_address_index(true);
+
+        let mut checkpoint = CheckpointBlock::new(
+            PowBlock::genesis().block_hash().unwrap(),
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            1,
+            Proof::test_proof(42),
+            node_id.clone(),
+        );
+        checkpoint.sign_miner(identity.skey());
+        checkpoint.compute_hash();
+
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+        chain
+            .append_block(Arc::new(PowBlock::Checkpoint(Arc::new(checkpoint))))
+            .unwrap();
+
+        // The second transaction is signed with the rotated signing address
+        let tx1 = Arc::new(transactions::send_coins(
+            TestAccount::A,
+            TestAccount::B,
+            100,
+            10,
+            1,
+        ));
+        let tx2 = Arc::new(transactions::send_coins(
+            TestAccount::A,
+            TestAccount::B,
+            50,
+            10,
+            2,
+        ));
+        let mut state = chain.canonical_tip_state();
+        state.apply_tx(tx1.clone());
+        state.apply_tx(tx2.clone());
+
+        let mut block = TransactionBlock::new(
+            checkpoint_hash,
+            crate::random_socket_addr(),
+            2,
+            Proof::test_proof(42),
+            node_id,
+        );
+        block.tx_checksums = Some(Vec::new());
+        block.pieces_sizes = Some(Vec::new());
+        block.tx_root = Some(compute_tx_root(&[tx1.clone(), tx2.clone()]));
+        block.state_root = Some(state.state_root());
+        block.transactions = Some(Arc::new(RwLock::new(vec![tx1.clone(), tx2.clone()])));
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        let block_hash = block.block_hash().unwrap();
+        chain
+            .append_block(Arc::new(PowBlock::Transaction(Arc::new(block))))
+            .unwrap();
+
+        assert_eq!(chain.address_history_len(&sender), 2);
+        assert_eq!(chain.address_history_len(&receiver), 2);
+
+        let sender_history = chain.address_history(&sender, 0, 10);
+        assert_eq!(sender_history.len(), 2);
+        assert_eq!(sender_history[0].tx_hash, tx2.tx_hash().unwrap());
+        assert_eq!(sender_history[0].block_hash, block_hash);
+        assert_eq!(sender_history[0].height, 2);
+        assert_eq!(sender_history[0].direction, TxDirection::Outgoing);
+        assert_eq!(sender_history[1].tx_hash, tx1.tx_hash().unwrap());
+
+        // Paginated queries
+        let page = chain.address_history(&receiver, 1, 1);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].tx_hash, tx1.tx_hash().unwrap());
+        assert_eq!(page[0].direction, TxDirection::Incoming);
+        assert!(chain.address_history(&receiver, 2, 1).is_empty());
+
+        // Rewinding removes the block's entries from the histories
+        chain.rewind(&checkpoint_hash).unwrap();
+        assert_eq!(chain.address_history_len(&sender), 0);
+        assert!(chain.address_history(&receiver, 0, 10).is_empty());
+    }
 
     quickcheck! {
         fn serialize_deserialize(block: Arc<TransactionBlock>) -> bool {
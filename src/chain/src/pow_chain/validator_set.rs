@@ -0,0 +1,289 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The set of validators which append transaction blocks.
+//!
+//! The validator set is made of the miners of the latest
+//! `VALIDATOR_SET_SIZE` checkpoint blocks. Each transaction block
+//! following a checkpoint block has its own slot and the slots are
+//! assigned round-robin, starting with the miner of the checkpoint
+//! block and going back in time. A miner of several of the latest
+//! checkpoint blocks appears as many times in the rotation, so slots
+//! are weighted by the work done by each miner.
+//!
+//! If the validator of a slot does not produce its block within
+//! `SLOT_TIMEOUT` seconds after the previous block, the slot is
+//! handed to the next validator in the rotation. This way a single
+//! offline or censoring validator cannot stall the chain.
+//!
+//! Handoffs are computed from block timestamps only, so that the
+//! validator of a slot does not depend on when the block is applied.
+//! Since block timestamps may be ahead of the local clock, a validator
+//! could still take over the slot of an online validator by dating its
+//! block in the future. Blocks received from the network which do so
+//! are therefore held back until the slot has also timed out on the
+//! local clock, which is a local acceptance policy and not a consensus
+//! rule.
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use chrono::prelude::*;
+use crypto::NodeId;
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+/// The number of latest checkpoint blocks whose miners
+/// make up the validator set.
+pub const VALIDATOR_SET_SIZE: usize = 5;
+
+/// How many seconds the validator of a slot has to produce
+/// its block before the slot is handed to the next validator.
+/// This must be greater than `MAX_FUTURE_BLOCK_TIME`.
+pub const SLOT_TIMEOUT: i64 = 3 * 60;
+
+#[derive(Clone, Debug, PartialEq)]
+/// The miners of the latest checkpoint blocks of a chain.
+pub struct ValidatorSet {
+    /// The miners of the latest `VALIDATOR_SET_SIZE` checkpoint
+    /// blocks, ordered by height.
+    miners: VecDeque<NodeId>,
+}
+
+impl ValidatorSet {
+    pub fn new() -> ValidatorSet {
+        ValidatorSet {
+            miners: VecDeque::with_capacity(VALIDATOR_SET_SIZE),
+        }
+    }
+
+    /// Adds the miner of a newly appended checkpoint block,
+    /// removing the miner of the oldest one if the set is full.
+    pub fn push(&mut self, miner_id: NodeId) {
+        if self.miners.len() == VALIDATOR_SET_SIZE {
+            self.miners.pop_front();
+        }
+
+        self.miners.push_back(miner_id);
+    }
+
+    /// Returns the miners of the latest checkpoint blocks, ordered by height.
+    pub fn miners(&self) -> impl Iterator<Item = &NodeId> {
+        self.miners.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.miners.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.miners.is_empty()
+    }
+
+    /// Returns the validator of the given slot, counted from the last
+    /// checkpoint block, once the slot has been handed off the given
+    /// number of times. Returns `None` if the set is empty.
+    pub fn slot_validator(&self, slot: u32, handoffs: u64) -> Option<&NodeId> {
+        if self.miners.is_empty() {
+            return None;
+        }
+
+        let len = self.miners.len() as u64;
+        let offset = (slot as u64 + handoffs) % len;
+
+        // Start with the miner of the last checkpoint block
+        self.miners.get((len - 1 - offset) as usize)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::new();
+
+        // Structure:
+        // 1) Miners count   - 8bits
+        // 2) Miners         - 256bits each
+        buf.write_u8(self.miners.len() as u8).unwrap();
+
+        for miner_id in self.miners.iter() {
+            buf.extend_from_slice(&(miner_id.0).0);
+        }
+
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ValidatorSet, &'static str> {
+        let mut rdr = Cursor::new(bytes);
+        let mut validator_set = ValidatorSet::new();
+
+        let miners_count = if let Ok(result) = rdr.read_u8() {
+            result as usize
+        } else {
+            return Err("Bad miners count");
+        };
+
+        if miners_count > VALIDATOR_SET_SIZE {
+            return Err("Too many miners");
+        }
+
+        if bytes.len() != 1 + miners_count * 32 {
+            return Err("Invalid validator set length");
+        }
+
+        for miner_id in bytes[1..].chunks(32) {
+            validator_set.push(NodeId::from_bytes(miner_id)?);
+        }
+
+        Ok(validator_set)
+    }
+}
+
+/// Returns how many times the slot following a block with the
+/// given timestamp has been handed off at the given time.
+pub fn slot_handoffs(previous: &DateTime<Utc>, timestamp: &DateTime<Utc>) -> u64 {
+    let elapsed = timestamp.signed_duration_since(*previous).num_seconds();
+
+    if elapsed <= 0 {
+        0
+    } else {
+        (elapsed / SLOT_TIMEOUT) as u64
+    }
+}
+
+/// Returns `true` if a block with the given timestamp, received at
+/// the given local time, takes over the slot following a block with
+/// the `previous` timestamp before it has timed out on the local clock.
+pub fn is_early_handoff(
+    previous: &DateTime<Utc>,
+    timestamp: &DateTime<Utc>,
+    now: &DateTime<Utc>,
+) -> bool {
+    slot_handoffs(previous, timestamp) > slot_handoffs(previous, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pow_chain::block_time::MAX_FUTURE_BLOCK_TIME;
+    use chrono::Duration;
+    use crypto::Identity;
+
+    fn node_id() -> NodeId {
+        NodeId(*Identity::new().pkey())
+    }
+
+    #[test]
+    fn it_assigns_slots_round_robin() {
+        let mut validator_set = ValidatorSet::new();
+        let miners: Vec<NodeId> = (0..3).map(|_| node_id()).collect();
+
+        assert_eq!(validator_set.slot_validator(0, 0), None);
+
+        for miner_id in miners.iter() {
+            validator_set.push(miner_id.clone());
+        }
+
+        assert_eq!(validator_set.slot_validator(0, 0), Some(&miners[2]));
+        assert_eq!(validator_set.slot_validator(1, 0), Some(&miners[1]));
+        assert_eq!(validator_set.slot_validator(2, 0), Some(&miners[0]));
+        assert_eq!(validator_set.slot_validator(3, 0), Some(&miners[2]));
+
+        // Timed out slots are handed to the next validator
+        assert_eq!(validator_set.slot_validator(0, 1), Some(&miners[1]));
+        assert_eq!(validator_set.slot_validator(1, 2), Some(&miners[2]));
+    }
+
+    #[test]
+    fn it_only_keeps_the_latest_miners() {
+        let mut validator_set = ValidatorSet::new();
+        let miners: Vec<NodeId> = (0..(VALIDATOR_SET_SIZE + 2)).map(|_| node_id()).collect();
+
+        for miner_id in miners.iter() {
+            validator_set.push(miner_id.clone());
+        }
+
+        assert_eq!(validator_set.len(), VALIDATOR_SET_SIZE);
+        assert!(validator_set.miners().eq(miners[2..].iter()));
+        assert_eq!(
+            ValidatorSet::from_bytes(&validator_set.to_bytes()),
+            Ok(validator_set)
+        );
+    }
+
+    #[test]
+    fn it_weights_slots_by_checkpoint_blocks() {
+        let mut validator_set = ValidatorSet::new();
+        let miner = node_id();
+        let other = node_id();
+
+        validator_set.push(miner.clone());
+        validator_set.push(other.clone());
+        validator_set.push(miner.clone());
+
+        let slots: Vec<&NodeId> = (0..3)
+            .map(|slot| validator_set.slot_validator(slot, 0).unwrap())
+            .collect();
+
+        assert_eq!(slots, vec![&miner, &other, &miner]);
+    }
+
+    #[test]
+    fn it_counts_slot_handoffs() {
+        let previous = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+
+        assert_eq!(slot_handoffs(&previous, &previous), 0);
+        assert_eq!(
+            slot_handoffs(&previous, &(previous - Duration::seconds(SLOT_TIMEOUT))),
+            0
+        );
+        assert_eq!(
+            slot_handoffs(&previous, &(previous + Duration::seconds(SLOT_TIMEOUT - 1))),
+            0
+        );
+        assert_eq!(
+            slot_handoffs(&previous, &(previous + Duration::seconds(SLOT_TIMEOUT))),
+            1
+        );
+        assert_eq!(
+            slot_handoffs(&previous, &(previous + Duration::seconds(3 * SLOT_TIMEOUT))),
+            3
+        );
+    }
+
+    #[test]
+    fn it_detects_handoffs_before_the_local_timeout() {
+        let previous = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let at = |seconds: i64| previous + Duration::seconds(seconds);
+
+        // The block is dated after the timeout but received before it
+        assert!(is_early_handoff(
+            &previous,
+            &at(SLOT_TIMEOUT),
+            &at(SLOT_TIMEOUT - 1)
+        ));
+        assert!(!is_early_handoff(
+            &previous,
+            &at(SLOT_TIMEOUT),
+            &at(SLOT_TIMEOUT)
+        ));
+        assert!(!is_early_handoff(
+            &previous,
+            &at(SLOT_TIMEOUT - 1),
+            &previous
+        ));
+
+        // Slots cannot time out within the drift allowed for timestamps
+        assert!(SLOT_TIMEOUT > MAX_FUTURE_BLOCK_TIME);
+    }
+}
@@ -0,0 +1,449 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Offline verification of the pow chain stored on disk.
+//!
+//! Loading a chain replays the blocks written after the root block,
+//! whose state is the one flushed to the state database, and panics
+//! if the ledger is inconsistent, which may happen if the node has
+//! crashed while flushing. The chain is therefore verified directly
+//! against the databases, without loading it.
+
+use crate::block::Block;
+use crate::chain::*;
+use crate::pow_chain::block::PowBlock;
+use crate::pow_chain::chain::PowChain;
+use crate::pow_chain::chain_state::PowChainState;
+use crate::pow_chain::light_chain::HeaderState;
+use crate::pow_chain::merkle::compute_tx_root;
+use crate::types::{BranchType, StateInterface};
+use crypto::Hash;
+use parking_lot::RwLock;
+use persistence::PersistentDb;
+use triomphe::Arc;
+
+#[derive(Clone, Debug, PartialEq)]
+/// The first inconsistency found while verifying a chain.
+pub struct Inconsistency {
+    /// The height of the first inconsistent block.
+    pub height: u64,
+
+    /// What is wrong with the block.
+    pub reason: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The outcome of verifying the chain stored on disk.
+pub struct ChainVerification {
+    /// The number of verified blocks after the root block.
+    pub verified: u64,
+
+    /// The hash of the last block which passed verification.
+    pub last_good_hash: Hash,
+
+    /// The height of the last block which passed verification.
+    pub last_good_height: u64,
+
+    /// The first inconsistency or `None` if the chain is valid.
+    pub inconsistency: Option<Inconsistency>,
+
+    /// Whether the state of every verified block has been recomputed.
+    /// Transactions are only stored in the transaction index so this
+    /// is `false` if a transaction set could not be found, after which
+    /// only the block headers are verified, as a light client would.
+    pub state_verified: bool,
+}
+
+/// Verifies the canonical chain stored in the given ledger, walking
+/// from the root block to the canonical tip. Each block is re-parsed
+/// and its hash, miner signature, height and parent are checked. The
+/// blocks are then applied, starting from the root state stored in
+/// the given state database, which verifies their proofs of work and
+/// recomputes their state roots. If the transactions of a block cannot
+/// be found, it and the following blocks are only validated as headers,
+/// which still checks their proofs of work and validator rights.
+/// `progress` is called with the height of each verified block.
+///
+/// Verification stops at the first inconsistent block. Errors are only
+/// returned if the root block or the root state cannot be read, since
+/// the chain cannot be rewound past them anyway.
+pub fn verify_chain(
+    chain_db: &PersistentDb,
+    state_db: PersistentDb,
+    mut progress: impl FnMut(u64),
+) -> Result<ChainVerification, &'static str> {
+    let tip_hash = match chain_db.retrieve(&TIP_KEY) {
+        Some(tip_hash) => PowChain::decode_hash(&tip_hash).ok_or("Invalid canonical tip hash")?,
+        None => PowBlock::genesis().block_hash().unwrap(),
+    };
+
+    let tip_height = match chain_db.retrieve(&CANONICAL_HEIGHT_KEY) {
+        Some(height) => decode_be_u64!(&height).map_err(|_| "Invalid canonical height")?,
+        None => 0,
+    };
+
+    let mut root_block = match chain_db.retrieve(&ROOT_KEY) {
+        Some(root_hash) => {
+            let root_hash = PowChain::decode_hash(&root_hash).ok_or("Invalid root block hash")?;
+            read_block(chain_db, &root_hash).map_err(|_| "Could not read the root block")?
+        }
+        None => PowBlock::genesis(),
+    };
+
+    let mut state = if state_db.retrieve(PersistentDb::RELOAD_FLAG).is_some() {
+        PowChainState::reload(state_db).map_err(|_| "Could not reload the root state")?
+    } else {
+        PowChainState::genesis(state_db)
+    };
+
+    // The root state is flushed before the root block key so
+    // if we stopped in between, the root block is the canonical
+    // block at the height of the root state.
+    if state.height() > root_block.height() {
+        let root_hash = canonical_hash(chain_db, state.height())
+            .map_err(|_| "Could not find the block of the root state")?;
+
+        root_block =
+            read_block(chain_db, &root_hash).map_err(|_| "Could not read the root block")?;
+    }
+
+    if state.height() != root_block.height() {
+        return Err("The root state does not belong to the root block");
+    }
+
+    let mut verification = ChainVerification {
+        verified: 0,
+        last_good_hash: root_block.block_hash().unwrap(),
+        last_good_height: root_block.height(),
+        inconsistency: None,
+        state_verified: true,
+    };
+
+    let mut parent = root_block;
+    let mut state = Some(state);
+    let mut headers = None;
+
+    for height in (parent.height() + 1)..=tip_height {
+        match verify_block(chain_db, &parent, height, &mut state, &mut headers) {
+            Ok(block) => {
+                verification.verified += 1;
+                verification.last_good_hash = block.block_hash().unwrap();
+                verification.last_good_height = height;
+                progress(height);
+                parent = block;
+            }
+            Err(reason) => {
+                verification.inconsistency = Some(Inconsistency { height, reason });
+                break;
+            }
+        }
+    }
+
+    if verification.inconsistency.is_none() && verification.last_good_hash != tip_hash {
+        verification.inconsistency = Some(Inconsistency {
+            height: tip_height,
+            reason: "The canonical tip does not match the canonical height",
+        });
+    }
+
+    verification.state_verified = state.is_some();
+    Ok(verification)
+}
+
+/// Rewinds the canonical tip of the given ledger to the last block
+/// which passed verification. Returns the number of deleted blocks.
+pub fn rewind_to_last_good(
+    chain_db: &mut PersistentDb,
+    verification: &ChainVerification,
+) -> Result<u64, &'static str> {
+    PowChain::rewind_ledger(
+        chain_db,
+        &verification.last_good_hash,
+        verification.last_good_height,
+    )
+}
+
+/// Verifies the canonical block at the given height and returns it.
+///
+/// If a state is given, the block is applied to it. The state is
+/// discarded if the transactions of the block cannot be found, in
+/// which case the block is validated against the header state of
+/// its parent, as are the following blocks.
+fn verify_block(
+    chain_db: &PersistentDb,
+    parent: &Arc<PowBlock>,
+    height: u64,
+    state: &mut Option<PowChainState>,
+    headers: &mut Option<HeaderState>,
+) -> Result<Arc<PowBlock>, &'static str> {
+    let block_hash = canonical_hash(chain_db, height)?;
+    let block = read_block(chain_db, &block_hash)?;
+
+    let stored_height = chain_db
+        .retrieve(&PowChain::compute_height_key(&block_hash).0)
+        .ok_or("Missing block height")?;
+
+    if decode_be_u64!(&stored_height).ok() != Some(height) || block.height() != height {
+        return Err("Bad block height");
+    }
+
+    if Some(block.parent_hash()) != parent.block_hash() {
+        return Err("The block is not a child of the previous canonical block");
+    }
+
+    let valid_sig = match *block {
+        PowBlock::Genesis => return Err("Unexpected genesis block"),
+        PowBlock::Checkpoint(ref block) => block.verify_miner_sig(),
+        PowBlock::Transaction(ref block) => block.verify_miner_sig(),
+    };
+
+    if !valid_sig {
+        return Err("Invalid miner signature");
+    }
+
+    let chain_state = match state.take() {
+        Some(chain_state) => chain_state,
+        None => {
+            let header_state = headers.take().unwrap();
+            *headers = Some(verify_header(header_state, block.clone())?);
+            return Ok(block);
+        }
+    };
+
+    let full_block = match *block {
+        PowBlock::Transaction(ref tx_block) => {
+            let transactions = PowChain::read_block_transactions(chain_db, &block_hash);
+
+            // Empty transaction sets are not indexed so the
+            // transactions are missing only if the roots differ.
+            if compute_tx_root(&transactions) != tx_block.tx_root.unwrap() {
+                debug!(
+                    "Could not find the transactions of block {}, only verifying headers from now on",
+                    height
+                );
+
+                let header_state = HeaderState::from_chain_state(parent.clone(), &chain_state);
+                *headers = Some(verify_header(header_state, block.clone())?);
+                return Ok(block);
+            }
+
+            let mut tx_block = (**tx_block).clone();
+            tx_block.transactions = Some(Arc::new(RwLock::new(transactions)));
+            Arc::new(PowBlock::Transaction(Arc::new(tx_block)))
+        }
+        _ => block.clone(),
+    };
+
+    match PowBlock::append_condition(full_block, chain_state, BranchType::Canonical) {
+        Ok(chain_state) => {
+            *state = Some(chain_state);
+            Ok(block)
+        }
+        Err(err) => {
+            debug!(
+                "Block {} does not satisfy the append condition: {:?}",
+                height, err
+            );
+            Err(describe_err(err))
+        }
+    }
+}
+
+/// Validates the given header against the header state of its parent,
+/// which checks the proof of work of checkpoint blocks and the validator
+/// rights of transaction blocks, and returns the header state of the block.
+fn verify_header(
+    header_state: HeaderState,
+    block: Arc<PowBlock>,
+) -> Result<HeaderState, &'static str> {
+    let height = block.height();
+
+    header_state.apply(block).map_err(|err| {
+        debug!("Block {} is not a valid header: {:?}", height, err);
+        describe_err(err)
+    })
+}
+
+fn canonical_hash(chain_db: &PersistentDb, height: u64) -> Result<Hash, &'static str> {
+    let height_key = crypto::hash_slice(&encode_be_u64!(height));
+    let block_hash = chain_db
+        .retrieve(&height_key.0)
+        .ok_or("Missing canonical block")?;
+
+    PowChain::decode_hash(&block_hash).ok_or("Invalid canonical block hash")
+}
+
+/// Reads the block with the given hash and checks
+/// that it is stored under its recomputed hash.
+fn read_block(chain_db: &PersistentDb, block_hash: &Hash) -> Result<Arc<PowBlock>, &'static str> {
+    let bytes = chain_db.retrieve(&block_hash.0).ok_or("Missing block")?;
+    let block = PowBlock::from_bytes(&bytes).map_err(|_| "Could not parse block")?;
+
+    // The hash is recomputed when parsing the block
+    if block.block_hash().as_ref() != Some(block_hash) {
+        return Err("Block hash mismatch");
+    }
+
+    Ok(block)
+}
+
+fn describe_err(err: ChainErr) -> &'static str {
+    match err {
+        ChainErr::BadAppendCondition(AppendCondErr::BadProof) => "Invalid proof of work",
+        ChainErr::BadAppendCondition(AppendCondErr::BadStateRoot) => "State root mismatch",
+        ChainErr::BadAppendCondition(AppendCondErr::BadTxRoot) => "Transaction root mismatch",
+        ChainErr::BadAppendCondition(AppendCondErr::BadTx) => "Invalid transaction",
+        ChainErr::BadAppendCondition(AppendCondErr::TimestampTooOld) => "Invalid timestamp",
+        ChainErr::BadAppendCondition(AppendCondErr::InvalidMiner) => "Invalid validator",
+        _ => "The block cannot be applied to the state of its parent",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pow_chain::checkpoint_block::CheckpointBlock;
+    use crate::pow_chain::transaction_block::TransactionBlock;
+    use crate::test_helpers::{init_chain_with_blocks, init_chain_with_blocks_mined_by};
+    use account::NormalAddress;
+    use crypto::{Identity, NodeId};
+    use miner::Proof;
+
+    #[test]
+    fn it_verifies_the_stored_chain() {
+        let (chain, blocks) = init_chain_with_blocks();
+        let chain_db = chain.get_db_and_state_root().0;
+        let mut verified_heights = Vec::new();
+
+        let verification = verify_chain(&chain_db, test_helpers::init_tempdb(), |h| {
+            verified_heights.push(h)
+        })
+        .unwrap();
+
+        assert_eq!(verification.verified, 2);
+        assert_eq!(verification.last_good_hash, blocks[1].block_hash().unwrap());
+        assert_eq!(verification.last_good_height, 2);
+        assert_eq!(verification.inconsistency, None);
+        assert!(verification.state_verified);
+        assert_eq!(verified_heights, vec![1, 2]);
+    }
+
+    #[test]
+    fn it_reports_the_first_inconsistency_and_rewinds_to_it() {
+        let (chain, blocks) = init_chain_with_blocks();
+        let mut chain_db = chain.get_db_and_state_root().0;
+
+        // Store the first block under the hash of the second one
+        chain_db.put(&blocks[1].block_hash().unwrap().0, &blocks[0].to_bytes());
+        chain_db.flush();
+
+        let verification = verify_chain(&chain_db, test_helpers::init_tempdb(), |_| {}).unwrap();
+
+        assert_eq!(verification.verified, 1);
+        assert_eq!(verification.last_good_hash, blocks[0].block_hash().unwrap());
+        assert_eq!(verification.last_good_height, 1);
+        assert_eq!(
+            verification.inconsistency,
+            Some(Inconsistency {
+                height: 2,
+                reason: "Block hash mismatch",
+            })
+        );
+
+        assert_eq!(rewind_to_last_good(&mut chain_db, &verification), Ok(1));
+
+        let verification = verify_chain(&chain_db, test_helpers::init_tempdb(), |_| {}).unwrap();
+
+        assert_eq!(verification.verified, 1);
+        assert_eq!(verification.last_good_height, 1);
+        assert_eq!(verification.inconsistency, None);
+        assert!(verification.state_verified);
+    }
+
+    #[test]
+    fn it_verifies_the_proofs_of_work_of_headers() {
+        let identity = Identity::new();
+        let node_id = NodeId(*identity.pkey());
+        let (_, blocks) = init_chain_with_blocks_mined_by(&identity);
+        let chain = crate::test_helpers::init_test_chains();
+
+        // The transactions are not indexed before the index is enabled
+        chain.append_block(blocks[0].clone()).unwrap();
+        chain.append_block(blocks[1].clone()).unwrap();
+
+        let mut block = TransactionBlock::new(
+            blocks[1].block_hash().unwrap(),
+            crate::random_socket_addr(),
+            3,
+            Proof::test_proof(42),
+            node_id.clone(),
+        );
+        block.tx_checksums = Some(Vec::new());
+        block.pieces_sizes = Some(Vec::new());
+        block.tx_root = Some(compute_tx_root(&[]));
+        block.state_root = Some(chain.canonical_tip_state().state_root());
+        block.transactions = Some(Arc::new(RwLock::new(Vec::new())));
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        let block_hash = block.block_hash().unwrap();
+        chain
+            .append_block(Arc::new(PowBlock::Transaction(Arc::new(block))))
+            .unwrap();
+
+        // Store a checkpoint block with an invalid proof of work
+        let mut checkpoint = CheckpointBlock::new(
+            block_hash,
+            NormalAddress::random(),
+            crate::random_socket_addr(),
+            4,
+            Proof::zero(42),
+            node_id,
+        );
+        checkpoint.sign_miner(identity.skey());
+        checkpoint.compute_hash();
+
+        let checkpoint_hash = checkpoint.block_hash().unwrap();
+        let checkpoint = PowBlock::Checkpoint(Arc::new(checkpoint));
+        let encoded_height = encode_be_u64!(4);
+        let mut chain_db = chain.get_db_and_state_root().0;
+
+        chain_db.put(&checkpoint_hash.0, &checkpoint.to_bytes());
+        chain_db.put(
+            &PowChain::compute_height_key(&checkpoint_hash).0,
+            &encoded_height,
+        );
+        chain_db.put(&crypto::hash_slice(&encoded_height).0, &checkpoint_hash.0);
+        chain_db.put(&TIP_KEY, &checkpoint_hash.0);
+        chain_db.put(&CANONICAL_HEIGHT_KEY, &encoded_height);
+        chain_db.flush();
+
+        let verification = verify_chain(&chain_db, test_helpers::init_tempdb(), |_| {}).unwrap();
+
+        assert_eq!(verification.verified, 3);
+        assert_eq!(verification.last_good_hash, block_hash);
+        assert_eq!(
+            verification.inconsistency,
+            Some(Inconsistency {
+                height: 4,
+                reason: "Invalid proof of work",
+            })
+        );
+        assert!(!verification.state_verified);
+    }
+}
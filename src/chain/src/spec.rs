@@ -0,0 +1,550 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Chain specifications describing a network. The chain spec of the
+//! network that the node is part of is set at startup, before the
+//! chain is initialized, and determines the genesis state.
+//!
+//! Chain specs are read from json files with the following format.
+//! The consensus parameters, upgrades and bootnodes are optional:
+//!
+//! ```json
+//! {
+//!     "name": "my-private-network",
+//!     "currencies": [
+//!         {
+//!             "name": "purple",
+//!             "coin_supply": 500000000,
+//!             "allocations": [
+//!                 { "address": "<base58 normal address>", "balance": 100000 }
+//!             ]
+//!         }
+//!     ],
+//!     "consensus": {
+//!         "allowed_txs_blocks": 25,
+//!         "edge_bits": 24,
+//!         "difficulty": 0,
+//!         "checkpoint_interval": 20
+//!     },
+//!     "upgrades": {
+//!         "contract_lifecycle": 0,
+//!         "block_timestamps": 1000,
+//!         "validator_rotation": 2000,
+//!         "vm_version_2": 3000
+//!     },
+//!     "bootnodes": ["127.0.0.1:44034"]
+//! }
+//! ```
+//!
+//! The upgrades map the names of protocol features to the height of the
+//! first block they apply to. Features which are not scheduled are never
+//! activated, so features introduced by newer versions of the node do
+//! not change the rules of a network until its chain spec is updated.
+//! The upgrades are not part of the genesis hash which allows scheduling
+//! upgrades on a running network. The default network does not schedule
+//! any upgrade yet.
+
+use crate::block::Block;
+use crate::pow_chain::block::{PowBlock, GENESIS_HASH_KEY};
+use crate::pow_chain::checkpoint_block::ALLOWED_TXS_BLOCKS;
+use account::NormalAddress;
+use byteorder::{BigEndian, WriteBytesExt};
+use crypto::{Hash, ShortHash};
+use lazy_static::*;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use transactions::{Feature, Genesis, GenesisCurrency};
+use triomphe::Arc;
+
+#[cfg(feature = "test")]
+use std::cell::RefCell;
+
+/// The name of the default network
+pub const DEFAULT_NETWORK_NAME: &'static str = "purple-testnet";
+
+/// The number of blocks between state checkpoints of the default network
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 20;
+
+/// The bootnodes of the default network
+const DEFAULT_BOOTNODES: &'static [&'static str] = &["95.179.130.222:44034", "45.32.111.18:44034"];
+
+#[cfg(not(feature = "test"))]
+lazy_static! {
+    static ref CHAIN_SPEC: RwLock<Arc<ChainSpec>> = RwLock::new(Arc::new(ChainSpec::default()));
+}
+
+#[cfg(feature = "test")]
+thread_local! {
+    static CHAIN_SPEC: RefCell<Arc<ChainSpec>> = RefCell::new(Arc::new(ChainSpec::default()));
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Consensus parameters of a network.
+pub struct ConsensusParams {
+    /// How many transaction blocks the validator is allowed to create
+    /// for a successfully appended checkpoint block.
+    pub allowed_txs_blocks: u32,
+
+    /// The edge bits of the genesis state.
+    pub edge_bits: u8,
+
+    /// The difficulty of the first checkpoint block.
+    pub difficulty: u64,
+
+    /// The number of blocks after which a state checkpoint will be made.
+    pub checkpoint_interval: u64,
+}
+
+impl Default for ConsensusParams {
+    fn default() -> ConsensusParams {
+        ConsensusParams {
+            allowed_txs_blocks: ALLOWED_TXS_BLOCKS,
+            edge_bits: miner::MIN_EDGE_BITS,
+            difficulty: 0,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// Activation heights of protocol features.
+pub struct Upgrades(HashMap<Feature, u64>);
+
+impl Upgrades {
+    /// Returns upgrades activating all known features at the genesis block.
+    pub fn all() -> Upgrades {
+        Upgrades(Feature::ALL.iter().map(|feature| (*feature, 0)).collect())
+    }
+
+    /// Schedules the activation of the given feature at the given height.
+    pub fn schedule(&mut self, feature: Feature, height: u64) {
+        self.0.insert(feature, height);
+    }
+
+    /// Returns the activation height of the given
+    /// feature or `None` if it is not scheduled.
+    pub fn activation_height(&self, feature: Feature) -> Option<u64> {
+        self.0.get(&feature).cloned()
+    }
+
+    /// Returns `true` if the given feature is active at the given height.
+    pub fn is_active(&self, feature: Feature, height: u64) -> bool {
+        match self.activation_height(feature) {
+            Some(activation_height) => height >= activation_height,
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The specification of a network.
+pub struct ChainSpec {
+    /// The name of the network.
+    pub name: String,
+
+    /// The genesis transaction creating the currencies of the network.
+    pub genesis: Genesis,
+
+    /// The consensus parameters of the network.
+    pub consensus: ConsensusParams,
+
+    /// Activation heights of protocol features.
+    pub upgrades: Upgrades,
+
+    /// Nodes to initially connect to.
+    pub bootnodes: Vec<SocketAddr>,
+
+    /// The hash of the genesis block which is derived from
+    /// the name, the genesis and the consensus parameters.
+    ///
+    /// The default network predates chain specs and keeps
+    /// the genesis hash it has been started with.
+    genesis_hash: Hash,
+}
+
+impl Default for ChainSpec {
+    fn default() -> ChainSpec {
+        ChainSpec::new(
+            DEFAULT_NETWORK_NAME.to_owned(),
+            Genesis::default(),
+            ConsensusParams::default(),
+            Upgrades::default(),
+            DEFAULT_BOOTNODES
+                .iter()
+                .map(|addr| addr.parse().unwrap())
+                .collect(),
+        )
+    }
+}
+
+impl ChainSpec {
+    pub fn new(
+        name: String,
+        genesis: Genesis,
+        consensus: ConsensusParams,
+        upgrades: Upgrades,
+        bootnodes: Vec<SocketAddr>,
+    ) -> ChainSpec {
+        let is_default_network = name == DEFAULT_NETWORK_NAME
+            && genesis == Genesis::default()
+            && consensus == ConsensusParams::default();
+        let genesis_hash = if is_default_network {
+            crypto::hash_slice(GENESIS_HASH_KEY)
+        } else {
+            Self::compute_genesis_hash(&name, &genesis, &consensus)
+        };
+
+        ChainSpec {
+            name,
+            genesis,
+            consensus,
+            upgrades,
+            bootnodes,
+            genesis_hash,
+        }
+    }
+
+    /// Parses and validates a chain spec in json format.
+    pub fn from_json(json: &str) -> Result<ChainSpec, &'static str> {
+        let raw: RawChainSpec = serde_json::from_str(json).map_err(|_| "Invalid chain spec")?;
+
+        if raw.name.is_empty() {
+            return Err("Invalid network name");
+        }
+
+        let mut currencies = Vec::with_capacity(raw.currencies.len());
+
+        for currency in raw.currencies {
+            let mut allocations = Vec::with_capacity(currency.allocations.len());
+
+            for allocation in currency.allocations {
+                let address = NormalAddress::from_base58(&allocation.address)
+                    .map_err(|_| "Invalid allocation address")?;
+                allocations.push((address, allocation.balance));
+            }
+
+            currencies.push(GenesisCurrency {
+                name: currency.name.into_bytes(),
+                coin_supply: currency.coin_supply,
+                allocations,
+            });
+        }
+
+        let genesis = Genesis::new(currencies)?;
+        let defaults = ConsensusParams::default();
+        let consensus = ConsensusParams {
+            allowed_txs_blocks: raw
+                .consensus
+                .allowed_txs_blocks
+                .unwrap_or(defaults.allowed_txs_blocks),
+            edge_bits: raw.consensus.edge_bits.unwrap_or(defaults.edge_bits),
+            difficulty: raw.consensus.difficulty.unwrap_or(defaults.difficulty),
+            checkpoint_interval: raw
+                .consensus
+                .checkpoint_interval
+                .unwrap_or(defaults.checkpoint_interval),
+        };
+
+        if consensus.allowed_txs_blocks == 0 {
+            return Err("Invalid allowed transaction blocks");
+        }
+
+        if consensus.edge_bits < miner::MIN_EDGE_BITS || consensus.edge_bits > miner::MAX_EDGE_BITS
+        {
+            return Err("Invalid edge bits");
+        }
+
+        // Proofs cannot have a difficulty greater than `u8::MAX`
+        if consensus.difficulty > u8::max_value() as u64 {
+            return Err("Invalid difficulty");
+        }
+
+        // The kept checkpoints must fit in the range of accepted heights
+        let max_checkpoint_interval =
+            (PowBlock::MIN_HEIGHT + PowBlock::MAX_HEIGHT) / PowBlock::MAX_CHECKPOINTS as u64;
+
+        if consensus.checkpoint_interval == 0
+            || consensus.checkpoint_interval > max_checkpoint_interval
+        {
+            return Err("Invalid checkpoint interval");
+        }
+
+        let mut upgrades = Upgrades::default();
+
+        for (name, height) in raw.upgrades {
+            let feature = Feature::from_name(&name).ok_or("Unknown upgrade")?;
+            upgrades.schedule(feature, height);
+        }
+
+        let mut bootnodes = Vec::with_capacity(raw.bootnodes.len());
+
+        for bootnode in raw.bootnodes.iter() {
+            bootnodes.push(bootnode.parse().map_err(|_| "Invalid bootnode address")?);
+        }
+
+        Ok(ChainSpec::new(
+            raw.name, genesis, consensus, upgrades, bootnodes,
+        ))
+    }
+
+    /// Returns the hash of the genesis block of the network.
+    pub fn genesis_hash(&self) -> Hash {
+        self.genesis_hash
+    }
+
+    /// Returns `true` if the given feature is active at the given height.
+    pub fn is_active(&self, feature: Feature, height: u64) -> bool {
+        self.upgrades.is_active(feature, height)
+    }
+
+    /// Returns the hash of the main currency of the network.
+    pub fn main_asset_hash(&self) -> ShortHash {
+        self.genesis.main_asset_hash()
+    }
+
+    fn compute_genesis_hash(name: &str, genesis: &Genesis, consensus: &ConsensusParams) -> Hash {
+        let mut buf: Vec<u8> = Vec::new();
+
+        buf.extend_from_slice(GENESIS_HASH_KEY);
+        buf.write_u16::<BigEndian>(name.len() as u16).unwrap();
+        buf.extend_from_slice(name.as_bytes());
+        buf.write_u16::<BigEndian>(genesis.currencies().len() as u16)
+            .unwrap();
+
+        for currency in genesis.currencies() {
+            buf.write_u16::<BigEndian>(currency.name.len() as u16)
+                .unwrap();
+            buf.extend_from_slice(&currency.name);
+            buf.write_u64::<BigEndian>(currency.coin_supply).unwrap();
+            buf.write_u32::<BigEndian>(currency.allocations.len() as u32)
+                .unwrap();
+
+            for (address, balance) in currency.allocations.iter() {
+                buf.extend_from_slice(address.as_bytes());
+                buf.write_u64::<BigEndian>(*balance).unwrap();
+            }
+        }
+
+        buf.write_u32::<BigEndian>(consensus.allowed_txs_blocks)
+            .unwrap();
+        buf.write_u8(consensus.edge_bits).unwrap();
+        buf.write_u64::<BigEndian>(consensus.difficulty).unwrap();
+        buf.write_u64::<BigEndian>(consensus.checkpoint_interval)
+            .unwrap();
+
+        crypto::hash_slice(&buf)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawChainSpec {
+    name: String,
+    currencies: Vec<RawCurrency>,
+
+    #[serde(default)]
+    consensus: RawConsensusParams,
+
+    #[serde(default)]
+    upgrades: HashMap<String, u64>,
+
+    #[serde(default)]
+    bootnodes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCurrency {
+    name: String,
+    coin_supply: u64,
+
+    #[serde(default)]
+    allocations: Vec<RawAllocation>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAllocation {
+    address: String,
+    balance: u64,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawConsensusParams {
+    allowed_txs_blocks: Option<u32>,
+    edge_bits: Option<u8>,
+    difficulty: Option<u64>,
+    checkpoint_interval: Option<u64>,
+}
+
+#[cfg(not(feature = "test"))]
+/// Sets the chain spec of the network. Call this before `init()`.
+pub fn set_chain_spec(spec: ChainSpec) {
+    let mut chain_spec = CHAIN_SPEC.write();
+    *chain_spec = Arc::new(spec);
+}
+
+#[cfg(feature = "test")]
+pub fn set_chain_spec(spec: ChainSpec) {
+    CHAIN_SPEC.with(|chain_spec| {
+        let mut chain_spec = chain_spec.borrow_mut();
+        *chain_spec = Arc::new(spec);
+    });
+}
+
+#[cfg(not(feature = "test"))]
+/// Returns the chain spec of the network. This is the
+/// default chain spec if none has been set.
+pub fn chain_spec() -> Arc<ChainSpec> {
+    let chain_spec = CHAIN_SPEC.read();
+    chain_spec.clone()
+}
+
+#[cfg(feature = "test")]
+pub fn chain_spec() -> Arc<ChainSpec> {
+    CHAIN_SPEC.with(|chain_spec| {
+        let chain_spec = chain_spec.borrow();
+        chain_spec.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transactions::TestAccount;
+
+    fn spec_json(consensus: &str, bootnodes: &str) -> String {
+        format!(
+            r#"{{
+                "name": "private",
+                "currencies": [
+                    {{
+                        "name": "main",
+                        "coin_supply": 1000,
+                        "allocations": [{{ "address": "{}", "balance": 100 }}]
+                    }},
+                    {{ "name": "other", "coin_supply": 50 }}
+                ],
+                "consensus": {},
+                "bootnodes": {}
+            }}"#,
+            TestAccount::A.to_perm_address().to_base58(),
+            consensus,
+            bootnodes
+        )
+    }
+
+    #[test]
+    fn it_parses_chain_specs() {
+        let json = spec_json(
+            r#"{ "allowed_txs_blocks": 10, "checkpoint_interval": 10 }"#,
+            r#"["127.0.0.1:44034"]"#,
+        );
+        let spec = ChainSpec::from_json(&json).unwrap();
+
+        assert_eq!(spec.name, "private");
+        assert_eq!(spec.genesis.currencies().len(), 2);
+        assert_eq!(
+            spec.genesis.currencies()[0].allocations,
+            vec![(TestAccount::A.to_perm_address(), 100)]
+        );
+        assert_eq!(
+            spec.main_asset_hash(),
+            crypto::hash_slice(b"main").to_short()
+        );
+        assert_eq!(spec.consensus.allowed_txs_blocks, 10);
+        assert_eq!(spec.consensus.edge_bits, miner::MIN_EDGE_BITS);
+        assert_eq!(spec.consensus.difficulty, 0);
+        assert_eq!(spec.consensus.checkpoint_interval, 10);
+        assert_eq!(spec.bootnodes, vec!["127.0.0.1:44034".parse().unwrap()]);
+    }
+
+    #[test]
+    fn it_rejects_invalid_chain_specs() {
+        let invalid = vec![
+            spec_json(r#"{ "allowed_txs_blocks": 0 }"#, "[]"),
+            spec_json(r#"{ "edge_bits": 8 }"#, "[]"),
+            spec_json(r#"{ "difficulty": 256 }"#, "[]"),
+            spec_json(r#"{ "checkpoint_interval": 0 }"#, "[]"),
+            spec_json(r#"{ "checkpoint_interval": 21 }"#, "[]"),
+            spec_json(r#"{ "unknown": 1 }"#, "[]"),
+            spec_json("{}", r#"["not an address"]"#),
+            spec_json("{}", "[]").replace(r#""balance": 100"#, r#""balance": 1001"#),
+            spec_json("{}", "[]").replace(&TestAccount::A.to_perm_address().to_base58(), "abc"),
+        ];
+
+        for json in invalid {
+            assert!(ChainSpec::from_json(&json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn it_parses_upgrades() {
+        let with_upgrades = |upgrades: &str| {
+            spec_json("{}", "[]").replace(
+                r#""bootnodes""#,
+                &format!(r#""upgrades": {}, "bootnodes""#, upgrades),
+            )
+        };
+
+        let spec = ChainSpec::from_json(&with_upgrades(r#"{ "block_timestamps": 10 }"#)).unwrap();
+
+        assert!(!spec.is_active(Feature::BlockTimestamps, 9));
+        assert!(spec.is_active(Feature::BlockTimestamps, 10));
+        assert!(spec.is_active(Feature::BlockTimestamps, 11));
+        assert!(!spec.is_active(Feature::ContractLifecycle, 1000));
+
+        // Scheduling upgrades does not change the genesis
+        let without_upgrades = ChainSpec::from_json(&spec_json("{}", "[]")).unwrap();
+        assert_eq!(spec.genesis_hash(), without_upgrades.genesis_hash());
+
+        assert!(ChainSpec::from_json(&with_upgrades(r#"{ "unknown": 10 }"#)).is_err());
+
+        // The default network has not scheduled any upgrade yet
+        for feature in Feature::ALL {
+            assert!(!ChainSpec::default().is_active(*feature, u64::max_value()));
+        }
+    }
+
+    #[test]
+    fn it_derives_the_genesis_hash_from_the_genesis() {
+        let spec = ChainSpec::from_json(&spec_json("{}", "[]")).unwrap();
+        let other_bootnodes =
+            ChainSpec::from_json(&spec_json("{}", r#"["127.0.0.1:44034"]"#)).unwrap();
+        let other_consensus =
+            ChainSpec::from_json(&spec_json(r#"{ "difficulty": 1 }"#, "[]")).unwrap();
+        let other_supply =
+            ChainSpec::from_json(&spec_json("{}", "[]").replace("1000", "1001")).unwrap();
+
+        assert_eq!(spec.genesis_hash(), other_bootnodes.genesis_hash());
+        assert_ne!(spec.genesis_hash(), other_consensus.genesis_hash());
+        assert_ne!(spec.genesis_hash(), other_supply.genesis_hash());
+        assert_ne!(spec.genesis_hash(), ChainSpec::default().genesis_hash());
+    }
+
+    #[test]
+    fn it_keeps_the_genesis_hash_of_the_default_network() {
+        assert_eq!(ChainSpec::default().consensus.checkpoint_interval, 20);
+        assert_eq!(
+            ChainSpec::default().genesis_hash(),
+            crypto::hash_slice(GENESIS_HASH_KEY)
+        );
+    }
+}
@@ -0,0 +1,293 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Merkle-Patricia proofs of entries in the state trie.
+//!
+//! A proof is made of the trie nodes visited while looking up a key,
+//! starting with the root node. A verifier loads the nodes in an
+//! in-memory database and repeats the lookup against a trusted state
+//! root so the proof can be checked without access to the full state.
+//! The same proof shows either the value of the key or that the key
+//! is not present in the state.
+
+use account::{Address, Balance};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use crypto::ShortHash;
+use hashdb::HashDB;
+use patricia_trie::{Trie, TrieDB};
+use persistence::{Codec, DbHasher, PersistentDb};
+use std::io::Cursor;
+
+/// Proof of the value stored under a key in the state trie.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateProof {
+    /// The key of the proven entry.
+    pub key: Vec<u8>,
+
+    /// The encoded trie nodes on the path to the key.
+    pub nodes: Vec<Vec<u8>>,
+}
+
+impl StateProof {
+    /// Verifies the proof against the given state root. Returns the
+    /// value stored under the key or `None` if the proof shows that
+    /// the key is not present in the state.
+    pub fn verify(&self, state_root: &ShortHash) -> Result<Option<Vec<u8>>, &'static str> {
+        let mut db = PersistentDb::new_in_memory();
+
+        for node in self.nodes.iter() {
+            db.insert(node);
+        }
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, state_root)
+            .map_err(|_| "The proof does not contain the state root")?;
+
+        match trie.get(&self.key) {
+            Ok(value) => Ok(value.map(|value| value.to_vec())),
+            Err(_) => Err("The proof is incomplete"),
+        }
+    }
+
+    /// Verifies the proof of a nonce entry against the given state root.
+    pub fn verify_nonce(&self, state_root: &ShortHash) -> Result<Option<u64>, &'static str> {
+        if !self.key.ends_with(b".n") {
+            return Err("The proof is not of a nonce");
+        }
+
+        match self.verify(state_root)? {
+            Some(nonce) => Ok(Some(decode_be_u64!(&nonce).map_err(|_| "Invalid nonce")?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Verifies the proof of a balance entry against the given state root.
+    pub fn verify_balance(&self, state_root: &ShortHash) -> Result<Option<Balance>, &'static str> {
+        match self.verify(state_root)? {
+            Some(balance) => Ok(Some(
+                Balance::from_bytes(&balance).map_err(|_| "Invalid balance")?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Serializes the proof. The format is the following:
+    /// `<key-len><key><nodes-count>[<node-len><node>]*`
+    ///
+    /// The key length is a big-endian `u16`, the nodes count is a
+    /// `u8` and the length of each node is a big-endian `u32`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        buf.write_u16::<BigEndian>(self.key.len() as u16).unwrap();
+        buf.extend_from_slice(&self.key);
+        buf.write_u8(self.nodes.len() as u8).unwrap();
+
+        for node in self.nodes.iter() {
+            buf.write_u32::<BigEndian>(node.len() as u32).unwrap();
+            buf.extend_from_slice(node);
+        }
+
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<StateProof, &'static str> {
+        let mut rdr = Cursor::new(bytes);
+        let key_len = rdr.read_u16::<BigEndian>().map_err(|_| "Bad key length")? as usize;
+        let start = rdr.position() as usize;
+
+        if bytes.len() < start + key_len + 1 {
+            return Err("Invalid proof length");
+        }
+
+        let key = bytes[start..(start + key_len)].to_vec();
+        rdr.set_position((start + key_len) as u64);
+
+        let nodes_count = rdr.read_u8().map_err(|_| "Bad nodes count")?;
+        let mut nodes = Vec::with_capacity(nodes_count as usize);
+
+        for _ in 0..nodes_count {
+            let node_len = rdr.read_u32::<BigEndian>().map_err(|_| "Bad node length")? as usize;
+            let start = rdr.position() as usize;
+
+            if node_len == 0 || bytes.len() < start + node_len {
+                return Err("Invalid node length");
+            }
+
+            nodes.push(bytes[start..(start + node_len)].to_vec());
+            rdr.set_position((start + node_len) as u64);
+        }
+
+        if rdr.position() as usize != bytes.len() {
+            return Err("Invalid proof length");
+        }
+
+        Ok(StateProof { key, nodes })
+    }
+}
+
+/// Returns the state key of the nonce of the given permanent address.
+///
+/// The key of a nonce has the following format:
+/// `<permanent-addr>.n`
+pub fn nonce_key(address: &Address) -> Vec<u8> {
+    [address.as_bytes(), &b".n"[..]].concat()
+}
+
+/// Returns the state key of the balance of the given
+/// permanent address in the currency with the given hash.
+///
+/// The key of a currency entry has the following format:
+/// `<permanent-addr>.<currency-hash>`
+pub fn balance_key(address: &Address, currency_hash: &ShortHash) -> Vec<u8> {
+    [address.as_bytes(), &b"."[..], &currency_hash.0[..]].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::pow_chain::block::PowBlock;
+    use crate::pow_chain::chain_state::PowChainState;
+    use crate::pow_chain::checkpoint_block::CheckpointBlock;
+    use crate::test_helpers;
+    use crate::types::StateInterface;
+    use crypto::{Hash, Identity, NodeId};
+    use miner::Proof;
+    use transactions::TestAccount;
+    use triomphe::Arc;
+
+    #[test]
+    fn it_proves_nonces_and_balances() {
+        let mut state = PowChainState::genesis(test_helpers::init_tempdb());
+        let sender = Address::Normal(TestAccount::A.to_perm_address());
+        let receiver = Address::Normal(TestAccount::B.to_perm_address());
+        let tx = transactions::send_coins(TestAccount::A, TestAccount::B, 100, 10, 1);
+
+        state.apply_tx(Arc::new(tx));
+
+        let state_root = state.state_root();
+        let nonce_proof = state.state_proof(&nonce_key(&sender)).unwrap();
+        let decoded = StateProof::from_bytes(&nonce_proof.to_bytes()).unwrap();
+
+        assert_eq!(decoded, nonce_proof);
+        assert_eq!(
+            nonce_proof.verify_nonce(&state_root).unwrap(),
+            state.get_account_nonce(&sender)
+        );
+
+        let currency_hash = crypto::hash_slice(transactions::MAIN_CUR_NAME).to_short();
+        let balance_proof = state
+            .state_proof(&balance_key(&receiver, &currency_hash))
+            .unwrap();
+
+        assert!(balance_proof.verify_balance(&state_root).unwrap().is_some());
+    }
+
+    #[test]
+    fn it_proves_absent_keys() {
+        let state = PowChainState::genesis(test_helpers::init_tempdb());
+        let address = Address::Normal(account::NormalAddress::random());
+        let proof = state.state_proof(&nonce_key(&address)).unwrap();
+
+        assert_eq!(proof.verify_nonce(&state.state_root()).unwrap(), None);
+    }
+
+    #[test]
+    fn it_proves_the_state_of_canonical_blocks() {
+        let chain = test_helpers::init_test_chains();
+        let tip = chain.canonical_tip();
+        let state_root = chain.canonical_tip_state().state_root();
+        let sender = Address::Normal(TestAccount::A.to_perm_address());
+        let key = nonce_key(&sender);
+        let proof = chain.state_proof(&tip.block_hash().unwrap(), &key).unwrap();
+
+        assert_eq!(
+            proof.verify_nonce(&state_root).unwrap(),
+            chain.get_account_nonce(&sender)
+        );
+        assert!(chain
+            .state_proof(&crypto::hash_slice(b"not-a-block"), &key)
+            .is_err());
+    }
+
+    #[test]
+    fn it_does_not_prove_the_state_of_orphan_blocks() {
+        let chain = test_helpers::init_test_chains();
+        let key = nonce_key(&Address::Normal(TestAccount::A.to_perm_address()));
+        let genesis_hash = PowBlock::genesis().block_hash().unwrap();
+        let canonical = checkpoint(genesis_hash);
+        let orphan = checkpoint(genesis_hash);
+
+        chain.append_block(canonical.clone()).unwrap();
+        chain.append_block(orphan.clone()).unwrap();
+
+        let orphan_hash = orphan.block_hash().unwrap();
+        assert!(chain.query_orphan(&orphan_hash).is_some());
+        assert!(!chain.is_canonical(&orphan_hash));
+        assert_eq!(
+            chain.state_proof(&orphan_hash, &key),
+            Err("The block is not canonical")
+        );
+        assert!(chain
+            .state_proof(&canonical.block_hash().unwrap(), &key)
+            .is_ok());
+    }
+
+    fn checkpoint(parent_hash: Hash) -> Arc<PowBlock> {
+        let identity = Identity::new();
+        let mut block = CheckpointBlock::new(
+            parent_hash,
+            account::NormalAddress::random(),
+            crate::random_socket_addr(),
+            1,
+            Proof::test_proof(42),
+            NodeId(*identity.pkey()),
+        );
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        Arc::new(PowBlock::Checkpoint(Arc::new(block)))
+    }
+
+    #[test]
+    fn it_rejects_invalid_proofs() {
+        let mut state = PowChainState::genesis(test_helpers::init_tempdb());
+        let sender = Address::Normal(TestAccount::A.to_perm_address());
+        let tx = transactions::send_coins(TestAccount::A, TestAccount::B, 100, 10, 1);
+        let old_root = state.state_root();
+
+        state.apply_tx(Arc::new(tx));
+
+        let state_root = state.state_root();
+        let proof = state.state_proof(&nonce_key(&sender)).unwrap();
+
+        // Against another state root
+        assert!(proof.verify(&old_root).is_err());
+
+        // With a missing node
+        let mut incomplete = proof.clone();
+        incomplete.nodes.pop();
+        assert!(incomplete.verify(&state_root).is_err());
+
+        // With a tampered node
+        let mut tampered = proof;
+        let last = tampered.nodes.len() - 1;
+        tampered.nodes[last].push(0);
+        assert!(tampered.verify(&state_root).is_err());
+    }
+}
@@ -21,19 +21,26 @@
 use crate::block::Block;
 use crate::pow_chain::block::*;
 use crate::pow_chain::chain::*;
+use crate::pow_chain::checkpoint_block::CheckpointBlock;
+use crate::pow_chain::merkle::compute_tx_root;
+use crate::pow_chain::transaction_block::TransactionBlock;
 use crate::pow_chain::PowChainState;
+use crate::types::StateInterface;
 use account::NormalAddress;
-use crypto::{Hash, NodeId};
+use crypto::{Hash, Identity, NodeId};
 use graphlib::{Graph, VertexId};
 use hashbrown::{HashMap, HashSet};
 use miner::Proof;
 use parking_lot::RwLock;
 use rand::prelude::*;
+use transactions::TestAccount;
 use triomphe::Arc;
 
 pub fn init_test_chains() -> PowChainRef {
     let pow_db = test_helpers::init_tempdb();
-    let state_db = test_helpers::init_tempdb();
+    let mut state_db = test_helpers::init_tempdb();
+    state_db.set_archival(true);
+
     let pow_chain = Arc::new(RwLock::new(PowChain::new(
         pow_db,
         PowChainState::genesis(state_db),
@@ -44,6 +51,65 @@ pub fn init_test_chains() -> PowChainRef {
     pow_chain_ref
 }
 
+/// Initializes a chain with a checkpoint block followed by a
+/// transaction block that includes a single transaction.
+pub fn init_chain_with_blocks() -> (PowChainRef, Vec<Arc<PowBlock>>) {
+    init_chain_with_blocks_mined_by(&Identity::new())
+}
+
+/// Same as `init_chain_with_blocks` with blocks mined by the given identity.
+pub fn init_chain_with_blocks_mined_by(identity: &Identity) -> (PowChainRef, Vec<Arc<PowBlock>>) {
+    let chain = init_test_chains();
+    let node_id = NodeId(*identity.pkey());
+
+    chain.chain.write().set_tx_index(true);
+
+    let mut checkpoint = CheckpointBlock::new(
+        PowBlock::genesis().block_hash().unwrap(),
+        NormalAddress::random(),
+        crate::random_socket_addr(),
+        1,
+        Proof::test_proof(42),
+        node_id.clone(),
+    );
+    checkpoint.sign_miner(identity.skey());
+    checkpoint.compute_hash();
+
+    let checkpoint_hash = checkpoint.block_hash().unwrap();
+    let checkpoint = Arc::new(PowBlock::Checkpoint(Arc::new(checkpoint)));
+    chain.append_block(checkpoint.clone()).unwrap();
+
+    let tx = Arc::new(transactions::send_coins(
+        TestAccount::A,
+        TestAccount::B,
+        100,
+        10,
+        1,
+    ));
+    let mut state = chain.canonical_tip_state();
+    state.apply_tx(tx.clone());
+
+    let mut block = TransactionBlock::new(
+        checkpoint_hash,
+        crate::random_socket_addr(),
+        2,
+        Proof::test_proof(42),
+        node_id,
+    );
+    block.tx_checksums = Some(Vec::new());
+    block.pieces_sizes = Some(Vec::new());
+    block.tx_root = Some(compute_tx_root(&[tx.clone()]));
+    block.state_root = Some(state.state_root());
+    block.transactions = Some(Arc::new(RwLock::new(vec![tx])));
+    block.sign_miner(identity.skey());
+    block.compute_hash();
+
+    let block = Arc::new(PowBlock::Transaction(Arc::new(block)));
+    chain.append_block(block.clone()).unwrap();
+
+    (chain, vec![checkpoint, block])
+}
+
 /// Wrapper struct around a block test set
 #[derive(Clone, Debug)]
 pub struct BlockTestSet {
@@ -16,11 +16,13 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use crate::block::Block;
 use crate::chain::ChainErr;
+use crate::state_proof::StateProof;
 use account::Address;
-use crypto::ShortHash;
+use crypto::{Hash, ShortHash};
 use std::fmt::Debug;
-use transactions::Tx;
+use transactions::{Feature, Tx};
 use triomphe::Arc;
 
 #[derive(Clone, Debug, PartialEq, Copy)]
@@ -41,6 +43,108 @@ pub enum OrphanType {
     DisconnectedTip,
 }
 
+/// Execution status of a transaction included in a block.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub enum TxStatus {
+    /// The transaction was applied successfully.
+    Success,
+
+    /// The transaction was applied but the execution
+    /// of the called contract failed.
+    Failed,
+}
+
+/// A transaction retrieved from the transaction index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedTx {
+    /// The indexed transaction.
+    pub tx: Arc<Tx>,
+
+    /// The hash of the block which includes the transaction.
+    pub block_hash: Hash,
+
+    /// The height of the block which includes the transaction.
+    pub height: u64,
+
+    /// The position of the transaction in the block.
+    pub position: u32,
+
+    /// The execution status of the transaction.
+    pub status: TxStatus,
+}
+
+/// The direction of a transaction relative to an address.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub enum TxDirection {
+    /// The address receives the transaction.
+    Incoming,
+
+    /// The address has created the transaction.
+    Outgoing,
+}
+
+/// A permanent address touched by a transaction included in a block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TouchedAddress {
+    /// The permanent address of the account.
+    pub address: Address,
+
+    /// The hash of the transaction.
+    pub tx_hash: Hash,
+
+    /// The hash of the asset moved from or to the address.
+    pub asset_hash: ShortHash,
+
+    /// The direction of the transaction.
+    pub direction: TxDirection,
+}
+
+/// An entry of the history of an address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddressTx {
+    /// The hash of the transaction.
+    pub tx_hash: Hash,
+
+    /// The hash of the block which includes the transaction.
+    pub block_hash: Hash,
+
+    /// The height of the block which includes the transaction.
+    pub height: u64,
+
+    /// The hash of the asset moved from or to the address.
+    pub asset_hash: ShortHash,
+
+    /// The direction of the transaction.
+    pub direction: TxDirection,
+}
+
+/// Event emitted when the canonical chain changes.
+#[derive(Debug)]
+pub enum ChainEvent<B: Block> {
+    /// The block has been written to the canonical chain.
+    BlockConnected(Arc<B>),
+
+    /// The block has been removed from the canonical
+    /// chain by a rewind or by a chain switch.
+    BlockDisconnected(Arc<B>),
+
+    /// The canonical chain has a new tip.
+    TipChanged(Arc<B>),
+}
+
+// Deriving `Clone` would require blocks to be `Clone`
+impl<B: Block> Clone for ChainEvent<B> {
+    fn clone(&self) -> Self {
+        match *self {
+            ChainEvent::BlockConnected(ref block) => ChainEvent::BlockConnected(block.clone()),
+            ChainEvent::BlockDisconnected(ref block) => {
+                ChainEvent::BlockDisconnected(block.clone())
+            }
+            ChainEvent::TipChanged(ref block) => ChainEvent::TipChanged(block.clone()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Copy)]
 pub enum BranchType {
     /// The canonical chain branch
@@ -53,6 +157,16 @@ pub enum BranchType {
 /// Generic trait for state that can be flushed to disk.
 pub trait Flushable {
     fn flush(&mut self) -> Result<(), ChainErr>;
+
+    /// Journals the data which is no longer referenced by
+    /// the state as dead at the given height.
+    fn journal(&mut self, _height: u64) {}
+
+    /// Deletes the data journaled as dead at or below the given height
+    /// which is still not referenced. Returns the number of reclaimed bytes.
+    fn prune(&mut self, _height: u64) -> u64 {
+        0
+    }
 }
 
 /// Chain state wrapper representing an in-memory state
@@ -125,6 +239,9 @@ pub trait StateInterface {
     /// Returns the current state root that is stored in the state
     fn state_root(&self) -> ShortHash;
 
+    /// Returns the height of the block to which the state belongs.
+    fn height(&self) -> u64;
+
     /// Returns the nonce of the account with the given address
     /// if it exists.
     fn get_account_nonce(&self, address: &Address) -> Option<u64>;
@@ -132,6 +249,34 @@ pub trait StateInterface {
     /// Validates the provided transaction against the stored chain state.
     fn validate_tx(&self, tx: Arc<Tx>) -> bool;
 
+    /// Returns `true` if the given protocol feature
+    /// is active at the given height.
+    fn is_active(&self, feature: Feature, height: u64) -> bool;
+
     /// Applies a single transaction on the stored chain state.
     fn apply_tx(&mut self, tx: Arc<Tx>);
+
+    /// Returns the execution statuses of the transactions applied
+    /// by the block which produced this state, in block order.
+    fn tx_statuses(&self) -> &[TxStatus] {
+        &[]
+    }
+
+    /// Returns the permanent addresses touched by the transactions
+    /// applied by the block which produced this state, in the order
+    /// in which they have been applied.
+    fn touched_addresses(&self) -> &[TouchedAddress] {
+        &[]
+    }
+
+    /// Builds a proof of the value stored under the given key
+    /// in the state trie or of its absence from the state.
+    fn state_proof(&self, key: &[u8]) -> Result<StateProof, &'static str>;
+
+    /// Returns a read-only view of the state at the given height whose
+    /// trie has the given root. The trie nodes of the root must still
+    /// be stored, which is only guaranteed in archival mode.
+    fn at_root(&self, height: u64, state_root: &ShortHash) -> Self
+    where
+        Self: Sized;
 }
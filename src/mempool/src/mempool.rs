@@ -21,8 +21,8 @@
 use crate::error::MempoolErr;
 use account::{Address, Balance, NormalAddress};
 use cfg_if::*;
-use chain::types::StateInterface;
-use chain::{PowChainRef, PowChainState};
+use chain::types::{ChainEvent, StateInterface};
+use chain::{Block, PowBlock, PowChainRef, PowChainState};
 use chrono::{DateTime, Duration, Utc};
 use constants::*;
 use crypto::{Hash, ShortHash};
@@ -133,6 +133,11 @@ pub struct Mempool {
 
     /// Contains all the propagated transactions
     propagated: HashSet<ShortHash>,
+
+    /// Transactions of blocks that have been disconnected from
+    /// the canonical chain, in the order in which they have been
+    /// applied. These are re-injected once the tip changes.
+    disconnected_txs: Vec<Arc<Tx>>,
 }
 
 pub struct TxSet {
@@ -195,6 +200,7 @@ impl Mempool {
             expire_duration: Duration::milliseconds(expire_threshold),
             expired: HashSet::new(),
             propagated: HashSet::new(),
+            disconnected_txs: Vec::new(),
         }
     }
 
@@ -744,6 +750,46 @@ impl Mempool {
         Some(tx_set.tx_set)
     }
 
+    /// Updates the mempool after a change of the canonical chain.
+    ///
+    /// Transactions of disconnected blocks are kept until the tip
+    /// changes and are then validated against the new tip state and
+    /// appended back. Transactions included in connected blocks are
+    /// removed from the mempool.
+    pub fn process_chain_event(&mut self, event: ChainEvent<PowBlock>) {
+        match event {
+            ChainEvent::BlockDisconnected(block) => {
+                // Blocks are disconnected starting with the tip so the
+                // transactions of each block are placed in front.
+                self.disconnected_txs.splice(0..0, block.transactions());
+            }
+
+            ChainEvent::BlockConnected(block) => {
+                for tx in block.transactions() {
+                    let tx_hash = tx.tx_hash().unwrap().to_short();
+
+                    self.disconnected_txs.retain(|disconnected| {
+                        disconnected.tx_hash().unwrap().to_short() != tx_hash
+                    });
+                    self.remove_internal(&tx_hash);
+                }
+
+                // The cached set may contain included transactions
+                self.next_tx_set_cache = None;
+            }
+
+            ChainEvent::TipChanged(_) => {
+                self.next_tx_set_cache = None;
+
+                for tx in std::mem::replace(&mut self.disconnected_txs, Vec::new()) {
+                    if let Err(err) = self.append_tx(tx) {
+                        debug!("Dropped transaction of disconnected block: {:?}", err);
+                    }
+                }
+            }
+        }
+    }
+
     fn get_account_nonce(&self, address: &Address) -> Option<u64> {
         self.chain_ref.get_account_nonce(&address)
     }
@@ -950,6 +996,10 @@ impl Mempool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chain::{CheckpointBlock, TransactionBlock};
+    use crypto::{Identity, NodeId};
+    use miner::Proof;
+    use parking_lot::RwLock;
     use quickcheck::*;
     use rand::prelude::*;
     use transactions::TestAccount;
@@ -971,6 +1021,177 @@ mod tests {
         assert_eq!(mempool.append_tx(tx), Err(MempoolErr::NonceLeq));
     }
 
+    #[test]
+    fn it_reinjects_disconnected_txs_when_the_tip_changes() {
+        let chain_db = test_helpers::init_tempdb();
+        let state_db = test_helpers::init_tempdb();
+        let chain = chain::init(chain_db, state_db, true);
+        let mut mempool = Mempool::new(chain.clone(), 10000, vec![], 80, 10000, 80);
+        let valid = Arc::new(transactions::send_coins(
+            TestAccount::A,
+            TestAccount::B,
+            100,
+            10,
+            1,
+        ));
+        let invalid = Arc::new(transactions::send_coins(
+            TestAccount::B,
+            TestAccount::A,
+            100,
+            10,
+            0,
+        ));
+        let valid_hash = valid.tx_hash().unwrap().to_short();
+        let invalid_hash = invalid.tx_hash().unwrap().to_short();
+
+        mempool.disconnected_txs = vec![valid, invalid];
+        mempool.process_chain_event(ChainEvent::TipChanged(PowBlock::genesis()));
+
+        assert!(mempool.exists(valid_hash));
+        assert!(!mempool.exists(invalid_hash));
+        assert!(mempool.disconnected_txs.is_empty());
+        assert!(mempool.next_tx_set_cache.is_none());
+    }
+
+    #[test]
+    fn it_reinjects_txs_of_blocks_disconnected_by_a_reorg() {
+        let chain_db = test_helpers::init_tempdb();
+        let state_db = test_helpers::init_tempdb();
+        let chain = chain::init(chain_db, state_db, true);
+        let events = chain.subscribe();
+        let mut mempool = Mempool::new(chain.clone(), 10000, vec![], 80, 10000, 80);
+        let genesis_hash = PowBlock::genesis().block_hash().unwrap();
+        let tx_a = Arc::new(transactions::send_coins(
+            TestAccount::A,
+            TestAccount::B,
+            100,
+            10,
+            1,
+        ));
+        let tx_b = Arc::new(transactions::send_coins(
+            TestAccount::B,
+            TestAccount::A,
+            100,
+            10,
+            1,
+        ));
+        let tx_c = Arc::new(transactions::send_coins(
+            TestAccount::C,
+            TestAccount::A,
+            100,
+            10,
+            1,
+        ));
+        let tx_a_hash = tx_a.tx_hash().unwrap().to_short();
+        let tx_b_hash = tx_b.tx_hash().unwrap().to_short();
+        let tx_c_hash = tx_c.tx_hash().unwrap().to_short();
+
+        // Checkpoint blocks do not change the state trie
+        // so the fork is built on top of the genesis state.
+        let mut fork_state = chain.canonical_tip_state();
+        let mut state = chain.canonical_tip_state();
+
+        // Canonical chain including `tx_a` and `tx_b`
+        let identity = Identity::new();
+        let checkpoint = checkpoint_block(genesis_hash, 1, &identity);
+        state.apply_tx(tx_a.clone());
+        state.apply_tx(tx_b.clone());
+        let block = transaction_block(
+            checkpoint.block_hash().unwrap(),
+            2,
+            &identity,
+            vec![tx_a.clone(), tx_b.clone()],
+            &state,
+        );
+
+        chain.append_block(checkpoint).unwrap();
+        chain.append_block(block).unwrap();
+
+        for event in events.try_iter() {
+            mempool.process_chain_event(event);
+        }
+
+        assert_eq!(mempool.append_tx(tx_c.clone()), Ok(()));
+
+        // Longer fork including only `tx_a` and `tx_c`
+        let identity = Identity::new();
+        let checkpoint = checkpoint_block(genesis_hash, 1, &identity);
+        fork_state.apply_tx(tx_a.clone());
+        let block1 = transaction_block(
+            checkpoint.block_hash().unwrap(),
+            2,
+            &identity,
+            vec![tx_a.clone()],
+            &fork_state,
+        );
+        fork_state.apply_tx(tx_c.clone());
+        let block2 = transaction_block(
+            block1.block_hash().unwrap(),
+            3,
+            &identity,
+            vec![tx_c.clone()],
+            &fork_state,
+        );
+        let fork_tip_hash = block2.block_hash().unwrap();
+
+        chain.append_block(checkpoint).unwrap();
+        chain.append_block(block1).unwrap();
+        chain.append_block(block2).unwrap();
+
+        assert_eq!(chain.canonical_tip().block_hash().unwrap(), fork_tip_hash);
+
+        for event in events.try_iter() {
+            mempool.process_chain_event(event);
+        }
+
+        // Only the transaction which is not included
+        // in the new canonical chain is re-injected.
+        assert!(mempool.exists(tx_b_hash));
+        assert!(!mempool.exists(tx_a_hash));
+        assert!(!mempool.exists(tx_c_hash));
+        assert!(mempool.disconnected_txs.is_empty());
+    }
+
+    fn checkpoint_block(parent_hash: Hash, height: u64, identity: &Identity) -> Arc<PowBlock> {
+        let mut block = CheckpointBlock::new(
+            parent_hash,
+            NormalAddress::random(),
+            chain::random_socket_addr(),
+            height,
+            Proof::test_proof(42),
+            NodeId(*identity.pkey()),
+        );
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        Arc::new(PowBlock::Checkpoint(Arc::new(block)))
+    }
+
+    fn transaction_block(
+        parent_hash: Hash,
+        height: u64,
+        identity: &Identity,
+        txs: Vec<Arc<Tx>>,
+        state: &PowChainState,
+    ) -> Arc<PowBlock> {
+        let mut block = TransactionBlock::new(
+            parent_hash,
+            chain::random_socket_addr(),
+            height,
+            Proof::test_proof(42),
+            NodeId(*identity.pkey()),
+        );
+        block.tx_checksums = Some(Vec::new());
+        block.pieces_sizes = Some(Vec::new());
+        block.tx_root = Some(chain::compute_tx_root(&txs));
+        block.state_root = Some(state.state_root());
+        block.transactions = Some(Arc::new(RwLock::new(txs)));
+        block.sign_miner(identity.skey());
+        block.compute_hash();
+
+        Arc::new(PowBlock::Transaction(Arc::new(block)))
+    }
+
     #[test]
     fn iter_over_b_tree_map_orders_by_key_for_balance() {
         let mut tree: BTreeMap<Balance, &'static str> = BTreeMap::new();
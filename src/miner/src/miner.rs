@@ -309,6 +309,10 @@ impl PurpleMiner {
                         solver.solutions.sols[i] = filtered_sols[i];
                     }
                     s.solutions.push(solver.solutions.clone());
+
+                    if let Some(ref listener) = s.solutions_listener {
+                        listener();
+                    }
                 }
                 if s.stats[instance].has_errored {
                     s.stats[instance].set_plugin_name(&solver.config.name);
@@ -407,6 +411,10 @@ impl PurpleMiner {
                 sd.difficulty = difficulty;
                 sd.solutions = vec![SolverSolutions::default()];
 
+                if let Some(ref listener) = sd.solutions_listener {
+                    listener();
+                }
+
                 return Ok(());
             }
         }
@@ -498,6 +506,16 @@ impl PurpleMiner {
         Some(crypto::hash_slice(&sd.header))
     }
 
+    /// Sets the function which is called each time
+    /// solutions are put into the output queue.
+    pub fn on_solutions<F>(&self, listener: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let mut sd = self.shared_data.write();
+        sd.solutions_listener = Some(std::sync::Arc::new(listener));
+    }
+
     /// Returns solutions if currently waiting.
     pub fn get_solutions(&self) -> Option<SolverSolutions> {
         let has_solutions = {
@@ -41,6 +41,9 @@ pub struct JobData {
 
     /// Current stats
     pub stats: Vec<SolverStats>,
+
+    /// Called each time solutions are put into the output queue
+    pub solutions_listener: Option<std::sync::Arc<dyn Fn() + Send + Sync>>,
 }
 
 impl Default for JobData {
@@ -52,6 +55,7 @@ impl Default for JobData {
             difficulty: 0,
             solutions: Vec::new(),
             stats: vec![],
+            solutions_listener: None,
         }
     }
 }
@@ -65,6 +69,7 @@ impl JobData {
             difficulty: 1,
             solutions: Vec::new(),
             stats: vec![SolverStats::default(); num_solvers],
+            solutions_listener: None,
         }
     }
 }
@@ -24,7 +24,7 @@ use parking_lot::Mutex;
 use std::boxed::Box;
 
 const MIN_EDGE_BITS: u8 = 24;
-const MAX_EDGE_BITS: u8 = 31;
+pub const MAX_EDGE_BITS: u8 = 31;
 pub const PROOF_SIZE: usize = 42;
 
 #[cfg(test)]
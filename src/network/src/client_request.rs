@@ -28,4 +28,5 @@ pub enum ClientRequest {
     RequestPieceInfo,
     RequestSubPiece,
     RequestBlocks,
+    RequestProof,
 }
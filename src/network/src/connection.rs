@@ -485,6 +485,10 @@ async fn handle_client_stream<
             RequestPeers::start_client_protocol_flow(&mut network, &mut sock, addr).await?;
         }
 
+        RequestProof::PACKET_TYPE => {
+            RequestProof::start_client_protocol_flow(&mut network, &mut sock, addr).await?;
+        }
+
         RequestPieceInfo::PACKET_TYPE => {
             RequestPieceInfo::start_client_protocol_flow(&mut network, &mut sock, addr).await?;
         }
@@ -543,11 +547,22 @@ async fn handle_server_stream<
             RequestBlock::start_server_protocol_flow(&mut network, &mut sock, addr, packet).await?;
         }
 
+        RequestBlocks::PACKET_TYPE => {
+            let packet = RequestBlocks::from_bytes(&bytes)?;
+            RequestBlocks::start_server_protocol_flow(&mut network, &mut sock, addr, packet)
+                .await?;
+        }
+
         RequestPeers::PACKET_TYPE => {
             let packet = RequestPeers::from_bytes(&bytes)?;
             RequestPeers::start_server_protocol_flow(&mut network, &mut sock, addr, packet).await?;
         }
 
+        RequestProof::PACKET_TYPE => {
+            let packet = RequestProof::from_bytes(&bytes)?;
+            RequestProof::start_server_protocol_flow(&mut network, &mut sock, addr, packet).await?;
+        }
+
         RequestPieceInfo::PACKET_TYPE => {
             let packet = RequestPieceInfo::from_bytes(&bytes)?;
             RequestPieceInfo::start_server_protocol_flow(&mut network, &mut sock, addr, packet)
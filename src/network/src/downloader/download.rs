@@ -45,6 +45,9 @@ pub struct Download {
     /// The state of the download
     pub(crate) state: DownloadState,
 
+    /// The type of the download
+    pub(crate) download_type: DownloadType,
+
     /// Pieces of the download
     pub(crate) pieces: Vec<Piece>,
 
@@ -87,6 +90,7 @@ impl Download {
             checksum_mappings,
             priority,
             state: DownloadState::NotStarted,
+            download_type: DownloadType::Block,
             completed: 0,
             created_at: Utc::now(),
         };
@@ -131,6 +135,7 @@ impl Download {
             checksum_mappings,
             priority,
             state: DownloadState::NotStarted,
+            download_type: DownloadType::Block,
             completed: 0,
             created_at: Utc::now(),
         };
@@ -138,6 +143,16 @@ impl Download {
         Ok(download)
     }
 
+    /// Creates a download of the given chunks of a state snapshot.
+    pub fn from_snapshot_chunks(
+        chunks: &[(ShortHash, u64)],
+        priority: u64,
+    ) -> Result<Self, DownloaderErr> {
+        let mut download = Self::from_checksums_and_sizes(chunks, priority)?;
+        download.download_type = DownloadType::State;
+        Ok(download)
+    }
+
     pub fn to_info(&self) -> DownloadInfo {
         let pieces = self.pieces.iter().map(|p| p.to_info()).collect();
 
@@ -147,7 +162,7 @@ impl Download {
             created_at: self.created_at,
             priority: self.priority,
             state: self.state,
-            download_type: DownloadType::Block,
+            download_type: self.download_type,
             pieces,
         }
     }
@@ -218,6 +233,15 @@ impl Download {
     pub fn state(&self) -> DownloadState {
         self.state
     }
+
+    /// Returns the data of each piece if the download is complete.
+    pub fn data(&self) -> Option<Vec<Vec<u8>>> {
+        if !self.is_complete() {
+            return None;
+        }
+
+        self.pieces.iter().map(|piece| piece.data()).collect()
+    }
 }
 
 #[cfg(test)]
@@ -803,6 +827,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_downloads_snapshot_chunks() {
+        let data = gen_random_bytes(MAX_SUB_PIECE_SIZE * 2 + 100);
+        let piece = Piece::from_data_unchecked(&data);
+        let info = piece.to_info();
+
+        // Chunks are addressed by the checksum of the piece with the same data
+        assert_eq!(piece.checksum, chain::chunk_checksum(&data));
+
+        let mut download =
+            Download::from_snapshot_chunks(&[(piece.checksum, data.len() as u64)], 0).unwrap();
+        assert_eq!(download.to_info().download_type, DownloadType::State);
+        assert!(download.data().is_none());
+
+        download.add_info(&piece.checksum, &info).unwrap();
+
+        let sub_pieces = info.sub_pieces.as_ref().unwrap();
+
+        for (sub_piece, raw) in sub_pieces.iter().zip(data.chunks(MAX_SUB_PIECE_SIZE)) {
+            download
+                .append_raw_sub_piece(&piece.checksum, &sub_piece.checksum, Arc::new(raw.to_vec()))
+                .unwrap();
+        }
+
+        assert!(download.is_complete());
+        assert_eq!(download.data(), Some(vec![data]));
+    }
+
     fn gen_random_bytes(num: usize) -> Vec<u8> {
         let mut rng = rand::thread_rng();
 
@@ -20,6 +20,7 @@ use crate::downloader::download::Download;
 use crate::downloader::download_info::DownloadInfo;
 use crate::downloader::error::DownloaderErr;
 use chain::Block;
+use chain::SnapshotManifest;
 use chain::TransactionBlock;
 use constants::*;
 use crypto::ShortHash;
@@ -53,6 +54,10 @@ pub struct Downloader {
 
     /// HashMap with all current block downloads
     block_downloads: Arc<DashMap<ShortHash, Arc<Mutex<Download>>>>,
+
+    /// HashMap with all current state snapshot downloads, keyed
+    /// by the checksum of the first chunk of each download.
+    state_downloads: Arc<DashMap<ShortHash, Arc<Mutex<Download>>>>,
 }
 
 impl Downloader {
@@ -60,6 +65,7 @@ impl Downloader {
         Downloader {
             info: DownloaderInfo::new(),
             block_downloads: Arc::new(DashMap::with_capacity(MAX_CONCURRENT_DOWNLOADS)),
+            state_downloads: Arc::new(DashMap::new()),
         }
     }
 
@@ -104,6 +110,65 @@ impl Downloader {
         Ok(())
     }
 
+    /// Schedules the download of the chunks of a state snapshot. The
+    /// chunks are split into downloads holding at most as many pieces
+    /// as a block download. Returns `Err(_)` if the downloads cannot
+    /// be scheduled.
+    pub async fn from_snapshot(
+        &self,
+        manifest: &SnapshotManifest,
+        priority: u64,
+    ) -> Result<(), DownloaderErr> {
+        debug!(
+            "Scheduling snapshot download for hash: {}, height: {}",
+            manifest.block_hash, manifest.height
+        );
+
+        if manifest.chunks.len() == 0 {
+            return Err(DownloaderErr::InvalidSize);
+        }
+
+        let mut downloads = Vec::new();
+
+        for chunks in manifest.chunks.chunks(MAX_TX_SET_SIZE / MAX_PIECE_SIZE) {
+            let key = chunks[0].0;
+
+            if self.state_downloads.get(&key).is_some() {
+                return Err(DownloaderErr::AlreadyHaveDownload);
+            }
+
+            downloads.push((key, Download::from_snapshot_chunks(chunks, priority)?));
+        }
+
+        for (key, download) in downloads {
+            self.info.state_infos.insert(key, download.to_info());
+            self.state_downloads
+                .insert(key, Arc::new(Mutex::new(download)));
+        }
+
+        debug!(
+            "Scheduling succeeded for snapshot with hash: {}, height: {}",
+            manifest.block_hash, manifest.height
+        );
+
+        Ok(())
+    }
+
+    /// Returns the chunks of the given snapshot in the order listed in
+    /// its manifest, or `None` if they have not all been downloaded.
+    pub fn snapshot_chunks(&self, manifest: &SnapshotManifest) -> Option<Vec<Vec<u8>>> {
+        let mut chunks = Vec::with_capacity(manifest.chunks.len());
+
+        for group in manifest.chunks.chunks(MAX_TX_SET_SIZE / MAX_PIECE_SIZE) {
+            let download = self.query_state(&group[0].0)?;
+            let data = download.lock().data()?;
+
+            chunks.extend(data);
+        }
+
+        Some(chunks)
+    }
+
     /// Returns the current downloader info
     pub fn get_info(&self) -> DownloaderInfo {
         self.info.clone()
@@ -117,6 +182,15 @@ impl Downloader {
         self.block_downloads.remove(hash).map(|(_, r)| r)
     }
 
+    pub fn query_state(&self, hash: &ShortHash) -> Option<Arc<Mutex<Download>>> {
+        self.state_downloads.get(hash).map(|r| r.clone())
+    }
+
+    pub fn remove_state(&self, hash: &ShortHash) -> Option<Arc<Mutex<Download>>> {
+        self.info.state_infos.remove(hash);
+        self.state_downloads.remove(hash).map(|(_, r)| r)
+    }
+
     async fn try_schedule_block_download(
         &self,
         block: Arc<TransactionBlock>,
@@ -174,12 +248,14 @@ impl Downloader {
 #[derive(Clone, Debug)]
 pub struct DownloaderInfo {
     pub(crate) block_infos: Arc<DashMap<ShortHash, DownloadInfo>>,
+    pub(crate) state_infos: Arc<DashMap<ShortHash, DownloadInfo>>,
 }
 
 impl DownloaderInfo {
     pub fn new() -> Self {
         DownloaderInfo {
             block_infos: Arc::new(DashMap::with_capacity(MAX_CONCURRENT_DOWNLOADS)),
+            state_infos: Arc::new(DashMap::new()),
         }
     }
 }
@@ -24,6 +24,7 @@ use crate::downloader::sub_pieces::SubPieces;
 use constants::*;
 use crypto::{BlakeHasher, ShortHash};
 use std::hash::Hasher;
+use triomphe::Arc;
 
 #[derive(Debug, PartialEq)]
 pub struct Piece {
@@ -53,13 +54,63 @@ impl Piece {
 
     /// Validates provided data and if successful, returns a `Piece` with the data.
     pub fn from_data(data: &[u8], checksum: ShortHash) -> Result<Piece, DownloaderErr> {
-        unimplemented!();
+        if data.len() == 0 || data.len() > MAX_PIECE_SIZE {
+            return Err(DownloaderErr::InvalidSize);
+        }
+
+        let piece = Self::from_data_unchecked(data);
+
+        if piece.checksum != checksum {
+            return Err(DownloaderErr::InvalidChecksum);
+        }
+
+        Ok(piece)
     }
 
     /// Returns a `Piece` with the provided data, performing no validation.
     /// This function will panic if the size of the data is greater than allowed.
     pub fn from_data_unchecked(data: &[u8]) -> Piece {
-        unimplemented!();
+        if data.len() > MAX_PIECE_SIZE {
+            panic!(
+                "Cannot create a Piece with a size greater than {}! Got: {}",
+                MAX_PIECE_SIZE,
+                data.len()
+            );
+        }
+
+        let mut sub_pieces = Vec::with_capacity(data.len() / MAX_SUB_PIECE_SIZE + 1);
+
+        for raw_sub_piece in data.chunks(MAX_SUB_PIECE_SIZE) {
+            let checksum = crypto::hash_slice(raw_sub_piece).to_short();
+            let mut sub_piece = SubPiece::new(raw_sub_piece.len() as u64, checksum);
+
+            sub_piece
+                .add_data(Arc::new(raw_sub_piece.to_vec()))
+                .unwrap();
+            sub_pieces.push(sub_piece);
+        }
+
+        let checksum = Self::compute_checksum(sub_pieces.iter().map(|s| &s.checksum));
+
+        Piece {
+            size: data.len() as u64,
+            completed: data.len() as u64,
+            checksum,
+            sub_pieces: Some(SubPieces::new(sub_pieces)),
+        }
+    }
+
+    /// Returns the data of the piece if all of its
+    /// sub-pieces have been downloaded.
+    pub fn data(&self) -> Option<Vec<u8>> {
+        let sub_pieces = self.sub_pieces.as_ref()?;
+        let mut data = Vec::with_capacity(self.size as usize);
+
+        for sub_piece in sub_pieces.sub_pieces.iter() {
+            data.extend_from_slice(sub_piece.data.as_ref()?);
+        }
+
+        Some(data)
     }
 
     pub fn from_info(info: &PieceInfo, checksum: ShortHash) -> Result<Piece, DownloaderErr> {
@@ -137,23 +188,27 @@ impl Piece {
 
     fn validate_checksum(&self, info: &PieceInfo) -> bool {
         if let Some(sub_pieces) = &info.sub_pieces {
-            let mut hasher = BlakeHasher::new();
-
-            for piece in sub_pieces.iter() {
-                hasher.write(&piece.checksum.0);
-            }
-
-            let hash = hasher.finish();
-            let hash = encode_le_u64!(hash);
-            let mut hash_bytes = [0; crypto::SHORT_HASH_BYTES];
-            hash_bytes.copy_from_slice(&hash);
-            let hash = ShortHash(hash_bytes);
-
-            hash == self.checksum
+            Self::compute_checksum(sub_pieces.iter().map(|s| &s.checksum)) == self.checksum
         } else {
             false
         }
     }
+
+    /// Computes the checksum of a piece from the checksums of its sub-pieces.
+    fn compute_checksum<'a>(checksums: impl Iterator<Item = &'a ShortHash>) -> ShortHash {
+        let mut hasher = BlakeHasher::new();
+
+        for checksum in checksums {
+            hasher.write(&checksum.0);
+        }
+
+        let hash = hasher.finish();
+        let hash = encode_le_u64!(hash);
+        let mut hash_bytes = [0; crypto::SHORT_HASH_BYTES];
+        hash_bytes.copy_from_slice(&hash);
+
+        ShortHash(hash_bytes)
+    }
 }
 
 #[cfg(test)]
@@ -226,6 +281,25 @@ mod tests {
         assert_eq!(piece.add_info(&info), Err(DownloaderErr::InvalidSize));
     }
 
+    #[test]
+    fn it_creates_pieces_from_data() {
+        let data = gen_random_bytes(MAX_PIECE_SIZE - 100);
+        let piece = Piece::from_data_unchecked(&data);
+        let checksum = piece.checksum;
+
+        assert_eq!(piece.size, data.len() as u64);
+        assert_eq!(piece.data(), Some(data.clone()));
+        assert_eq!(
+            piece.to_info().sub_pieces.unwrap().len(),
+            MAX_PIECE_SIZE / MAX_SUB_PIECE_SIZE
+        );
+        assert_eq!(Piece::from_data(&data, checksum), Ok(piece));
+        assert_eq!(
+            Piece::from_data(&data, crypto::hash_slice(b"random_hash").to_short()),
+            Err(DownloaderErr::InvalidChecksum)
+        );
+    }
+
     #[test]
     fn it_doesnt_have_info() {
         let piece = Piece::new(123, crypto::hash_slice(b"random_hash").to_short());
@@ -100,6 +100,12 @@ pub enum NetworkErr {
 
     /// Generic IO error
     IoErr,
+
+    /// The received state or inclusion proof is invalid
+    InvalidProof,
+
+    /// The request can only be answered by full nodes
+    NotAFullNode,
 }
 
 impl From<DownloaderErr> for NetworkErr {
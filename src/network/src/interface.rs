@@ -102,8 +102,8 @@ pub trait NetworkInterface: Clone + Send + Sync {
     /// Returns a reference to the current network name
     fn network_name(&self) -> &str;
 
-    /// Returns a reference to the `PowChain`.
-    fn pow_chain_ref(&self) -> PowChainRef;
+    /// Returns a reference to the `PowChain` if we are a full node.
+    fn pow_chain_ref(&self) -> Option<PowChainRef>;
 
     /// Returns a reference to the `Downloader`
     fn downloader(&self) -> Downloader;
@@ -122,6 +122,9 @@ pub trait NetworkInterface: Clone + Send + Sync {
     /// Returns a reference to the mempool if we have one.
     fn mempool_ref(&self) -> Option<Arc<RwLock<Mempool>>>;
 
+    /// Returns a reference to the `LightChain` if we are a light client.
+    fn light_chain_ref(&self) -> Option<LightChainRef>;
+
     /// Call-back that executes after a `Connect` or `ConnectPool`
     /// packet has been successfully processed from a peer.
     fn after_connect(&self, peer: &SocketAddr);
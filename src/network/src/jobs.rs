@@ -20,14 +20,22 @@
 
 use crate::interface::NetworkInterface;
 use crate::network::Network;
+use crate::priority::NetworkPriority;
+use crate::protocol_flow::request_blocks::RequestBlocksSenderArgs;
+use crate::validation::sender::Sender;
+use rand::prelude::IteratorRandom;
 use std::sync::atomic::Ordering;
 use std::time::Duration;
 
+/// The number of headers requested at once by a light client.
+const LIGHT_SYNC_BATCH_SIZE: u8 = 64;
+
 /// Starts jobs that are executed once every ~1 second.
 pub async fn start_periodic_jobs(network: Network) {
     loop {
         debug!("Executing periodic jobs...");
         tokio::spawn(account_bytes_read_write_for_peers(network.clone()));
+        tokio::spawn(sync_light_chain_headers(network.clone()));
         tokio::time::delay_for(Duration::from_secs(1)).await;
     }
 }
@@ -54,3 +62,40 @@ async fn account_bytes_read_write_for_peers(network: Network) {
         });
     }
 }
+
+/// Requests the headers following our tip from a random
+/// peer if we are a light client.
+async fn sync_light_chain_headers(network: Network) {
+    let light_chain = if let Some(light_chain) = network.light_chain_ref() {
+        light_chain
+    } else {
+        return;
+    };
+
+    let tip_hash = light_chain.tip().block_hash().unwrap();
+    let peers = network.peers();
+    let addr = if let Some(peer) = peers.iter().choose(&mut rand::thread_rng()) {
+        *peer.key()
+    } else {
+        return;
+    };
+
+    let packet = {
+        let peer = peers.get(&addr).unwrap();
+        let mut sender = peer.validator.request_blocks.sender.lock();
+        let args = RequestBlocksSenderArgs::new(LIGHT_SYNC_BATCH_SIZE, tip_hash, false);
+
+        if let Ok(packet) = sender.send(args) {
+            packet
+        } else {
+            return;
+        }
+    };
+
+    debug!("Requesting headers from {}", addr);
+
+    network
+        .send_to_peer(&addr, &packet, NetworkPriority::Medium)
+        .map_err(|err| warn!("Could not request headers from {}: {:?}", addr, err))
+        .unwrap_or(());
+}
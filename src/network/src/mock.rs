@@ -217,8 +217,8 @@ impl NetworkInterface for MockNetwork {
         Ok(())
     }
 
-    fn pow_chain_ref(&self) -> PowChainRef {
-        self.pow_chain_ref.clone()
+    fn pow_chain_ref(&self) -> Option<PowChainRef> {
+        Some(self.pow_chain_ref.clone())
     }
 
     fn has_peer(&self, addr: &SocketAddr) -> bool {
@@ -269,6 +269,10 @@ impl NetworkInterface for MockNetwork {
         unimplemented!();
     }
 
+    fn light_chain_ref(&self) -> Option<LightChainRef> {
+        None
+    }
+
     fn after_connect(&self, _peer: &SocketAddr) {}
 }
 
@@ -23,6 +23,7 @@ use crate::error::NetworkErr;
 use crate::interface::NetworkInterface;
 use crate::packet::Packet;
 use crate::packets::connect::Connect;
+use crate::packets::ProofRequest;
 use crate::peer::ConnectionType;
 use crate::priority::NetworkPriority;
 use crate::validation::sender::Sender as SenderTrait;
@@ -58,8 +59,8 @@ pub struct Network {
     /// Our secret key
     pub(crate) secret_key: Sk,
 
-    /// Reference to the `PowChain`
-    pow_chain_ref: PowChainRef,
+    /// Reference to the `PowChain` if we are a full node
+    pow_chain_ref: Option<PowChainRef>,
 
     /// Sender to `PowChain` block buffer
     pow_chain_sender: Sender<(SocketAddr, Arc<PowBlock>)>,
@@ -85,6 +86,9 @@ pub struct Network {
     /// Reference to the mempool
     pub(crate) mempool_ref: Option<Arc<RwLock<Mempool>>>,
 
+    /// Reference to the `LightChain` if we are a light client
+    pub(crate) light_chain_ref: Option<LightChainRef>,
+
     #[cfg(feature = "miner")]
     /// Our retrieved ip address
     pub(crate) our_ip: SocketAddr,
@@ -98,9 +102,10 @@ impl Network {
         secret_key: Sk,
         max_peers: usize,
         pow_chain_sender: Sender<(SocketAddr, Arc<PowBlock>)>,
-        pow_chain_ref: PowChainRef,
+        pow_chain_ref: Option<PowChainRef>,
         bootstrap_cache: BootstrapCache,
         mempool_ref: Option<Arc<RwLock<Mempool>>>,
+        light_chain_ref: Option<LightChainRef>,
         accept_connections: Arc<AtomicBool>,
         our_ip: Option<SocketAddr>,
     ) -> Network {
@@ -115,6 +120,7 @@ impl Network {
             pow_chain_ref,
             bootstrap_cache,
             mempool_ref,
+            light_chain_ref,
             accept_connections,
             downloader: Downloader::new(),
 
@@ -166,6 +172,23 @@ impl Network {
             None => panic!("There is no listed peer with the given address!"),
         }
     }
+
+    /// Requests the given proof from the first peer which is able
+    /// to accept a new request. Verified answers are cached in the
+    /// `LightChain` so this must only be called by light clients.
+    pub fn request_proof(&self, request: ProofRequest) -> Result<(), NetworkErr> {
+        for peer in self.peers.iter() {
+            let addr = peer.key();
+            let mut sender = peer.validator.request_proof.sender.lock();
+
+            if let Ok(packet) = sender.send(request.clone()) {
+                debug!("Requesting proof from {}", addr);
+                return self.send_to_peer(addr, &packet, NetworkPriority::Medium);
+            }
+        }
+
+        Err(NetworkErr::NoPeers)
+    }
 }
 
 impl NetworkInterface for Network {
@@ -273,7 +296,7 @@ impl NetworkInterface for Network {
         self.network_name.as_str()
     }
 
-    fn pow_chain_ref(&self) -> PowChainRef {
+    fn pow_chain_ref(&self) -> Option<PowChainRef> {
         self.pow_chain_ref.clone()
     }
 
@@ -313,6 +336,10 @@ impl NetworkInterface for Network {
         self.mempool_ref.clone()
     }
 
+    fn light_chain_ref(&self) -> Option<LightChainRef> {
+        self.light_chain_ref.clone()
+    }
+
     fn after_connect(&self, addr: &SocketAddr) {
         debug!("Executing after connect callback for {}", addr);
 
@@ -29,11 +29,13 @@ pub mod request_block;
 pub mod request_blocks;
 pub mod request_peers;
 pub mod request_piece_info;
+pub mod request_proof;
 pub mod request_sub_piece;
 pub mod request_tx;
 pub mod send_blocks;
 pub mod send_peers;
 pub mod send_piece_info;
+pub mod send_proof;
 pub mod send_sub_piece;
 pub mod send_tx;
 pub use self::announce_block::*;
@@ -49,10 +51,12 @@ pub use self::request_block::*;
 pub use self::request_blocks::*;
 pub use self::request_peers::*;
 pub use self::request_piece_info::*;
+pub use self::request_proof::*;
 pub use self::request_sub_piece::*;
 pub use self::request_tx::*;
 pub use self::send_blocks::*;
 pub use self::send_peers::*;
 pub use self::send_piece_info::*;
+pub use self::send_proof::*;
 pub use self::send_sub_piece::*;
 pub use self::send_tx::*;
@@ -210,6 +210,18 @@ impl Packet for RequestBlocks {
 
         Ok(())
     }
+
+    async fn start_server_protocol_flow<
+        N: NetworkInterface,
+        S: AsyncWrite + AsyncWriteExt + AsyncRead + AsyncReadExt + Unpin + Send + Sync,
+    >(
+        network: &mut N,
+        sock: &mut S,
+        peer: &SocketAddr,
+        initial_packet: Arc<Self>,
+    ) -> Result<(), NetworkErr> {
+        Self::handle(network, sock, peer, initial_packet, ConnectionType::Server).await
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,307 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::client_request::ClientRequest;
+use crate::connection::*;
+use crate::error::NetworkErr;
+use crate::interface::NetworkInterface;
+use crate::packet::Packet;
+use crate::packets::SendProof;
+use crate::peer::ConnectionType;
+use crate::priority::NetworkPriority;
+use crate::validation::receiver::Receiver;
+use async_trait::async_trait;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use crypto::Hash;
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::io::{AsyncReadExt, AsyncWriteExt};
+use rand::prelude::*;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use triomphe::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+/// The proof requested by a light client.
+pub enum ProofRequest {
+    /// Proof of the value stored under a state key
+    /// in the state of the block with the given hash.
+    State { block_hash: Hash, key: Vec<u8> },
+
+    /// Proof that the transaction with the given
+    /// hash is included in a canonical block.
+    Tx { tx_hash: Hash },
+}
+
+impl ProofRequest {
+    const STATE: u8 = 0;
+    const TX: u8 = 1;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestProof {
+    /// Randomly generated nonce
+    pub(crate) nonce: u64,
+
+    /// The requested proof
+    pub(crate) request: ProofRequest,
+}
+
+impl RequestProof {
+    pub fn new(request: ProofRequest) -> RequestProof {
+        let mut rng = rand::thread_rng();
+
+        RequestProof {
+            nonce: rng.gen(),
+            request,
+        }
+    }
+}
+
+#[async_trait]
+impl Packet for RequestProof {
+    const PACKET_TYPE: u8 = 22;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+        let packet_type: u8 = Self::PACKET_TYPE;
+
+        // Packet structure:
+        // 1) Packet type(22)   - 8bits
+        // 2) Nonce             - 64bits
+        // 3) Proof type        - 8bits
+        //
+        // For state proofs:
+        // 4) Block hash        - 32bytes
+        // 5) Key length        - 16bits
+        // 6) Key               - Key length bytes
+        //
+        // For transaction proofs:
+        // 4) Tx hash           - 32bytes
+        buffer.write_u8(packet_type).unwrap();
+        buffer.write_u64::<BigEndian>(self.nonce).unwrap();
+
+        match self.request {
+            ProofRequest::State {
+                ref block_hash,
+                ref key,
+            } => {
+                buffer.write_u8(ProofRequest::STATE).unwrap();
+                buffer.extend_from_slice(&block_hash.0);
+                buffer.write_u16::<BigEndian>(key.len() as u16).unwrap();
+                buffer.extend_from_slice(key);
+            }
+
+            ProofRequest::Tx { ref tx_hash } => {
+                buffer.write_u8(ProofRequest::TX).unwrap();
+                buffer.extend_from_slice(&tx_hash.0);
+            }
+        }
+
+        buffer
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Arc<RequestProof>, NetworkErr> {
+        let mut rdr = Cursor::new(bytes);
+
+        let packet_type = if let Ok(result) = rdr.read_u8() {
+            result
+        } else {
+            return Err(NetworkErr::BadFormat);
+        };
+
+        if packet_type != Self::PACKET_TYPE {
+            return Err(NetworkErr::BadFormat);
+        }
+
+        if bytes.len() < 42 {
+            return Err(NetworkErr::BadFormat);
+        }
+
+        rdr.set_position(1);
+
+        let nonce = if let Ok(result) = rdr.read_u64::<BigEndian>() {
+            result
+        } else {
+            return Err(NetworkErr::BadFormat);
+        };
+
+        rdr.set_position(9);
+
+        let proof_type = if let Ok(result) = rdr.read_u8() {
+            result
+        } else {
+            return Err(NetworkErr::BadFormat);
+        };
+
+        let hash = {
+            let mut hash = [0; 32];
+            hash.copy_from_slice(&bytes[10..42]);
+
+            Hash(hash)
+        };
+
+        let request = match proof_type {
+            ProofRequest::STATE => {
+                rdr.set_position(42);
+
+                let key_len = if let Ok(result) = rdr.read_u16::<BigEndian>() {
+                    result
+                } else {
+                    return Err(NetworkErr::BadFormat);
+                };
+
+                if bytes.len() != 44 + key_len as usize {
+                    return Err(NetworkErr::BadFormat);
+                }
+
+                ProofRequest::State {
+                    block_hash: hash,
+                    key: bytes[44..].to_vec(),
+                }
+            }
+
+            ProofRequest::TX => {
+                if bytes.len() != 42 {
+                    return Err(NetworkErr::BadFormat);
+                }
+
+                ProofRequest::Tx { tx_hash: hash }
+            }
+
+            _ => return Err(NetworkErr::BadFormat),
+        };
+
+        let packet = RequestProof { nonce, request };
+
+        Ok(Arc::new(packet))
+    }
+
+    async fn handle<N: NetworkInterface, S: AsyncWrite + AsyncWriteExt + Unpin + Send + Sync>(
+        network: &mut N,
+        sock: &mut S,
+        addr: &SocketAddr,
+        packet: Arc<Self>,
+        conn_type: ConnectionType,
+    ) -> Result<(), NetworkErr> {
+        debug!(
+            "Received RequestProof packet from {} with nonce {}",
+            addr, packet.nonce
+        );
+
+        // Retrieve receiver mutex
+        let receiver = {
+            let peers = network.peers();
+            let peer = peers.get(addr).ok_or(NetworkErr::SessionExpired)?;
+
+            peer.validator.request_proof.receiver.clone()
+        };
+
+        // Attempt to receive packet
+        let packet = {
+            let mut receiver = receiver.lock();
+            receiver.receive(network as &N, addr, &packet)?
+        };
+
+        debug!("Sending SendProof packet to {}", addr);
+
+        // Send `SendProof` packet back to peer
+        network.send_to_peer(addr, &packet, NetworkPriority::Medium)?;
+
+        debug!("SendProof packet sent to {}", addr);
+
+        Ok(())
+    }
+
+    fn to_client_request(&self) -> Option<ClientRequest> {
+        Some(ClientRequest::RequestProof)
+    }
+
+    async fn start_client_protocol_flow<
+        N: NetworkInterface,
+        S: AsyncWrite + AsyncWriteExt + AsyncRead + AsyncReadExt + Unpin + Send + Sync,
+    >(
+        network: &mut N,
+        sock: &mut S,
+        peer: &SocketAddr,
+    ) -> Result<(), NetworkErr> {
+        // Read response packet from stream
+        let bytes = read_raw_packet(sock, network as &_, peer, true)
+            .await
+            .map_err(|_| NetworkErr::IoErr)?;
+
+        // Deserialize packet
+        let packet = SendProof::from_bytes(&bytes)?;
+
+        // Handle packet
+        SendProof::handle(network, sock, peer, packet, ConnectionType::Client).await?;
+
+        Ok(())
+    }
+
+    async fn start_server_protocol_flow<
+        N: NetworkInterface,
+        S: AsyncWrite + AsyncWriteExt + AsyncRead + AsyncReadExt + Unpin + Send + Sync,
+    >(
+        network: &mut N,
+        sock: &mut S,
+        peer: &SocketAddr,
+        initial_packet: Arc<Self>,
+    ) -> Result<(), NetworkErr> {
+        Self::handle(network, sock, peer, initial_packet, ConnectionType::Server).await
+    }
+}
+
+#[cfg(test)]
+use quickcheck::Arbitrary;
+
+#[cfg(test)]
+impl Arbitrary for ProofRequest {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> ProofRequest {
+        if Arbitrary::arbitrary(g) {
+            ProofRequest::State {
+                block_hash: Arbitrary::arbitrary(g),
+                key: Arbitrary::arbitrary(g),
+            }
+        } else {
+            ProofRequest::Tx {
+                tx_hash: Arbitrary::arbitrary(g),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Arbitrary for RequestProof {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> RequestProof {
+        RequestProof {
+            nonce: Arbitrary::arbitrary(g),
+            request: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    quickcheck! {
+        fn serialize_deserialize(packet: Arc<RequestProof>) -> bool {
+            packet == RequestProof::from_bytes(&RequestProof::to_bytes(&packet)).unwrap()
+        }
+    }
+}
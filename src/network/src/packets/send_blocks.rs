@@ -21,6 +21,7 @@ use crate::error::NetworkErr;
 use crate::interface::NetworkInterface;
 use crate::packet::Packet;
 use crate::peer::ConnectionType;
+use crate::validation::sender::Sender;
 use async_trait::async_trait;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use chain::*;
@@ -151,7 +152,47 @@ impl Packet for SendBlocks {
         packet: Arc<Self>,
         conn_type: ConnectionType,
     ) -> Result<(), NetworkErr> {
-        unimplemented!();
+        debug!(
+            "Received SendBlocks packet from {} with nonce {}",
+            addr, packet.nonce
+        );
+
+        // Retrieve sender mutex
+        let sender = {
+            let peers = network.peers();
+            let peer = peers.get(addr).ok_or(NetworkErr::SessionExpired)?;
+
+            peer.validator.request_blocks.sender.clone()
+        };
+
+        // Attempt to acknowledge packet
+        {
+            let mut sender = sender.lock();
+            sender.acknowledge(&packet)?;
+        }
+
+        // Light clients only follow the headers of the received
+        // blocks while full nodes buffer them for appending.
+        if let Some(light_chain) = network.light_chain_ref() {
+            for block in packet.blocks.iter() {
+                match light_chain.append_header(block.clone()) {
+                    Ok(()) | Err(ChainErr::AlreadyInChain) => {}
+                    Err(err) => {
+                        debug!("Could not append header from {}: {:?}", addr, err);
+                        break;
+                    }
+                }
+            }
+        } else {
+            for block in packet.blocks.iter() {
+                network
+                    .pow_chain_sender()
+                    .send((*addr, block.clone()))
+                    .map_err(|_| NetworkErr::CouldNotSend)?;
+            }
+        }
+
+        Ok(())
     }
 
     fn to_client_request(&self) -> Option<ClientRequest> {
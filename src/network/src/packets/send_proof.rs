@@ -0,0 +1,357 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::client_request::ClientRequest;
+use crate::error::NetworkErr;
+use crate::interface::NetworkInterface;
+use crate::packet::Packet;
+use crate::peer::ConnectionType;
+use crate::validation::sender::Sender;
+use async_trait::async_trait;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chain::{StateProof, TxInclusionProof};
+use crypto::Hash;
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::io::{AsyncReadExt, AsyncWriteExt};
+use std::io::Cursor;
+use std::net::SocketAddr;
+use transactions::Tx;
+use triomphe::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+/// The answer of a full node to a `RequestProof` packet.
+pub enum ProofResponse {
+    /// Proof of a state entry in the block with the given hash.
+    State { block_hash: Hash, proof: StateProof },
+
+    /// Proof that the given transaction is included
+    /// in the block with the given hash.
+    Tx {
+        block_hash: Hash,
+        tx: Arc<Tx>,
+        proof: TxInclusionProof,
+    },
+
+    /// The requested proof cannot be built by the peer.
+    NotFound,
+}
+
+impl ProofResponse {
+    const STATE: u8 = 0;
+    const TX: u8 = 1;
+    const NOT_FOUND: u8 = 2;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SendProof {
+    /// The nonce of the answered `RequestProof` packet
+    pub(crate) nonce: u64,
+
+    /// The requested proof
+    pub(crate) response: ProofResponse,
+}
+
+impl SendProof {
+    pub fn new(response: ProofResponse, nonce: u64) -> SendProof {
+        SendProof { nonce, response }
+    }
+}
+
+#[async_trait]
+impl Packet for SendProof {
+    const PACKET_TYPE: u8 = 23;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+        let packet_type: u8 = Self::PACKET_TYPE;
+
+        // Packet structure:
+        // 1) Packet type(23)   - 8bits
+        // 2) Nonce             - 64bits
+        // 3) Proof type        - 8bits
+        //
+        // For state proofs:
+        // 4) Block hash        - 32bytes
+        // 5) State proof       - Binary of the remaining length
+        //
+        // For transaction proofs:
+        // 4) Block hash        - 32bytes
+        // 5) Tx length         - 32bits
+        // 6) Tx                - Binary of tx length
+        // 7) Inclusion proof   - Binary of the remaining length
+        buffer.write_u8(packet_type).unwrap();
+        buffer.write_u64::<BigEndian>(self.nonce).unwrap();
+
+        match self.response {
+            ProofResponse::State {
+                ref block_hash,
+                ref proof,
+            } => {
+                buffer.write_u8(ProofResponse::STATE).unwrap();
+                buffer.extend_from_slice(&block_hash.0);
+                buffer.extend_from_slice(&proof.to_bytes());
+            }
+
+            ProofResponse::Tx {
+                ref block_hash,
+                ref tx,
+                ref proof,
+            } => {
+                let tx = tx.to_bytes();
+
+                buffer.write_u8(ProofResponse::TX).unwrap();
+                buffer.extend_from_slice(&block_hash.0);
+                buffer.write_u32::<BigEndian>(tx.len() as u32).unwrap();
+                buffer.extend_from_slice(&tx);
+                buffer.extend_from_slice(&proof.to_bytes());
+            }
+
+            ProofResponse::NotFound => {
+                buffer.write_u8(ProofResponse::NOT_FOUND).unwrap();
+            }
+        }
+
+        buffer
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Arc<SendProof>, NetworkErr> {
+        let mut rdr = Cursor::new(bytes);
+
+        let packet_type = if let Ok(result) = rdr.read_u8() {
+            result
+        } else {
+            return Err(NetworkErr::BadFormat);
+        };
+
+        if packet_type != Self::PACKET_TYPE {
+            return Err(NetworkErr::BadFormat);
+        }
+
+        rdr.set_position(1);
+
+        let nonce = if let Ok(result) = rdr.read_u64::<BigEndian>() {
+            result
+        } else {
+            return Err(NetworkErr::BadFormat);
+        };
+
+        rdr.set_position(9);
+
+        let proof_type = if let Ok(result) = rdr.read_u8() {
+            result
+        } else {
+            return Err(NetworkErr::BadFormat);
+        };
+
+        let response = match proof_type {
+            ProofResponse::NOT_FOUND => {
+                if bytes.len() != 10 {
+                    return Err(NetworkErr::BadFormat);
+                }
+
+                ProofResponse::NotFound
+            }
+
+            ProofResponse::STATE | ProofResponse::TX => {
+                if bytes.len() < 42 {
+                    return Err(NetworkErr::BadFormat);
+                }
+
+                let block_hash = {
+                    let mut hash = [0; 32];
+                    hash.copy_from_slice(&bytes[10..42]);
+
+                    Hash(hash)
+                };
+
+                if proof_type == ProofResponse::STATE {
+                    let proof =
+                        StateProof::from_bytes(&bytes[42..]).map_err(|_| NetworkErr::BadFormat)?;
+
+                    ProofResponse::State { block_hash, proof }
+                } else {
+                    rdr.set_position(42);
+
+                    let tx_len = if let Ok(result) = rdr.read_u32::<BigEndian>() {
+                        result as usize
+                    } else {
+                        return Err(NetworkErr::BadFormat);
+                    };
+
+                    if bytes.len() < 46 + tx_len {
+                        return Err(NetworkErr::BadFormat);
+                    }
+
+                    let tx = Tx::from_bytes(&bytes[46..(46 + tx_len)])
+                        .map_err(|_| NetworkErr::BadFormat)?;
+                    let proof = TxInclusionProof::from_bytes(&bytes[(46 + tx_len)..])
+                        .map_err(|_| NetworkErr::BadFormat)?;
+
+                    ProofResponse::Tx {
+                        block_hash,
+                        tx: Arc::new(tx),
+                        proof,
+                    }
+                }
+            }
+
+            _ => return Err(NetworkErr::BadFormat),
+        };
+
+        let packet = SendProof { nonce, response };
+
+        Ok(Arc::new(packet))
+    }
+
+    async fn handle<N: NetworkInterface, S: AsyncWrite + AsyncWriteExt + Unpin + Send + Sync>(
+        network: &mut N,
+        sock: &mut S,
+        addr: &SocketAddr,
+        packet: Arc<Self>,
+        conn_type: ConnectionType,
+    ) -> Result<(), NetworkErr> {
+        debug!(
+            "Received SendProof packet from {} with nonce {}",
+            addr, packet.nonce
+        );
+
+        // Retrieve sender mutex
+        let sender = {
+            let peers = network.peers();
+            let peer = peers.get(addr).ok_or(NetworkErr::SessionExpired)?;
+
+            peer.validator.request_proof.sender.clone()
+        };
+
+        // Attempt to acknowledge packet
+        {
+            let mut sender = sender.lock();
+            sender.acknowledge(&packet)?;
+        }
+
+        // Only light clients request proofs
+        let light_chain = network
+            .light_chain_ref()
+            .ok_or(NetworkErr::CannotStartProtocolFlow)?;
+
+        match packet.response {
+            ProofResponse::State {
+                ref block_hash,
+                ref proof,
+            } => {
+                light_chain
+                    .verify_state_proof(block_hash, proof)
+                    .map_err(|err| {
+                        debug!("Invalid state proof from {}: {}", addr, err);
+                        NetworkErr::InvalidProof
+                    })?;
+            }
+
+            ProofResponse::Tx {
+                ref block_hash,
+                ref tx,
+                ref proof,
+            } => {
+                if !light_chain.verify_tx_inclusion(block_hash, tx, proof) {
+                    debug!("Invalid inclusion proof from {}", addr);
+                    return Err(NetworkErr::InvalidProof);
+                }
+            }
+
+            ProofResponse::NotFound => {
+                debug!("Peer {} could not build the requested proof", addr);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_client_request(&self) -> Option<ClientRequest> {
+        None
+    }
+}
+
+#[cfg(test)]
+use quickcheck::Arbitrary;
+
+#[cfg(test)]
+impl Arbitrary for ProofResponse {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> ProofResponse {
+        let random: u8 = Arbitrary::arbitrary(g);
+
+        match random % 3 {
+            0 => {
+                let nodes: Vec<Vec<u8>> = Arbitrary::arbitrary(g);
+
+                ProofResponse::State {
+                    block_hash: Arbitrary::arbitrary(g),
+                    proof: StateProof {
+                        key: Arbitrary::arbitrary(g),
+                        nodes: nodes
+                            .into_iter()
+                            .map(|mut node| {
+                                node.push(0);
+                                node
+                            })
+                            .collect(),
+                    },
+                }
+            }
+
+            1 => {
+                let tx: Tx = Arbitrary::arbitrary(g);
+                let tx_count: u32 = Arbitrary::arbitrary(g);
+                let tx_count = tx_count.max(1);
+                let position: u32 = Arbitrary::arbitrary(g);
+
+                ProofResponse::Tx {
+                    block_hash: Arbitrary::arbitrary(g),
+                    tx: Arc::new(tx),
+                    proof: TxInclusionProof {
+                        position: position % tx_count,
+                        tx_count,
+                        siblings: Arbitrary::arbitrary(g),
+                    },
+                }
+            }
+
+            _ => ProofResponse::NotFound,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Arbitrary for SendProof {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> SendProof {
+        SendProof {
+            nonce: Arbitrary::arbitrary(g),
+            response: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    quickcheck! {
+        fn serialize_deserialize(packet: Arc<SendProof>) -> bool {
+            packet == SendProof::from_bytes(&SendProof::to_bytes(&packet)).unwrap()
+        }
+    }
+}
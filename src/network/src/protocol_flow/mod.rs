@@ -19,4 +19,5 @@
 pub mod ping_pong;
 pub mod request_blocks;
 pub mod request_peers;
+pub mod request_proof;
 pub mod transaction_propagation;
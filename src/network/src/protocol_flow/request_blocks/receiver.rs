@@ -45,7 +45,7 @@ impl Receiver<RequestBlocks, SendBlocks> for RequestBlocksReceiver {
         packet: &RequestBlocks,
     ) -> Result<SendBlocks, NetworkErr> {
         if let RequestBlocksReceiverState::Ready = self.state {
-            let chain = network.pow_chain_ref();
+            let chain = network.pow_chain_ref().ok_or(NetworkErr::NotAFullNode)?;
 
             if packet.is_descending {
                 if let Some(blocks) = chain.query_descending(&packet.from, packet.requested_blocks)
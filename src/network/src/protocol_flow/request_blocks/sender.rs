@@ -34,6 +34,16 @@ pub struct RequestBlocksSenderArgs {
     is_descending: bool,
 }
 
+impl RequestBlocksSenderArgs {
+    pub fn new(requested_blocks: u8, from: Hash, is_descending: bool) -> RequestBlocksSenderArgs {
+        RequestBlocksSenderArgs {
+            requested_blocks,
+            from,
+            is_descending,
+        }
+    }
+}
+
 impl Sender<RequestBlocks, SendBlocks, RequestBlocksSenderArgs> for RequestBlocksSender {
     fn send(&mut self, args: RequestBlocksSenderArgs) -> Result<RequestBlocks, NetworkErr> {
         if let RequestBlocksSenderState::Ready = self.state {
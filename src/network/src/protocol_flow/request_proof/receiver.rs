@@ -0,0 +1,96 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::error::NetworkErr;
+use crate::interface::NetworkInterface;
+use crate::packets::{ProofRequest, ProofResponse, RequestProof, SendProof};
+use crate::protocol_flow::request_proof::receiver_state::RequestProofReceiverState;
+use crate::validation::receiver::Receiver;
+use std::net::SocketAddr;
+
+#[derive(Debug)]
+pub struct RequestProofReceiver {
+    state: RequestProofReceiverState,
+}
+
+impl RequestProofReceiver {
+    pub fn new() -> RequestProofReceiver {
+        RequestProofReceiver {
+            state: RequestProofReceiverState::default(),
+        }
+    }
+}
+
+impl Receiver<RequestProof, SendProof> for RequestProofReceiver {
+    fn receive<N: NetworkInterface>(
+        &mut self,
+        network: &N,
+        sender: &SocketAddr,
+        packet: &RequestProof,
+    ) -> Result<SendProof, NetworkErr> {
+        if let RequestProofReceiverState::Ready = self.state {
+            let chain = match network.pow_chain_ref() {
+                Some(chain) => chain,
+
+                // Light clients have no proofs to give
+                None => return Ok(SendProof::new(ProofResponse::NotFound, packet.nonce)),
+            };
+
+            let response = match packet.request {
+                ProofRequest::State {
+                    ref block_hash,
+                    ref key,
+                } => match chain.state_proof(block_hash, key) {
+                    Ok(proof) => ProofResponse::State {
+                        block_hash: *block_hash,
+                        proof,
+                    },
+                    Err(_) => ProofResponse::NotFound,
+                },
+
+                ProofRequest::Tx { ref tx_hash } => {
+                    match (chain.query_tx(tx_hash), chain.tx_inclusion_proof(tx_hash)) {
+                        (Some(indexed), Some((block_hash, proof))) => ProofResponse::Tx {
+                            block_hash,
+                            tx: indexed.tx,
+                            proof,
+                        },
+                        _ => ProofResponse::NotFound,
+                    }
+                }
+            };
+
+            Ok(SendProof::new(response, packet.nonce))
+        } else {
+            Err(NetworkErr::ReceiverStateErr)
+        }
+    }
+
+    fn done(&self) -> bool {
+        unimplemented!();
+    }
+
+    /// Returns true if the receiver is able to receive packets.
+    fn can_receive(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        self.state = RequestProofReceiverState::Ready;
+    }
+}
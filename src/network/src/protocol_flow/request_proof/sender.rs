@@ -0,0 +1,133 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::error::NetworkErr;
+use crate::packets::{ProofRequest, ProofResponse, RequestProof, SendProof};
+use crate::protocol_flow::request_proof::sender_state::RequestProofSenderState;
+use crate::validation::sender::Sender;
+
+#[derive(Debug, Default)]
+pub struct RequestProofSender {
+    state: RequestProofSenderState,
+}
+
+impl Sender<RequestProof, SendProof, ProofRequest> for RequestProofSender {
+    fn send(&mut self, request: ProofRequest) -> Result<RequestProof, NetworkErr> {
+        if let RequestProofSenderState::Ready = self.state {
+            let request_proof = RequestProof::new(request.clone());
+
+            // Await a `SendProof` with the generated nonce
+            self.state = RequestProofSenderState::Waiting(request_proof.nonce, request);
+
+            Ok(request_proof)
+        } else {
+            Err(NetworkErr::CouldNotSend)
+        }
+    }
+
+    fn acknowledge(&mut self, packet: &SendProof) -> Result<(), NetworkErr> {
+        if let RequestProofSenderState::Waiting(nonce, ref request) = self.state {
+            // The response must answer the request we have sent
+            let answers_request = match (request, &packet.response) {
+                (_, ProofResponse::NotFound) => true,
+                (
+                    ProofRequest::State { block_hash, key },
+                    ProofResponse::State {
+                        block_hash: proof_block_hash,
+                        proof,
+                    },
+                ) => block_hash == proof_block_hash && key == &proof.key,
+                (ProofRequest::Tx { tx_hash }, ProofResponse::Tx { tx, .. }) => {
+                    tx.tx_hash().as_ref() == Some(tx_hash)
+                }
+                _ => false,
+            };
+
+            if nonce == packet.nonce && answers_request {
+                // Reset state
+                self.state = RequestProofSenderState::Ready;
+
+                Ok(())
+            } else {
+                Err(NetworkErr::AckErr)
+            }
+        } else {
+            Err(NetworkErr::SenderStateErr)
+        }
+    }
+
+    fn done(&self) -> bool {
+        unimplemented!();
+    }
+
+    fn can_send(&self) -> bool {
+        self.state == RequestProofSenderState::Ready
+    }
+
+    fn reset(&mut self) {
+        self.state = RequestProofSenderState::Ready;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::Hash;
+
+    #[test]
+    fn it_acknowledges_only_matching_responses() {
+        let mut sender = RequestProofSender::default();
+        let tx = transactions::send_coins(
+            transactions::TestAccount::A,
+            transactions::TestAccount::B,
+            100,
+            10,
+            1,
+        );
+        let request = ProofRequest::Tx {
+            tx_hash: crypto::hash_slice(b"other-tx"),
+        };
+        let packet = sender.send(request).unwrap();
+        let response = ProofResponse::Tx {
+            block_hash: Hash::NULL,
+            tx: triomphe::Arc::new(tx),
+            proof: chain::TxInclusionProof {
+                position: 0,
+                tx_count: 1,
+                siblings: vec![],
+            },
+        };
+
+        assert!(!sender.can_send());
+        assert_eq!(
+            sender.acknowledge(&SendProof::new(response, packet.nonce)),
+            Err(NetworkErr::AckErr)
+        );
+        assert_eq!(
+            sender.acknowledge(&SendProof::new(
+                ProofResponse::NotFound,
+                packet.nonce.wrapping_add(1)
+            )),
+            Err(NetworkErr::AckErr)
+        );
+        assert!(sender
+            .acknowledge(&SendProof::new(ProofResponse::NotFound, packet.nonce))
+            .is_ok());
+        assert!(sender.can_send());
+    }
+}
@@ -0,0 +1,36 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::protocol_flow::request_proof::*;
+use parking_lot::Mutex;
+use triomphe::Arc;
+
+#[derive(Clone, Debug)]
+pub struct RequestProof {
+    pub(crate) sender: Arc<Mutex<RequestProofSender>>,
+    pub(crate) receiver: Arc<Mutex<RequestProofReceiver>>,
+}
+
+impl RequestProof {
+    pub fn new() -> RequestProof {
+        RequestProof {
+            sender: Default::default(),
+            receiver: Arc::new(Mutex::new(RequestProofReceiver::new())),
+        }
+    }
+}
@@ -20,6 +20,7 @@ use crate::bootstrap::cache::BootstrapCache;
 use crate::protocol_flow::ping_pong::PingPong;
 use crate::protocol_flow::request_blocks::RequestBlocks;
 use crate::protocol_flow::request_peers::RequestPeers;
+use crate::protocol_flow::request_proof::RequestProof;
 use crate::protocol_flow::transaction_propagation::TransactionPropagation;
 use std::default::Default;
 
@@ -36,6 +37,9 @@ pub struct ProtocolValidator {
     /// Request peers protocol flow
     pub(crate) request_peers: RequestPeers,
 
+    /// Request proof protocol flow
+    pub(crate) request_proof: RequestProof,
+
     /// Transaction propagation protocol flow
     pub(crate) transaction_propagation: TransactionPropagation,
 }
@@ -46,6 +50,7 @@ impl ProtocolValidator {
             ping_pong: Default::default(),
             request_blocks: RequestBlocks::new(),
             request_peers: RequestPeers::new(bootstrap_cache),
+            request_proof: RequestProof::new(),
             transaction_propagation: Default::default(),
         }
     }
@@ -23,7 +23,7 @@ use hashbrown::HashMap;
 use hashdb::Hasher as HasherTrait;
 use hashdb::{AsHashDB, HashDB};
 use rlp::NULL_RLP;
-use rocksdb::{DBCompactionStyle, Options, WriteBatch, DB};
+use rocksdb::{DBCompactionStyle, IteratorMode, Options, WriteBatch, DB};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hasher;
 use std::path::Path;
@@ -73,17 +73,33 @@ pub struct PersistentDb {
     pub db_ref: Option<Arc<DB>>,
     pub cf_name: Option<&'static str>,
     pub memory_db: HashMap<Vec<u8>, Operation>,
+
+    /// Whether removed trie nodes are kept so that
+    /// past states can still be read.
+    archival: bool,
+
+    /// Whether trie nodes are reference counted and only
+    /// deleted once they are pruned.
+    journaling: bool,
+
+    /// Trie nodes which are no longer referenced since the
+    /// dead nodes were last taken.
+    dead_nodes: Vec<ShortHash>,
 }
 
 impl PersistentDb {
     pub const ROOT_HASH_KEY: &'static [u8] = b"root_hash";
     pub const RELOAD_FLAG: &'static [u8] = b"reload_flag";
+    pub const NODE_REFS_PREFIX: &'static [u8] = b"node_refs";
 
     pub fn new(db_ref: Arc<DB>, cf_name: Option<&'static str>) -> PersistentDb {
         PersistentDb {
             db_ref: Some(db_ref),
             cf_name,
             memory_db: HashMap::new(),
+            archival: false,
+            journaling: false,
+            dead_nodes: Vec::new(),
         }
     }
 
@@ -93,6 +109,89 @@ impl PersistentDb {
             db_ref: None,
             cf_name: None,
             memory_db: HashMap::new(),
+            archival: false,
+            journaling: false,
+            dead_nodes: Vec::new(),
+        }
+    }
+
+    /// Sets whether trie nodes removed through the `HashDB`
+    /// interface are kept. Other entries are always deleted.
+    pub fn set_archival(&mut self, archival: bool) {
+        self.archival = archival;
+    }
+
+    /// Sets whether trie nodes are reference counted instead of being
+    /// deleted when removed through the `HashDB` interface. Nodes which
+    /// are no longer referenced are only deleted by `prune_node`.
+    pub fn set_journaling(&mut self, journaling: bool) {
+        self.journaling = journaling;
+    }
+
+    /// Returns the trie nodes which are no longer referenced
+    /// since the last call, in the order they were released.
+    pub fn take_dead_nodes(&mut self) -> Vec<ShortHash> {
+        std::mem::replace(&mut self.dead_nodes, Vec::new())
+    }
+
+    /// Deletes the given trie node if it is still not referenced.
+    /// Returns the number of reclaimed bytes.
+    pub fn prune_node(&mut self, key: &ShortHash) -> u64 {
+        let refs_key = Self::node_refs_key(key);
+
+        match self.node_refs(&refs_key) {
+            Some(0) => {}
+            _ => return 0,
+        }
+
+        let reclaimed = match self.retrieve(&key.0) {
+            Some(node) => (key.0.len() + node.len()) as u64,
+            None => 0,
+        };
+
+        self.delete(&key.0);
+        self.delete(&refs_key);
+        reclaimed
+    }
+
+    fn node_refs_key(key: &ShortHash) -> Vec<u8> {
+        [Self::NODE_REFS_PREFIX, &key.0].concat()
+    }
+
+    fn node_refs(&self, refs_key: &[u8]) -> Option<u32> {
+        self.retrieve(refs_key)
+            .map(|refs| decode_be_u32!(&refs).unwrap())
+    }
+
+    /// Writes the given trie node and increments its reference count.
+    fn reference_node(&mut self, key: &ShortHash, val: &[u8]) {
+        let refs_key = Self::node_refs_key(key);
+
+        match self.node_refs(&refs_key) {
+            Some(refs) => self.put(&refs_key, &encode_be_u32!(refs + 1)),
+
+            // Nodes written before journaling was enabled are not
+            // counted so they are never pruned.
+            None if self.retrieve(&key.0).is_some() => {}
+
+            None => self.put(&refs_key, &encode_be_u32!(1)),
+        }
+
+        self.put(&key.0, val);
+    }
+
+    /// Decrements the reference count of the given trie
+    /// node, marking it as dead once it reaches zero.
+    fn release_node(&mut self, key: &ShortHash) {
+        let refs_key = Self::node_refs_key(key);
+
+        match self.node_refs(&refs_key) {
+            Some(1) => {
+                self.put(&refs_key, &encode_be_u32!(0));
+                self.dead_nodes.push(key.clone());
+            }
+            Some(refs) if refs > 1 => self.put(&refs_key, &encode_be_u32!(refs - 1)),
+            _ => {}
         }
     }
 
@@ -243,6 +342,39 @@ impl PersistentDb {
         }
     }
 
+    /// Returns an iterator over all the entries of the
+    /// database, including the pending ones.
+    pub fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        let mem_db_iter = self.memory_db.iter().filter_map(|(k, op)| {
+            if let Operation::Put(value) = op {
+                Some((
+                    k.clone().into_boxed_slice(),
+                    value.clone().into_boxed_slice(),
+                ))
+            } else {
+                None
+            }
+        });
+
+        if let Some(db_ref) = &self.db_ref {
+            let memory_db = &self.memory_db;
+            let db_iter = if let Some(cf) = self.cf_name {
+                let cf = db_ref.cf_handle(cf).unwrap();
+                db_ref.iterator_cf(cf, IteratorMode::Start)
+            } else {
+                db_ref.iterator(IteratorMode::Start)
+            };
+
+            Box::new(
+                db_iter
+                    .filter(move |(k, _v)| !memory_db.get(&k.to_vec()).is_some())
+                    .chain(mem_db_iter),
+            )
+        } else {
+            Box::new(mem_db_iter)
+        }
+    }
+
     fn get_db(&self, key: &[u8]) -> Option<Vec<u8>> {
         let db_ref = self.db_ref.as_ref().unwrap();
 
@@ -322,7 +454,12 @@ impl HashDB<DbHasher, ElasticArray128<u8>> for PersistentDb {
         }
 
         let val_hash = DbHasher::hash(val);
-        self.put(&val_hash.0, val);
+
+        if self.journaling {
+            self.reference_node(&val_hash, val);
+        } else {
+            self.put(&val_hash.0, val);
+        }
 
         val_hash
     }
@@ -340,15 +477,24 @@ impl HashDB<DbHasher, ElasticArray128<u8>> for PersistentDb {
             return;
         }
 
-        self.put(&key.0, &val);
+        if self.journaling {
+            self.reference_node(&key, &val);
+        } else {
+            self.put(&key.0, &val);
+        }
     }
 
     fn remove(&mut self, key: &ShortHash) {
-        if key == &ShortHash::NULL_RLP {
+        // Past states reference the removed nodes in archival mode
+        if key == &ShortHash::NULL_RLP || self.archival {
             return;
         }
 
-        self.delete(&key.0);
+        if self.journaling {
+            self.release_node(key);
+        } else {
+            self.delete(&key.0);
+        }
     }
 }
 
@@ -549,6 +695,70 @@ mod tests {
         assert!(!persistent_db.contains(&key));
     }
 
+    #[test]
+    fn it_keeps_removed_nodes_in_archival_mode() {
+        let dir = TempDir::new("purple_test").unwrap();
+        let path = dir.path().join("database");
+        let path = path.to_str().unwrap();
+        let db = DB::open_default(path).unwrap();
+        let db_ref = Arc::new(db);
+        let mut persistent_db = PersistentDb::new(db_ref, None);
+        let data = b"Hello world";
+
+        persistent_db.set_archival(true);
+
+        let key = persistent_db.insert(data);
+        persistent_db.flush();
+        persistent_db.remove(&key);
+        persistent_db.flush();
+        assert!(persistent_db.contains(&key));
+
+        // Entries are still deleted through the key-value interface
+        persistent_db.delete(&key.0);
+        persistent_db.flush();
+        assert!(!persistent_db.contains(&key));
+    }
+
+    #[test]
+    fn it_only_prunes_unreferenced_nodes_when_journaling() {
+        let dir = TempDir::new("purple_test").unwrap();
+        let path = dir.path().join("database");
+        let path = path.to_str().unwrap();
+        let db = DB::open_default(path).unwrap();
+        let db_ref = Arc::new(db);
+        let mut persistent_db = PersistentDb::new(db_ref, None);
+        let data = b"Hello world";
+
+        persistent_db.set_journaling(true);
+
+        // The node is referenced twice
+        let key = persistent_db.insert(data);
+        persistent_db.insert(data);
+        persistent_db.flush();
+
+        persistent_db.remove(&key);
+        assert!(persistent_db.take_dead_nodes().is_empty());
+        assert_eq!(persistent_db.prune_node(&key), 0);
+
+        persistent_db.remove(&key);
+        persistent_db.flush();
+        assert_eq!(persistent_db.take_dead_nodes(), vec![key.clone()]);
+        assert!(persistent_db.contains(&key));
+
+        // Referencing the node again keeps it from being pruned
+        persistent_db.insert(data);
+        assert_eq!(persistent_db.prune_node(&key), 0);
+        assert!(persistent_db.contains(&key));
+
+        persistent_db.remove(&key);
+        assert_eq!(
+            persistent_db.prune_node(&key),
+            (key.0.len() + data.len()) as u64
+        );
+        persistent_db.flush();
+        assert!(!persistent_db.contains(&key));
+    }
+
     #[test]
     fn it_keeps_last_operation_per_key() {
         let dir = TempDir::new("purple_test").unwrap();
@@ -630,4 +840,32 @@ mod tests {
         assert!(persistent_db.get(&key2).is_none());
         assert!(persistent_db.get(&key3).is_none());
     }
+
+    #[test]
+    fn iter_returns_flushed_and_pending_entries() {
+        let dir = TempDir::new("purple_test").unwrap();
+        let path = dir.path().join("database");
+        let path = path.to_str().unwrap();
+        let db = DB::open_default(path).unwrap();
+        let db_ref = Arc::new(db);
+        let mut persistent_db = PersistentDb::new(db_ref, None);
+
+        persistent_db.put(b"test1", b"data1");
+        persistent_db.put(b"test2", b"data2");
+        persistent_db.flush();
+        persistent_db.put(b"test3", b"data3");
+        persistent_db.delete(b"test2");
+
+        let oracle_set = set![
+            (
+                b"test1".to_vec().into_boxed_slice(),
+                b"data1".to_vec().into_boxed_slice()
+            ),
+            (
+                b"test3".to_vec().into_boxed_slice(),
+                b"data3".to_vec().into_boxed_slice()
+            )
+        ];
+        assert!(oracle_set == persistent_db.iter().collect());
+    }
 }
@@ -19,7 +19,9 @@
 #![allow(deprecated, unused)]
 
 use account::NormalAddress;
-use chain::{Block, CheckpointBlock, PowBlock, PowChainRef};
+use chain::types::ChainEvent;
+use chain::{Block, CheckpointBlock, PowBlock, PowChainRef, PowChainState};
+use crossbeam_channel::select;
 use network::{Network, NetworkInterface};
 use network::{NetworkPriority, Packet};
 use parking_lot::RwLock;
@@ -29,6 +31,7 @@ use std::net::SocketAddr;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
+use std::time::Duration;
 use triomphe::Arc;
 
 #[cfg(any(
@@ -98,6 +101,17 @@ pub fn start_miner(
         let miner = PurpleMiner::new();
         let miner = Rc::new(RefCell::new(miner));
 
+        // The tip is tracked through chain events so that the
+        // miner switches to a new tip as soon as it is written.
+        let chain_events = pow_chain.subscribe();
+        let (mut tip, mut tip_state) = pow_chain.canonical_tip_with_state();
+
+        // Wake up as soon as the solvers find solutions
+        let (solutions_tx, solutions_rx) = crossbeam_channel::unbounded();
+        miner.borrow().on_solutions(move || {
+            let _ = solutions_tx.send(());
+        });
+
         // Flag miner as being started
         MINER_IS_STARTED.store(true, Ordering::Relaxed);
 
@@ -120,8 +134,6 @@ pub fn start_miner(
                 let current_height = { miner.borrow().current_height(plugin_type) };
 
                 if let Some(miner_height) = current_height {
-                    let tip = pow_chain.canonical_tip();
-                    let tip_state = pow_chain.canonical_tip_state();
                     let current_height = tip.height();
 
                     if miner_height == current_height {
@@ -184,7 +196,7 @@ pub fn start_miner(
                             // TODO: Maybe hook this to a progress visualizer
                         }
                     } else if miner_height < current_height {
-                        try_notify(miner.clone(), pow_chain.clone(), plugin_type);
+                        try_notify(miner.clone(), &tip, &tip_state, plugin_type);
                     } else {
                         unreachable!();
                     }
@@ -193,11 +205,39 @@ pub fn start_miner(
                     //debug!("Miner is stand-by...");
                 }
             } else {
-                try_notify(miner.clone(), pow_chain.clone(), plugin_type);
+                try_notify(miner.clone(), &tip, &tip_state, plugin_type);
             }
 
-            // Don't hog the scheduler
-            thread::sleep_ms(1);
+            // Wait for the tip to change or for the solvers to find
+            // solutions. A paused miner is checked again every second.
+            let event = if MINER_IS_PAUSED.load(Ordering::Relaxed) {
+                select! {
+                    recv(chain_events) -> event => event.ok(),
+                    recv(solutions_rx) -> _ => None,
+                    default(Duration::from_secs(1)) => None,
+                }
+            } else {
+                select! {
+                    recv(chain_events) -> event => event.ok(),
+                    recv(solutions_rx) -> _ => None,
+                }
+            };
+
+            let tip_changed = event
+                .into_iter()
+                .chain(chain_events.try_iter())
+                .any(|event| match event {
+                    ChainEvent::TipChanged(_) => true,
+                    _ => false,
+                });
+
+            // Read the tip along with its state so that
+            // the miner never pairs a tip with another state.
+            if tip_changed {
+                let (new_tip, new_state) = pow_chain.canonical_tip_with_state();
+                tip = new_tip;
+                tip_state = new_state;
+            }
         }
     }).map_err(|_| "Could not start miner thread!")?;
 
@@ -212,7 +252,8 @@ pub fn start_miner(
 ))]
 fn try_notify(
     mut miner: Rc<RefCell<PurpleMiner>>,
-    pow_chain: PowChainRef,
+    tip: &PowBlock,
+    tip_state: &PowChainState,
     plugin_type: PluginType,
 ) {
     let is_paused = MINER_IS_PAUSED.load(Ordering::Relaxed);
@@ -220,12 +261,10 @@ fn try_notify(
     if !is_paused {
         let mut miner = (*miner).borrow_mut();
 
-        // Schedule miner to work on the current tip
-        let tip_state = pow_chain.canonical_tip_state();
-        let tip = pow_chain.canonical_tip();
+        // Schedule miner to work on the given tip
         let current_height = tip.height();
         let header_hash = tip.block_hash().unwrap();
-        let difficulty = tip_state.difficulty;
+        let difficulty = tip_state.checkpoint_difficulty(current_height + 1);
 
         if !miner.are_solvers_started() {
             debug!("Starting solvers...");
@@ -239,6 +278,5 @@ fn try_notify(
         miner.notify(current_height, &header_hash.0, difficulty, plugin_type);
     } else {
         debug!("Miner is paused...");
-        thread::sleep_ms(1000);
     }
 }
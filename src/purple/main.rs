@@ -26,6 +26,7 @@ extern crate log;
 extern crate unwrap;
 #[macro_use]
 extern crate jsonrpc_macros;
+extern crate jsonrpc_http_server;
 
 #[macro_use(slog_error, slog_info, slog_trace, slog_log, slog_o)]
 extern crate slog;
@@ -40,7 +41,8 @@ extern crate reqwest;
 
 use account::addresses::normal::NormalAddress;
 use cfg_if::*;
-use clap::{App, Arg};
+use chain::{Block, ChainSpec, LightChain, LightChainRef, PowChainRef};
+use clap::{App, Arg, SubCommand};
 use crypto::{Identity, NodeId, SecretKey as Sk};
 use elastic_array::ElasticArray128;
 use hashdb::HashDB;
@@ -52,6 +54,7 @@ use persistence::PersistentDb;
 use slog::Drain;
 use std::collections::HashMap;
 use std::fs;
+use std::io::{BufReader, BufWriter};
 use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::path::Path;
@@ -76,8 +79,8 @@ use mimalloc::MiMalloc;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-const DEFAULT_NETWORK_NAME: &'static str = "purple-testnet";
-const BOOTNODES: &'static [&'static str] = &["95.179.130.222:44034", "45.32.111.18:44034"];
+/// The number of blocks between progress reports of chain commands
+const PROGRESS_INTERVAL: u64 = 1000;
 
 fn main() {
     let drain = slog_async::Async::default(slog_envlogger::new(
@@ -112,56 +115,133 @@ fn main() {
     let storage_db_path = db_path.join("node_storage");
     let pow_chain_db_path = db_path.join("pow_chain_db");
     let state_db_path = db_path.join("state_db");
+    let light_chain_db_path = db_path.join("light_chain_db");
     let bootstrap_cache_db_path = bootstrap_cache_path.join("bootstrap_cache_db");
 
     let storage_wal_path = db_path.join("node_storage_wal");
     let pow_chain_wal_path = db_path.join("pow_chain_db_wal");
     let state_wal_path = db_path.join("state_db_wal");
+    let light_chain_wal_path = db_path.join("light_chain_db_wal");
     let bootstrap_cache_wal_path = bootstrap_cache_path.join("bootstrap_cache_db_wal");
 
     let storage_db = Arc::new(persistence::open_database(
         &storage_db_path,
         &storage_wal_path,
     ));
-    let pow_chain_db = Arc::new(persistence::open_database(
-        &pow_chain_db_path,
-        &pow_chain_wal_path,
-    ));
-    let state_db = Arc::new(persistence::open_database(&state_db_path, &state_wal_path));
     let bootstrap_cache_db = Arc::new(persistence::open_database(
         &bootstrap_cache_db_path,
         &bootstrap_cache_wal_path,
     ));
     let mut node_storage = PersistentDb::new(storage_db, None);
-    let pow_chain_db = PersistentDb::new(pow_chain_db, None);
-    let state_db = PersistentDb::new(state_db, None);
     let bootstrap_cache_db = PersistentDb::new(bootstrap_cache_db, None);
     let bootstrap_cache = BootstrapCache::new(bootstrap_cache_db, argv.bootstrap_cache_size);
 
-    let pow_chain = chain::init(pow_chain_db, state_db, argv.archival_mode);
+    // The chain spec determines the genesis state
+    // so it must be set before initializing the chain.
+    chain::set_chain_spec(argv.chain_spec.clone());
+
+    // Light clients only store the headers of the chain
+    // so they neither open nor load the full chain.
+    let (pow_chain, light_chain) = if argv.light {
+        let light_chain_db = Arc::new(persistence::open_database(
+            &light_chain_db_path,
+            &light_chain_wal_path,
+        ));
+        let light_chain_db = PersistentDb::new(light_chain_db, None);
+        let light_chain = LightChain::new(light_chain_db);
+
+        (
+            None,
+            Some(LightChainRef::new(Arc::new(RwLock::new(light_chain)))),
+        )
+    } else {
+        let pow_chain_db = Arc::new(persistence::open_database(
+            &pow_chain_db_path,
+            &pow_chain_wal_path,
+        ));
+        let state_db = Arc::new(persistence::open_database(&state_db_path, &state_wal_path));
+        let mut pow_chain_db = PersistentDb::new(pow_chain_db, None);
+        let state_db = PersistentDb::new(state_db, None);
+
+        // The chain is verified before loading it
+        // since an inconsistent chain cannot be loaded.
+        if let Some(Command::VerifyChain { rewind }) = argv.command {
+            verify_chain(&mut pow_chain_db, state_db, rewind);
+            return;
+        }
+
+        let pow_chain = chain::init(pow_chain_db, state_db, argv.archival_mode);
+
+        if argv.tx_index {
+            pow_chain.chain.write().set_tx_index(true);
+        }
+
+        if argv.address_index {
+            pow_chain.chain.write().set_address_index(true);
+        }
+
+        (Some(pow_chain), None)
+    };
 
     info!("Database initialization was successful!");
 
-    let mempool: Option<Arc<RwLock<Mempool>>> = if argv.no_mempool {
-        None
-    } else {
-        info!("Initializing mempool...");
-        let main_cur_hash = crypto::hash_slice(transactions::MAIN_CUR_NAME).to_short();
-        let mempool = Arc::new(RwLock::new(Mempool::new(
-            pow_chain.clone(),
-            argv.mempool_size,
-            vec![main_cur_hash],
-            80,
-            argv.mempool_expire,
-            argv.prune_threshold,
-        )));
-        info!("Mempool initialization was successful!");
-
-        Some(mempool)
+    // Run the given chain command instead of starting the node.
+    // Chain commands cannot be given to light clients.
+    if let Some(command) = argv.command {
+        run_chain_command(command, pow_chain.as_ref().unwrap());
+        return;
+    }
+
+    let mempool: Option<Arc<RwLock<Mempool>>> = match pow_chain {
+        Some(ref pow_chain) if !argv.no_mempool => {
+            info!("Initializing mempool...");
+            let main_cur_hash = argv.chain_spec.main_asset_hash();
+            let mempool = Arc::new(RwLock::new(Mempool::new(
+                pow_chain.clone(),
+                argv.mempool_size,
+                vec![main_cur_hash],
+                80,
+                argv.mempool_expire,
+                argv.prune_threshold,
+            )));
+            info!("Mempool initialization was successful!");
+
+            // Re-inject transactions of disconnected blocks on reorgs
+            let chain_events = pow_chain.subscribe();
+            let mempool_clone = mempool.clone();
+            let builder = thread::Builder::new().name("Mempool chain events thread".to_owned());
+
+            builder
+                .spawn(move || {
+                    for event in chain_events.iter() {
+                        mempool_clone.write().process_chain_event(event);
+                    }
+                })
+                .expect("Could not spawn mempool chain events thread");
+
+            Some(mempool)
+        }
+        _ => None,
     };
 
     let (pow_tx, pow_rx) = flume::unbounded();
 
+    // Append the blocks received from the network
+    if let Some(ref pow_chain) = pow_chain {
+        let pow_chain = pow_chain.clone();
+        let builder = thread::Builder::new().name("Received blocks thread".to_owned());
+
+        builder
+            .spawn(move || {
+                for (addr, block) in pow_rx.iter() {
+                    if let Err(err) = pow_chain.append_received_block(block) {
+                        debug!("Could not append block received from {}: {:?}", addr, err);
+                    }
+                }
+            })
+            .expect("Could not spawn received blocks thread");
+    }
+
     info!("Setting up the network...");
 
     let (node_id, skey) = fetch_credentials(&mut node_storage);
@@ -208,6 +288,7 @@ fn main() {
         pow_chain.clone(),
         bootstrap_cache,
         mempool,
+        light_chain.clone(),
         accept_connections.clone(),
         Some(our_ip),
     );
@@ -228,10 +309,28 @@ fn main() {
         pow_chain.clone(),
         bootstrap_cache,
         mempool,
+        light_chain.clone(),
         accept_connections.clone(),
         None,
     );
 
+    // The server is closed when the handle is dropped
+    // so it must outlive the runtime below.
+    let _rpc_server = if argv.no_rpc {
+        None
+    } else {
+        info!("Starting json-rpc server...");
+        let io = match (pow_chain.clone(), light_chain) {
+            (Some(pow_chain), _) => rpc::io_handler(pow_chain),
+            (None, Some(light_chain)) => rpc::light_io_handler(light_chain, network.clone()),
+            (None, None) => unreachable!(),
+        };
+        let server =
+            rpc::start_server(io, argv.rpc_port).expect("Could not start the json-rpc server");
+        info!("Json-rpc server listening on {}", server.address());
+        Some(server)
+    };
+
     // Fetch default panic hook
     let hook = std::panic::take_hook();
 
@@ -267,7 +366,7 @@ fn main() {
 
                 // Start mining
                 crate::jobs::start_miner(
-                    pow_chain,
+                    pow_chain.expect("Light clients cannot mine"),
                     network.clone(),
                     our_ip,
                     proof_delay,
@@ -354,13 +453,139 @@ fn fetch_credentials(db: &mut PersistentDb) -> (NodeId, Sk) {
     }
 }
 
+fn verify_chain(pow_chain_db: &mut PersistentDb, state_db: PersistentDb, rewind: bool) {
+    info!("Verifying chain...");
+
+    let result = chain::verify_chain(pow_chain_db, state_db, |height| {
+        if height % PROGRESS_INTERVAL == 0 {
+            info!("Verified block {}", height);
+        }
+    });
+
+    let verification = match result {
+        Ok(verification) => verification,
+        Err(err) => {
+            error!("Could not verify chain: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if !verification.state_verified {
+        warn!("Some transactions are missing so only the block headers have been fully verified. Run the node with --tx-index to verify state roots");
+    }
+
+    let inconsistency = match verification.inconsistency {
+        Some(ref inconsistency) => inconsistency,
+        None => {
+            info!(
+                "Successfully verified {} blocks! Canonical tip height is {}",
+                verification.verified, verification.last_good_height
+            );
+            return;
+        }
+    };
+
+    error!(
+        "Block {} is inconsistent: {}. The last good block is {} at height {}",
+        inconsistency.height,
+        inconsistency.reason,
+        verification.last_good_hash,
+        verification.last_good_height
+    );
+
+    if !rewind {
+        error!("Run verify-chain with --rewind to rewind the chain to the last good block");
+        std::process::exit(1);
+    }
+
+    match chain::rewind_to_last_good(pow_chain_db, &verification) {
+        Ok(deleted) => info!(
+            "Successfully rewound the chain to height {}! Deleted {} blocks",
+            verification.last_good_height, deleted
+        ),
+        Err(err) => {
+            error!("Could not rewind chain: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_chain_command(command: Command, pow_chain: &PowChainRef) {
+    match command {
+        Command::VerifyChain { .. } => unreachable!(),
+
+        Command::ExportChain { path, from } => {
+            info!("Exporting chain to {}...", path.display());
+
+            let file = fs::File::create(&path).expect("Could not create export file");
+            let mut writer = BufWriter::new(file);
+            let tip_height = pow_chain.canonical_tip().height();
+            let result = chain::export_chain(pow_chain, &mut writer, from, |height| {
+                if height % PROGRESS_INTERVAL == 0 || height == tip_height {
+                    info!("Exported block {}/{}", height, tip_height);
+                }
+            });
+
+            match result {
+                Ok(exported) => info!("Successfully exported {} blocks!", exported),
+                Err(err) => {
+                    error!("Could not export chain: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Command::ImportChain { path } => {
+            info!("Importing chain from {}...", path.display());
+
+            let file = fs::File::open(&path).expect("Could not open import file");
+            let mut reader = BufReader::new(file);
+            let result = chain::import_chain(pow_chain, &mut reader, |height| {
+                if height % PROGRESS_INTERVAL == 0 {
+                    info!("Imported block {}", height);
+                }
+            });
+
+            match result {
+                Ok(imported) => info!(
+                    "Successfully imported {} blocks! Canonical tip height is {}",
+                    imported,
+                    pow_chain.canonical_tip().height()
+                ),
+                Err(err) => {
+                    error!(
+                        "Could not import chain: {}. Importing the same file again resumes the import",
+                        err
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
 fn get_storage_path(network_name: &str) -> PathBuf {
     Path::new(&dirs::home_dir().unwrap())
         .join("purple")
         .join(network_name)
 }
 
+/// Commands which are run instead of starting the node
+enum Command {
+    /// Export the canonical chain starting at the given height
+    ExportChain { path: PathBuf, from: u64 },
+
+    /// Import a chain exported with `ExportChain`
+    ImportChain { path: PathBuf },
+
+    /// Verify the stored chain, optionally rewinding
+    /// it to the last block that passed verification
+    VerifyChain { rewind: bool },
+}
+
 struct Argv {
+    command: Option<Command>,
+    chain_spec: ChainSpec,
     network_name: String,
     bootnodes: Vec<SocketAddr>,
     mempool_size: u32,
@@ -370,9 +595,14 @@ struct Argv {
     bootstrap_cache_size: u64,
     max_peers: usize,
     no_mempool: bool,
+    light: bool,
     interactive: bool,
     archival_mode: bool,
+    tx_index: bool,
+    address_index: bool,
     wipe: bool,
+    no_rpc: bool,
+    rpc_port: u16,
 
     #[cfg(any(
         feature = "miner-cpu",
@@ -437,6 +667,14 @@ fn parse_cli_args() -> Argv {
                 .help("The name of the network")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("chain_spec")
+                .long("chain-spec")
+                .value_name("CHAIN_SPEC_FILE")
+                .conflicts_with("network_name")
+                .help("A json file describing the network to join, including its name, genesis allocations, currencies, consensus parameters and bootnodes. The default network is joined if this is missing")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("mempool_size")
                 .long("mempool-size")
@@ -475,11 +713,30 @@ fn parse_cli_args() -> Argv {
                 .conflicts_with("prune_threshold")
                 .help("Start the node without a mempool")
         )
+        .arg(
+            Arg::with_name("light")
+                .long("light")
+                .conflicts_with("mempool_size")
+                .conflicts_with("mempool_expire")
+                .conflicts_with("prune_threshold")
+                .conflicts_with("prune")
+                .conflicts_with("tx_index")
+                .conflicts_with("address_index")
+                .help("Start the node as a light client which follows block headers only and queries full peers for state and transaction proofs")
+        )
         .arg(
             Arg::with_name("no_rpc")
                 .long("no-rpc")
+                .conflicts_with("rpc_port")
                 .help("Start the node without the json-rpc interface")
         )
+        .arg(
+            Arg::with_name("rpc_port")
+                .long("rpc-port")
+                .value_name("PORT")
+                .help("The local port on which to serve the json-rpc interface. Default is 44035")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("no_bootnodes")
                 .long("no-bootnodes")
@@ -522,7 +779,53 @@ fn parse_cli_args() -> Argv {
         .arg(
             Arg::with_name("prune")
                 .long("prune")
-                .help("Whether to prune the ledger or to keep the entire transaction history. False by default"),
+                .help("Whether to prune the dead state, indexed transactions and address history of old blocks. Block headers are always kept. False by default"),
+        )
+        .arg(
+            Arg::with_name("tx_index")
+                .long("tx-index")
+                .help("Index transactions by hash so that they can be looked up once they are included in a block"),
+        )
+        .arg(
+            Arg::with_name("address_index")
+                .long("address-index")
+                .help("Index the transactions touching each account so that its history can be listed"),
+        )
+        .subcommand(
+            SubCommand::with_name("export-chain")
+                .about("Export the canonical chain to a file. Requires a node that has been synced with --tx-index")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("The file to write the chain to")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("HEIGHT")
+                        .help("The height of the first exported block. Default is 1")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import-chain")
+                .about("Validate and import the blocks of an exported chain")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("The file to read the chain from")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify-chain")
+                .about("Verify the blocks and state roots of the stored chain without starting the node")
+                .arg(
+                    Arg::with_name("rewind")
+                        .long("rewind")
+                        .help("Rewind the chain to the last good block if an inconsistency is found"),
+                ),
         );
 
     #[cfg(any(
@@ -537,6 +840,7 @@ fn parse_cli_args() -> Argv {
             Arg::with_name("start_mining")
                 .long("start-mining")
                 .requires("collector_address")
+                .conflicts_with("light")
                 .help("Start the node as a miner node"),
         )
         .arg(
@@ -562,10 +866,20 @@ fn parse_cli_args() -> Argv {
 
     let matches = argv.get_matches();
 
+    let chain_spec = if let Some(arg) = matches.value_of("chain_spec") {
+        let json = unwrap!(fs::read_to_string(arg), "Could not read <CHAIN_SPEC_FILE>");
+        unwrap!(
+            ChainSpec::from_json(&json),
+            "Bad value for <CHAIN_SPEC_FILE>"
+        )
+    } else {
+        ChainSpec::default()
+    };
+
     let network_name: String = if let Some(arg) = matches.value_of("network_name") {
         unwrap!(arg.parse(), "Expected value for <NETWORK_NAME>")
     } else {
-        DEFAULT_NETWORK_NAME.to_owned()
+        chain_spec.name.clone()
     };
 
     let mempool_size: u32 = if let Some(arg) = matches.value_of("mempool_size") {
@@ -592,6 +906,12 @@ fn parse_cli_args() -> Argv {
         44034
     };
 
+    let rpc_port: u16 = if let Some(arg) = matches.value_of("rpc_port") {
+        unwrap!(arg.parse(), "Bad value for <PORT>")
+    } else {
+        44035
+    };
+
     let bootstrap_cache_size: u64 = if let Some(arg) = matches.value_of("bootstrap_cache_size") {
         unwrap!(arg.parse(), "Bad value for <SIZE>")
     } else {
@@ -609,7 +929,7 @@ fn parse_cli_args() -> Argv {
             .map(|addr| unwrap!(addr.parse(), "Bad value for <IP_ADDRESSES>"))
             .collect()
     } else {
-        BOOTNODES.iter().map(|addr| addr.parse().unwrap()).collect()
+        chain_spec.bootnodes.clone()
     };
 
     #[cfg(feature = "miner-test-mode")]
@@ -619,13 +939,48 @@ fn parse_cli_args() -> Argv {
         1
     };
 
+    let command = match matches.subcommand() {
+        ("export-chain", Some(sub_matches)) => {
+            let path = PathBuf::from(sub_matches.value_of("file").unwrap());
+            let from: u64 = if let Some(arg) = sub_matches.value_of("from") {
+                unwrap!(arg.parse(), "Bad value for <HEIGHT>")
+            } else {
+                1
+            };
+
+            Some(Command::ExportChain { path, from })
+        }
+        ("import-chain", Some(sub_matches)) => {
+            let path = PathBuf::from(sub_matches.value_of("file").unwrap());
+            Some(Command::ImportChain { path })
+        }
+        ("verify-chain", Some(sub_matches)) => {
+            let rewind = sub_matches.is_present("rewind");
+            Some(Command::VerifyChain { rewind })
+        }
+        _ => None,
+    };
+
     let archival_mode: bool = !matches.is_present("prune");
+    let tx_index: bool = matches.is_present("tx_index");
+    let address_index: bool = matches.is_present("address_index");
     let no_mempool: bool = matches.is_present("no_mempool");
+    let light: bool = matches.is_present("light");
     let interactive: bool = matches.is_present("interactive");
     let wipe: bool = matches.is_present("wipe");
+    let no_rpc: bool = matches.is_present("no_rpc");
     let no_bootnodes: bool = matches.is_present("no_bootnodes");
     let bootnodes = if no_bootnodes { Vec::new() } else { bootnodes };
 
+    // Chain commands need the full chain
+    if light && command.is_some() {
+        clap::Error::with_description(
+            "Chain commands cannot be run by light clients",
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+
     cfg_if! {
         if #[cfg(any(
             feature = "miner-cpu",
@@ -643,17 +998,24 @@ fn parse_cli_args() -> Argv {
     }
 
     Argv {
+        command,
+        chain_spec,
         bootnodes,
         network_name,
         bootstrap_cache_size,
         max_peers,
         no_mempool,
+        light,
         interactive,
         mempool_size,
         mempool_expire,
         prune_threshold,
         archival_mode,
+        tx_index,
+        address_index,
         wipe,
+        no_rpc,
+        rpc_port,
         port,
 
         #[cfg(any(
@@ -678,6 +1040,7 @@ fn parse_cli_args() -> Argv {
 }
 
 mod jobs;
+mod rpc;
 
 // Check that we can safely cast a `usize` to a `u64`.
 static_assertions::const_assert! {
@@ -0,0 +1,319 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Json-rpc methods exposed by the node.
+
+use chain::types::StateInterface;
+use chain::{Block, LightChainRef, PowChainRef, Simulation};
+use crypto::Hash;
+use jsonrpc_core::{Error, IoHandler, Result, Value};
+use jsonrpc_http_server::{Server, ServerBuilder};
+use network::packets::ProofRequest;
+use network::Network;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use transactions::Tx;
+
+build_rpc_trait! {
+    pub trait Rpc {
+        /// Simulates the given hex encoded transaction against the
+        /// canonical tip state without changing it.
+        ///
+        /// If the second parameter is `false`, the signature of the
+        /// transaction is not verified. Unsigned transactions must
+        /// still be encoded with a placeholder signature.
+        #[rpc(name = "simulate_transaction")]
+        fn simulate_transaction(&self, String, bool) -> Result<Value>;
+
+        /// Returns a proof of the value stored under the given hex
+        /// encoded state key at the canonical tip along with the
+        /// state root it can be verified against.
+        #[rpc(name = "state_proof")]
+        fn state_proof(&self, String) -> Result<Value>;
+
+        /// Returns the value stored under the given hex encoded state
+        /// key as of the canonical block at the given height, along
+        /// with a proof of it against the state root at that height.
+        ///
+        /// The states of old blocks can only be read in archival mode.
+        #[rpc(name = "state_at")]
+        fn state_at(&self, u64, String) -> Result<Value>;
+    }
+}
+
+build_rpc_trait! {
+    pub trait LightRpc {
+        /// Returns the value stored under the given hex encoded state
+        /// key as of the latest transaction block header, if it has
+        /// been proven by a full peer.
+        ///
+        /// Otherwise a proof is requested from a full peer and
+        /// `verified` is `false`. The value can be read once the
+        /// proof has been received and verified.
+        #[rpc(name = "verified_state")]
+        fn verified_state(&self, String) -> Result<Value>;
+
+        /// Returns the hash of the canonical header which includes the
+        /// transaction with the given hex encoded hash, if a full peer
+        /// has proven it.
+        ///
+        /// Otherwise an inclusion proof is requested from a full peer
+        /// and `verified` is `false`.
+        #[rpc(name = "verified_tx")]
+        fn verified_tx(&self, String) -> Result<Value>;
+    }
+}
+
+pub struct RpcImpl {
+    chain: PowChainRef,
+}
+
+impl Rpc for RpcImpl {
+    fn simulate_transaction(&self, tx: String, verify_signature: bool) -> Result<Value> {
+        let bytes = hex::decode(&tx).map_err(|_| Error::invalid_params("Invalid hex encoding"))?;
+        let tx = Tx::from_bytes(&bytes).map_err(Error::invalid_params)?;
+        let simulation = self
+            .chain
+            .canonical_tip_state()
+            .simulate_tx(&tx, verify_signature);
+
+        Ok(simulation_to_json(simulation))
+    }
+
+    fn state_proof(&self, key: String) -> Result<Value> {
+        let key = hex::decode(&key).map_err(|_| Error::invalid_params("Invalid hex encoding"))?;
+
+        // Read the tip and its state under the same lock
+        let (tip, state) = {
+            let chain = self.chain.chain.read();
+            (chain.canonical_tip(), chain.canonical_tip_state())
+        };
+        let block_hash = tip.block_hash().unwrap();
+        let proof = state.state_proof(&key).map_err(Error::invalid_params)?;
+
+        Ok(Value::Object(
+            vec![
+                (
+                    "block_hash".to_owned(),
+                    Value::String(hex::encode(&block_hash.0)),
+                ),
+                ("height".to_owned(), Value::String(tip.height().to_string())),
+                (
+                    "state_root".to_owned(),
+                    Value::String(hex::encode(&state.state_root().0)),
+                ),
+                (
+                    "proof".to_owned(),
+                    Value::String(hex::encode(proof.to_bytes())),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        ))
+    }
+
+    fn state_at(&self, height: u64, key: String) -> Result<Value> {
+        let key = hex::decode(&key).map_err(|_| Error::invalid_params("Invalid hex encoding"))?;
+
+        // Read the block and its state under the same lock
+        let (block_hash, state) = {
+            let chain = self.chain.chain.read();
+            let block_hash = chain
+                .block_hash_at_height(height)
+                .ok_or_else(|| Error::invalid_params("No canonical block at the given height"))?;
+            let state = chain.state_at(height).map_err(Error::invalid_params)?;
+
+            (block_hash, state)
+        };
+        let state_root = state.state_root();
+        let proof = state.state_proof(&key).map_err(Error::invalid_params)?;
+        let value = proof.verify(&state_root).map_err(Error::invalid_params)?;
+
+        Ok(Value::Object(
+            vec![
+                (
+                    "block_hash".to_owned(),
+                    Value::String(hex::encode(&block_hash.0)),
+                ),
+                ("height".to_owned(), Value::String(height.to_string())),
+                (
+                    "state_root".to_owned(),
+                    Value::String(hex::encode(&state_root.0)),
+                ),
+                ("value".to_owned(), optional_hex(value)),
+                (
+                    "proof".to_owned(),
+                    Value::String(hex::encode(proof.to_bytes())),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        ))
+    }
+}
+
+pub struct LightRpcImpl {
+    light_chain: LightChainRef,
+    network: Network,
+}
+
+impl LightRpc for LightRpcImpl {
+    fn verified_state(&self, key: String) -> Result<Value> {
+        let key = hex::decode(&key).map_err(|_| Error::invalid_params("Invalid hex encoding"))?;
+
+        if let Some((block_hash, state_root, value)) = self.light_chain.verified_state(&key) {
+            return Ok(Value::Object(
+                vec![
+                    ("verified".to_owned(), Value::Bool(true)),
+                    (
+                        "block_hash".to_owned(),
+                        Value::String(hex::encode(&block_hash.0)),
+                    ),
+                    (
+                        "state_root".to_owned(),
+                        Value::String(hex::encode(&state_root.0)),
+                    ),
+                    ("value".to_owned(), optional_hex(value)),
+                ]
+                .into_iter()
+                .collect(),
+            ));
+        }
+
+        let (block_hash, _) = self
+            .light_chain
+            .latest_state_root()
+            .ok_or_else(|| Error::invalid_params("No transaction block header yet"))?;
+
+        self.network
+            .request_proof(ProofRequest::State { block_hash, key })
+            .map_err(|_| Error::internal_error())?;
+
+        Ok(unverified())
+    }
+
+    fn verified_tx(&self, tx_hash: String) -> Result<Value> {
+        let bytes =
+            hex::decode(&tx_hash).map_err(|_| Error::invalid_params("Invalid hex encoding"))?;
+
+        if bytes.len() != 32 {
+            return Err(Error::invalid_params("Invalid transaction hash"));
+        }
+
+        let mut tx_hash = Hash::NULL;
+        tx_hash.0.copy_from_slice(&bytes);
+
+        if let Some(block_hash) = self.light_chain.verified_tx(&tx_hash) {
+            return Ok(Value::Object(
+                vec![
+                    ("verified".to_owned(), Value::Bool(true)),
+                    (
+                        "block_hash".to_owned(),
+                        Value::String(hex::encode(&block_hash.0)),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            ));
+        }
+
+        self.network
+            .request_proof(ProofRequest::Tx { tx_hash })
+            .map_err(|_| Error::internal_error())?;
+
+        Ok(unverified())
+    }
+}
+
+/// Returns a handler serving the json-rpc methods of full nodes.
+pub fn io_handler(chain: PowChainRef) -> IoHandler {
+    let mut io = IoHandler::new();
+    io.extend_with(RpcImpl { chain }.to_delegate());
+    io
+}
+
+/// Returns a handler serving the json-rpc methods of light clients.
+pub fn light_io_handler(light_chain: LightChainRef, network: Network) -> IoHandler {
+    let mut io = IoHandler::new();
+    io.extend_with(
+        LightRpcImpl {
+            light_chain,
+            network,
+        }
+        .to_delegate(),
+    );
+    io
+}
+
+/// Starts serving the given json-rpc methods over http on the given
+/// local port. The server runs on its own threads until the returned
+/// handle is dropped.
+pub fn start_server(io: IoHandler, port: u16) -> io::Result<Server> {
+    let addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port);
+    ServerBuilder::new(io).start_http(&addr)
+}
+
+fn simulation_to_json(simulation: Simulation) -> Value {
+    let state_diff = simulation
+        .state_diff
+        .into_iter()
+        .map(|change| {
+            Value::Object(
+                vec![
+                    ("key".to_owned(), Value::String(hex::encode(change.key))),
+                    ("before".to_owned(), optional_hex(change.before)),
+                    ("after".to_owned(), optional_hex(change.after)),
+                ]
+                .into_iter()
+                .collect(),
+            )
+        })
+        .collect();
+
+    Value::Object(
+        vec![
+            ("success".to_owned(), Value::Bool(simulation.success)),
+            (
+                "gas_used".to_owned(),
+                Value::String(simulation.gas_used.to_string()),
+            ),
+            (
+                "logs".to_owned(),
+                Value::Array(simulation.logs.into_iter().map(Value::String).collect()),
+            ),
+            ("state_diff".to_owned(), Value::Array(state_diff)),
+        ]
+        .into_iter()
+        .collect(),
+    )
+}
+
+fn unverified() -> Value {
+    Value::Object(
+        vec![("verified".to_owned(), Value::Bool(false))]
+            .into_iter()
+            .collect(),
+    )
+}
+
+fn optional_hex(bytes: Option<Vec<u8>>) -> Value {
+    match bytes {
+        Some(bytes) => Value::String(hex::encode(bytes)),
+        None => Value::Null,
+    }
+}
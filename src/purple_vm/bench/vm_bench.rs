@@ -39,8 +39,14 @@ fn criterion_benchmark(c: &mut Criterion) {
 
         vm.load(module).unwrap();
         b.iter(|| {
-            vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
-                .unwrap()
+            vm.execute(
+                &mut trie,
+                0,
+                0,
+                &[],
+                Gas::from_bytes(b"1000000000.0").unwrap(),
+            )
+            .unwrap()
         });
     });
 
@@ -69,8 +75,14 @@ fn criterion_benchmark(c: &mut Criterion) {
 
         vm.load(module).unwrap();
         b.iter(|| {
-            vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
-                .unwrap()
+            vm.execute(
+                &mut trie,
+                0,
+                0,
+                &[],
+                Gas::from_bytes(b"1000000000.0").unwrap(),
+            )
+            .unwrap()
         });
     });
 
@@ -99,8 +111,14 @@ fn criterion_benchmark(c: &mut Criterion) {
 
         vm.load(module).unwrap();
         b.iter(|| {
-            vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
-                .unwrap()
+            vm.execute(
+                &mut trie,
+                0,
+                0,
+                &[],
+                Gas::from_bytes(b"1000000000.0").unwrap(),
+            )
+            .unwrap()
         });
     });
 }
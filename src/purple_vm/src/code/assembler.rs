@@ -0,0 +1,120 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::instruction_set::Instruction;
+use hashbrown::HashMap;
+
+lazy_static! {
+    static ref MNEMONICS: HashMap<String, u8> = {
+        let mut mnemonics = HashMap::new();
+
+        for byte in 0..=255 {
+            if let Some(op) = Instruction::from_repr(byte) {
+                mnemonics.insert(format!("{:?}", op), byte);
+            }
+        }
+
+        mnemonics
+    };
+}
+
+/// Assembles Purple assembly (PASM) source into a block of bytecode.
+///
+/// The source is a sequence of tokens separated by whitespace or commas.
+/// Each token is either an instruction mnemonic, spelled exactly as the
+/// corresponding `Instruction` variant, or a byte literal in decimal or
+/// in hexadecimal with a `0x` prefix. Everything after a `;` until the
+/// end of the line is a comment.
+pub fn assemble(source: &str) -> Result<Vec<u8>, &'static str> {
+    let mut block = Vec::new();
+
+    for line in source.lines() {
+        let line = match line.find(';') {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+
+        for token in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if token.is_empty() {
+                continue;
+            }
+
+            if let Some(byte) = MNEMONICS.get(token) {
+                block.push(*byte);
+                continue;
+            }
+
+            let byte = if token.starts_with("0x") {
+                u8::from_str_radix(&token[2..], 16)
+            } else {
+                token.parse::<u8>()
+            };
+
+            match byte {
+                Ok(byte) => block.push(byte),
+                Err(_) => return Err("Invalid token! Expected an instruction or a byte literal"),
+            }
+        }
+    }
+
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_assembles_instructions_and_bytes() {
+        let source = "
+            Begin 0x00          ; 0 Arity
+            PushLocal 1, 0
+            i32Const 0x00 0x00 0x00 0x05
+            Return 0x01
+            End
+        ";
+
+        let block = assemble(source).unwrap();
+
+        assert_eq!(
+            block,
+            vec![
+                Instruction::Begin.repr(),
+                0x00,
+                Instruction::PushLocal.repr(),
+                0x01,
+                0x00,
+                Instruction::i32Const.repr(),
+                0x00,
+                0x00,
+                0x00,
+                0x05,
+                Instruction::Return.repr(),
+                0x01,
+                Instruction::End.repr(),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_unknown_tokens() {
+        assert!(assemble("Begin 0x00 Jump End").is_err());
+        assert!(assemble("Begin 0x100 End").is_err());
+        assert!(assemble("Begin 256 End").is_err());
+    }
+}
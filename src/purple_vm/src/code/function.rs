@@ -278,6 +278,12 @@ impl Function {
                             result_len += 2;
                         }
 
+                        Instruction::MemoryLoad | Instruction::MemoryStore => {
+                            // Account for the load or store instruction and the offset
+                            offset += 5;
+                            result_len += 5;
+                        }
+
                         _ => {
                             // Do nothing
                         }
@@ -16,24 +16,32 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+pub mod assembler;
 pub mod function;
 pub mod import;
 pub mod transition;
 pub mod validator;
 
-use self::validator::Validator;
+use self::validator::{Signature, Validator};
 use crate::function::Function;
 use crate::import::Import;
 use crate::instruction_set::Instruction;
 use crate::module::Module;
 use crate::primitives::r#type::VmType;
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use hashbrown::HashSet;
 use std::hash::Hash;
 use std::io::Cursor;
 use std::str;
 
-const VM_VERSION: u8 = 1;
+/// The latest version of the instruction set supported by the VM.
+///
+/// Version 2 adds the linear memory instructions, functions without
+/// a return value and calls checked against the called signatures.
+pub const VM_VERSION: u8 = 2;
+
+/// Return type byte of functions that do not return a value.
+const NO_RETURN_TYPE: u8 = 0x00;
 
 #[derive(Clone, Debug)]
 pub struct Code(Vec<u8>);
@@ -43,6 +51,66 @@ impl Code {
         Code(code.to_vec())
     }
 
+    /// Encodes the given functions into code with an empty imports
+    /// section, following the binary structure described in `validate()`.
+    pub fn from_functions(functions: &[Function]) -> Code {
+        Self::encode(functions, VM_VERSION)
+    }
+
+    /// Returns code targeting the given version of the instruction set
+    /// whose only function is the `main` function of a contract with
+    /// the given block.
+    pub fn from_main_block(block: &[u8], vm_version: u8) -> Code {
+        let main = Function {
+            arity: 0,
+            name: "main".to_owned(),
+            block: block.to_vec(),
+            arguments: Vec::new(),
+            return_type: None,
+        };
+
+        Self::encode(&[main], vm_version)
+    }
+
+    fn encode(functions: &[Function], vm_version: u8) -> Code {
+        let mut functions_section: Vec<u8> = Vec::new();
+
+        for fun in functions.iter() {
+            let return_type = match fun.return_type {
+                Some(return_type) => return_type.to_op(),
+                None => NO_RETURN_TYPE,
+            };
+
+            functions_section.write_u8(fun.name.len() as u8).unwrap();
+            functions_section.write_u8(fun.arity).unwrap();
+            functions_section.write_u8(return_type).unwrap();
+            functions_section
+                .write_u16::<BigEndian>(fun.block.len() as u16)
+                .unwrap();
+            functions_section.extend_from_slice(fun.name.as_bytes());
+            functions_section.extend(fun.arguments.iter().map(|a| a.to_op()));
+            functions_section.extend_from_slice(&fun.block);
+        }
+
+        // Addresses length and data length
+        let imports_section: Vec<u8> = vec![0, 0, 0, 0];
+        let mut buf: Vec<u8> = Vec::new();
+
+        buf.write_u8(vm_version).unwrap();
+        buf.write_u16::<BigEndian>(imports_section.len() as u16)
+            .unwrap();
+        buf.write_u16::<BigEndian>(functions_section.len() as u16)
+            .unwrap();
+        buf.extend_from_slice(&imports_section);
+        buf.extend_from_slice(&functions_section);
+
+        Code(buf)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
     /// Converts the binary code to it's internal vm representation.
     pub fn to_mod(&self) -> Module {
         unimplemented!();
@@ -85,23 +153,39 @@ impl Code {
     /// An entry in the functions section has the following format:
     /// 1) Function name length  - 8bits                - The length of the function name field.
     /// 2) Arity                 - 8bits                - The arity of the function.
-    /// 3) Return type           - 8bits                - The return type of the function.
+    /// 3) Return type           - 8bits                - The return type of the function or 0x00 if it doesn't return a value.
     /// 4) Block length          - 16bits               - The length of the block field.
     /// 5) Function name         - Variable length      - The name of the function. Must be valid utf8.
     /// 6) Argument types        - Variable length      - The types of the arguments.
     /// 7) Block                 - Variable length      - The function's block of code.
-    pub fn validate(&mut self) -> bool {
+    ///
+    /// The code must target the given version of the instruction set,
+    /// which is the version active at the height the code is deployed at.
+    ///
+    /// Code targeting version 1 is validated with the rules it has been
+    /// deployed with, including their quirks, so that its validity does
+    /// not change. Functions must then return a value and calls are
+    /// not checked.
+    pub fn validate(&mut self, vm_version: u8) -> bool {
+        // Newer versions are not supported by this node.
+        if vm_version > VM_VERSION {
+            return false;
+        }
+
+        let legacy = vm_version < 2;
+
         // The code cannot be empty.
         if self.0.len() == 0 {
             return false;
         }
 
-        let mut cursor = Cursor::new(&mut self.0);
+        let mut code = self.0.clone();
+        let mut cursor = Cursor::new(&mut code);
 
         // Check version byte
         match cursor.read_u8() {
             Ok(byte) => {
-                if byte != VM_VERSION {
+                if byte != vm_version {
                     return false;
                 }
             }
@@ -168,8 +252,16 @@ impl Code {
         let mut buf = cursor.into_inner();
         let _: Vec<u8> = buf.drain(..4).collect();
 
-        let mut encoded_addresses = if buf.len() > addresses_len as usize {
-            let result: Vec<u8> = buf.drain(..imports_len as usize).collect();
+        let mut encoded_addresses = if legacy {
+            // Version 1 drains the length of the data field
+            if buf.len() > addresses_len as usize && buf.len() >= imports_len as usize {
+                let result: Vec<u8> = buf.drain(..imports_len as usize).collect();
+                result
+            } else {
+                return false;
+            }
+        } else if buf.len() >= addresses_len as usize {
+            let result: Vec<u8> = buf.drain(..addresses_len as usize).collect();
             result
         } else {
             return false;
@@ -191,6 +283,10 @@ impl Code {
                 break;
             }
 
+            if encoded_addresses.len() < 33 {
+                return false;
+            }
+
             let mut buf = [0; 33];
             let result: Vec<u8> = encoded_addresses.drain(..33).collect();
 
@@ -223,8 +319,15 @@ impl Code {
                 _ => return false,
             };
 
-            // Invalid in case of out of bounds index
-            if address_idx as usize > addresses.len() - 1 {
+            // Invalid in case of out of bounds index. Version 1
+            // accepts any index if there are no addresses.
+            let out_of_bounds = if legacy {
+                address_idx as usize > addresses.len().wrapping_sub(1)
+            } else {
+                address_idx as usize >= addresses.len()
+            };
+
+            if out_of_bounds {
                 return false;
             }
 
@@ -275,15 +378,14 @@ impl Code {
             cursor.set_position(2);
 
             let return_type = match cursor.read_u8() {
-                Ok(result) => VmType::from_op(result),
+                Ok(NO_RETURN_TYPE) if !legacy => None,
+                Ok(result) => match VmType::from_op(result) {
+                    Some(result) => Some(result),
+                    None => return false,
+                },
                 _ => return false,
             };
 
-            let return_type = match return_type {
-                Some(result) => result,
-                None => return false,
-            };
-
             cursor.set_position(3);
 
             let block_len = match cursor.read_u16::<BigEndian>() {
@@ -305,9 +407,16 @@ impl Code {
                 return false;
             };
 
-            let arguments = if buf.len() > arity as usize {
+            // Version 1 drains the length of the function name
+            let arguments_len = if legacy {
+                function_name_len as usize
+            } else {
+                arity as usize
+            };
+
+            let arguments = if buf.len() > arity as usize && buf.len() >= arguments_len {
                 let result: Option<Vec<VmType>> = buf
-                    .drain(..function_name_len as usize)
+                    .drain(..arguments_len)
                     .map(|v| VmType::from_op(v))
                     .collect();
 
@@ -326,7 +435,7 @@ impl Code {
                 return false;
             };
 
-            if !validate_block(&block, return_type, &arguments) {
+            if legacy && !Validator::new().validate_block(&block) {
                 return false;
             }
 
@@ -335,12 +444,28 @@ impl Code {
                 name: function_name,
                 arguments,
                 block,
-                return_type: Some(return_type),
+                return_type,
             };
 
             functions.push(function);
         }
 
+        // Validate function blocks. This is done after all functions
+        // are decoded so that calls can be checked against the
+        // signatures of the called functions.
+        if !legacy {
+            let signatures: Vec<Signature> = functions
+                .iter()
+                .map(|f| (f.arguments.clone(), f.return_type))
+                .collect();
+
+            for fun in functions.iter() {
+                if !validate_function_block(&fun.block, &fun.arguments, &signatures) {
+                    return false;
+                }
+            }
+        }
+
         // Check for unique function names
         let imports_names: Vec<&str> = imports.iter().map(|i| i.function_name.as_str()).collect();
 
@@ -363,8 +488,8 @@ where
     iter.into_iter().all(move |x| uniq.insert(x))
 }
 
-fn validate_block(block: &[u8], return_type: VmType, argv: &[VmType]) -> bool {
-    let mut validator = Validator::new();
+fn validate_function_block(block: &[u8], argv: &[VmType], signatures: &[Signature]) -> bool {
+    let mut validator = Validator::with_signatures(argv, signatures);
 
     for byte in block {
         validator.push_op(*byte);
@@ -385,13 +510,134 @@ mod tests {
     #[test]
     fn validate_it_fails_on_empty_code() {
         let mut code = Code::new(&[]);
-        assert!(!code.validate());
+        assert!(!code.validate(VM_VERSION));
     }
 
     #[test]
     fn validate_fails_on_bad_vm_version() {
         let mut code = Code::new(&[0x03, 0x00, 0x01, 0x00, 0x01]);
-        assert!(!code.validate());
+        assert!(!code.validate(VM_VERSION));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn validate_fails_on_inactive_vm_version() {
+        let block = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::PickLocal.repr(),
+            0x00,
+            0x00,
+            Instruction::Return.repr(),
+            0x01,
+            Instruction::End.repr(),
+        ];
+
+        let function = Function {
+            arity: 1,
+            name: "identity".to_owned(),
+            block,
+            arguments: vec![VmType::I64],
+            return_type: Some(VmType::I64),
+        };
+
+        let mut code = Code::from_functions(&[function]);
+        assert!(code.validate(VM_VERSION));
+        assert!(!code.validate(VM_VERSION - 1));
+        assert!(!code.validate(VM_VERSION + 1));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn validate_accepts_encoded_functions() {
+        let block = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::PickLocal.repr(),
+            0x00,
+            0x00,
+            Instruction::Return.repr(),
+            0x01,
+            Instruction::End.repr(),
+        ];
+
+        let function = Function {
+            arity: 1,
+            name: "identity".to_owned(),
+            block,
+            arguments: vec![VmType::I64],
+            return_type: Some(VmType::I64),
+        };
+
+        let mut code = Code::from_functions(&[function]);
+        assert!(code.validate(VM_VERSION));
+
+        // Validation does not consume the code
+        assert!(code.validate(VM_VERSION));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn validate_checks_memory_instructions_against_the_version() {
+        let block = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::MemorySize.repr(),
+            Instruction::MemoryGrow.repr(),
+            Instruction::Return.repr(),
+            0x00,
+            Instruction::End.repr(),
+        ];
+
+        assert!(validate_function_block(&block, &[], &[]));
+        assert!(!Validator::new().validate_block(&block));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn validate_checks_wrapping_instructions_against_the_version() {
+        let block = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::PushOperand.repr(),
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x7f,
+            0xff,
+            0xff,
+            0xff,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::AddWrapping.repr(),
+            Instruction::Return.repr(),
+            0x00,
+            Instruction::End.repr(),
+        ];
+
+        assert!(validate_function_block(&block, &[], &[]));
+        assert!(!Validator::new().validate_block(&block));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn validate_accepts_main_blocks_of_the_given_version() {
+        let block = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::MemorySize.repr(),
+            Instruction::MemoryGrow.repr(),
+            Instruction::Return.repr(),
+            0x00,
+            Instruction::End.repr(),
+        ];
+
+        assert!(Code::from_main_block(&block, VM_VERSION).validate(VM_VERSION));
+        assert!(!Code::from_main_block(&block, VM_VERSION).validate(VM_VERSION - 1));
+        assert!(!Code::from_main_block(&[0x00, 0x01], VM_VERSION).validate(VM_VERSION));
     }
 
     #[test]
@@ -400,9 +646,9 @@ mod tests {
         let mut code2 = Code::new(&[0x01, 0x00, 0x01, 0x00, 0x00]);
         let mut code3 = Code::new(&[0x01, 0x00, 0x00, 0x00, 0x00]);
 
-        assert!(!code1.validate());
-        assert!(!code2.validate());
-        assert!(!code3.validate());
+        assert!(!code1.validate(VM_VERSION));
+        assert!(!code2.validate(VM_VERSION));
+        assert!(!code3.validate(VM_VERSION));
     }
 }
 
@@ -414,7 +660,7 @@ fn validate_block_it_fails_on_invalid_first_instruction() {
         Instruction::Nop.repr(),
         Instruction::End.repr(),
     ];
-    assert!(!validate_block(&block, VmType::I32, &[]));
+    assert!(!validate_function_block(&block, &[], &[]));
 }
 
 #[cfg(test)]
@@ -425,12 +671,12 @@ fn validate_block_it_fails_on_invalid_last_instruction() {
         Instruction::Nop.repr(),
         Instruction::Nop.repr(),
     ];
-    assert!(!validate_block(&block, VmType::I32, &[]));
+    assert!(!validate_function_block(&block, &[], &[]));
 }
 
 #[cfg(test)]
 #[test]
 fn validate_block_it_fails_on_empty_block() {
     let block = vec![Instruction::Begin.repr(), Instruction::End.repr()];
-    assert!(!validate_block(&block, VmType::I32, &[]));
+    assert!(!validate_function_block(&block, &[], &[]));
 }
@@ -19,7 +19,7 @@
 use crate::code::transition::Transition;
 use crate::frame::Frame;
 use crate::instruction_set::OPS_LIST;
-use crate::instruction_set::{Instruction, CT_FLOW_OPS};
+use crate::instruction_set::{Instruction, CT_FLOW_OPS, MEMORY_OPS, WRAPPING_OPS};
 use crate::primitives::control_flow::CfOperator;
 use crate::primitives::r#type::VmType;
 use crate::stack::Stack;
@@ -57,8 +57,22 @@ pub struct Validator {
 
     /// The arity of the latest validated block
     last_arity: Option<u8>,
+
+    /// The types of the arguments of the validated function
+    arguments: Vec<VmType>,
+
+    /// The signatures of the functions that can be called
+    /// from the validated block. Calls are not checked if
+    /// there are no signatures.
+    signatures: Vec<Signature>,
+
+    /// The version of the instruction set that the validated block targets
+    vm_version: u8,
 }
 
+/// The argument types and return type of a function.
+pub type Signature = (Vec<VmType>, Option<VmType>);
+
 impl Validator {
     pub fn new() -> Validator {
         Validator {
@@ -69,9 +83,29 @@ impl Validator {
             call_stack: Stack::new(),
             operand_stack: Stack::new(),
             last_arity: None,
+            arguments: Vec::new(),
+            signatures: Vec::new(),
+            vm_version: 1,
         }
     }
 
+    /// Creates a validator for the block of a function that receives
+    /// the given arguments and that can call the functions with the
+    /// given signatures.
+    ///
+    /// The arguments are placed on the locals stack of the first
+    /// frame and each `Call` instruction is checked against the
+    /// signature of the called function. The block targets version
+    /// 2 of the instruction set, so the linear memory instructions
+    /// are allowed as well and a loop can be left after any instruction.
+    pub fn with_signatures(arguments: &[VmType], signatures: &[Signature]) -> Validator {
+        let mut validator = Validator::new();
+        validator.arguments = arguments.to_vec();
+        validator.signatures = signatures.to_vec();
+        validator.vm_version = 2;
+        validator
+    }
+
     pub fn push_op(&mut self, op: u8) {
         if let Validity::IrrefutablyInvalid = self.state {
             panic!("Cannot switch state since the state machine is DONE.");
@@ -83,8 +117,11 @@ impl Validator {
             match Instruction::from_repr(op) {
                 Some(Instruction::Begin) => {
                     // Push first frame
-                    self.call_stack
-                        .push(Frame::new(Some(CfOperator::Begin), None, None));
+                    self.call_stack.push(Frame::new(
+                        Some(CfOperator::Begin),
+                        None,
+                        Some(self.arguments.clone()),
+                    ));
 
                     // The first element in the validation stack
                     // is the operand that is being validated.
@@ -119,6 +156,13 @@ impl Validator {
                 Some(Transition::Op(op)) => {
                     let is_ct_flow_op = CT_FLOW_OPS.iter().find(|o| *o == &op);
 
+                    if self.vm_version < 2
+                        && (MEMORY_OPS.contains(&op) || WRAPPING_OPS.contains(&op))
+                    {
+                        self.state = Validity::IrrefutablyInvalid;
+                        return;
+                    }
+
                     let mut allow_else = false;
 
                     // If op is `End`, pop frame from stack.
@@ -133,6 +177,10 @@ impl Validator {
                         }
 
                         self.call_stack.pop();
+
+                        // The operand stack does not outlive
+                        // the block it has been created in.
+                        self.operand_stack = Stack::new();
                     }
 
                     // Changes state to `Valid` if the stack is empty.
@@ -140,7 +188,15 @@ impl Validator {
                         self.state = Validity::Valid;
                     } else {
                         let mut next = match op {
-                            // TODO: Return transitions for all ops with non-default transitions
+                            Instruction::Begin => {
+                                // Mark op for argument validation. Nested blocks
+                                // are flagged so that they are not mistaken for
+                                // the top-level block of the function.
+                                self.validation_stack
+                                    .push((Instruction::Begin.repr(), false));
+
+                                ARITY_TRANSITIONS.to_vec()
+                            }
                             Instruction::PushLocal => {
                                 // Mark op for argument validation
                                 self.validation_stack
@@ -162,6 +218,152 @@ impl Validator {
 
                                 vec![Transition::AnyByte]
                             }
+                            Instruction::PickOperand => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::PickOperand.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::PopLocal => {
+                                let frame = self.call_stack.peek_mut();
+
+                                if frame.locals.is_empty() {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                frame.locals.pop();
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
+                            Instruction::PopOperand => {
+                                if self.operand_stack.is_empty() {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                self.operand_stack.pop();
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
+                            Instruction::PeekLocal => {
+                                let frame = self.call_stack.peek();
+
+                                if frame.locals.is_empty()
+                                    || self.operand_stack.len() >= OPERAND_STACK_SIZE
+                                {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                // Copy topmost local to the operand stack
+                                let item = *frame.locals.peek();
+                                self.operand_stack.push(item);
+
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
+                            Instruction::PeekOperand => {
+                                if self.operand_stack.is_empty() {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                // Copy topmost operand to the locals stack
+                                let item = *self.operand_stack.peek();
+                                let frame = self.call_stack.peek_mut();
+                                frame.locals.push(item);
+
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
+                            Instruction::Call => {
+                                // Mark op for argument validation
+                                self.validation_stack.push((Instruction::Call.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::Return => {
+                                // Mark op for arity validation
+                                self.validation_stack
+                                    .push((Instruction::Return.repr(), true));
+
+                                ARITY_TRANSITIONS.to_vec()
+                            }
+                            Instruction::i32Load
+                            | Instruction::i64Load
+                            | Instruction::f32Load
+                            | Instruction::f64Load
+                            | Instruction::i32Load8Signed
+                            | Instruction::i32Load8Unsigned
+                            | Instruction::i32Load16Signed
+                            | Instruction::i32Load16Unsigned
+                            | Instruction::i64Load8Signed
+                            | Instruction::i64Load8Unsigned
+                            | Instruction::i64Load16Signed
+                            | Instruction::i64Load16Unsigned
+                            | Instruction::i64Load32Signed
+                            | Instruction::i64Load32Unsigned => {
+                                if self.operand_stack.len() >= OPERAND_STACK_SIZE {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                // Mark op for coordinates validation
+                                self.validation_stack.push((op.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::i32Store
+                            | Instruction::i64Store
+                            | Instruction::f32Store
+                            | Instruction::f64Store
+                            | Instruction::i32Store8
+                            | Instruction::i32Store16
+                            | Instruction::i64Store8
+                            | Instruction::i64Store16
+                            | Instruction::i64Store32 => {
+                                if self.operand_stack.is_empty()
+                                    || *self.operand_stack.peek() != memory_op_type(op)
+                                {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                // The stored item leaves the operand stack
+                                self.operand_stack.pop();
+
+                                // Mark op for coordinates validation
+                                self.validation_stack.push((op.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::MemoryLoad | Instruction::MemoryStore => {
+                                // Mark op for validating the memory
+                                // instruction and the address offset.
+                                self.validation_stack.push((op.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::MemorySize => {
+                                if self.operand_stack.len() >= OPERAND_STACK_SIZE {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                // The size is pushed in pages
+                                self.operand_stack.push(VmType::I32);
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
+                            Instruction::MemoryGrow => {
+                                // The number of added pages is replaced
+                                // with the previous number of pages.
+                                if self.operand_stack.is_empty()
+                                    || *self.operand_stack.peek() != VmType::I32
+                                {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
                             Instruction::Loop => {
                                 // Mark op for argument validation
                                 self.validation_stack.push((Instruction::Loop.repr(), true));
@@ -180,9 +382,26 @@ impl Validator {
 
                                 ARITY_TRANSITIONS.to_vec()
                             }
-                            Instruction::Add | Instruction::Mul | Instruction::Eq => {
+                            Instruction::Add | Instruction::Mul => {
+                                let len = self.operand_stack.len();
+                                if len > OPERAND_STACK_SIZE
+                                    || len < 2
+                                    || !are_same_type(&self.operand_stack)
+                                {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                // All operands are folded into the result
+                                fold_operands(&mut self.operand_stack, len);
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
+                            Instruction::Eq => {
                                 let len = self.operand_stack.len();
-                                if len > OPERAND_STACK_SIZE || len < 2 {
+                                if len > OPERAND_STACK_SIZE
+                                    || len < 2
+                                    || !are_same_type(&self.operand_stack)
+                                {
                                     self.state = Validity::IrrefutablyInvalid;
                                     return;
                                 }
@@ -209,19 +428,31 @@ impl Validator {
                             Instruction::Div | Instruction::CopySign => {
                                 if self.operand_stack.len() != 2
                                     || !are_float_type(&self.operand_stack)
+                                    || !are_same_type(&self.operand_stack)
                                 {
                                     self.state = Validity::IrrefutablyInvalid;
                                     return;
                                 }
 
+                                fold_operands(&mut self.operand_stack, 2);
                                 DEFAULT_TRANSITIONS.to_vec()
                             }
                             Instruction::Sub
                             | Instruction::DivSigned
                             | Instruction::DivUnsigned
                             | Instruction::RemSigned
-                            | Instruction::RemUnsigned
-                            | Instruction::Ne
+                            | Instruction::RemUnsigned => {
+                                if self.operand_stack.len() != 2
+                                    || !are_same_type(&self.operand_stack)
+                                {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                fold_operands(&mut self.operand_stack, 2);
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
+                            Instruction::Ne
                             | Instruction::LtSigned
                             | Instruction::LtUnsigned
                             | Instruction::GtSigned
@@ -230,7 +461,9 @@ impl Validator {
                             | Instruction::LeUnsigned
                             | Instruction::GeSigned
                             | Instruction::GeUnsigned => {
-                                if self.operand_stack.len() != 2 {
+                                if self.operand_stack.len() != 2
+                                    || !are_same_type(&self.operand_stack)
+                                {
                                     self.state = Validity::IrrefutablyInvalid;
                                     return;
                                 }
@@ -239,11 +472,15 @@ impl Validator {
                             }
                             Instruction::Min | Instruction::Max => {
                                 let len = self.operand_stack.len();
-                                if len > OPERAND_STACK_SIZE || len < 1 {
+                                if len > OPERAND_STACK_SIZE
+                                    || len < 1
+                                    || !are_same_type(&self.operand_stack)
+                                {
                                     self.state = Validity::IrrefutablyInvalid;
                                     return;
                                 }
 
+                                fold_operands(&mut self.operand_stack, len);
                                 DEFAULT_TRANSITIONS.to_vec()
                             }
                             Instruction::Eqz => {
@@ -264,11 +501,33 @@ impl Validator {
                             | Instruction::Rotr => {
                                 if self.operand_stack.len() != 2
                                     || !are_integer_type(&self.operand_stack)
+                                    || !are_same_type(&self.operand_stack)
+                                {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                fold_operands(&mut self.operand_stack, 2);
+                                DEFAULT_TRANSITIONS.to_vec()
+                            }
+                            Instruction::AddWrapping
+                            | Instruction::SubWrapping
+                            | Instruction::MulWrapping
+                            | Instruction::DivUnsignedWrapping
+                            | Instruction::RemSignedWrapping
+                            | Instruction::RemUnsignedWrapping
+                            | Instruction::ShlWrapping
+                            | Instruction::ShrSignedWrapping
+                            | Instruction::ShrUnsignedWrapping => {
+                                if self.operand_stack.len() != 2
+                                    || !are_scalar_integer_type(&self.operand_stack)
+                                    || !are_same_type(&self.operand_stack)
                                 {
                                     self.state = Validity::IrrefutablyInvalid;
                                     return;
                                 }
 
+                                fold_operands(&mut self.operand_stack, 2);
                                 DEFAULT_TRANSITIONS.to_vec()
                             }
                             Instruction::i64Wrapi32
@@ -303,6 +562,10 @@ impl Validator {
                                     return;
                                 }
 
+                                // Replace operand with the converted type
+                                self.operand_stack.pop();
+                                self.operand_stack.push(conversion_result_type(op));
+
                                 DEFAULT_TRANSITIONS.to_vec()
                             }
                             Instruction::Fetch => {
@@ -403,6 +666,13 @@ impl Validator {
                     }
                 }
                 Some(Transition::Byte(_)) | Some(Transition::AnyByte) => {
+                    // Immediates are only accepted after an op
+                    // that has been marked for validation.
+                    if self.validation_stack.is_empty() {
+                        self.state = Validity::IrrefutablyInvalid;
+                        return;
+                    }
+
                     let (operand, _) = self.validation_stack.as_slice()[0];
 
                     match Instruction::from_repr(operand) {
@@ -417,14 +687,14 @@ impl Validator {
                                 panic!("Invalid transition! Expected a byte transition!");
                             };
 
-                            self.validation_stack.pop();
+                            let (_, is_first_block) = self.validation_stack.pop();
 
                             // Only allow 0 arity for first begin block
-                            if self.call_stack.len() == 1 && byte == 0x00 {
+                            if is_first_block && byte == 0x00 {
                                 // Continue validation
                                 self.state = Validity::Invalid;
                                 next_transitions = Some(Instruction::Begin.transitions());
-                            } else if self.call_stack.len() == 1 {
+                            } else if is_first_block {
                                 // The arity is not 0 so anything further
                                 // is invalid as well.
                                 self.state = Validity::IrrefutablyInvalid;
@@ -533,7 +803,9 @@ impl Validator {
 
                             if self.validation_buffer.len() == 2 {
                                 match decode_be_u16!(&self.validation_buffer) {
-                                    Ok(idx) => {
+                                    Ok(idx)
+                                        if (idx as usize) < self.call_stack.peek().locals.len() =>
+                                    {
                                         let frame = self.call_stack.peek_mut();
                                         frame.locals.pick(idx as usize);
 
@@ -544,12 +816,185 @@ impl Validator {
                                         next_transitions = Some(Instruction::Begin.transitions());
                                         self.state = Validity::Invalid;
                                     }
-                                    Err(_) => {
+                                    _ => {
+                                        self.state = Validity::IrrefutablyInvalid;
+                                    }
+                                }
+                            }
+                        }
+                        Some(Instruction::PickOperand) => {
+                            self.validation_buffer.push(op);
+
+                            if self.validation_buffer.len() == 2 {
+                                match decode_be_u16!(&self.validation_buffer) {
+                                    Ok(idx) if (idx as usize) < self.operand_stack.len() => {
+                                        self.operand_stack.pick(idx as usize);
+
+                                        // Cleanup
+                                        self.validation_buffer = vec![];
+                                        self.validation_stack = Stack::new();
+
+                                        next_transitions = Some(Instruction::Begin.transitions());
+                                        self.state = Validity::Invalid;
+                                    }
+                                    _ => {
                                         self.state = Validity::IrrefutablyInvalid;
                                     }
                                 }
                             }
                         }
+                        Some(Instruction::Return) => {
+                            // The next byte after a `Return` instruction is
+                            // the number of returned values, which are taken
+                            // from the locals stack.
+                            self.validation_stack.pop();
+
+                            let valid = ARITY_TRANSITIONS.iter().find(|t| t.accepts_byte(op));
+
+                            match valid {
+                                Some(Transition::Byte(arity))
+                                    if self.call_stack.peek().locals.len() >= *arity as usize =>
+                                {
+                                    next_transitions = Some(Instruction::Return.transitions());
+                                    self.state = Validity::Invalid;
+                                }
+                                _ => {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                }
+                            }
+                        }
+                        Some(Instruction::Call) => {
+                            // The next two bytes after a `Call`
+                            // instruction are the function index.
+                            self.validation_buffer.push(op);
+
+                            if self.validation_buffer.len() == 2 {
+                                let idx = decode_be_u16!(&self.validation_buffer).unwrap() as usize;
+
+                                // Cleanup
+                                self.validation_buffer = vec![];
+                                self.validation_stack = Stack::new();
+
+                                if self.signatures.is_empty() || self.validate_call(idx) {
+                                    next_transitions = Some(Instruction::Begin.transitions());
+                                    self.state = Validity::Invalid;
+                                } else {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                }
+                            }
+                        }
+                        Some(Instruction::i32Load)
+                        | Some(Instruction::i64Load)
+                        | Some(Instruction::f32Load)
+                        | Some(Instruction::f64Load)
+                        | Some(Instruction::i32Load8Signed)
+                        | Some(Instruction::i32Load8Unsigned)
+                        | Some(Instruction::i32Load16Signed)
+                        | Some(Instruction::i32Load16Unsigned)
+                        | Some(Instruction::i64Load8Signed)
+                        | Some(Instruction::i64Load8Unsigned)
+                        | Some(Instruction::i64Load16Signed)
+                        | Some(Instruction::i64Load16Unsigned)
+                        | Some(Instruction::i64Load32Signed)
+                        | Some(Instruction::i64Load32Unsigned) => {
+                            // The next two bytes after a load
+                            // instruction are the heap coordinates.
+                            self.validation_buffer.push(op);
+
+                            if self.validation_buffer.len() == 2 {
+                                let load_op = Instruction::from_repr(operand).unwrap();
+
+                                // Push the loaded type to the operand stack
+                                self.operand_stack.push(memory_op_type(load_op));
+
+                                // Cleanup
+                                self.validation_buffer = vec![];
+                                self.validation_stack = Stack::new();
+
+                                next_transitions = Some(Instruction::Begin.transitions());
+                                self.state = Validity::Invalid;
+                            }
+                        }
+                        Some(Instruction::i32Store)
+                        | Some(Instruction::i64Store)
+                        | Some(Instruction::f32Store)
+                        | Some(Instruction::f64Store)
+                        | Some(Instruction::i32Store8)
+                        | Some(Instruction::i32Store16)
+                        | Some(Instruction::i64Store8)
+                        | Some(Instruction::i64Store16)
+                        | Some(Instruction::i64Store32) => {
+                            // The next two bytes after a store
+                            // instruction are the heap coordinates.
+                            self.validation_buffer.push(op);
+
+                            if self.validation_buffer.len() == 2 {
+                                // Cleanup
+                                self.validation_buffer = vec![];
+                                self.validation_stack = Stack::new();
+
+                                next_transitions = Some(Instruction::Begin.transitions());
+                                self.state = Validity::Invalid;
+                            }
+                        }
+                        Some(Instruction::MemoryLoad) | Some(Instruction::MemoryStore) => {
+                            // The next byte after a memory instruction is the
+                            // load or store instruction describing the accessed
+                            // value, followed by 4 bytes of address offset.
+                            self.validation_buffer.push(op);
+
+                            if self.validation_buffer.len() == 1 {
+                                let is_load = operand == Instruction::MemoryLoad.repr();
+                                let valid = match Instruction::from_repr(op) {
+                                    Some(op) if is_load => MEMORY_LOAD_OPS.contains(&op),
+                                    Some(op) => MEMORY_STORE_OPS.contains(&op),
+                                    None => false,
+                                };
+
+                                if !valid {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+                            }
+
+                            if self.validation_buffer.len() == 5 {
+                                let memory_op =
+                                    Instruction::from_repr(self.validation_buffer[0]).unwrap();
+                                let value_type = memory_op_type(memory_op);
+                                let operands = self.operand_stack.as_slice();
+                                let len = operands.len();
+
+                                // A load takes an address and a store
+                                // takes an address and the stored value.
+                                let valid = if operand == Instruction::MemoryLoad.repr() {
+                                    len >= 1 && operands[len - 1] == VmType::I32
+                                } else {
+                                    len >= 2
+                                        && operands[len - 2] == VmType::I32
+                                        && operands[len - 1] == value_type
+                                };
+
+                                if !valid {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                    return;
+                                }
+
+                                self.operand_stack.pop();
+
+                                if operand == Instruction::MemoryLoad.repr() {
+                                    self.operand_stack.push(value_type);
+                                } else {
+                                    self.operand_stack.pop();
+                                }
+
+                                // Cleanup
+                                self.validation_buffer = vec![];
+                                self.validation_stack = Stack::new();
+
+                                next_transitions = Some(Instruction::Begin.transitions());
+                                self.state = Validity::Invalid;
+                            }
+                        }
                         Some(Instruction::If) => {
                             if self.validation_stack.len() != 1 {
                                 panic!(format!("The validation stack can only have 1 element at this point! Got: {}", self.validation_stack.len()));
@@ -741,7 +1186,10 @@ impl Validator {
                                 }
                             }
                         }
-                        _ => unimplemented!(),
+                        _ => {
+                            // The marked op does not accept immediates
+                            self.state = Validity::IrrefutablyInvalid;
+                        }
                     }
                 }
                 None => {
@@ -750,7 +1198,11 @@ impl Validator {
             }
 
             // Set next transitions
-            if let Some(next_transitions) = next_transitions {
+            if let Some(mut next_transitions) = next_transitions {
+                if self.vm_version >= 2 {
+                    self.allow_break(&mut next_transitions);
+                }
+
                 self.transitions = next_transitions;
             }
         }
@@ -786,6 +1238,69 @@ impl Validator {
         self.valid()
     }
 
+    /// Allows `Break` and `BreakIf` at the start of the next instruction
+    /// if it can be any instruction and there is a loop in the stack.
+    fn allow_break(&self, transitions: &mut Vec<Transition>) {
+        let has_loop = self
+            .call_stack
+            .as_slice()
+            .iter()
+            .any(|o| o.scope_type == Some(CfOperator::Loop));
+
+        let any_op = transitions.len() > 1
+            && transitions.iter().any(|t| {
+                if let Transition::Op(Instruction::End) = t {
+                    true
+                } else {
+                    false
+                }
+            });
+
+        let has_break = transitions.iter().any(|t| {
+            if let Transition::Op(Instruction::Break) = t {
+                true
+            } else {
+                false
+            }
+        });
+
+        if has_loop && any_op && !has_break {
+            transitions.push(Transition::Op(Instruction::Break));
+            transitions.push(Transition::Op(Instruction::BreakIf));
+        }
+    }
+
+    /// Moves the arguments of the function with the given index from
+    /// the locals stack and pushes its return value in their place.
+    ///
+    /// Returns `false` if there is no function with the given index
+    /// or if the arguments on the locals stack do not match its signature.
+    fn validate_call(&mut self, idx: usize) -> bool {
+        let (arguments, return_type) = match self.signatures.get(idx) {
+            Some(signature) => signature.clone(),
+            None => return false,
+        };
+
+        let frame = self.call_stack.peek_mut();
+        let locals = frame.locals.as_slice();
+
+        if locals.len() < arguments.len()
+            || &locals[locals.len() - arguments.len()..] != arguments.as_slice()
+        {
+            return false;
+        }
+
+        for _ in 0..arguments.len() {
+            frame.locals.pop();
+        }
+
+        if let Some(return_type) = return_type {
+            frame.locals.push(return_type);
+        }
+
+        true
+    }
+
     fn reset(&mut self) {
         self.state = Validity::Invalid;
         self.transitions = Vec::new();
@@ -925,9 +1440,9 @@ impl Validator {
                                 // Check the type of the popped item
                                 match instr {
                                     Some(Instruction::PopOperand) => {
-                                        let at = self.operand_stack.peek();
-
-                                        if *at != arg_type {
+                                        if self.operand_stack.is_empty()
+                                            || *self.operand_stack.peek() != arg_type
+                                        {
                                             self.state = Validity::IrrefutablyInvalid;
 
                                             // Cleanup
@@ -939,9 +1454,10 @@ impl Validator {
                                     }
                                     Some(Instruction::PopLocal) => {
                                         let frame = self.call_stack.peek();
-                                        let at = frame.locals.peek();
 
-                                        if *at != arg_type {
+                                        if frame.locals.is_empty()
+                                            || *frame.locals.peek() != arg_type
+                                        {
                                             self.state = Validity::IrrefutablyInvalid;
 
                                             // Cleanup
@@ -1073,9 +1589,7 @@ impl Validator {
     fn is_idx_valid(&mut self, idx: usize) -> bool {
         let operand = self.operand_stack.peek();
         match operand {
-            VmType::I32 | VmType::I64 | VmType::F32 | VmType::F64 => {
-                panic!("Type of operand is not array")
-            }
+            VmType::I32 | VmType::I64 | VmType::F32 | VmType::F64 => false,
             VmType::i32Array2 | VmType::i64Array2 | VmType::f32Array2 | VmType::f64Array2 => {
                 idx < 2
             }
@@ -1134,21 +1648,142 @@ fn valid_operand_type(operand_stack: &Stack<VmType>, op: Instruction) -> bool {
         | Instruction::f64TruncUnsignedi64
         | Instruction::f64Demotef32
         | Instruction::f64Reinterpreti64 => operand.is_f64(),
-        _ => panic!(),
-    }
-}
-
-fn are_float_type(operand_stack: &Stack<VmType>) -> bool {
-    for v in operand_stack.as_slice() {
-        if v.is_int() {
-            return false;
-        }
+        _ => false,
     }
-    return true;
 }
 
-fn are_integer_type(operand_stack: &Stack<VmType>) -> bool {
-    !are_float_type(operand_stack)
+/// Returns the type that is pushed to the operand
+/// stack after performing the given conversion.
+fn conversion_result_type(op: Instruction) -> VmType {
+    match op {
+        Instruction::i64Wrapi32
+        | Instruction::f32TruncSignedi32
+        | Instruction::f32TruncUnsignedi32
+        | Instruction::f64TruncSignedi32
+        | Instruction::f64TruncUnsignedi32
+        | Instruction::f32Reinterpreti32 => VmType::I32,
+        Instruction::i32ExtendSignedi64
+        | Instruction::i32ExtendUnsignedi64
+        | Instruction::f32TruncSignedi64
+        | Instruction::f32TruncUnsignedi64
+        | Instruction::f64TruncSignedi64
+        | Instruction::f64TruncUnsignedi64
+        | Instruction::f64Reinterpreti64 => VmType::I64,
+        Instruction::i32ConvertSignedf32
+        | Instruction::i32ConvertUnsignedf32
+        | Instruction::i64ConvertSignedf32
+        | Instruction::i64ConvertUnsignedf32
+        | Instruction::f64Demotef32
+        | Instruction::i32Reinterpretf32 => VmType::F32,
+        Instruction::i32ConvertSignedf64
+        | Instruction::i32ConvertUnsignedf64
+        | Instruction::i64ConvertSignedf64
+        | Instruction::i64ConvertUnsignedf64
+        | Instruction::f32Promotef64
+        | Instruction::i64Reinterpretf64 => VmType::F64,
+        op => panic!(format!("Op {:?} is not a conversion!", op)),
+    }
+}
+
+/// Load instructions describing the values that can
+/// be loaded from the linear memory.
+const MEMORY_LOAD_OPS: &'static [Instruction] = &[
+    Instruction::i32Load,
+    Instruction::i64Load,
+    Instruction::i32Load8Signed,
+    Instruction::i32Load8Unsigned,
+    Instruction::i32Load16Signed,
+    Instruction::i32Load16Unsigned,
+    Instruction::i64Load8Signed,
+    Instruction::i64Load8Unsigned,
+    Instruction::i64Load16Signed,
+    Instruction::i64Load16Unsigned,
+    Instruction::i64Load32Signed,
+    Instruction::i64Load32Unsigned,
+];
+
+/// Store instructions describing the values that can
+/// be stored to the linear memory.
+const MEMORY_STORE_OPS: &'static [Instruction] = &[
+    Instruction::i32Store,
+    Instruction::i64Store,
+    Instruction::i32Store8,
+    Instruction::i32Store16,
+    Instruction::i64Store8,
+    Instruction::i64Store16,
+    Instruction::i64Store32,
+];
+
+/// Returns the type of the value that is loaded
+/// or stored by the given memory instruction.
+fn memory_op_type(op: Instruction) -> VmType {
+    match op {
+        Instruction::i32Load
+        | Instruction::i32Load8Signed
+        | Instruction::i32Load8Unsigned
+        | Instruction::i32Load16Signed
+        | Instruction::i32Load16Unsigned
+        | Instruction::i32Store
+        | Instruction::i32Store8
+        | Instruction::i32Store16 => VmType::I32,
+        Instruction::i64Load
+        | Instruction::i64Load8Signed
+        | Instruction::i64Load8Unsigned
+        | Instruction::i64Load16Signed
+        | Instruction::i64Load16Unsigned
+        | Instruction::i64Load32Signed
+        | Instruction::i64Load32Unsigned
+        | Instruction::i64Store
+        | Instruction::i64Store8
+        | Instruction::i64Store16
+        | Instruction::i64Store32 => VmType::I64,
+        Instruction::f32Load | Instruction::f32Store => VmType::F32,
+        Instruction::f64Load | Instruction::f64Store => VmType::F64,
+        op => panic!(format!("Op {:?} is not a memory instruction!", op)),
+    }
+}
+
+/// Replaces the topmost `count` operands with a single
+/// operand of the same type, i.e. the result of an operation.
+fn fold_operands(operand_stack: &mut Stack<VmType>, count: usize) {
+    let mut result = None;
+
+    for _ in 0..count {
+        result = Some(operand_stack.pop());
+    }
+
+    if let Some(result) = result {
+        operand_stack.push(result);
+    }
+}
+
+fn are_same_type(operand_stack: &Stack<VmType>) -> bool {
+    let operands = operand_stack.as_slice();
+
+    match operands.first() {
+        Some(first) => operands.iter().all(|v| v == first),
+        None => true,
+    }
+}
+
+fn are_float_type(operand_stack: &Stack<VmType>) -> bool {
+    for v in operand_stack.as_slice() {
+        if v.is_int() {
+            return false;
+        }
+    }
+    return true;
+}
+
+fn are_integer_type(operand_stack: &Stack<VmType>) -> bool {
+    operand_stack.as_slice().iter().all(|v| v.is_int())
+}
+
+fn are_scalar_integer_type(operand_stack: &Stack<VmType>) -> bool {
+    operand_stack
+        .as_slice()
+        .iter()
+        .all(|v| *v == VmType::I32 || *v == VmType::I64)
 }
 
 fn get_next_elem(val_stack: &Stack<(u8, bool)>) -> (VmType, usize) {
@@ -3597,4 +4232,472 @@ mod tests {
             assert!(!is_valid(block));
         }
     }
+
+    #[test]
+    fn it_has_transitions_for_every_instruction() {
+        for byte in 0..=255 {
+            if let Some(op) = Instruction::from_repr(byte) {
+                assert!(!op.transitions().is_empty(), "{:?} has no transitions", op);
+            }
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_panic_on_any_instruction() {
+        for byte in 0..=255 {
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,
+                Instruction::Nop.repr(),
+                byte,
+                Instruction::Nop.repr(),
+                Instruction::End.repr(),
+            ];
+
+            is_valid(block);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_rejects_unknown_opcodes() {
+        for byte in 0..=255 {
+            if Instruction::from_repr(byte).is_some() {
+                continue;
+            }
+
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,
+                Instruction::Nop.repr(),
+                byte,
+                Instruction::End.repr(),
+            ];
+
+            assert!(!is_valid(block));
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_rejects_constants_outside_of_argument_declarations() {
+        let constants = vec![
+            Instruction::i32Const,
+            Instruction::i64Const,
+            Instruction::f32Const,
+            Instruction::f64Const,
+            Instruction::i32Array2,
+            Instruction::i64Array256,
+            Instruction::f32Array16,
+            Instruction::f64Array8,
+        ];
+
+        for c in constants {
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,
+                Instruction::Nop.repr(),
+                c.repr(),
+                0x00,
+                0x00,
+                0x00,
+                0x01,
+                Instruction::End.repr(),
+            ];
+
+            assert!(!is_valid(block));
+        }
+    }
+
+    fn get_block_with_i32_local(instructions: &[u8]) -> Vec<u8> {
+        let mut block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::Nop.repr(),
+            Instruction::PushLocal.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+        ];
+
+        block.extend_from_slice(instructions);
+        block.push(Instruction::Nop.repr());
+        block.push(Instruction::End.repr());
+        block
+    }
+
+    fn get_block_with_i32_operand(instructions: &[u8]) -> Vec<u8> {
+        let mut block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::Nop.repr(),
+            Instruction::PushOperand.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+        ];
+
+        block.extend_from_slice(instructions);
+        block.push(Instruction::Nop.repr());
+        block.push(Instruction::End.repr());
+        block
+    }
+
+    #[test]
+    fn it_validates_pick_local() {
+        let block = get_block_with_i32_local(&[Instruction::PickLocal.repr(), 0x00, 0x00]);
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    fn it_fails_pick_local_with_out_of_bounds_index() {
+        let block = get_block_with_i32_local(&[Instruction::PickLocal.repr(), 0x00, 0x01]);
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    fn it_validates_pick_operand() {
+        let block = get_block_with_i32_operand(&[Instruction::PickOperand.repr(), 0x00, 0x00]);
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    fn it_fails_pick_operand_with_out_of_bounds_index() {
+        let block = get_block_with_i32_operand(&[Instruction::PickOperand.repr(), 0x01, 0x00]);
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    fn it_validates_pop_local() {
+        let block = get_block_with_i32_local(&[Instruction::PopLocal.repr()]);
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    fn it_fails_pop_local_on_empty_locals() {
+        let block = get_block_with_i32_operand(&[Instruction::PopLocal.repr()]);
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    fn it_validates_pop_operand() {
+        let block = get_block_with_i32_operand(&[Instruction::PopOperand.repr()]);
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    fn it_fails_pop_operand_on_empty_operand_stack() {
+        let block = get_block_with_i32_local(&[Instruction::PopOperand.repr()]);
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    fn it_validates_peek_local() {
+        // The peeked local is usable as an operand
+        let block = get_block_with_i32_local(&[
+            Instruction::PeekLocal.repr(),
+            Instruction::PeekLocal.repr(),
+            Instruction::Add.repr(),
+        ]);
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    fn it_fails_peek_local_on_empty_locals() {
+        let block = get_block_with_i32_operand(&[Instruction::PeekLocal.repr()]);
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    fn it_validates_peek_operand() {
+        // The peeked operand is usable as a local
+        let block = get_block_with_i32_operand(&[
+            Instruction::PeekOperand.repr(),
+            Instruction::PickLocal.repr(),
+            0x00,
+            0x00,
+        ]);
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    fn it_fails_peek_operand_on_empty_operand_stack() {
+        let block = get_block_with_i32_local(&[Instruction::PeekOperand.repr()]);
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    fn it_validates_call() {
+        let block = get_block_with_i32_local(&[Instruction::Call.repr(), 0x00, 0x01]);
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    fn it_validates_loads() {
+        let loads = vec![
+            Instruction::i32Load,
+            Instruction::i64Load,
+            Instruction::f32Load,
+            Instruction::f64Load,
+            Instruction::i32Load8Signed,
+            Instruction::i32Load8Unsigned,
+            Instruction::i32Load16Signed,
+            Instruction::i32Load16Unsigned,
+            Instruction::i64Load8Signed,
+            Instruction::i64Load8Unsigned,
+            Instruction::i64Load16Signed,
+            Instruction::i64Load16Unsigned,
+            Instruction::i64Load32Signed,
+            Instruction::i64Load32Unsigned,
+        ];
+
+        for op in loads {
+            // The loaded value must be on the operand stack
+            let block =
+                get_block_with_i32_local(&[op.repr(), 0x00, 0x01, Instruction::PopOperand.repr()]);
+            assert!(is_valid(block), "{:?} is not valid", op);
+        }
+    }
+
+    #[test]
+    fn it_validates_stores_with_correct_operand_type() {
+        let i32_stores = vec![
+            Instruction::i32Store,
+            Instruction::i32Store8,
+            Instruction::i32Store16,
+        ];
+
+        for op in i32_stores {
+            let block = get_block_with_i32_operand(&[op.repr(), 0x00, 0x01]);
+            assert!(is_valid(block), "{:?} is not valid", op);
+        }
+
+        let stores = vec![
+            (Instruction::i64Load, Instruction::i64Store),
+            (Instruction::i64Load, Instruction::i64Store8),
+            (Instruction::i64Load, Instruction::i64Store16),
+            (Instruction::i64Load, Instruction::i64Store32),
+            (Instruction::f32Load, Instruction::f32Store),
+            (Instruction::f64Load, Instruction::f64Store),
+        ];
+
+        for (load, store) in stores {
+            let block =
+                get_block_with_i32_local(&[load.repr(), 0x00, 0x01, store.repr(), 0x00, 0x02]);
+            assert!(is_valid(block), "{:?} is not valid", store);
+        }
+    }
+
+    #[test]
+    fn it_fails_stores_with_wrong_operand_type() {
+        let stores = vec![
+            Instruction::i64Store,
+            Instruction::f32Store,
+            Instruction::f64Store,
+            Instruction::i64Store8,
+            Instruction::i64Store16,
+            Instruction::i64Store32,
+        ];
+
+        for op in stores {
+            let block = get_block_with_i32_operand(&[op.repr(), 0x00, 0x01]);
+            assert!(!is_valid(block), "{:?} is valid", op);
+        }
+    }
+
+    #[test]
+    fn it_fails_stores_on_empty_operand_stack() {
+        let block = get_block_with_i32_local(&[Instruction::i32Store.repr(), 0x00, 0x01]);
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    fn it_type_checks_conversion_results() {
+        let block = get_block_with_i32_operand(&[
+            Instruction::i32ExtendSignedi64.repr(),
+            Instruction::i64Store.repr(),
+            0x00,
+            0x01,
+        ]);
+        assert!(is_valid(block));
+
+        let block = get_block_with_i32_operand(&[
+            Instruction::i32ExtendSignedi64.repr(),
+            Instruction::i32Store.repr(),
+            0x00,
+            0x01,
+        ]);
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_operands_of_different_types() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::Nop.repr(),
+            Instruction::PushOperand.repr(),
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::f32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::Add.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+        ];
+
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_carry_operands_across_blocks() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::Nop.repr(),
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::PushOperand.repr(),
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::End.repr(),
+            Instruction::Add.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+        ];
+
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_folds_operands_after_addition() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::Nop.repr(),
+            Instruction::PushOperand.repr(),
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::Add.repr(),
+            Instruction::Eqz.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+        ];
+
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_return_with_arity() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::PushLocal.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x05,
+            Instruction::Return.repr(),
+            0x01,
+            Instruction::End.repr(),
+        ];
+
+        assert!(is_valid(block));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_on_return_with_more_values_than_locals() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::PushLocal.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x05,
+            Instruction::Return.repr(),
+            0x02,
+            Instruction::End.repr(),
+        ];
+
+        assert!(!is_valid(block));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_calls_against_signatures() {
+        let signatures: Vec<Signature> = vec![(vec![VmType::I32], Some(VmType::I64))];
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::PickLocal.repr(),   // Copy argument
+            0x00,
+            0x00,
+            Instruction::Call.repr(),
+            0x00,
+            0x00,
+            Instruction::Return.repr(),
+            0x01,
+            Instruction::End.repr(),
+        ];
+
+        let mut validator = Validator::with_signatures(&[VmType::I32], &signatures);
+        assert!(validator.validate_block(&block));
+
+        // Argument type does not match
+        let mut validator = Validator::with_signatures(&[VmType::I64], &signatures);
+        assert!(!validator.validate_block(&block));
+
+        // There is no argument to copy
+        let mut validator = Validator::with_signatures(&[], &signatures);
+        assert!(!validator.validate_block(&block));
+    }
 }
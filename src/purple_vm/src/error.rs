@@ -63,4 +63,10 @@ pub enum VmError {
 
     /// Passed index exceeds array length
     IndexOutOfBound,
+
+    /// The execution has consumed all of the given gas
+    OutOfGas,
+
+    /// An access is outside of the bounds of the linear memory
+    MemoryOutOfBounds,
 }
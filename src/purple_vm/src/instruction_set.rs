@@ -128,6 +128,12 @@ pub enum Instruction {
     GeSigned              = 0x53,
     GeUnsigned            = 0x54,
 
+    // Linear memory
+    MemoryLoad            = 0x55,
+    MemoryStore           = 0x56,
+    MemorySize            = 0x57,
+    MemoryGrow            = 0x58,
+
     // Constants
     i32Const              = 0x60,
     i64Const              = 0x61,
@@ -193,6 +199,17 @@ pub enum Instruction {
     f32Reinterpreti32     = 0x9d,
     f64Reinterpreti64     = 0x9e,
 
+    // Wrapping integer operations
+    AddWrapping           = 0x9f,
+    SubWrapping           = 0xa0,
+    MulWrapping           = 0xa1,
+    DivUnsignedWrapping   = 0xa2,
+    RemSignedWrapping     = 0xa3,
+    RemUnsignedWrapping   = 0xa4,
+    ShlWrapping           = 0xa5,
+    ShrSignedWrapping     = 0xa6,
+    ShrUnsignedWrapping   = 0xa7,
+
     // Blockchain api
     AssetInfo             = 0xf0,
     GetBalance            = 0xf1,
@@ -216,7 +233,6 @@ pub enum Instruction {
 impl Instruction {
     pub fn transitions(&self) -> Vec<Transition> {
         match *self {
-            // TODO: Add transitions for all ops
             Instruction::Halt => {
                 // Nothing comes really, after halt
                 vec![Transition::Op(Instruction::End)]
@@ -240,6 +256,8 @@ impl Instruction {
             Instruction::PushLocal              => DEFAULT_TRANSITIONS.to_vec(),  
             Instruction::PopLocal               => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::PickLocal              => DEFAULT_TRANSITIONS.to_vec(),  
+            Instruction::PeekOperand            => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::PeekLocal              => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::Call                   => DEFAULT_TRANSITIONS.to_vec(), 
 
             // State
@@ -275,6 +293,8 @@ impl Instruction {
 
             // Array operations
             Instruction::Fetch                  => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::Grow                   => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::ArrayStore             => DEFAULT_TRANSITIONS.to_vec(),
 
             // Common operations
             Instruction::Add                    => DEFAULT_TRANSITIONS.to_vec(),
@@ -321,6 +341,54 @@ impl Instruction {
             Instruction::GeSigned               => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::GeUnsigned             => DEFAULT_TRANSITIONS.to_vec(),
 
+            // Linear memory
+            Instruction::MemoryLoad             => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::MemoryStore            => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::MemorySize             => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::MemoryGrow             => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Constants
+            //
+            // Constants are only ever found in argument declarations
+            // and are followed by either other declarations or by the
+            // immediate bytes of the declared values.
+            Instruction::i32Const               => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Const               => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Const               => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Const               => CONST_TRANSITIONS.to_vec(),
+            Instruction::i32Array2              => CONST_TRANSITIONS.to_vec(),
+            Instruction::i32Array4              => CONST_TRANSITIONS.to_vec(),
+            Instruction::i32Array8              => CONST_TRANSITIONS.to_vec(),
+            Instruction::i32Array16             => CONST_TRANSITIONS.to_vec(),
+            Instruction::i32Array32             => CONST_TRANSITIONS.to_vec(),
+            Instruction::i32Array64             => CONST_TRANSITIONS.to_vec(),
+            Instruction::i32Array128            => CONST_TRANSITIONS.to_vec(),
+            Instruction::i32Array256            => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Array2              => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Array4              => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Array8              => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Array16             => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Array32             => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Array64             => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Array128            => CONST_TRANSITIONS.to_vec(),
+            Instruction::i64Array256            => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Array2              => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Array4              => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Array8              => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Array16             => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Array32             => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Array64             => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Array128            => CONST_TRANSITIONS.to_vec(),
+            Instruction::f32Array256            => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Array2              => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Array4              => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Array8              => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Array16             => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Array32             => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Array64             => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Array128            => CONST_TRANSITIONS.to_vec(),
+            Instruction::f64Array256            => CONST_TRANSITIONS.to_vec(),
+
             // Datatype conversions
             Instruction::i64Wrapi32             => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::f32TruncSignedi32      => DEFAULT_TRANSITIONS.to_vec(),
@@ -348,6 +416,17 @@ impl Instruction {
             Instruction::f32Reinterpreti32      => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::f64Reinterpreti64      => DEFAULT_TRANSITIONS.to_vec(),
 
+            // Wrapping integer operations
+            Instruction::AddWrapping            => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::SubWrapping            => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::MulWrapping            => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::DivUnsignedWrapping    => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::RemSignedWrapping      => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::RemUnsignedWrapping    => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::ShlWrapping            => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::ShrSignedWrapping      => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::ShrUnsignedWrapping    => DEFAULT_TRANSITIONS.to_vec(),
+
             // Blockchain api
             Instruction::AssetInfo              => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::GetBalance             => DEFAULT_TRANSITIONS.to_vec(),
@@ -368,20 +447,48 @@ impl Instruction {
                 // Not after suicide either, stay safe kids
                 vec![Transition::Op(Instruction::End)]
             },
-            op => panic!(format!("Op {:?} has no transitions!", op))
         }
     }
 
+    /// Returns the amount of gas that is consumed
+    /// when executing the instruction.
     pub fn gas_price(&self) -> Gas {
-        unimplemented!();
+        match self.repr() {
+            // Halt
+            0x00 => ZERO_GAS.clone(),
+
+            // State
+            0x13..=0x14 => STATE_GAS.clone(),
+
+            // Memory load and store
+            0x15..=0x2b => MEMORY_GAS.clone(),
+
+            // Linear memory access
+            0x55..=0x57 => MEMORY_GAS.clone(),
+
+            // Linear memory growth, which allocates new pages
+            0x58 => STATE_GAS.clone(),
+
+            // Blockchain api
+            0xf0..=0xff => API_GAS.clone(),
+
+            // Everything else
+            _ => BASE_GAS.clone(),
+        }
     }
 }
 
 lazy_static! {
+    static ref ZERO_GAS: Gas = Gas::from_bytes(b"0.0").unwrap();
+    static ref BASE_GAS: Gas = Gas::from_bytes(b"1.0").unwrap();
+    static ref MEMORY_GAS: Gas = Gas::from_bytes(b"3.0").unwrap();
+    static ref STATE_GAS: Gas = Gas::from_bytes(b"50.0").unwrap();
+    static ref API_GAS: Gas = Gas::from_bytes(b"100.0").unwrap();
     static ref DEFAULT_TRANSITIONS: Vec<Transition> =
         OPS_LIST.iter().map(|op| Transition::Op(*op)).collect();
     static ref COMP_TRANSITIONS: Vec<Transition> =
         COMP_OPS.iter().map(|op| Transition::Op(*op)).collect();
+    static ref CONST_TRANSITIONS: Vec<Transition> = vec![Transition::AnyByte];
 }
 
 /// List containing opcodes which handle control flow or begin blocks.
@@ -392,6 +499,34 @@ pub const CT_FLOW_OPS: &'static [Instruction] = &[
     Instruction::Else,
 ];
 
+/// List containing the linear memory opcodes, which are
+/// only valid in code targeting version 2 of the instruction set.
+pub const MEMORY_OPS: &'static [Instruction] = &[
+    Instruction::MemoryLoad,
+    Instruction::MemoryStore,
+    Instruction::MemorySize,
+    Instruction::MemoryGrow,
+];
+
+/// List containing the wrapping integer opcodes, which are
+/// only valid in code targeting version 2 of the instruction set.
+///
+/// They only take `i32` or `i64` operands. Arithmetic wraps around
+/// on overflow, unsigned operations read their operands as two's
+/// complement bit patterns and shift counts are taken modulo the
+/// bit width, as in WebAssembly.
+pub const WRAPPING_OPS: &'static [Instruction] = &[
+    Instruction::AddWrapping,
+    Instruction::SubWrapping,
+    Instruction::MulWrapping,
+    Instruction::DivUnsignedWrapping,
+    Instruction::RemSignedWrapping,
+    Instruction::RemUnsignedWrapping,
+    Instruction::ShlWrapping,
+    Instruction::ShrSignedWrapping,
+    Instruction::ShrUnsignedWrapping,
+];
+
 #[rustfmt::skip]
 /// List containing all comparison operators.
 pub const COMP_OPS: &'static [Instruction] = &[
@@ -426,11 +561,13 @@ pub const OPS_LIST: &'static [Instruction] = &[
     Instruction::PushLocal             ,
     Instruction::PopLocal              ,
     Instruction::PickLocal             ,
+    Instruction::PeekLocal             ,
 
     // Operand stack 
     Instruction::PushOperand           ,
     Instruction::PopOperand            ,
     Instruction::PickOperand           ,
+    Instruction::PeekOperand           ,
 
     // State
     Instruction::GetState              ,
@@ -513,6 +650,12 @@ pub const OPS_LIST: &'static [Instruction] = &[
     Instruction::GeSigned              ,
     Instruction::GeUnsigned            ,
 
+    // Linear memory
+    Instruction::MemoryLoad            ,
+    Instruction::MemoryStore           ,
+    Instruction::MemorySize            ,
+    Instruction::MemoryGrow            ,
+
     // Datatype conversions
     Instruction::i64Wrapi32            ,
     Instruction::f32TruncSignedi32     ,
@@ -540,7 +683,19 @@ pub const OPS_LIST: &'static [Instruction] = &[
     Instruction::f32Reinterpreti32     ,
     Instruction::f64Reinterpreti64     ,
 
+    // Wrapping integer operations
+    Instruction::AddWrapping           ,
+    Instruction::SubWrapping           ,
+    Instruction::MulWrapping           ,
+    Instruction::DivUnsignedWrapping   ,
+    Instruction::RemSignedWrapping     ,
+    Instruction::RemUnsignedWrapping   ,
+    Instruction::ShlWrapping           ,
+    Instruction::ShrSignedWrapping     ,
+    Instruction::ShrUnsignedWrapping   ,
+
     // Blockchain api
+    Instruction::AssetInfo             ,
     Instruction::GetBalance            ,
     Instruction::SendCurrency          ,
     Instruction::Mint                  ,
@@ -548,6 +703,7 @@ pub const OPS_LIST: &'static [Instruction] = &[
     Instruction::CreateContract        ,
     Instruction::CreateCurrency        ,
     Instruction::CreateMintable        ,
+    Instruction::CreateUnique          ,
     Instruction::CallerAddress         ,
     Instruction::CallCurrency          ,
     Instruction::RandomNumber          ,
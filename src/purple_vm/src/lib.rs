@@ -34,6 +34,7 @@ extern crate serde_derive;
 #[macro_use]
 extern crate lazy_static;
 
+pub use crate::code::assembler::*;
 pub use crate::code::function::*;
 pub use crate::code::validator::*;
 pub use crate::code::*;
@@ -41,7 +42,10 @@ pub use crate::error::*;
 pub use crate::gas::*;
 pub use crate::instruction_set::*;
 pub use crate::module::*;
+pub use crate::primitives::r#type::VmType;
+pub use crate::primitives::value::VmValue;
 pub use crate::virtual_machine::*;
+pub use crate::wasm::*;
 
 mod address;
 mod code;
@@ -55,3 +59,4 @@ mod stack;
 
 #[rustfmt::skip]
 mod virtual_machine;
+mod wasm;
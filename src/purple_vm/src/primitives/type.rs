@@ -104,6 +104,49 @@ impl VmType {
         }
     }
 
+    /// Returns the byte of the instruction that declares this type.
+    /// This is the inverse of `from_op()`.
+    pub fn to_op(&self) -> u8 {
+        match *self {
+            VmType::I32 => Instruction::i32Const.repr(),
+            VmType::I64 => Instruction::i64Const.repr(),
+            VmType::F32 => Instruction::f32Const.repr(),
+            VmType::F64 => Instruction::f64Const.repr(),
+            VmType::i32Array2 => Instruction::i32Array2.repr(),
+            VmType::i32Array4 => Instruction::i32Array4.repr(),
+            VmType::i32Array8 => Instruction::i32Array8.repr(),
+            VmType::i32Array16 => Instruction::i32Array16.repr(),
+            VmType::i32Array32 => Instruction::i32Array32.repr(),
+            VmType::i32Array64 => Instruction::i32Array64.repr(),
+            VmType::i32Array128 => Instruction::i32Array128.repr(),
+            VmType::i32Array256 => Instruction::i32Array256.repr(),
+            VmType::i64Array2 => Instruction::i64Array2.repr(),
+            VmType::i64Array4 => Instruction::i64Array4.repr(),
+            VmType::i64Array8 => Instruction::i64Array8.repr(),
+            VmType::i64Array16 => Instruction::i64Array16.repr(),
+            VmType::i64Array32 => Instruction::i64Array32.repr(),
+            VmType::i64Array64 => Instruction::i64Array64.repr(),
+            VmType::i64Array128 => Instruction::i64Array128.repr(),
+            VmType::i64Array256 => Instruction::i64Array256.repr(),
+            VmType::f32Array2 => Instruction::f32Array2.repr(),
+            VmType::f32Array4 => Instruction::f32Array4.repr(),
+            VmType::f32Array8 => Instruction::f32Array8.repr(),
+            VmType::f32Array16 => Instruction::f32Array16.repr(),
+            VmType::f32Array32 => Instruction::f32Array32.repr(),
+            VmType::f32Array64 => Instruction::f32Array64.repr(),
+            VmType::f32Array128 => Instruction::f32Array128.repr(),
+            VmType::f32Array256 => Instruction::f32Array256.repr(),
+            VmType::f64Array2 => Instruction::f64Array2.repr(),
+            VmType::f64Array4 => Instruction::f64Array4.repr(),
+            VmType::f64Array8 => Instruction::f64Array8.repr(),
+            VmType::f64Array16 => Instruction::f64Array16.repr(),
+            VmType::f64Array32 => Instruction::f64Array32.repr(),
+            VmType::f64Array64 => Instruction::f64Array64.repr(),
+            VmType::f64Array128 => Instruction::f64Array128.repr(),
+            VmType::f64Array256 => Instruction::f64Array256.repr(),
+        }
+    }
+
     pub fn validate_structure(&self, buf: &[u8]) -> bool {
         if buf.len() != self.byte_size() {
             return false;
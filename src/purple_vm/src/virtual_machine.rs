@@ -35,6 +35,12 @@ use std::io::Cursor;
 
 const MAX_OP_ARITY: u8 = 8;
 
+/// The size in bytes of a page of linear memory.
+pub const PAGE_SIZE: usize = 65536;
+
+/// The maximum number of pages the linear memory can grow to.
+pub const MAX_MEMORY_PAGES: u32 = 32;
+
 #[derive(Debug)]
 pub struct Vm {
     ip: Option<Address>,
@@ -42,6 +48,10 @@ pub struct Vm {
     call_stack: Stack<Frame<VmValue>>,
     operand_stack: Stack<VmValue>,
     heap: Vec<Vec<Option<VmValue>>>,
+    memory: Vec<u8>,
+    return_values: Vec<VmValue>,
+    current_time: i64,
+    self_destructed: bool,
 }
 
 impl Vm {
@@ -56,11 +66,22 @@ impl Vm {
             modules: Vec::new(),
             ip: None,
             heap: heap,
+            memory: Vec::new(),
             call_stack: Stack::<Frame<VmValue>>::new(),
             operand_stack: Stack::<VmValue>::new(),
+            return_values: Vec::new(),
+            current_time: 0,
+            self_destructed: false,
         }
     }
 
+    /// Sets the time, in seconds since the unix epoch, which is
+    /// pushed by the `CurrentTime` instruction. This is the
+    /// timestamp of the block which executes the code.
+    pub fn set_current_time(&mut self, timestamp: i64) {
+        self.current_time = timestamp;
+    }
+
     /// Loads a module into the virtual machine
     pub fn load(&mut self, module: Module) -> Result<(), VmError> {
         if self.modules.iter().any(|m| m == &module) {
@@ -78,11 +99,26 @@ impl Vm {
         }
     }
 
+    /// Returns the values returned by the entry function
+    /// of the last execution, if any.
+    pub fn return_values(&self) -> &[VmValue] {
+        &self.return_values
+    }
+
+    /// Returns `true` if the last execution was ended by
+    /// the `Suicide` instruction. The caller is then
+    /// responsible for destroying the executed contract.
+    pub fn self_destructed(&self) -> bool {
+        self.self_destructed
+    }
+
     /// Executes the code loaded in the virtual machine
     /// on the given state.
     ///
     /// If it succeeds, this function returns the amount
-    /// of gas that was consumed.
+    /// of gas that was consumed. The execution fails with
+    /// `VmError::OutOfGas` as soon as it would consume more
+    /// than the given amount of gas.
     pub fn execute(
         &mut self,
         trie: &mut TrieDBMut<DbHasher, Codec>,
@@ -108,14 +144,29 @@ impl Vm {
 
         // Set instruction pointer
         self.ip = Some(ip);
+        self.return_values = Vec::new();
+        self.self_destructed = false;
+
+        // Each execution starts with an empty linear memory
+        self.memory = Vec::new();
+
+        let mut gas_used = Gas::from_bytes(b"0.0").unwrap();
 
         // Execute code
-        loop {
+        'execution: loop {
             if let Some(ref mut ip) = self.ip {
                 let module = &self.modules[ip.module_idx];
                 let fun = &module.functions[ip.fun_idx];
                 let op = fun.fetch(ip.ip);
 
+                if let Some(op) = Instruction::from_repr(op) {
+                    gas_used += op.gas_price();
+
+                    if gas_used > gas {
+                        return Err(VmError::OutOfGas);
+                    }
+                }
+
                 if cfg!(test) {
                     if let Some(op) = Instruction::from_repr(op) {
                         println!("DEBUG OP: {:?}", op);
@@ -208,6 +259,13 @@ impl Vm {
                         } else {
                             // Pop frames until we reach one without a scope type
                             loop {
+                                // We have returned from the entry function.
+                                // Keep the returned values and stop execution.
+                                if self.call_stack.is_empty() {
+                                    self.return_values = return_values;
+                                    break 'execution;
+                                }
+
                                 let frame = self.call_stack.pop();
                                 let return_address = frame.return_address.clone();
 
@@ -391,6 +449,23 @@ impl Vm {
 
                         ip.increment();
                     }
+                    Some(Instruction::PeekLocal) => {
+                        let frame = self.call_stack.peek();
+
+                        // Copy topmost item on the locals stack to the operand stack
+                        let item = *frame.locals.peek();
+                        self.operand_stack.push(item);
+
+                        ip.increment();
+                    }
+                    Some(Instruction::PeekOperand) => {
+                        // Copy topmost item on the operand stack to the locals stack
+                        let item = *self.operand_stack.peek();
+                        let frame = self.call_stack.peek_mut();
+                        frame.locals.push(item);
+
+                        ip.increment();
+                    }
                     Some(Instruction::End) => {
                         let frame = self.call_stack.pop();
                         let scope_type = frame.scope_type.clone();
@@ -686,6 +761,42 @@ impl Vm {
                         perform_integer_common(Instruction::Rotr, &mut self.operand_stack)?;
                         ip.increment();
                     }
+                    Some(Instruction::AddWrapping) => {
+                        perform_wrapping(Instruction::AddWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::SubWrapping) => {
+                        perform_wrapping(Instruction::SubWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::MulWrapping) => {
+                        perform_wrapping(Instruction::MulWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::DivUnsignedWrapping) => {
+                        perform_wrapping(Instruction::DivUnsignedWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::RemSignedWrapping) => {
+                        perform_wrapping(Instruction::RemSignedWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::RemUnsignedWrapping) => {
+                        perform_wrapping(Instruction::RemUnsignedWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::ShlWrapping) => {
+                        perform_wrapping(Instruction::ShlWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::ShrSignedWrapping) => {
+                        perform_wrapping(Instruction::ShrSignedWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::ShrUnsignedWrapping) => {
+                        perform_wrapping(Instruction::ShrUnsignedWrapping, &mut self.operand_stack)?;
+                        ip.increment();
+                    }
                     Some(Instruction::i64Wrapi32) => {
                         perform_data_conversion(Instruction::i64Wrapi32, &mut self.operand_stack)?;
                         ip.increment();
@@ -1008,7 +1119,10 @@ impl Vm {
                         let y = fun.fetch(ip.ip) as usize;
 
                         // Fetch elem
-                        let elem = self.heap[x][y].unwrap();
+                        let elem = match self.heap[x][y] {
+                            Some(elem) => elem,
+                            None => return Err(VmError::InvalidOperand),
+                        };
 
                         // Set heap location to `None`
                         self.heap[x][y] = None;
@@ -1288,6 +1402,83 @@ impl Vm {
                             panic!("Cannot receive value other than i64!");
                         }
                     }
+                    Some(Instruction::MemoryLoad) => {
+                        if self.operand_stack.is_empty() {
+                            panic!("The operand stack cannot be empty when calling a memory load instruction!");
+                        }
+
+                        // The next byte is the load instruction describing the
+                        // loaded value, followed by the offset of the address.
+                        ip.increment();
+                        let op = Instruction::from_repr(fun.fetch(ip.ip)).unwrap();
+                        ip.increment();
+                        let bytes: Vec<u8> = fetch_bytes(4, ip, fun);
+                        let offset = decode_be_u32!(&bytes).unwrap();
+
+                        let address = self.operand_stack.pop();
+                        let value = perform_memory_load(&self.memory, op, address, offset)?;
+
+                        self.operand_stack.push(value);
+                        ip.increment();
+                    }
+                    Some(Instruction::MemoryStore) => {
+                        if self.operand_stack.len() < 2 {
+                            panic!("The operand stack must contain an address and a value when calling a memory store instruction!");
+                        }
+
+                        // The next byte is the store instruction describing the
+                        // stored value, followed by the offset of the address.
+                        ip.increment();
+                        let op = Instruction::from_repr(fun.fetch(ip.ip)).unwrap();
+                        ip.increment();
+                        let bytes: Vec<u8> = fetch_bytes(4, ip, fun);
+                        let offset = decode_be_u32!(&bytes).unwrap();
+
+                        let value = self.operand_stack.pop();
+                        let address = self.operand_stack.pop();
+
+                        perform_memory_store(&mut self.memory, op, address, offset, value)?;
+                        ip.increment();
+                    }
+                    Some(Instruction::MemorySize) => {
+                        let pages = self.memory.len() / PAGE_SIZE;
+
+                        self.operand_stack.push(VmValue::I32(pages as i32));
+                        ip.increment();
+                    }
+                    Some(Instruction::MemoryGrow) => {
+                        if self.operand_stack.is_empty() {
+                            panic!("The operand stack cannot be empty when calling a memory grow instruction!");
+                        }
+
+                        let pages = self.memory.len() / PAGE_SIZE;
+                        let result = match self.operand_stack.pop() {
+                            VmValue::I32(delta)
+                                if delta >= 0 && pages as u64 + delta as u64 <= MAX_MEMORY_PAGES as u64 =>
+                            {
+                                let new_len = (pages + delta as usize) * PAGE_SIZE;
+                                self.memory.resize(new_len, 0);
+
+                                pages as i32
+                            }
+                            VmValue::I32(_) => -1,
+                            _ => return Err(VmError::InvalidOperand),
+                        };
+
+                        // The previous number of pages or -1 if the memory cannot grow
+                        self.operand_stack.push(VmValue::I32(result));
+                        ip.increment();
+                    }
+                    Some(Instruction::CurrentTime) => {
+                        self.operand_stack.push(VmValue::I64(self.current_time));
+                        ip.increment();
+                    }
+                    Some(Instruction::Suicide) => {
+                        // Stop the execution, the contract is destroyed
+                        // by the caller once the execution returns.
+                        self.self_destructed = true;
+                        break 'execution;
+                    }
                     _ => unimplemented!(),
                 }
             } else {
@@ -1300,7 +1491,7 @@ impl Vm {
         self.call_stack = Stack::<Frame<VmValue>>::new();
         self.operand_stack = Stack::<VmValue>::new();
 
-        Ok(Gas::from_bytes(b"0.0").unwrap())
+        Ok(gas_used)
     }
 }
 
@@ -3196,6 +3387,101 @@ fn perform_array_store(operand_stack: &mut Stack<VmValue>, idx: usize) -> Result
     Ok(())
 }
 
+/// Returns the range of `len` bytes of the linear memory which is
+/// accessed at the given address and offset, failing if it is out
+/// of the bounds of the memory.
+fn memory_range(
+    memory_len: usize,
+    address: VmValue,
+    offset: u32,
+    len: usize,
+) -> Result<std::ops::Range<usize>, VmError> {
+    let address = match address {
+        VmValue::I32(address) => address as u32 as u64 + offset as u64,
+        _ => return Err(VmError::InvalidOperand),
+    };
+
+    if address + len as u64 > memory_len as u64 {
+        return Err(VmError::MemoryOutOfBounds);
+    }
+
+    Ok(address as usize..address as usize + len)
+}
+
+fn perform_memory_load(
+    memory: &[u8],
+    op: Instruction,
+    address: VmValue,
+    offset: u32,
+) -> Result<VmValue, VmError> {
+    let len = match op {
+        Instruction::i32Load8Signed
+        | Instruction::i32Load8Unsigned
+        | Instruction::i64Load8Signed
+        | Instruction::i64Load8Unsigned => 1,
+        Instruction::i32Load16Signed
+        | Instruction::i32Load16Unsigned
+        | Instruction::i64Load16Signed
+        | Instruction::i64Load16Unsigned => 2,
+        Instruction::i32Load | Instruction::i64Load32Signed | Instruction::i64Load32Unsigned => 4,
+        Instruction::i64Load => 8,
+        _ => return Err(VmError::InvalidOperator),
+    };
+
+    let range = memory_range(memory.len(), address, offset, len)?;
+    let mut buf = [0; 8];
+
+    buf[..len].copy_from_slice(&memory[range]);
+
+    let unsigned = u64::from_le_bytes(buf);
+
+    // Sign extend the loaded bytes
+    let shift = 64 - 8 * len as u32;
+    let signed = ((unsigned << shift) as i64) >> shift;
+
+    let value = match op {
+        Instruction::i32Load
+        | Instruction::i32Load8Signed
+        | Instruction::i32Load16Signed => VmValue::I32(signed as i32),
+        Instruction::i32Load8Unsigned | Instruction::i32Load16Unsigned => {
+            VmValue::I32(unsigned as i32)
+        }
+        Instruction::i64Load
+        | Instruction::i64Load8Signed
+        | Instruction::i64Load16Signed
+        | Instruction::i64Load32Signed => VmValue::I64(signed),
+        _ => VmValue::I64(unsigned as i64),
+    };
+
+    Ok(value)
+}
+
+fn perform_memory_store(
+    memory: &mut [u8],
+    op: Instruction,
+    address: VmValue,
+    offset: u32,
+    value: VmValue,
+) -> Result<(), VmError> {
+    let (len, bytes) = match (op, value) {
+        (Instruction::i32Store, VmValue::I32(value)) => (4, value as u32 as u64),
+        (Instruction::i32Store8, VmValue::I32(value)) => (1, value as u32 as u64),
+        (Instruction::i32Store16, VmValue::I32(value)) => (2, value as u32 as u64),
+        (Instruction::i64Store, VmValue::I64(value)) => (8, value as u64),
+        (Instruction::i64Store8, VmValue::I64(value)) => (1, value as u64),
+        (Instruction::i64Store16, VmValue::I64(value)) => (2, value as u64),
+        (Instruction::i64Store32, VmValue::I64(value)) => (4, value as u64),
+        _ => return Err(VmError::InvalidOperand),
+    };
+
+    let range = memory_range(memory.len(), address, offset, len)?;
+
+    // Values are truncated to the stored width
+    memory[range].copy_from_slice(&bytes.to_le_bytes()[..len]);
+
+    Ok(())
+}
+
 fn perform_comparison(op: Instruction, operands: &[VmValue]) -> Result<bool, VmError> {
     let op_len = operands.len();
     match op {
@@ -4258,12 +4544,102 @@ fn perform_integer_common(
     Ok(())
 }
 
+/// Performs a wrapping integer operation on two `i32` or `i64`
+/// operands. Unsigned operations read the operands as two's
+/// complement bit patterns and shift counts are taken modulo
+/// the bit width.
+fn perform_wrapping(op: Instruction, operand_stack: &mut Stack<VmValue>) -> Result<(), VmError> {
+    let len = operand_stack.len();
+
+    if len != 2 {
+        panic!(format!(
+            "Can perform {:?} only on 2 operands. Got {:?}",
+            op, len
+        ))
+    }
+
+    let second = operand_stack.pop();
+    let first = operand_stack.pop();
+
+    let result = match (first, second) {
+        (VmValue::I32(val1), VmValue::I32(val2)) => {
+            let (unsigned1, unsigned2) = (val1 as u32, val2 as u32);
+
+            let result = match op {
+                Instruction::AddWrapping => val1.wrapping_add(val2),
+                Instruction::SubWrapping => val1.wrapping_sub(val2),
+                Instruction::MulWrapping => val1.wrapping_mul(val2),
+                Instruction::DivUnsignedWrapping => match unsigned1.checked_div(unsigned2) {
+                    Some(res) => res as i32,
+                    None => return Err(VmError::DivideByZero),
+                },
+                Instruction::RemSignedWrapping => {
+                    if val2 == 0 {
+                        return Err(VmError::DivideByZero);
+                    }
+
+                    val1.wrapping_rem(val2)
+                }
+                Instruction::RemUnsignedWrapping => match unsigned1.checked_rem(unsigned2) {
+                    Some(res) => res as i32,
+                    None => return Err(VmError::DivideByZero),
+                },
+                Instruction::ShlWrapping => val1.wrapping_shl(unsigned2),
+                Instruction::ShrSignedWrapping => val1.wrapping_shr(unsigned2),
+                Instruction::ShrUnsignedWrapping => unsigned1.wrapping_shr(unsigned2) as i32,
+                _ => panic!(format!("Must receive a wrapping operation. Got {:?}", op)),
+            };
+
+            VmValue::I32(result)
+        }
+        (VmValue::I64(val1), VmValue::I64(val2)) => {
+            let (unsigned1, unsigned2) = (val1 as u64, val2 as u64);
+
+            let result = match op {
+                Instruction::AddWrapping => val1.wrapping_add(val2),
+                Instruction::SubWrapping => val1.wrapping_sub(val2),
+                Instruction::MulWrapping => val1.wrapping_mul(val2),
+                Instruction::DivUnsignedWrapping => match unsigned1.checked_div(unsigned2) {
+                    Some(res) => res as i64,
+                    None => return Err(VmError::DivideByZero),
+                },
+                Instruction::RemSignedWrapping => {
+                    if val2 == 0 {
+                        return Err(VmError::DivideByZero);
+                    }
+
+                    val1.wrapping_rem(val2)
+                }
+                Instruction::RemUnsignedWrapping => match unsigned1.checked_rem(unsigned2) {
+                    Some(res) => res as i64,
+                    None => return Err(VmError::DivideByZero),
+                },
+                Instruction::ShlWrapping => val1.wrapping_shl(unsigned2 as u32),
+                Instruction::ShrSignedWrapping => val1.wrapping_shr(unsigned2 as u32),
+                Instruction::ShrUnsignedWrapping => unsigned1.wrapping_shr(unsigned2 as u32) as i64,
+                _ => panic!(format!("Must receive a wrapping operation. Got {:?}", op)),
+            };
+
+            VmValue::I64(result)
+        }
+        _ => return Err(VmError::InvalidOperator),
+    };
+
+    operand_stack.push(result);
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crypto::{Hash, ShortHash};
     use rand::Rng;
 
+    /// The gas limit of executions which are not
+    /// testing the gas consumption.
+    const TEST_GAS: &'static [u8] = b"1000000000.0";
+
     #[test]
     #[rustfmt::skip]
     #[should_panic(expected = "first instruction cannot be a Loop instruction")]
@@ -4295,7 +4671,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap()).unwrap();
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
     }
 
     #[test]
@@ -4329,7 +4705,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap()).unwrap();
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
     }
 
     #[test]
@@ -4423,7 +4799,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap()).unwrap();
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
 
         assert!(true);
     }
@@ -4555,7 +4931,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap()).unwrap();
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
 
         assert!(true);
     }
@@ -4699,7 +5075,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap()).unwrap();
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
 
         assert!(true);
     }
@@ -4838,7 +5214,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap()).unwrap();
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
 
         assert!(true);
     }
@@ -4973,7 +5349,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap())
             .unwrap();
 
         assert!(true);
@@ -5074,7 +5450,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap())
             .unwrap();
 
         assert!(true);
@@ -5178,7 +5554,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap())
             .unwrap();
 
         assert!(true);
@@ -5297,7 +5673,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap())
             .unwrap();
 
         assert!(true);
@@ -5416,7 +5792,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap())
             .unwrap();
 
         assert!(true);
@@ -5464,7 +5840,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        let result = vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap());
+        let result = vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap());
 
         assert_eq!(result, Err(VmError::Overflow));
     }
@@ -5511,7 +5887,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        let result = vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap());
+        let result = vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap());
 
         assert_eq!(result, Err(VmError::Overflow));
     }
@@ -5562,7 +5938,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        let result = vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap());
+        let result = vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap());
 
         assert_eq!(result, Err(VmError::Overflow));
     }
@@ -5589,7 +5965,7 @@ mod tests {
         };
 
         vm.load(module).unwrap();
-        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap())
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap())
     }
 
     #[test]
@@ -12286,4 +12662,325 @@ mod tests {
 
         assert_eq!(execute_vm_code_common(block), Err(VmError::IndexOutOfBound));
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_keeps_values_returned_from_the_entry_function() {
+        let mut vm = Vm::new();
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Return.repr(),
+            0x01,                             // Return the received argument
+            Instruction::End.repr()
+        ];
+
+        let function = Function {
+            arity: 1,
+            name: "debug_test".to_owned(),
+            block: block,
+            return_type: Some(VmType::I32),
+            arguments: vec![VmType::I32]
+        };
+
+        let module = Module {
+            module_hash: Hash::NULL,
+            functions: vec![function],
+            imports: vec![]
+        };
+
+        vm.load(module).unwrap();
+
+        let gas_used = vm.execute(&mut trie, 0, 0, &[VmValue::I32(42)], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
+
+        assert_eq!(vm.return_values(), &[VmValue::I32(42)]);
+        assert_eq!(gas_used, Gas::from_bytes(b"3.0").unwrap());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_enforces_the_gas_limit() {
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        let function = Function {
+            arity: 0,
+            name: "debug_test".to_owned(),
+            block: block,
+            return_type: None,
+            arguments: vec![]
+        };
+
+        let module = Module {
+            module_hash: Hash::NULL,
+            functions: vec![function],
+            imports: vec![]
+        };
+
+        let mut vm = Vm::new();
+        vm.load(module.clone()).unwrap();
+        let gas_used = vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
+
+        let mut vm = Vm::new();
+        vm.load(module.clone()).unwrap();
+        assert_eq!(vm.execute(&mut trie, 0, 0, &[], gas_used.clone()), Ok(gas_used.clone()));
+
+        let mut vm = Vm::new();
+        vm.load(module).unwrap();
+        let limit = gas_used - Gas::from_bytes(b"1.0").unwrap();
+        assert_eq!(vm.execute(&mut trie, 0, 0, &[], limit), Err(VmError::OutOfGas));
+        assert_eq!(vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(b"0.0").unwrap()), Err(VmError::OutOfGas));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_pushes_the_current_time() {
+        let mut vm = Vm::new();
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+        let mut bitmask: u8 = 0;
+
+        bitmask.set(0, true);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::CurrentTime.repr(),
+            Instruction::PushLocal.repr(),    // Move the time from the operand stack to locals
+            0x01,
+            bitmask,                          // Reference bits
+            Instruction::i64Const.repr(),
+            Instruction::PopOperand.repr(),
+            Instruction::Return.repr(),
+            0x01,
+            Instruction::End.repr()
+        ];
+
+        let function = Function {
+            arity: 0,
+            name: "debug_test".to_owned(),
+            block: block,
+            return_type: Some(VmType::I64),
+            arguments: vec![]
+        };
+
+        let module = Module {
+            module_hash: Hash::NULL,
+            functions: vec![function],
+            imports: vec![]
+        };
+
+        vm.load(module).unwrap();
+        vm.set_current_time(1_600_000_000);
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
+
+        assert_eq!(vm.return_values(), &[VmValue::I64(1_600_000_000)]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_self_destructs() {
+        let mut vm = Vm::new();
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Suicide.repr(),
+            Instruction::End.repr()
+        ];
+
+        let function = Function {
+            arity: 0,
+            name: "debug_test".to_owned(),
+            block: block,
+            return_type: None,
+            arguments: vec![]
+        };
+
+        let module = Module {
+            module_hash: Hash::NULL,
+            functions: vec![function],
+            imports: vec![]
+        };
+
+        vm.load(module).unwrap();
+        assert!(!vm.self_destructed());
+
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
+
+        assert!(vm.self_destructed());
+        assert!(vm.return_values().is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_loads_and_stores_linear_memory() {
+        let mut vm = Vm::new();
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+        let mut bitmask: u8 = 0;
+
+        bitmask.set(0, true);
+        bitmask.set(1, true);
+        bitmask.set(2, true);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,                             // Grow the memory by 1 page
+            0x00,
+            0x00,
+            0x01,
+            Instruction::MemoryGrow.repr(),   // Pushes the previous size which is used as address
+            Instruction::PushOperand.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0xff,                             // -2
+            0xff,
+            0xff,
+            0xfe,
+            Instruction::MemoryStore.repr(),
+            Instruction::i32Store8.repr(),
+            0x00,                             // Offset 4
+            0x00,
+            0x00,
+            0x04,
+            Instruction::PushOperand.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,                             // Address 4
+            0x00,
+            0x00,
+            0x04,
+            Instruction::MemoryLoad.repr(),
+            Instruction::i32Load8Signed.repr(),
+            0x00,                             // Offset 0
+            0x00,
+            0x00,
+            0x00,
+            Instruction::PushOperand.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,                             // Address 0
+            0x00,
+            0x00,
+            0x00,
+            Instruction::MemoryLoad.repr(),
+            Instruction::i32Load8Unsigned.repr(),
+            0x00,                             // Offset 4
+            0x00,
+            0x00,
+            0x04,
+            Instruction::MemorySize.repr(),
+            Instruction::PushLocal.repr(),    // Move the results to locals
+            0x03,
+            bitmask,                          // Reference bits
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            Instruction::PopOperand.repr(),
+            Instruction::PopOperand.repr(),
+            Instruction::PopOperand.repr(),
+            Instruction::Return.repr(),
+            0x03,
+            Instruction::End.repr()
+        ];
+
+        let function = Function {
+            arity: 0,
+            name: "debug_test".to_owned(),
+            block: block,
+            return_type: Some(VmType::I32),
+            arguments: vec![]
+        };
+
+        let module = Module {
+            module_hash: Hash::NULL,
+            functions: vec![function],
+            imports: vec![]
+        };
+
+        vm.load(module).unwrap();
+        vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()).unwrap();
+
+        assert_eq!(vm.return_values(), &[VmValue::I32(1), VmValue::I32(254), VmValue::I32(-2)]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_on_out_of_bounds_memory_access() {
+        let mut vm = Vm::new();
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,                             // Address 0 without any allocated page
+            0x00,
+            0x00,
+            0x00,
+            Instruction::MemoryLoad.repr(),
+            Instruction::i32Load.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::End.repr()
+        ];
+
+        let function = Function {
+            arity: 0,
+            name: "debug_test".to_owned(),
+            block: block,
+            return_type: None,
+            arguments: vec![]
+        };
+
+        let module = Module {
+            module_hash: Hash::NULL,
+            functions: vec![function],
+            imports: vec![]
+        };
+
+        vm.load(module).unwrap();
+
+        assert_eq!(
+            vm.execute(&mut trie, 0, 0, &[], Gas::from_bytes(TEST_GAS).unwrap()),
+            Err(VmError::MemoryOutOfBounds)
+        );
+    }
 }
@@ -0,0 +1,1212 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Translator from WebAssembly modules to Purple code.
+//!
+//! Only a restricted subset of WebAssembly is supported:
+//!
+//! * Values are `i32` or `i64`. Floating point types and
+//! instructions are rejected since their results are not
+//! deterministic across platforms.
+//! * Functions and blocks return at most one value and blocks
+//! do not take parameters.
+//! * There is at most one table and one linear memory of at most
+//! `MAX_MEMORY_PAGES` pages. Element and data segments must be active.
+//! * Globals are initialized with constants.
+//! * Functions can be imported only from the `purple` module, in which
+//! case they are mapped to blockchain api instructions. Since the api
+//! instructions do not take arguments from the stack, the imported
+//! functions cannot have parameters or results.
+//! * Instructions added after the MVP, such as sign extension and
+//! bulk memory instructions, are not supported.
+//!
+//! The translated code behaves like the wasm module except that:
+//!
+//! * Globals and the linear memory do not persist between
+//! executions of the exported functions.
+//!
+//! The values of the wasm value stack are kept on the locals stack
+//! and operators move their operands to the operand stack. Locals and
+//! globals are kept in heap cells and all wasm blocks are translated
+//! to `Loop` blocks that are left with `Break` and `BreakIf`.
+//!
+//! Integer operators are translated to the wrapping instructions of
+//! the VM, which wrap around on overflow and read the operands of
+//! unsigned operations as bit patterns like wasm does. Unsigned
+//! comparisons flip the sign bits of their operands and compare
+//! them as signed values.
+//!
+//! Exported functions keep their export name. Other functions are
+//! named after their index, e.g. `f3`. If the module has globals,
+//! a memory or a start function, exports are instead translated to
+//! functions that initialize them before calling the exported one.
+
+mod reader;
+mod translator;
+
+use self::reader::Reader;
+use self::translator::*;
+use crate::code::function::Function;
+use crate::code::{Code, VM_VERSION};
+use crate::instruction_set::Instruction;
+use crate::primitives::r#type::VmType;
+use crate::virtual_machine::{MAX_MEMORY_PAGES, PAGE_SIZE};
+use hashbrown::{HashMap, HashSet};
+
+/// The module from which the blockchain api can be imported.
+const API_MODULE: &'static str = "purple";
+
+/// The maximum number of table elements.
+const MAX_TABLE_SIZE: u32 = 65536;
+
+/// Blockchain api instructions by the name they are imported with.
+const API_IMPORTS: &'static [(&'static str, Instruction)] = &[
+    ("asset_info", Instruction::AssetInfo),
+    ("get_balance", Instruction::GetBalance),
+    ("send_currency", Instruction::SendCurrency),
+    ("mint", Instruction::Mint),
+    ("burn", Instruction::Burn),
+    ("create_contract", Instruction::CreateContract),
+    ("create_currency", Instruction::CreateCurrency),
+    ("create_mintable", Instruction::CreateMintable),
+    ("create_unique", Instruction::CreateUnique),
+    ("caller_address", Instruction::CallerAddress),
+    ("call_currency", Instruction::CallCurrency),
+    ("random_number", Instruction::RandomNumber),
+    ("current_time", Instruction::CurrentTime),
+    ("current_price", Instruction::CurrentPrice),
+    ("price_at", Instruction::PriceAt),
+    ("suicide", Instruction::Suicide),
+];
+
+/// Translates the given WebAssembly module to Purple code.
+///
+/// Returns an error if the module uses features outside of
+/// the supported subset or if the resulting code is invalid.
+pub fn translate_wasm(module: &[u8]) -> Result<Code, &'static str> {
+    let mut reader = Reader::new(module);
+
+    if reader.read_bytes(4)? != b"\0asm" {
+        return Err("Invalid wasm magic number");
+    }
+
+    if reader.read_bytes(4)? != [0x01, 0x00, 0x00, 0x00] {
+        return Err("Unsupported wasm version");
+    }
+
+    let mut types: Vec<FuncType> = Vec::new();
+    let mut callees: Vec<Callee> = Vec::new();
+    let mut function_types: Vec<FuncType> = Vec::new();
+    let mut table: Option<Vec<Option<u32>>> = None;
+    let mut memory_pages: Option<u32> = None;
+    let mut globals: Vec<Global> = Vec::new();
+    let mut exports: HashMap<u32, String> = HashMap::new();
+    let mut start: Option<u32> = None;
+    let mut bodies: Vec<&[u8]> = Vec::new();
+    let mut data: Vec<(u32, &[u8])> = Vec::new();
+    let mut last_section = 0;
+
+    while !reader.is_empty() {
+        let id = reader.read_u8()?;
+        let len = reader.read_var_u32()? as usize;
+        let mut section = Reader::new(reader.read_bytes(len)?);
+
+        // Custom sections can appear anywhere
+        if id == 0 {
+            continue;
+        }
+
+        let order = section_order(id)?;
+
+        if order <= last_section {
+            return Err("Invalid wasm section order");
+        }
+
+        last_section = order;
+
+        match id {
+            // Type section
+            1 => {
+                for _ in 0..section.read_var_u32()? {
+                    types.push(read_func_type(&mut section)?);
+                }
+            }
+
+            // Import section
+            2 => {
+                for _ in 0..section.read_var_u32()? {
+                    let module_name = section.read_name()?;
+                    let field_name = section.read_name()?;
+
+                    if section.read_u8()? != 0x00 {
+                        return Err("Only function imports are supported");
+                    }
+
+                    let func_type = match types.get(section.read_var_u32()? as usize) {
+                        Some(func_type) => func_type,
+                        None => return Err("Invalid type index"),
+                    };
+
+                    if module_name != API_MODULE {
+                        return Err("Functions can only be imported from the purple module");
+                    }
+
+                    if !func_type.params.is_empty() || func_type.result.is_some() {
+                        return Err("Imported functions cannot have parameters or results");
+                    }
+
+                    match API_IMPORTS.iter().find(|(name, _)| *name == field_name) {
+                        Some((_, instruction)) => callees.push(Callee::Api(*instruction)),
+                        None => return Err("Unknown blockchain api function"),
+                    }
+                }
+            }
+
+            // Function section
+            3 => {
+                for _ in 0..section.read_var_u32()? {
+                    match types.get(section.read_var_u32()? as usize) {
+                        Some(func_type) => function_types.push(func_type.clone()),
+                        None => return Err("Invalid type index"),
+                    }
+                }
+            }
+
+            // Table section
+            4 => {
+                if section.read_var_u32()? > 1 || section.read_u8()? != 0x70 {
+                    return Err("Only a single function table is supported");
+                }
+
+                let size = read_limits(&mut section)?;
+
+                if size > MAX_TABLE_SIZE {
+                    return Err("The table is too large");
+                }
+
+                table = Some(vec![None; size as usize]);
+            }
+
+            // Memory section
+            5 => {
+                if section.read_var_u32()? > 1 {
+                    return Err("Only a single memory is supported");
+                }
+
+                let pages = read_limits(&mut section)?;
+
+                if pages > MAX_MEMORY_PAGES {
+                    return Err("The memory is too large");
+                }
+
+                memory_pages = Some(pages);
+            }
+
+            // Global section
+            6 => {
+                for _ in 0..section.read_var_u32()? {
+                    let value_type = read_value_type(&mut section)?;
+
+                    let mutable = match section.read_u8()? {
+                        0x00 => false,
+                        0x01 => true,
+                        _ => return Err("Invalid global mutability"),
+                    };
+
+                    let value = read_const_expr(&mut section, value_type)?;
+
+                    globals.push(Global {
+                        value_type,
+                        mutable,
+                        value,
+                    });
+                }
+            }
+
+            // Export section
+            7 => {
+                for _ in 0..section.read_var_u32()? {
+                    let name = section.read_name()?;
+                    let kind = section.read_u8()?;
+                    let idx = section.read_var_u32()?;
+
+                    // Tables, memories and globals are not visible
+                    // outside of the translated code.
+                    match kind {
+                        0x00 => {
+                            exports.insert(idx, name);
+                        }
+                        0x01..=0x03 => {}
+                        _ => return Err("Invalid export kind"),
+                    }
+                }
+            }
+
+            // Start section
+            8 => start = Some(section.read_var_u32()?),
+
+            // Element section
+            9 => {
+                let functions_len = (callees.len() + function_types.len()) as u32;
+
+                let table = match table {
+                    Some(ref mut table) => table,
+                    None => return Err("The module does not define a table"),
+                };
+
+                for _ in 0..section.read_var_u32()? {
+                    if section.read_var_u32()? != 0 {
+                        return Err("Only active element segments are supported");
+                    }
+
+                    let offset = read_const_expr(&mut section, VmType::I32)? as u32 as usize;
+                    let len = section.read_var_u32()? as usize;
+
+                    if offset.saturating_add(len) > table.len() {
+                        return Err("Element segment out of bounds");
+                    }
+
+                    for i in 0..len {
+                        let fun_idx = section.read_var_u32()?;
+
+                        if fun_idx >= functions_len {
+                            return Err("Invalid function index");
+                        }
+
+                        table[offset + i] = Some(fun_idx);
+                    }
+                }
+            }
+
+            // Code section
+            10 => {
+                for _ in 0..section.read_var_u32()? {
+                    let len = section.read_var_u32()? as usize;
+                    bodies.push(section.read_bytes(len)?);
+                }
+            }
+
+            // Data section
+            11 => {
+                let memory_len = match memory_pages {
+                    Some(pages) => pages as usize * PAGE_SIZE,
+                    None => return Err("The module does not define a memory"),
+                };
+
+                for _ in 0..section.read_var_u32()? {
+                    if section.read_var_u32()? != 0 {
+                        return Err("Only active data segments are supported");
+                    }
+
+                    let offset = read_const_expr(&mut section, VmType::I32)? as u32;
+                    let len = section.read_var_u32()? as usize;
+                    let bytes = section.read_bytes(len)?;
+
+                    if offset as usize + len > memory_len {
+                        return Err("Data segment out of bounds");
+                    }
+
+                    data.push((offset, bytes));
+                }
+            }
+
+            // Data count section
+            12 => {
+                section.read_var_u32()?;
+            }
+
+            _ => unreachable!(),
+        }
+
+        if !section.is_empty() {
+            return Err("Invalid wasm section length");
+        }
+    }
+
+    if function_types.len() != bodies.len() {
+        return Err("The function and code sections do not match");
+    }
+
+    if function_types.is_empty() {
+        return Err("The module does not define any function");
+    }
+
+    if function_types.len() > u16::max_value() as usize {
+        return Err("Too many functions");
+    }
+
+    let imports_len = callees.len();
+
+    for (idx, func_type) in function_types.iter().enumerate() {
+        callees.push(Callee::Function(idx as u16, func_type.clone()));
+    }
+
+    // The start function is called with the index of its translation
+    let start = match start {
+        Some(idx) => match callees.get(idx as usize) {
+            Some(Callee::Function(idx, func_type)) => {
+                if !func_type.params.is_empty() || func_type.result.is_some() {
+                    return Err("The start function cannot have parameters or results");
+                }
+
+                Some(*idx)
+            }
+            _ => return Err("Invalid start function"),
+        },
+        None => None,
+    };
+
+    let scans = bodies
+        .iter()
+        .map(|body| scan_function(body))
+        .collect::<Result<Vec<FunctionScan>, &'static str>>()?;
+
+    let mut functions: Vec<FunctionInfo> = Vec::with_capacity(bodies.len());
+    let mut next_cell = RESERVED_CELLS + globals.len();
+
+    for (idx, scan) in scans.iter().enumerate() {
+        let func_type = function_types[idx].clone();
+        let mut locals = func_type.params.clone();
+
+        locals.extend_from_slice(&scan.locals);
+
+        let first_cell = next_cell;
+        next_cell += locals.len();
+
+        if next_cell > MAX_CELLS {
+            return Err("Too many locals and globals");
+        }
+
+        functions.push(FunctionInfo {
+            func_type,
+            locals,
+            first_cell,
+            reachable: HashSet::new(),
+        });
+    }
+
+    // The functions that each function calls directly
+    let calls: Vec<Vec<u16>> = scans
+        .iter()
+        .map(|scan| {
+            let mut result = Vec::new();
+
+            for idx in scan.calls.iter() {
+                if let Some(Callee::Function(idx, _)) = callees.get(*idx as usize) {
+                    result.push(*idx);
+                }
+            }
+
+            for type_idx in scan.indirect_calls.iter() {
+                let (table, func_type) = match (&table, types.get(*type_idx as usize)) {
+                    (Some(table), Some(func_type)) => (table, func_type),
+                    _ => continue,
+                };
+
+                for fun_idx in table.iter().filter_map(|element| *element) {
+                    if let Callee::Function(idx, element_type) = &callees[fun_idx as usize] {
+                        if element_type == func_type {
+                            result.push(*idx);
+                        }
+                    }
+                }
+            }
+
+            result
+        })
+        .collect();
+
+    for (idx, function) in functions.iter_mut().enumerate() {
+        let mut stack = calls[idx].clone();
+
+        while let Some(callee) = stack.pop() {
+            if function.reachable.insert(callee) {
+                stack.extend_from_slice(&calls[callee as usize]);
+            }
+        }
+    }
+
+    let info = ModuleInfo {
+        types,
+        callees,
+        functions,
+        globals,
+        table,
+        has_memory: memory_pages.is_some(),
+    };
+
+    let memory_pages = memory_pages.unwrap_or(0);
+    let needs_entry = !info.globals.is_empty() || memory_pages > 0 || start.is_some();
+    let mut translated: Vec<Function> = Vec::with_capacity(bodies.len());
+    let mut entries: Vec<Function> = Vec::new();
+
+    for (idx, body) in bodies.iter().enumerate() {
+        let func_type = &info.functions[idx].func_type;
+        let wasm_idx = (imports_len + idx) as u32;
+        let block = translate_function(body, idx, &info)?;
+        let default_name = format!("f{}", idx);
+
+        if func_type.params.len() > u8::max_value() as usize {
+            return Err("Too many function parameters");
+        }
+
+        let (name, entry_name) = match exports.remove(&wasm_idx) {
+            Some(name) if needs_entry => (default_name, Some(name)),
+            Some(name) => (name, None),
+            None => (default_name, None),
+        };
+
+        translated.push(function(name, block, func_type)?);
+
+        if let Some(entry_name) = entry_name {
+            let block = entry_function(&info, memory_pages, &data, start, idx as u16);
+            entries.push(function(entry_name, block, func_type)?);
+        }
+    }
+
+    // Any remaining export refers to an imported function
+    if !exports.is_empty() {
+        return Err("Imported functions cannot be exported");
+    }
+
+    // The entry functions follow the translated functions
+    // so that the indices of the latter are preserved.
+    translated.extend(entries);
+
+    let mut code = Code::from_functions(&translated);
+
+    if !code.validate(VM_VERSION) {
+        return Err("The translated code is invalid");
+    }
+
+    Ok(code)
+}
+
+fn function(name: String, block: Vec<u8>, func_type: &FuncType) -> Result<Function, &'static str> {
+    if name.len() > u8::max_value() as usize {
+        return Err("Function name is too long");
+    }
+
+    if block.len() > u16::max_value() as usize {
+        return Err("Function is too large");
+    }
+
+    Ok(Function {
+        arity: func_type.params.len() as u8,
+        block,
+        name,
+        arguments: func_type.params.clone(),
+        return_type: func_type.result,
+    })
+}
+
+/// Returns the position of the section with the given id in the
+/// order of the sections. The data count section is placed
+/// between the element and code sections.
+fn section_order(id: u8) -> Result<u8, &'static str> {
+    match id {
+        1..=9 => Ok(id),
+        12 => Ok(10),
+        10 | 11 => Ok(id + 1),
+        _ => Err("Unsupported wasm section"),
+    }
+}
+
+fn read_func_type(reader: &mut Reader) -> Result<FuncType, &'static str> {
+    if reader.read_u8()? != 0x60 {
+        return Err("Invalid function type");
+    }
+
+    let mut params = Vec::new();
+
+    for _ in 0..reader.read_var_u32()? {
+        params.push(read_value_type(reader)?);
+    }
+
+    let result = match reader.read_var_u32()? {
+        0 => None,
+        1 => Some(read_value_type(reader)?),
+        _ => return Err("Functions can return at most one value"),
+    };
+
+    Ok(FuncType { params, result })
+}
+
+/// Reads the limits of a table or memory and returns the minimum.
+fn read_limits(reader: &mut Reader) -> Result<u32, &'static str> {
+    match reader.read_u8()? {
+        0x00 => reader.read_var_u32(),
+        0x01 => {
+            let min = reader.read_var_u32()?;
+
+            if reader.read_var_u32()? < min {
+                return Err("Invalid limits");
+            }
+
+            Ok(min)
+        }
+        _ => Err("Invalid limits"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::VmError;
+    use crate::gas::Gas;
+    use crate::module::Module;
+    use crate::primitives::value::VmValue;
+    use crate::virtual_machine::Vm;
+    use bitvec::Bits;
+    use crypto::{Hash, ShortHash};
+    use patricia_trie::TrieDBMut;
+    use persistence::{Codec, DbHasher};
+
+    /// Builds a wasm module from the given section entries.
+    fn module(
+        types: &[&[u8]],
+        imports: &[&[u8]],
+        functions: &[&[u8]],
+        exports: &[&[u8]],
+        bodies: &[&[u8]],
+    ) -> Vec<u8> {
+        sections(&[
+            (1, vec_of(types)),
+            (2, vec_of(imports)),
+            (3, vec_of(functions)),
+            (7, vec_of(exports)),
+            (10, code(bodies)),
+        ])
+    }
+
+    /// Builds a wasm module from the given sections.
+    fn sections(sections: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut module = b"\0asm\x01\x00\x00\x00".to_vec();
+
+        for (id, payload) in sections {
+            module.push(*id);
+            module.extend_from_slice(&var_u32(payload.len()));
+            module.extend_from_slice(payload);
+        }
+
+        module
+    }
+
+    fn code(bodies: &[&[u8]]) -> Vec<u8> {
+        let bodies: Vec<Vec<u8>> = bodies
+            .iter()
+            .map(|body| [&var_u32(body.len())[..], body].concat())
+            .collect();
+        let bodies: Vec<&[u8]> = bodies.iter().map(|body| body.as_slice()).collect();
+
+        vec_of(&bodies)
+    }
+
+    fn vec_of(items: &[&[u8]]) -> Vec<u8> {
+        let mut result = var_u32(items.len());
+
+        for item in items {
+            result.extend_from_slice(item);
+        }
+
+        result
+    }
+
+    fn var_u32(mut value: usize) -> Vec<u8> {
+        let mut result = Vec::new();
+
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                result.push(byte);
+                return result;
+            }
+
+            result.push(byte | 0x80);
+        }
+    }
+
+    /// Decodes the functions of the given code.
+    fn functions(code: &Code) -> Vec<Function> {
+        let mut code = code.as_bytes().to_vec();
+        let mut functions = Vec::new();
+
+        // Skip header and imports section
+        code.drain(..9);
+
+        while !code.is_empty() {
+            let name_len = code[0] as usize;
+            let arity = code[1] as usize;
+            let block_len = decode_be_u16!(&code[3..5]).unwrap() as usize;
+            let name = String::from_utf8(code[5..5 + name_len].to_vec()).unwrap();
+            let start = 5 + name_len + arity;
+
+            functions.push(Function {
+                arity: arity as u8,
+                block: code[start..start + block_len].to_vec(),
+                name,
+                arguments: code[5 + name_len..start]
+                    .iter()
+                    .map(|op| VmType::from_op(*op).unwrap())
+                    .collect(),
+                return_type: VmType::from_op(code[2]),
+            });
+            code.drain(..start + block_len);
+        }
+
+        functions
+    }
+
+    fn function(code: &Code, name: &str) -> Function {
+        functions(code)
+            .into_iter()
+            .find(|fun| fun.name == name)
+            .unwrap()
+    }
+
+    /// Executes the function with the given name. The wasm test
+    /// functions check their results with `unreachable`, which
+    /// fails the execution.
+    fn execute(code: &Code, name: &str, argv: &[VmValue]) -> Result<Gas, VmError> {
+        let functions = functions(code);
+        let fun_idx = functions.iter().position(|fun| fun.name == name).unwrap();
+        let module = Module {
+            module_hash: Hash::NULL,
+            functions,
+            imports: vec![],
+        };
+
+        let mut vm = Vm::new();
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        vm.load(module).unwrap();
+        vm.execute(
+            &mut trie,
+            0,
+            fun_idx,
+            argv,
+            Gas::from_bytes(b"1000000000.0").unwrap(),
+        )
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_translates_arithmetic() {
+        // (func (export "sub") (param i32 i32) (result i32)
+        //   local.get 0 local.get 1 i32.sub)
+        let module = module(
+            &[&[0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f]],
+            &[],
+            &[&[0x00]],
+            &[&[0x03, b's', b'u', b'b', 0x00, 0x00]],
+            &[&[0x00, 0x20, 0x00, 0x20, 0x01, 0x6b, 0x0b]],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+        let fun = function(&code, "sub");
+        let mut bitmask: u8 = 0;
+        let mut first_popped: u8 = 0;
+
+        bitmask.set(0, true);
+        bitmask.set(1, true);
+        first_popped.set(0, true);
+
+        // The parameters are kept in the cells following the reserved cells
+        assert_eq!(RESERVED_CELLS, 0x0103);
+        assert_eq!(fun.arity, 2);
+        assert_eq!(fun.block, vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::PushOperand.repr(),  // Move the second argument to its cell
+            0x01,
+            first_popped,
+            Instruction::i32Const.repr(),
+            Instruction::PopLocal.repr(),
+            Instruction::i32Store.repr(),
+            0x01,
+            0x04,
+            Instruction::PushOperand.repr(),  // Move the first argument to its cell
+            0x01,
+            first_popped,
+            Instruction::i32Const.repr(),
+            Instruction::PopLocal.repr(),
+            Instruction::i32Store.repr(),
+            0x01,
+            0x03,
+            Instruction::i32Load.repr(),      // local.get 0
+            0x01,
+            0x03,
+            Instruction::PeekOperand.repr(),
+            Instruction::i32Store.repr(),
+            0x01,
+            0x03,
+            Instruction::i32Load.repr(),      // local.get 1
+            0x01,
+            0x04,
+            Instruction::PeekOperand.repr(),
+            Instruction::i32Store.repr(),
+            0x01,
+            0x04,
+            Instruction::PickLocal.repr(),    // Copy first operand on top
+            0x00,
+            0x00,
+            Instruction::PushOperand.repr(),
+            0x02,
+            bitmask,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            Instruction::PopLocal.repr(),
+            Instruction::PopLocal.repr(),
+            Instruction::PopLocal.repr(),     // Drop original first operand
+            Instruction::SubWrapping.repr(),
+            Instruction::PushLocal.repr(),
+            0x01,
+            first_popped,
+            Instruction::i32Const.repr(),
+            Instruction::PopOperand.repr(),
+            Instruction::Return.repr(),
+            0x01,
+            Instruction::End.repr(),
+        ]);
+
+        assert!(execute(&code, "sub", &[VmValue::I32(10), VmValue::I32(3)]).is_ok());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_wraps_integer_arithmetic_like_wasm() {
+        // (func (export "main")
+        //   i32.const 0x7fffffff i32.const 1 i32.add
+        //   i32.const 0x80000000 i32.ne if unreachable end
+        //   i32.const -1 i32.const 2 i32.div_u
+        //   i32.const 0x7fffffff i32.ne if unreachable end
+        //   i32.const -1 i32.const 1 i32.shr_u
+        //   i32.const 0x7fffffff i32.ne if unreachable end
+        //   i32.const 1 i32.const 33 i32.shl
+        //   i32.const 2 i32.ne if unreachable end
+        //   i32.const 1 i32.const -1 i32.lt_u i32.eqz if unreachable end
+        //   i64.const -1 i32.wrap_i64 i32.const -1 i32.ne if unreachable end
+        //   i32.const -1 i64.extend_i32_u
+        //   i64.const 0xffffffff i64.ne if unreachable end
+        //   i64.const 0x8000000000000000 i64.const -1 i64.rem_s
+        //   i64.const 0 i64.ne if unreachable end)
+        let body: &[u8] = &[
+            0x00,
+            0x41, 0xff, 0xff, 0xff, 0xff, 0x07, 0x41, 0x01, 0x6a,
+            0x41, 0x80, 0x80, 0x80, 0x80, 0x78, 0x47, 0x04, 0x40, 0x00, 0x0b,
+            0x41, 0x7f, 0x41, 0x02, 0x6e,
+            0x41, 0xff, 0xff, 0xff, 0xff, 0x07, 0x47, 0x04, 0x40, 0x00, 0x0b,
+            0x41, 0x7f, 0x41, 0x01, 0x76,
+            0x41, 0xff, 0xff, 0xff, 0xff, 0x07, 0x47, 0x04, 0x40, 0x00, 0x0b,
+            0x41, 0x01, 0x41, 0x21, 0x74,
+            0x41, 0x02, 0x47, 0x04, 0x40, 0x00, 0x0b,
+            0x41, 0x01, 0x41, 0x7f, 0x49, 0x45, 0x04, 0x40, 0x00, 0x0b,
+            0x42, 0x7f, 0xa7, 0x41, 0x7f, 0x47, 0x04, 0x40, 0x00, 0x0b,
+            0x41, 0x7f, 0xad,
+            0x42, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x52, 0x04, 0x40, 0x00, 0x0b,
+            0x42, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f, 0x42, 0x7f, 0x81,
+            0x42, 0x00, 0x52, 0x04, 0x40, 0x00, 0x0b,
+            0x0b,
+        ];
+        let module = module(
+            &[&[0x60, 0x00, 0x00]],
+            &[],
+            &[&[0x00]],
+            &[&[0x04, b'm', b'a', b'i', b'n', 0x00, 0x00]],
+            &[body],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+        assert!(execute(&code, "main", &[]).is_ok());
+    }
+
+    #[test]
+    fn it_translates_conditionals_and_calls() {
+        // (func $max (export "max") (param i64 i64) (result i64)
+        //   local.get 0 local.get 1 i64.gt_s
+        //   if local.get 0 return else local.get 1 return end
+        //   i64.const 0)
+        // (func (export "main") (result i64)
+        //   i64.const 5 i64.const -7 call $max)
+        let module = module(
+            &[
+                &[0x60, 0x02, 0x7e, 0x7e, 0x01, 0x7e],
+                &[0x60, 0x00, 0x01, 0x7e],
+            ],
+            &[],
+            &[&[0x00], &[0x01]],
+            &[
+                &[0x03, b'm', b'a', b'x', 0x00, 0x00],
+                &[0x04, b'm', b'a', b'i', b'n', 0x00, 0x01],
+            ],
+            &[
+                &[
+                    0x00, 0x20, 0x00, 0x20, 0x01, 0x55, 0x04, 0x40, 0x20, 0x00, 0x0f, 0x05, 0x20,
+                    0x01, 0x0f, 0x0b, 0x42, 0x00, 0x0b,
+                ],
+                &[0x00, 0x42, 0x05, 0x42, 0x79, 0x10, 0x00, 0x0b],
+            ],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+        let main = function(&code, "main");
+
+        assert!(main
+            .block
+            .windows(3)
+            .any(|w| w == [Instruction::Call.repr(), 0x00, 0x00]));
+        assert!(execute(&code, "main", &[]).is_ok());
+    }
+
+    #[test]
+    fn it_translates_loops_and_mutable_locals() {
+        // (func $fac (param i64) (result i64) (local i64)
+        //   i64.const 1 local.set 1
+        //   block
+        //     loop
+        //       local.get 0 i64.eqz br_if 1
+        //       local.get 1 local.get 0 i64.mul local.set 1
+        //       local.get 0 i64.const 1 i64.sub local.set 0
+        //       br 0
+        //     end
+        //   end
+        //   local.get 1)
+        // (func (export "main")
+        //   i64.const 5 call $fac i64.const 120 i64.ne if unreachable end)
+        let module = module(
+            &[&[0x60, 0x01, 0x7e, 0x01, 0x7e], &[0x60, 0x00, 0x00]],
+            &[],
+            &[&[0x00], &[0x01]],
+            &[&[0x04, b'm', b'a', b'i', b'n', 0x00, 0x01]],
+            &[
+                &[
+                    0x01, 0x01, 0x7e, 0x42, 0x01, 0x21, 0x01, 0x02, 0x40, 0x03, 0x40, 0x20, 0x00,
+                    0x50, 0x0d, 0x01, 0x20, 0x01, 0x20, 0x00, 0x7e, 0x21, 0x01, 0x20, 0x00, 0x42,
+                    0x01, 0x7d, 0x21, 0x00, 0x0c, 0x00, 0x0b, 0x0b, 0x20, 0x01, 0x0b,
+                ],
+                &[
+                    0x00, 0x42, 0x05, 0x10, 0x00, 0x42, 0xf8, 0x00, 0x52, 0x04, 0x40, 0x00, 0x0b,
+                    0x0b,
+                ],
+            ],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+        assert!(execute(&code, "main", &[]).is_ok());
+    }
+
+    #[test]
+    fn it_translates_recursive_calls() {
+        // (func $fib (param i32) (result i32)
+        //   local.get 0 i32.const 2 i32.lt_s
+        //   if (result i32)
+        //     local.get 0
+        //   else
+        //     local.get 0 i32.const 1 i32.sub call $fib
+        //     local.get 0 i32.const 2 i32.sub call $fib
+        //     i32.add
+        //   end)
+        // (func (export "main")
+        //   i32.const 10 call $fib i32.const 55 i32.ne if unreachable end)
+        let module = module(
+            &[&[0x60, 0x01, 0x7f, 0x01, 0x7f], &[0x60, 0x00, 0x00]],
+            &[],
+            &[&[0x00], &[0x01]],
+            &[&[0x04, b'm', b'a', b'i', b'n', 0x00, 0x01]],
+            &[
+                &[
+                    0x00, 0x20, 0x00, 0x41, 0x02, 0x48, 0x04, 0x7f, 0x20, 0x00, 0x05, 0x20, 0x00,
+                    0x41, 0x01, 0x6b, 0x10, 0x00, 0x20, 0x00, 0x41, 0x02, 0x6b, 0x10, 0x00, 0x6a,
+                    0x0b, 0x0b,
+                ],
+                &[
+                    0x00, 0x41, 0x0a, 0x10, 0x00, 0x41, 0x37, 0x47, 0x04, 0x40, 0x00, 0x0b, 0x0b,
+                ],
+            ],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+        assert!(execute(&code, "main", &[]).is_ok());
+    }
+
+    #[test]
+    fn it_translates_branch_tables() {
+        // (func $classify (param i32) (result i32)
+        //   block block block
+        //     local.get 0 br_table 0 1 2
+        //   end i32.const 10 return
+        //   end i32.const 20 return
+        //   end i32.const 30)
+        // (func (export "main")
+        //   i32.const 0 call $classify i32.const 10 i32.ne if unreachable end
+        //   i32.const 1 call $classify i32.const 20 i32.ne if unreachable end
+        //   i32.const 5 call $classify i32.const 30 i32.ne if unreachable end)
+        let module = module(
+            &[&[0x60, 0x01, 0x7f, 0x01, 0x7f], &[0x60, 0x00, 0x00]],
+            &[],
+            &[&[0x00], &[0x01]],
+            &[&[0x04, b'm', b'a', b'i', b'n', 0x00, 0x01]],
+            &[
+                &[
+                    0x00, 0x02, 0x40, 0x02, 0x40, 0x02, 0x40, 0x20, 0x00, 0x0e, 0x02, 0x00, 0x01,
+                    0x02, 0x0b, 0x41, 0x0a, 0x0f, 0x0b, 0x41, 0x14, 0x0f, 0x0b, 0x41, 0x1e, 0x0b,
+                ],
+                &[
+                    0x00, 0x41, 0x00, 0x10, 0x00, 0x41, 0x0a, 0x47, 0x04, 0x40, 0x00, 0x0b, 0x41,
+                    0x01, 0x10, 0x00, 0x41, 0x14, 0x47, 0x04, 0x40, 0x00, 0x0b, 0x41, 0x05, 0x10,
+                    0x00, 0x41, 0x1e, 0x47, 0x04, 0x40, 0x00, 0x0b, 0x0b,
+                ],
+            ],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+        assert!(execute(&code, "main", &[]).is_ok());
+    }
+
+    #[test]
+    fn it_translates_select() {
+        // (func $max (param i64 i64) (result i64)
+        //   local.get 0 local.get 1 local.get 0 local.get 1 i64.gt_s select)
+        // (func (export "main")
+        //   i64.const 5 i64.const -7 call $max i64.const 5 i64.ne if unreachable end
+        //   i64.const -7 i64.const 5 call $max i64.const 5 i64.ne if unreachable end)
+        let module = module(
+            &[&[0x60, 0x02, 0x7e, 0x7e, 0x01, 0x7e], &[0x60, 0x00, 0x00]],
+            &[],
+            &[&[0x00], &[0x01]],
+            &[&[0x04, b'm', b'a', b'i', b'n', 0x00, 0x01]],
+            &[
+                &[
+                    0x00, 0x20, 0x00, 0x20, 0x01, 0x20, 0x00, 0x20, 0x01, 0x55, 0x1b, 0x0b,
+                ],
+                &[
+                    0x00, 0x42, 0x05, 0x42, 0x79, 0x10, 0x00, 0x42, 0x05, 0x52, 0x04, 0x40, 0x00,
+                    0x0b, 0x42, 0x79, 0x42, 0x05, 0x10, 0x00, 0x42, 0x05, 0x52, 0x04, 0x40, 0x00,
+                    0x0b, 0x0b,
+                ],
+            ],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+        assert!(execute(&code, "main", &[]).is_ok());
+    }
+
+    #[test]
+    fn it_translates_memory_globals_and_start() {
+        // (memory 1)
+        // (global (mut i32) (i32.const 5))
+        // (data (i32.const 16) "\01\02\03\04\05\06\07\08\09")
+        // (func $start global.get 0 i32.const 2 i32.mul global.set 0)
+        // (func (export "main")
+        //   global.get 0 i32.const 10 i32.ne if unreachable end
+        //   i32.const 16 i32.load i32.const 0x04030201 i32.ne if unreachable end
+        //   i32.const 0 i32.load8_u offset=24 i32.const 9 i32.ne if unreachable end
+        //   i32.const 100 i64.const -3 i64.store
+        //   i32.const 100 i64.load i64.const -3 i64.ne if unreachable end
+        //   memory.size i32.const 1 i32.ne if unreachable end)
+        // (start $start)
+        let module = sections(&[
+            (1, vec_of(&[&[0x60, 0x00, 0x00]])),
+            (3, vec_of(&[&[0x00], &[0x00]])),
+            (5, vec_of(&[&[0x00, 0x01]])),
+            (6, vec_of(&[&[0x7f, 0x01, 0x41, 0x05, 0x0b]])),
+            (7, vec_of(&[&[0x04, b'm', b'a', b'i', b'n', 0x00, 0x01]])),
+            (8, vec![0x00]),
+            (
+                10,
+                code(&[
+                    &[0x00, 0x23, 0x00, 0x41, 0x02, 0x6c, 0x24, 0x00, 0x0b],
+                    &[
+                        0x00, 0x23, 0x00, 0x41, 0x0a, 0x47, 0x04, 0x40, 0x00, 0x0b, 0x41, 0x10,
+                        0x28, 0x02, 0x00, 0x41, 0x81, 0x84, 0x8c, 0x20, 0x47, 0x04, 0x40, 0x00,
+                        0x0b, 0x41, 0x00, 0x2d, 0x00, 0x18, 0x41, 0x09, 0x47, 0x04, 0x40, 0x00,
+                        0x0b, 0x41, 0xe4, 0x00, 0x42, 0x7d, 0x37, 0x03, 0x00, 0x41, 0xe4, 0x00,
+                        0x29, 0x03, 0x00, 0x42, 0x7d, 0x52, 0x04, 0x40, 0x00, 0x0b, 0x3f, 0x00,
+                        0x41, 0x01, 0x47, 0x04, 0x40, 0x00, 0x0b, 0x0b,
+                    ],
+                ]),
+            ),
+            (
+                11,
+                vec_of(&[&[
+                    0x00, 0x41, 0x10, 0x0b, 0x09, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+                    0x09,
+                ]]),
+            ),
+        ]);
+
+        let code = translate_wasm(&module).unwrap();
+
+        // The export initializes the module before calling the translated function
+        assert_eq!(function(&code, "f1").arity, 0);
+        assert!(execute(&code, "main", &[]).is_ok());
+    }
+
+    #[test]
+    fn it_translates_indirect_calls() {
+        // (table 3 funcref)
+        // (elem (i32.const 0) $double $square)
+        // (func $double (param i32) (result i32) local.get 0 i32.const 2 i32.mul)
+        // (func $square (param i32) (result i32) local.get 0 local.get 0 i32.mul)
+        // (func (export "main")
+        //   i32.const 7 i32.const 0 call_indirect (type 0) i32.const 14 i32.ne if unreachable end
+        //   i32.const 7 i32.const 1 call_indirect (type 0) i32.const 49 i32.ne if unreachable end)
+        // (func (export "missing") i32.const 7 i32.const 2 call_indirect (type 0) drop)
+        let module = sections(&[
+            (
+                1,
+                vec_of(&[&[0x60, 0x01, 0x7f, 0x01, 0x7f], &[0x60, 0x00, 0x00]]),
+            ),
+            (3, vec_of(&[&[0x00], &[0x00], &[0x01], &[0x01]])),
+            (4, vec_of(&[&[0x70, 0x00, 0x03]])),
+            (
+                7,
+                vec_of(&[
+                    &[0x04, b'm', b'a', b'i', b'n', 0x00, 0x02],
+                    &[0x07, b'm', b'i', b's', b's', b'i', b'n', b'g', 0x00, 0x03],
+                ]),
+            ),
+            (9, vec_of(&[&[0x00, 0x41, 0x00, 0x0b, 0x02, 0x00, 0x01]])),
+            (
+                10,
+                code(&[
+                    &[0x00, 0x20, 0x00, 0x41, 0x02, 0x6c, 0x0b],
+                    &[0x00, 0x20, 0x00, 0x20, 0x00, 0x6c, 0x0b],
+                    &[
+                        0x00, 0x41, 0x07, 0x41, 0x00, 0x11, 0x00, 0x00, 0x41, 0x0e, 0x47, 0x04,
+                        0x40, 0x00, 0x0b, 0x41, 0x07, 0x41, 0x01, 0x11, 0x00, 0x00, 0x41, 0x31,
+                        0x47, 0x04, 0x40, 0x00, 0x0b, 0x0b,
+                    ],
+                    &[0x00, 0x41, 0x07, 0x41, 0x02, 0x11, 0x00, 0x00, 0x1a, 0x0b],
+                ]),
+            ),
+        ]);
+
+        let code = translate_wasm(&module).unwrap();
+
+        assert!(execute(&code, "main", &[]).is_ok());
+        assert_eq!(
+            execute(&code, "missing", &[]),
+            Err(VmError::AssertionFailed)
+        );
+    }
+
+    #[test]
+    fn it_maps_imports_to_api_instructions() {
+        // (import "purple" "caller_address" (func))
+        // (func (export "main") call 0)
+        let import = [
+            &[0x06][..],
+            b"purple",
+            &[0x0e],
+            b"caller_address",
+            &[0x00, 0x00],
+        ]
+        .concat();
+
+        let module = module(
+            &[&[0x60, 0x00, 0x00]],
+            &[&import],
+            &[&[0x00]],
+            &[&[0x04, b'm', b'a', b'i', b'n', 0x00, 0x01]],
+            &[&[0x00, 0x10, 0x00, 0x0b]],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+
+        assert_eq!(
+            function(&code, "main").block,
+            vec![
+                Instruction::Begin.repr(),
+                0x00,
+                Instruction::CallerAddress.repr(),
+                Instruction::Return.repr(),
+                0x00,
+                Instruction::End.repr(),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_traps_on_unreachable() {
+        // (func (export "main") unreachable)
+        let module = module(
+            &[&[0x60, 0x00, 0x00]],
+            &[],
+            &[&[0x00]],
+            &[&[0x04, b'm', b'a', b'i', b'n', 0x00, 0x00]],
+            &[&[0x00, 0x00, 0x0b]],
+        );
+
+        let code = translate_wasm(&module).unwrap();
+        assert_eq!(execute(&code, "main", &[]), Err(VmError::AssertionFailed));
+    }
+
+    #[test]
+    fn it_rejects_floats() {
+        // (func (result f32) f32.const 0)
+        let module = module(
+            &[&[0x60, 0x00, 0x01, 0x7d]],
+            &[],
+            &[&[0x00]],
+            &[],
+            &[&[0x00, 0x43, 0x00, 0x00, 0x00, 0x00, 0x0b]],
+        );
+
+        assert_eq!(
+            translate_wasm(&module).err(),
+            Some("Floating point types are not allowed")
+        );
+    }
+
+    #[test]
+    fn it_rejects_unsupported_instructions() {
+        // (func (param i32) (result i32) local.get 0 i32.clz)
+        let clz = module(
+            &[&[0x60, 0x01, 0x7f, 0x01, 0x7f]],
+            &[],
+            &[&[0x00]],
+            &[],
+            &[&[0x00, 0x20, 0x00, 0x67, 0x0b]],
+        );
+
+        assert_eq!(
+            translate_wasm(&clz).err(),
+            Some("Unsupported wasm instruction")
+        );
+
+        // (func (param i32) (result i32) local.get 0 i32.load)
+        let load = module(
+            &[&[0x60, 0x01, 0x7f, 0x01, 0x7f]],
+            &[],
+            &[&[0x00]],
+            &[],
+            &[&[0x00, 0x20, 0x00, 0x28, 0x02, 0x00, 0x0b]],
+        );
+
+        assert_eq!(
+            translate_wasm(&load).err(),
+            Some("The module does not define a memory")
+        );
+        assert!(translate_wasm(b"\0asm\x02\x00\x00\x00").is_err());
+    }
+}
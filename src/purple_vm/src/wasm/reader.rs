@@ -0,0 +1,149 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::str;
+
+/// Reader over the binary encoding of a WebAssembly module.
+#[derive(Clone)]
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Reader<'a> {
+        Reader { buf, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    pub fn peek_u8(&self) -> Result<u8, &'static str> {
+        match self.buf.get(self.pos) {
+            Some(byte) => Ok(*byte),
+            None => Err("Unexpected end of wasm module"),
+        }
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, &'static str> {
+        let byte = self.peek_u8()?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if self.buf.len() - self.pos < len {
+            return Err("Unexpected end of wasm module");
+        }
+
+        let result = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(result)
+    }
+
+    /// Reads an unsigned LEB128 encoded 32 bit integer.
+    pub fn read_var_u32(&mut self) -> Result<u32, &'static str> {
+        let mut result: u64 = 0;
+
+        for i in 0..5 {
+            let byte = self.read_u8()?;
+            result |= ((byte & 0x7f) as u64) << (i * 7);
+
+            if byte & 0x80 == 0 {
+                if result > u32::max_value() as u64 {
+                    return Err("Invalid LEB128 integer");
+                }
+
+                return Ok(result as u32);
+            }
+        }
+
+        Err("Invalid LEB128 integer")
+    }
+
+    /// Reads a signed LEB128 encoded 32 bit integer.
+    pub fn read_var_i32(&mut self) -> Result<i32, &'static str> {
+        let result = self.read_var_signed(5)?;
+
+        if result < i32::min_value() as i64 || result > i32::max_value() as i64 {
+            return Err("Invalid LEB128 integer");
+        }
+
+        Ok(result as i32)
+    }
+
+    /// Reads a signed LEB128 encoded 64 bit integer.
+    pub fn read_var_i64(&mut self) -> Result<i64, &'static str> {
+        self.read_var_signed(10)
+    }
+
+    /// Reads a length prefixed utf8 string.
+    pub fn read_name(&mut self) -> Result<String, &'static str> {
+        let len = self.read_var_u32()? as usize;
+        let bytes = self.read_bytes(len)?;
+
+        match str::from_utf8(bytes) {
+            Ok(result) => Ok(result.to_owned()),
+            Err(_) => Err("Invalid utf8 name"),
+        }
+    }
+
+    fn read_var_signed(&mut self, max_bytes: usize) -> Result<i64, &'static str> {
+        let mut result: i64 = 0;
+        let mut shift = 0;
+
+        for _ in 0..max_bytes {
+            let byte = self.read_u8()?;
+            result |= ((byte & 0x7f) as i64) << shift;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                // Sign extend
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+
+                return Ok(result);
+            }
+        }
+
+        Err("Invalid LEB128 integer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_leb128_integers() {
+        let mut reader = Reader::new(&[0xe5, 0x8e, 0x26, 0x7f, 0xc0, 0xbb, 0x78]);
+
+        assert_eq!(reader.read_var_u32().unwrap(), 624485);
+        assert_eq!(reader.read_var_i32().unwrap(), -1);
+        assert_eq!(reader.read_var_i64().unwrap(), -123456);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn it_rejects_overlong_integers() {
+        let mut reader = Reader::new(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert!(reader.read_var_u32().is_err());
+    }
+}
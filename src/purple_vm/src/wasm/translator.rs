@@ -0,0 +1,1756 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::instruction_set::Instruction;
+use crate::primitives::r#type::VmType;
+use crate::wasm::reader::Reader;
+use bitvec::Bits;
+use hashbrown::HashSet;
+
+/// The heap cell holding the number of frames that are left to be
+/// exited by a branch. It is always 0 outside of a branch.
+const BRANCH_CELL: usize = 0;
+
+/// The heap cell passing the result of a block or of a call.
+const RESULT_CELL: usize = 1;
+
+/// Heap cells holding intermediate values of a single instruction.
+const TEMP_CELL: usize = 2;
+const AUX_CELL: usize = 3;
+
+/// The first of the heap cells passing the arguments of calls
+/// which save the locals of the calling function.
+const ARGUMENT_CELLS: usize = 4;
+
+/// The number of heap cells used by the translated code itself.
+/// The cells of the globals and of the function locals follow.
+pub const RESERVED_CELLS: usize = ARGUMENT_CELLS + 255;
+
+/// The number of heap cells available to the translated code.
+pub const MAX_CELLS: usize = 256 * 256;
+
+/// Integer binary operators in the order of their wasm opcodes,
+/// starting from `i32.add` (0x6a) and `i64.add` (0x7c). Signed
+/// division is the only one that can still abort on overflow,
+/// which also traps in wasm.
+const BINARY_OPS: &'static [Instruction] = &[
+    Instruction::AddWrapping,
+    Instruction::SubWrapping,
+    Instruction::MulWrapping,
+    Instruction::DivSigned,
+    Instruction::DivUnsignedWrapping,
+    Instruction::RemSignedWrapping,
+    Instruction::RemUnsignedWrapping,
+    Instruction::And,
+    Instruction::Or,
+    Instruction::Xor,
+    Instruction::ShlWrapping,
+    Instruction::ShrSignedWrapping,
+    Instruction::ShrUnsignedWrapping,
+    Instruction::Rotl,
+    Instruction::Rotr,
+];
+
+/// Integer comparison operators in the order of their wasm opcodes,
+/// starting from `i32.eq` (0x46) and `i64.eq` (0x51).
+const COMPARISON_OPS: &'static [Instruction] = &[
+    Instruction::Eq,
+    Instruction::Ne,
+    Instruction::LtSigned,
+    Instruction::LtUnsigned,
+    Instruction::GtSigned,
+    Instruction::GtUnsigned,
+    Instruction::LeSigned,
+    Instruction::LeUnsigned,
+    Instruction::GeSigned,
+    Instruction::GeUnsigned,
+];
+
+/// Linear memory loads in the order of their wasm opcodes, starting
+/// from `i32.load` (0x28). Floating point loads are `None`.
+const MEMORY_LOADS: &'static [Option<(Instruction, VmType)>] = &[
+    Some((Instruction::i32Load, VmType::I32)),
+    Some((Instruction::i64Load, VmType::I64)),
+    None,
+    None,
+    Some((Instruction::i32Load8Signed, VmType::I32)),
+    Some((Instruction::i32Load8Unsigned, VmType::I32)),
+    Some((Instruction::i32Load16Signed, VmType::I32)),
+    Some((Instruction::i32Load16Unsigned, VmType::I32)),
+    Some((Instruction::i64Load8Signed, VmType::I64)),
+    Some((Instruction::i64Load8Unsigned, VmType::I64)),
+    Some((Instruction::i64Load16Signed, VmType::I64)),
+    Some((Instruction::i64Load16Unsigned, VmType::I64)),
+    Some((Instruction::i64Load32Signed, VmType::I64)),
+    Some((Instruction::i64Load32Unsigned, VmType::I64)),
+];
+
+/// Linear memory stores in the order of their wasm opcodes, starting
+/// from `i32.store` (0x36). Floating point stores are `None`.
+const MEMORY_STORES: &'static [Option<(Instruction, VmType)>] = &[
+    Some((Instruction::i32Store, VmType::I32)),
+    Some((Instruction::i64Store, VmType::I64)),
+    None,
+    None,
+    Some((Instruction::i32Store8, VmType::I32)),
+    Some((Instruction::i32Store16, VmType::I32)),
+    Some((Instruction::i64Store8, VmType::I64)),
+    Some((Instruction::i64Store16, VmType::I64)),
+    Some((Instruction::i64Store32, VmType::I64)),
+];
+
+/// The signature of a wasm function.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuncType {
+    pub params: Vec<VmType>,
+    pub result: Option<VmType>,
+}
+
+/// The target of a wasm `call` instruction.
+#[derive(Clone, Debug)]
+pub enum Callee {
+    /// An imported function that is mapped to a blockchain api instruction.
+    Api(Instruction),
+
+    /// A function defined in the module with its index in the
+    /// translated functions and its signature.
+    Function(u16, FuncType),
+}
+
+impl Callee {
+    pub fn func_type(&self) -> FuncType {
+        match self {
+            // Api instructions neither take arguments nor return values
+            Callee::Api(_) => FuncType {
+                params: vec![],
+                result: None,
+            },
+            Callee::Function(_, func_type) => func_type.clone(),
+        }
+    }
+}
+
+/// A global variable of a wasm module.
+#[derive(Clone, Debug)]
+pub struct Global {
+    pub value_type: VmType,
+    pub mutable: bool,
+
+    /// The initial value of the global.
+    pub value: i64,
+}
+
+/// A function defined in a wasm module.
+#[derive(Clone, Debug)]
+pub struct FunctionInfo {
+    pub func_type: FuncType,
+
+    /// The types of the parameters followed by the declared locals.
+    pub locals: Vec<VmType>,
+
+    /// The heap cell of the first parameter. The other parameters
+    /// and the declared locals are kept in the following cells.
+    pub first_cell: usize,
+
+    /// The indices of the functions that this function
+    /// can end up calling, directly or not.
+    pub reachable: HashSet<u16>,
+}
+
+/// The parts of a wasm module that its functions depend on.
+#[derive(Clone, Debug)]
+pub struct ModuleInfo {
+    pub types: Vec<FuncType>,
+
+    /// The targets of `call` instructions by function index.
+    pub callees: Vec<Callee>,
+
+    /// The functions defined in the module.
+    pub functions: Vec<FunctionInfo>,
+
+    /// The globals, which are kept in the heap cells
+    /// following the reserved cells.
+    pub globals: Vec<Global>,
+
+    /// The function indices of the elements of the table, if any.
+    pub table: Option<Vec<Option<u32>>>,
+
+    pub has_memory: bool,
+}
+
+/// The declared locals of a wasm function and the functions it calls.
+pub struct FunctionScan {
+    pub locals: Vec<VmType>,
+
+    /// The indices of the functions called by `call` instructions.
+    pub calls: Vec<u32>,
+
+    /// The type indices of `call_indirect` instructions.
+    pub indirect_calls: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FrameKind {
+    /// The frame of the function.
+    Function,
+
+    /// The frame of a `block`.
+    Block,
+
+    /// The frame of the body of a `loop`. Branching to the loop exits
+    /// this frame, after which the enclosing `Restart` frame is ended
+    /// and thus entered again.
+    Loop,
+
+    /// The frame enclosing the body of a `loop`.
+    Restart,
+
+    /// The frame of an `if` and of its `else` part.
+    If,
+
+    /// The frame of the `then` part of an `if` with an `else` part.
+    Then,
+
+    /// A frame used by a single instruction.
+    Inner,
+}
+
+/// Model of a frame of the translated code. Apart from the frame
+/// of the function, all frames are `Loop` frames that are only left
+/// with `Break`, `BreakIf` or `Return`.
+struct Frame {
+    kind: FrameKind,
+
+    /// The result type of the wasm block.
+    result: Option<VmType>,
+
+    /// The types of the wasm values on the locals stack of the frame.
+    values: Vec<VmType>,
+
+    /// Whether the rest of the frame is unreachable.
+    unreachable: bool,
+
+    /// Whether a branch can exit the frame and more frames
+    /// after it, in which case the code after the frame
+    /// continues the branch.
+    propagates: bool,
+}
+
+/// Reads the declared locals of a wasm function body and
+/// collects the functions that it calls.
+pub fn scan_function(body: &[u8]) -> Result<FunctionScan, &'static str> {
+    let mut reader = Reader::new(body);
+    let locals = read_locals(&mut reader)?;
+    let mut calls = Vec::new();
+    let mut indirect_calls = Vec::new();
+
+    while !reader.is_empty() {
+        match reader.read_u8()? {
+            // call
+            0x10 => calls.push(reader.read_var_u32()?),
+
+            // call_indirect
+            0x11 => {
+                indirect_calls.push(reader.read_var_u32()?);
+                reader.read_u8()?;
+            }
+
+            op => skip_immediates(op, &mut reader)?,
+        }
+    }
+
+    Ok(FunctionScan {
+        locals,
+        calls,
+        indirect_calls,
+    })
+}
+
+/// Translates the body of the wasm function with the given index
+/// into a block of code that receives the same arguments.
+pub fn translate_function(
+    body: &[u8],
+    idx: usize,
+    module: &ModuleInfo,
+) -> Result<Vec<u8>, &'static str> {
+    let info = &module.functions[idx];
+    let mut reader = Reader::new(body);
+    let mut translator = Translator {
+        code: Vec::new(),
+        frames: vec![Frame {
+            kind: FrameKind::Function,
+            result: info.func_type.result,
+            values: Vec::new(),
+            unreachable: false,
+            propagates: false,
+        }],
+        module,
+        idx,
+        uses_branch_cell: false,
+    };
+
+    // The locals are already known
+    read_locals(&mut reader)?;
+    translator.translate(&mut reader)?;
+
+    if !reader.is_empty() {
+        return Err("Unexpected bytes after the end of the function");
+    }
+
+    // The arguments are moved to the cells of the parameters
+    // and the declared locals are zero initialized.
+    let mut block = vec![Instruction::Begin.repr(), 0x00];
+    let params_len = info.func_type.params.len();
+
+    for (i, param) in info.func_type.params.iter().enumerate().rev() {
+        block.extend_from_slice(&pop_local(*param));
+        block.extend_from_slice(&heap_store(*param, info.first_cell + i));
+    }
+
+    for (i, local_type) in info.locals.iter().enumerate().skip(params_len) {
+        block.extend_from_slice(&push_zero(*local_type));
+        block.extend_from_slice(&heap_store(*local_type, info.first_cell + i));
+    }
+
+    if translator.uses_branch_cell {
+        block.extend_from_slice(&push_zero(VmType::I32));
+        block.extend_from_slice(&heap_store(VmType::I32, BRANCH_CELL));
+    }
+
+    block.extend_from_slice(&translator.code);
+    Ok(block)
+}
+
+/// Builds the block of the function which is called in place of
+/// an exported function. It initializes the globals and the linear
+/// memory, calls the start function and then the exported function.
+pub fn entry_function(
+    module: &ModuleInfo,
+    memory_pages: u32,
+    data: &[(u32, &[u8])],
+    start: Option<u16>,
+    fun_idx: u16,
+) -> Vec<u8> {
+    let func_type = &module.functions[fun_idx as usize].func_type;
+    let mut block = vec![Instruction::Begin.repr(), 0x00];
+
+    for (i, global) in module.globals.iter().enumerate() {
+        let bytes = match global.value_type {
+            VmType::I32 => encode_be_i32!(global.value as i32),
+            _ => encode_be_i64!(global.value),
+        };
+
+        block.extend_from_slice(&push_const(global.value_type, &bytes));
+        block.extend_from_slice(&heap_store(global.value_type, RESERVED_CELLS + i));
+    }
+
+    if memory_pages > 0 {
+        block.extend_from_slice(&push_const(VmType::I32, &encode_be_u32!(memory_pages)));
+        block.extend_from_slice(&[
+            Instruction::MemoryGrow.repr(),
+            Instruction::PopOperand.repr(),
+        ]);
+    }
+
+    // Data segments are written in chunks of 8 bytes
+    for (offset, bytes) in data {
+        for (i, chunk) in bytes.chunks(8).enumerate() {
+            let address = offset + 8 * i as u32;
+
+            if chunk.len() == 8 {
+                let mut buf = [0; 8];
+                buf.copy_from_slice(chunk);
+
+                let value = i64::from_le_bytes(buf);
+
+                block.extend_from_slice(&[
+                    Instruction::PushOperand.repr(),
+                    0x02,
+                    0x00,
+                    Instruction::i32Const.repr(),
+                    Instruction::i64Const.repr(),
+                ]);
+                block.extend_from_slice(&encode_be_u32!(address));
+                block.extend_from_slice(&encode_be_i64!(value));
+                block.extend_from_slice(&memory_access(
+                    Instruction::MemoryStore,
+                    Instruction::i64Store,
+                    0,
+                ));
+            } else {
+                for (j, byte) in chunk.iter().enumerate() {
+                    block.extend_from_slice(&[
+                        Instruction::PushOperand.repr(),
+                        0x02,
+                        0x00,
+                        Instruction::i32Const.repr(),
+                        Instruction::i32Const.repr(),
+                    ]);
+                    block.extend_from_slice(&encode_be_u32!(address + j as u32));
+                    block.extend_from_slice(&encode_be_i32!(*byte as i32));
+                    block.extend_from_slice(&memory_access(
+                        Instruction::MemoryStore,
+                        Instruction::i32Store8,
+                        0,
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(start) = start {
+        block.push(Instruction::Call.repr());
+        block.extend_from_slice(&encode_be_u16!(start));
+    }
+
+    // The arguments are passed on from the locals stack
+    block.push(Instruction::Call.repr());
+    block.extend_from_slice(&encode_be_u16!(fun_idx));
+    block.extend_from_slice(&[
+        Instruction::Return.repr(),
+        func_type.result.is_some() as u8,
+        Instruction::End.repr(),
+    ]);
+
+    block
+}
+
+/// Reads a wasm value type. Floating point types are not
+/// allowed in order to keep execution deterministic.
+pub fn read_value_type(reader: &mut Reader) -> Result<VmType, &'static str> {
+    match reader.read_u8()? {
+        0x7f => Ok(VmType::I32),
+        0x7e => Ok(VmType::I64),
+        0x7d | 0x7c => Err("Floating point types are not allowed"),
+        _ => Err("Invalid value type"),
+    }
+}
+
+/// Reads a constant expression of the given type.
+pub fn read_const_expr(reader: &mut Reader, value_type: VmType) -> Result<i64, &'static str> {
+    let value = match (reader.read_u8()?, value_type) {
+        (0x41, VmType::I32) => reader.read_var_i32()? as i64,
+        (0x42, VmType::I64) => reader.read_var_i64()?,
+        _ => return Err("Unsupported constant expression"),
+    };
+
+    if reader.read_u8()? != 0x0b {
+        return Err("Unsupported constant expression");
+    }
+
+    Ok(value)
+}
+
+struct Translator<'a> {
+    code: Vec<u8>,
+    frames: Vec<Frame>,
+    module: &'a ModuleInfo,
+
+    /// The index of the translated function.
+    idx: usize,
+
+    /// Whether the branch cell is used, in which case
+    /// the function initializes it.
+    uses_branch_cell: bool,
+}
+
+impl<'a> Translator<'a> {
+    fn translate(&mut self, reader: &mut Reader) -> Result<(), &'static str> {
+        loop {
+            let op = reader.read_u8()?;
+
+            match op {
+                // unreachable
+                0x00 => {
+                    self.trap();
+                    self.skip(reader)?;
+                }
+
+                // nop
+                0x01 => {}
+
+                // block
+                0x02 => {
+                    let result = read_block_type(reader)?;
+                    self.begin(FrameKind::Block, result);
+                }
+
+                // loop
+                0x03 => {
+                    let result = read_block_type(reader)?;
+                    self.begin(FrameKind::Restart, None);
+                    self.begin(FrameKind::Loop, result);
+                }
+
+                // if
+                0x04 => {
+                    let result = read_block_type(reader)?;
+                    let has_else = has_else(reader)?;
+
+                    if result.is_some() && !has_else {
+                        return Err("An if with a result must have an else part");
+                    }
+
+                    self.pop_type(VmType::I32)?;
+                    self.push_condition();
+                    self.begin(FrameKind::If, result);
+
+                    if has_else {
+                        self.begin(FrameKind::Then, result);
+                    }
+
+                    // Leave the `then` part if the condition is 0
+                    self.code.extend_from_slice(&[
+                        Instruction::BreakIf.repr(),
+                        Instruction::Eq.repr(),
+                        Instruction::PopOperand.repr(),
+                        Instruction::PopOperand.repr(),
+                    ]);
+                }
+
+                // else
+                0x05 => {
+                    if self.frame().kind != FrameKind::Then {
+                        return Err("Unexpected else");
+                    }
+
+                    // Exit the `then` part and the `if`
+                    self.fall_through(2)?;
+                    self.close();
+                }
+
+                // end
+                0x0b => match self.frame().kind {
+                    FrameKind::Function => {
+                        if !self.frame().unreachable {
+                            self.emit_return()?;
+                        }
+
+                        self.code.push(Instruction::End.repr());
+                        return Ok(());
+                    }
+                    FrameKind::Block | FrameKind::If => {
+                        self.fall_through(1)?;
+
+                        let frame = self.close();
+                        self.push_result(frame.result);
+                    }
+                    FrameKind::Loop => {
+                        // Exit the body and the enclosing frame
+                        self.fall_through(2)?;
+                        self.close();
+
+                        let frame = self.close();
+                        self.push_result(frame.result);
+                    }
+                    _ => return Err("Missing else"),
+                },
+
+                // br
+                0x0c => {
+                    let depth = reader.read_var_u32()?;
+
+                    self.branch(depth)?;
+                    self.skip(reader)?;
+                }
+
+                // br_if
+                0x0d => {
+                    let depth = reader.read_var_u32()?;
+                    self.branch_if(depth)?;
+                }
+
+                // br_table
+                0x0e => {
+                    let count = reader.read_var_u32()?;
+                    let mut depths = Vec::new();
+
+                    for _ in 0..count {
+                        depths.push(reader.read_var_u32()?);
+                    }
+
+                    let default = reader.read_var_u32()?;
+
+                    self.branch_table(&depths, default)?;
+                    self.skip(reader)?;
+                }
+
+                // return
+                0x0f => {
+                    self.emit_return()?;
+                    self.skip(reader)?;
+                }
+
+                // call
+                0x10 => {
+                    let idx = reader.read_var_u32()? as usize;
+                    self.call(idx)?;
+                }
+
+                // call_indirect
+                0x11 => {
+                    let type_idx = reader.read_var_u32()? as usize;
+
+                    if reader.read_u8()? != 0x00 {
+                        return Err("Invalid table index");
+                    }
+
+                    self.call_indirect(type_idx)?;
+                }
+
+                // drop
+                0x1a => {
+                    self.pop()?;
+                    self.code.push(Instruction::PopLocal.repr());
+                }
+
+                // select
+                0x1b => self.select()?,
+
+                // select with the type of the operands
+                0x1c => {
+                    if reader.read_var_u32()? != 1 {
+                        return Err("Invalid select");
+                    }
+
+                    read_value_type(reader)?;
+                    self.select()?;
+                }
+
+                // local.get, local.set, local.tee
+                0x20 | 0x21 | 0x22 => {
+                    let idx = reader.read_var_u32()? as usize;
+                    let info = &self.module.functions[self.idx];
+
+                    let local_type = match info.locals.get(idx) {
+                        Some(local_type) => *local_type,
+                        None => return Err("Invalid local index"),
+                    };
+
+                    let cell = info.first_cell + idx;
+
+                    match op {
+                        0x20 => self.read_cell(local_type, cell),
+                        0x21 => self.store_cell(local_type, cell)?,
+                        _ => {
+                            if self.peek()? != local_type {
+                                return Err("Type mismatch");
+                            }
+
+                            // Copy the topmost value to the cell
+                            self.code.push(Instruction::PeekLocal.repr());
+                            self.code.extend_from_slice(&heap_store(local_type, cell));
+                        }
+                    }
+                }
+
+                // global.get, global.set
+                0x23 | 0x24 => {
+                    let idx = reader.read_var_u32()? as usize;
+
+                    let global = match self.module.globals.get(idx) {
+                        Some(global) => global.clone(),
+                        None => return Err("Invalid global index"),
+                    };
+
+                    let cell = RESERVED_CELLS + idx;
+
+                    if op == 0x23 {
+                        self.read_cell(global.value_type, cell);
+                    } else if global.mutable {
+                        self.store_cell(global.value_type, cell)?;
+                    } else {
+                        return Err("Cannot set an immutable global");
+                    }
+                }
+
+                // Floating point loads and stores
+                0x2a | 0x2b | 0x38 | 0x39 => {
+                    return Err("Floating point instructions are not allowed");
+                }
+
+                // Loads
+                0x28..=0x35 => {
+                    let (load_op, value_type) = MEMORY_LOADS[(op - 0x28) as usize].unwrap();
+                    let offset = self.read_memarg(reader)?;
+
+                    self.load_operands(&[VmType::I32])?;
+                    self.code.extend_from_slice(&memory_access(
+                        Instruction::MemoryLoad,
+                        load_op,
+                        offset,
+                    ));
+                    self.store_operand(value_type);
+                }
+
+                // Stores
+                0x36..=0x3e => {
+                    let (store_op, value_type) = MEMORY_STORES[(op - 0x36) as usize].unwrap();
+                    let offset = self.read_memarg(reader)?;
+
+                    self.load_operands(&[VmType::I32, value_type])?;
+                    self.code.extend_from_slice(&memory_access(
+                        Instruction::MemoryStore,
+                        store_op,
+                        offset,
+                    ));
+                }
+
+                // memory.size
+                0x3f => {
+                    self.read_memory_idx(reader)?;
+                    self.code.push(Instruction::MemorySize.repr());
+                    self.store_operand(VmType::I32);
+                }
+
+                // memory.grow
+                0x40 => {
+                    self.read_memory_idx(reader)?;
+                    self.load_operands(&[VmType::I32])?;
+                    self.code.push(Instruction::MemoryGrow.repr());
+                    self.store_operand(VmType::I32);
+                }
+
+                // i32.const
+                0x41 => {
+                    let value = reader.read_var_i32()?;
+                    self.push_value(VmType::I32, &encode_be_i32!(value));
+                }
+
+                // i64.const
+                0x42 => {
+                    let value = reader.read_var_i64()?;
+                    self.push_value(VmType::I64, &encode_be_i64!(value));
+                }
+
+                // i32.eqz, i64.eqz
+                0x45 | 0x50 => {
+                    let operand_type = if op == 0x45 { VmType::I32 } else { VmType::I64 };
+                    self.compare(operand_type, Instruction::Eq, true)?;
+                }
+
+                // i32 comparisons
+                0x46..=0x4f => {
+                    self.compare(VmType::I32, COMPARISON_OPS[(op - 0x46) as usize], false)?;
+                }
+
+                // i64 comparisons
+                0x51..=0x5a => {
+                    self.compare(VmType::I64, COMPARISON_OPS[(op - 0x51) as usize], false)?;
+                }
+
+                // i32 binary operators
+                0x6a..=0x78 => self.binary_op(VmType::I32, BINARY_OPS[(op - 0x6a) as usize])?,
+
+                // i64 binary operators
+                0x7c..=0x8a => self.binary_op(VmType::I64, BINARY_OPS[(op - 0x7c) as usize])?,
+
+                // i32.wrap_i64
+                0xa7 => {
+                    // Sign extend the low 32 bits so the value fits in an i32
+                    self.push_value(VmType::I64, &encode_be_i64!(32));
+                    self.binary_op(VmType::I64, Instruction::ShlWrapping)?;
+                    self.push_value(VmType::I64, &encode_be_i64!(32));
+                    self.binary_op(VmType::I64, Instruction::ShrSignedWrapping)?;
+                    self.convert(VmType::I64, VmType::I32, Instruction::i64Wrapi32)?;
+                }
+
+                // i64.extend_i32_s
+                0xac => self.convert(VmType::I32, VmType::I64, Instruction::i32ExtendSignedi64)?,
+
+                // i64.extend_i32_u
+                0xad => {
+                    // Clear the bits set by the sign extension of negative values
+                    self.convert(VmType::I32, VmType::I64, Instruction::i32ExtendSignedi64)?;
+                    self.push_value(VmType::I64, &encode_be_i64!(u32::max_value() as i64));
+                    self.binary_op(VmType::I64, Instruction::And)?;
+                }
+
+                // Floating point instructions
+                0x43 | 0x44 | 0x5b..=0x66 | 0x8b..=0xa6 | 0xa8..=0xab | 0xae..=0xbf => {
+                    return Err("Floating point instructions are not allowed");
+                }
+
+                _ => return Err("Unsupported wasm instruction"),
+            }
+        }
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn pop(&mut self) -> Result<VmType, &'static str> {
+        match self.frame().values.pop() {
+            Some(value_type) => Ok(value_type),
+            None => Err("Value stack underflow"),
+        }
+    }
+
+    fn peek(&mut self) -> Result<VmType, &'static str> {
+        match self.frame().values.last() {
+            Some(value_type) => Ok(*value_type),
+            None => Err("Value stack underflow"),
+        }
+    }
+
+    fn pop_type(&mut self, expected: VmType) -> Result<(), &'static str> {
+        if self.pop()? != expected {
+            return Err("Type mismatch");
+        }
+
+        Ok(())
+    }
+
+    /// Marks the rest of the current frame as unreachable and
+    /// skips it until the `else` or `end` instruction closing it.
+    fn skip(&mut self, reader: &mut Reader) -> Result<(), &'static str> {
+        self.frame().unreachable = true;
+        skip_unreachable(reader)
+    }
+
+    /// Begins a new `Loop` frame.
+    fn begin(&mut self, kind: FrameKind, result: Option<VmType>) {
+        self.code
+            .extend_from_slice(&[Instruction::Loop.repr(), 0x00]);
+        self.frames.push(Frame {
+            kind,
+            result,
+            values: Vec::new(),
+            unreachable: false,
+            propagates: false,
+        });
+    }
+
+    /// Ends the current frame. If a branch can exit more frames
+    /// after it, the branch is continued by exiting the enclosing
+    /// frame as well if the branch cell is not 0 and decrementing it.
+    fn close(&mut self) -> Frame {
+        self.code.push(Instruction::End.repr());
+
+        let frame = self.frames.pop().unwrap();
+
+        if frame.propagates {
+            let mut bitmask: u8 = 0;
+            bitmask.set(0, true);
+
+            self.code
+                .extend_from_slice(&heap_load(VmType::I32, BRANCH_CELL));
+            self.code
+                .extend_from_slice(&push_const(VmType::I32, &encode_be_i32!(1)));
+            self.code
+                .extend_from_slice(&[Instruction::Sub.repr(), Instruction::PeekOperand.repr()]);
+            self.code
+                .extend_from_slice(&heap_store(VmType::I32, BRANCH_CELL));
+            self.code.extend_from_slice(&[
+                Instruction::PushOperand.repr(),
+                0x02,
+                bitmask,
+                Instruction::i32Const.repr(),
+                Instruction::i32Const.repr(),
+                Instruction::PopLocal.repr(),
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                Instruction::BreakIf.repr(),
+                Instruction::GeSigned.repr(),
+                Instruction::PopOperand.repr(),
+                Instruction::PopOperand.repr(),
+            ]);
+
+            // The branch has ended so the cell is reset
+            self.code.extend_from_slice(&push_zero(VmType::I32));
+            self.code
+                .extend_from_slice(&heap_store(VmType::I32, BRANCH_CELL));
+            self.uses_branch_cell = true;
+        }
+
+        frame
+    }
+
+    /// Exits the given number of frames, counting from the current one.
+    fn exit(&mut self, count: usize) {
+        if count > 1 {
+            self.code
+                .extend_from_slice(&push_const(VmType::I32, &encode_be_i32!(count as i32 - 1)));
+            self.code
+                .extend_from_slice(&heap_store(VmType::I32, BRANCH_CELL));
+
+            let len = self.frames.len();
+
+            for frame in self.frames[len + 1 - count..].iter_mut() {
+                frame.propagates = true;
+            }
+        }
+
+        self.code.push(Instruction::Break.repr());
+    }
+
+    /// Exits the given number of frames at the end of a wasm block,
+    /// passing the result of the block in the result cell.
+    fn fall_through(&mut self, count: usize) -> Result<(), &'static str> {
+        if self.frame().unreachable {
+            return Ok(());
+        }
+
+        let result = self.frame().result;
+
+        if let Some(result) = result {
+            self.store_cell(result, RESULT_CELL)?;
+        }
+
+        if !self.frame().values.is_empty() {
+            return Err("Invalid value stack at the end of block");
+        }
+
+        self.exit(count);
+        Ok(())
+    }
+
+    /// Pushes the result of a wasm block that has been passed in the result cell.
+    fn push_result(&mut self, result: Option<VmType>) {
+        if let Some(result) = result {
+            self.load_cell(result, RESULT_CELL);
+        }
+    }
+
+    /// Returns the index of the frame of the label with the given depth.
+    fn label(&self, depth: u32) -> Result<usize, &'static str> {
+        let mut depth = depth as usize;
+
+        for (idx, frame) in self.frames.iter().enumerate().rev() {
+            match frame.kind {
+                FrameKind::Function | FrameKind::Block | FrameKind::Loop | FrameKind::If => {
+                    if depth == 0 {
+                        return Ok(idx);
+                    }
+
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+
+        Err("Invalid branch depth")
+    }
+
+    /// Returns the type of the value passed by
+    /// branches to the frame with the given index.
+    fn label_type(&self, idx: usize) -> Option<VmType> {
+        let frame = &self.frames[idx];
+
+        match frame.kind {
+            // Branching to a loop starts it again
+            FrameKind::Loop => None,
+            _ => frame.result,
+        }
+    }
+
+    fn branch(&mut self, depth: u32) -> Result<(), &'static str> {
+        let idx = self.label(depth)?;
+
+        if self.frames[idx].kind == FrameKind::Function {
+            return self.emit_return();
+        }
+
+        if let Some(value_type) = self.label_type(idx) {
+            self.store_cell(value_type, RESULT_CELL)?;
+        }
+
+        let count = self.frames.len() - idx;
+        self.exit(count);
+        Ok(())
+    }
+
+    /// Branches to the label with the given depth from a frame
+    /// which is entered if the operand stack holds a condition
+    /// different from 0. The passed value is in the result cell.
+    fn conditional_branch(&mut self, depth: u32, comparison: Instruction) {
+        let idx = self.label(depth).unwrap();
+        let value_type = self.label_type(idx);
+
+        self.begin(FrameKind::Inner, None);
+        self.code.extend_from_slice(&[
+            Instruction::BreakIf.repr(),
+            comparison.repr(),
+            Instruction::PopOperand.repr(),
+            Instruction::PopOperand.repr(),
+        ]);
+
+        if self.frames[idx].kind == FrameKind::Function {
+            self.push_result(value_type);
+
+            // The value type has already been checked
+            self.emit_return().unwrap();
+        } else {
+            let count = self.frames.len() - idx;
+            self.exit(count);
+        }
+
+        self.close();
+    }
+
+    fn branch_if(&mut self, depth: u32) -> Result<(), &'static str> {
+        self.pop_type(VmType::I32)?;
+
+        let idx = self.label(depth)?;
+        let value_type = self.label_type(idx);
+
+        // Move the condition to the operand stack and
+        // copy the passed value to the result cell.
+        self.code.extend_from_slice(&pop_local(VmType::I32));
+
+        if let Some(value_type) = value_type {
+            if self.peek()? != value_type {
+                return Err("Type mismatch");
+            }
+
+            self.code.push(Instruction::PeekLocal.repr());
+            self.code
+                .extend_from_slice(&heap_store(value_type, RESULT_CELL));
+        }
+
+        self.code.extend_from_slice(&push_zero(VmType::I32));
+        self.conditional_branch(depth, Instruction::Eq);
+        Ok(())
+    }
+
+    fn branch_table(&mut self, depths: &[u32], default: u32) -> Result<(), &'static str> {
+        let default_idx = self.label(default)?;
+        let value_type = self.label_type(default_idx);
+
+        for depth in depths {
+            let idx = self.label(*depth)?;
+
+            if self.label_type(idx) != value_type {
+                return Err("Type mismatch");
+            }
+        }
+
+        self.store_cell(VmType::I32, TEMP_CELL)?;
+
+        if let Some(value_type) = value_type {
+            if self.peek()? != value_type {
+                return Err("Type mismatch");
+            }
+
+            self.code.push(Instruction::PeekLocal.repr());
+            self.code
+                .extend_from_slice(&heap_store(value_type, RESULT_CELL));
+        }
+
+        for (i, depth) in depths.iter().enumerate() {
+            self.push_temp_operand();
+            self.code
+                .extend_from_slice(&push_const(VmType::I32, &encode_be_u32!(i as u32)));
+            self.conditional_branch(*depth, Instruction::Ne);
+        }
+
+        self.branch(default)
+    }
+
+    fn emit_return(&mut self) -> Result<(), &'static str> {
+        let result = self.module.functions[self.idx].func_type.result;
+
+        let arity = match result {
+            Some(result) => {
+                if self.peek()? != result {
+                    return Err("Type mismatch");
+                }
+
+                1
+            }
+            None => 0,
+        };
+
+        self.code
+            .extend_from_slice(&[Instruction::Return.repr(), arity]);
+        Ok(())
+    }
+
+    /// Fails the execution with an assertion that does not hold.
+    fn trap(&mut self) {
+        self.code.extend_from_slice(&[
+            Instruction::PushOperand.repr(),
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::Eq.repr(),
+        ]);
+    }
+
+    /// Returns `true` if the locals of the translated function must be
+    /// saved before calling the function with the given index since
+    /// it can call the translated function again.
+    fn must_save_locals(&self, callee: &Callee) -> bool {
+        match callee {
+            Callee::Api(_) => false,
+            Callee::Function(idx, _) => {
+                let idx = *idx as usize;
+
+                idx == self.idx
+                    || self.module.functions[idx]
+                        .reachable
+                        .contains(&(self.idx as u16))
+            }
+        }
+    }
+
+    /// Moves the locals of the translated function from their
+    /// cells to the locals stack.
+    fn save_locals(&mut self) {
+        let info = &self.module.functions[self.idx];
+        let locals: Vec<(VmType, usize)> = info
+            .locals
+            .iter()
+            .enumerate()
+            .map(|(i, local_type)| (*local_type, info.first_cell + i))
+            .collect();
+
+        for (local_type, cell) in locals {
+            self.load_cell(local_type, cell);
+        }
+    }
+
+    /// Moves the saved locals of the translated function
+    /// from the locals stack back to their cells.
+    fn restore_locals(&mut self) -> Result<(), &'static str> {
+        let info = &self.module.functions[self.idx];
+        let locals: Vec<(VmType, usize)> = info
+            .locals
+            .iter()
+            .enumerate()
+            .map(|(i, local_type)| (*local_type, info.first_cell + i))
+            .collect();
+
+        for (local_type, cell) in locals.into_iter().rev() {
+            self.store_cell(local_type, cell)?;
+        }
+
+        Ok(())
+    }
+
+    fn store_arguments(&mut self, params: &[VmType]) -> Result<(), &'static str> {
+        for (i, param) in params.iter().enumerate().rev() {
+            self.store_cell(*param, ARGUMENT_CELLS + i)?;
+        }
+
+        Ok(())
+    }
+
+    fn load_arguments(&mut self, params: &[VmType]) {
+        for (i, param) in params.iter().enumerate() {
+            self.load_cell(*param, ARGUMENT_CELLS + i);
+        }
+    }
+
+    /// Calls the given function with the arguments on the locals stack.
+    fn emit_call(&mut self, callee: &Callee) -> Result<(), &'static str> {
+        let func_type = callee.func_type();
+
+        for param in func_type.params.iter().rev() {
+            self.pop_type(*param)?;
+        }
+
+        match callee {
+            Callee::Api(instruction) => self.code.push(instruction.repr()),
+            Callee::Function(idx, _) => {
+                self.code.push(Instruction::Call.repr());
+                self.code.extend_from_slice(&encode_be_u16!(*idx));
+            }
+        }
+
+        if let Some(result) = func_type.result {
+            self.frame().values.push(result);
+        }
+
+        Ok(())
+    }
+
+    fn call(&mut self, idx: usize) -> Result<(), &'static str> {
+        let callee = match self.module.callees.get(idx) {
+            Some(callee) => callee.clone(),
+            None => return Err("Invalid function index"),
+        };
+
+        if !self.must_save_locals(&callee) {
+            return self.emit_call(&callee);
+        }
+
+        let func_type = callee.func_type();
+
+        self.store_arguments(&func_type.params)?;
+        self.save_locals();
+        self.load_arguments(&func_type.params);
+        self.emit_call(&callee)?;
+
+        if let Some(result) = func_type.result {
+            self.store_cell(result, RESULT_CELL)?;
+        }
+
+        self.restore_locals()?;
+        self.push_result(func_type.result);
+        Ok(())
+    }
+
+    /// Calls the table element with the index on the locals stack by
+    /// comparing the index to each element with the expected type.
+    fn call_indirect(&mut self, type_idx: usize) -> Result<(), &'static str> {
+        let func_type = match self.module.types.get(type_idx) {
+            Some(func_type) => func_type.clone(),
+            None => return Err("Invalid type index"),
+        };
+
+        let table = match self.module.table {
+            Some(ref table) => table,
+            None => return Err("The module does not define a table"),
+        };
+
+        let mut callees: Vec<(usize, Callee)> = Vec::new();
+
+        for (i, element) in table.iter().enumerate() {
+            if let Some(fun_idx) = element {
+                let callee = self.module.callees[*fun_idx as usize].clone();
+
+                if callee.func_type() == func_type {
+                    callees.push((i, callee));
+                }
+            }
+        }
+
+        let save_locals = callees.iter().any(|(_, c)| self.must_save_locals(c));
+
+        self.store_cell(VmType::I32, TEMP_CELL)?;
+        self.store_arguments(&func_type.params)?;
+
+        if save_locals {
+            self.save_locals();
+        }
+
+        self.begin(FrameKind::Inner, None);
+
+        for (i, callee) in callees {
+            self.push_temp_operand();
+            self.code
+                .extend_from_slice(&push_const(VmType::I32, &encode_be_u32!(i as u32)));
+            self.begin(FrameKind::Inner, None);
+            self.code.extend_from_slice(&[
+                Instruction::BreakIf.repr(),
+                Instruction::Ne.repr(),
+                Instruction::PopOperand.repr(),
+                Instruction::PopOperand.repr(),
+            ]);
+
+            self.load_arguments(&func_type.params);
+            self.emit_call(&callee)?;
+
+            if let Some(result) = func_type.result {
+                self.store_cell(result, RESULT_CELL)?;
+            }
+
+            self.exit(2);
+            self.close();
+        }
+
+        // There is no element with the index and the expected type
+        self.trap();
+        self.close();
+
+        if save_locals {
+            self.restore_locals()?;
+        }
+
+        self.push_result(func_type.result);
+        Ok(())
+    }
+
+    fn select(&mut self) -> Result<(), &'static str> {
+        self.store_cell(VmType::I32, TEMP_CELL)?;
+
+        let value_type = self.peek()?;
+
+        self.store_cell(value_type, AUX_CELL)?;
+        self.store_cell(value_type, RESULT_CELL)?;
+
+        // Replace the first value with the second one if the condition is 0
+        self.code
+            .extend_from_slice(&heap_load(VmType::I32, TEMP_CELL));
+        self.code.extend_from_slice(&push_zero(VmType::I32));
+        self.begin(FrameKind::Inner, None);
+        self.code.extend_from_slice(&[
+            Instruction::BreakIf.repr(),
+            Instruction::Ne.repr(),
+            Instruction::PopOperand.repr(),
+            Instruction::PopOperand.repr(),
+        ]);
+        self.code
+            .extend_from_slice(&heap_load(value_type, AUX_CELL));
+        self.code
+            .extend_from_slice(&heap_store(value_type, RESULT_CELL));
+        self.code.push(Instruction::Break.repr());
+        self.close();
+
+        self.load_cell(value_type, RESULT_CELL);
+        Ok(())
+    }
+
+    /// Pushes 1 if the comparison holds for the two topmost values
+    /// and 0 otherwise. If `with_zero` is `true`, the topmost value
+    /// is compared to 0 instead.
+    fn compare(
+        &mut self,
+        operand_type: VmType,
+        mut comparison: Instruction,
+        with_zero: bool,
+    ) -> Result<(), &'static str> {
+        self.code.extend_from_slice(&push_zero(VmType::I32));
+        self.code
+            .extend_from_slice(&heap_store(VmType::I32, TEMP_CELL));
+
+        if with_zero {
+            let mut bitmask: u8 = 0;
+            bitmask.set(0, true);
+
+            self.pop_type(operand_type)?;
+            self.code.extend_from_slice(&[
+                Instruction::PushOperand.repr(),
+                0x02,
+                bitmask,
+                operand_type.to_op(),
+                operand_type.to_op(),
+                Instruction::PopLocal.repr(),
+            ]);
+            self.code.extend_from_slice(&zero_bytes(operand_type));
+        } else {
+            if let Some(signed) = signed_comparison(comparison) {
+                self.flip_sign_bits(operand_type)?;
+                comparison = signed;
+            }
+
+            self.load_operands(&[operand_type, operand_type])?;
+        }
+
+        self.begin(FrameKind::Inner, None);
+        self.code.extend_from_slice(&[
+            Instruction::BreakIf.repr(),
+            negate(comparison).repr(),
+            Instruction::PopOperand.repr(),
+            Instruction::PopOperand.repr(),
+        ]);
+        self.code
+            .extend_from_slice(&push_const(VmType::I32, &encode_be_i32!(1)));
+        self.code
+            .extend_from_slice(&heap_store(VmType::I32, TEMP_CELL));
+        self.code.push(Instruction::Break.repr());
+        self.close();
+
+        self.load_cell(VmType::I32, TEMP_CELL);
+        Ok(())
+    }
+
+    /// Flips the sign bits of the two topmost values, which maps
+    /// their unsigned order to the signed order of the results.
+    fn flip_sign_bits(&mut self, operand_type: VmType) -> Result<(), &'static str> {
+        let sign_bit = match operand_type {
+            VmType::I32 => encode_be_i32!(i32::min_value()),
+            _ => encode_be_i64!(i64::min_value()),
+        };
+
+        self.store_cell(operand_type, AUX_CELL)?;
+        self.push_value(operand_type, &sign_bit);
+        self.binary_op(operand_type, Instruction::Xor)?;
+        self.load_cell(operand_type, AUX_CELL);
+        self.push_value(operand_type, &sign_bit);
+        self.binary_op(operand_type, Instruction::Xor)
+    }
+
+    /// Moves the condition on the locals stack to the
+    /// operand stack, followed by 0.
+    fn push_condition(&mut self) {
+        self.code.extend_from_slice(&pop_local(VmType::I32));
+        self.code.extend_from_slice(&push_zero(VmType::I32));
+    }
+
+    /// Copies the value of the temporary cell to the operand stack.
+    fn push_temp_operand(&mut self) {
+        self.code
+            .extend_from_slice(&heap_load(VmType::I32, TEMP_CELL));
+        self.code.push(Instruction::PeekOperand.repr());
+        self.code.extend_from_slice(&pop_local(VmType::I32));
+        self.code
+            .extend_from_slice(&heap_store(VmType::I32, TEMP_CELL));
+    }
+
+    /// Copies the value of the given cell to the locals stack.
+    fn read_cell(&mut self, value_type: VmType, cell: usize) {
+        self.code.extend_from_slice(&heap_load(value_type, cell));
+        self.code.push(Instruction::PeekOperand.repr());
+        self.code.extend_from_slice(&heap_store(value_type, cell));
+        self.frame().values.push(value_type);
+    }
+
+    /// Moves the value of the given cell to the locals stack.
+    fn load_cell(&mut self, value_type: VmType, cell: usize) {
+        self.code.extend_from_slice(&heap_load(value_type, cell));
+        self.store_operand(value_type);
+    }
+
+    /// Moves the topmost value on the locals stack to the given cell.
+    fn store_cell(&mut self, value_type: VmType, cell: usize) -> Result<(), &'static str> {
+        self.pop_type(value_type)?;
+        self.code.extend_from_slice(&pop_local(value_type));
+        self.code.extend_from_slice(&heap_store(value_type, cell));
+        Ok(())
+    }
+
+    fn push_value(&mut self, value_type: VmType, bytes: &[u8]) {
+        self.code.extend_from_slice(&[
+            Instruction::PushLocal.repr(),
+            0x01,
+            0x00,
+            value_type.to_op(),
+        ]);
+        self.code.extend_from_slice(bytes);
+        self.frame().values.push(value_type);
+    }
+
+    /// Moves the topmost values from the locals stack
+    /// to the operand stack, keeping their order.
+    fn load_operands(&mut self, operand_types: &[VmType]) -> Result<(), &'static str> {
+        for operand_type in operand_types.iter().rev() {
+            self.pop_type(*operand_type)?;
+        }
+
+        let count = operand_types.len();
+        let mut bitmask: u8 = 0;
+
+        for i in 0..count {
+            bitmask.set(i as u8, true);
+        }
+
+        // Popping the operands reverses their order so the
+        // first operand is copied on top of the second one.
+        if count == 2 {
+            let idx = self.frame().values.len();
+
+            self.code.push(Instruction::PickLocal.repr());
+            self.code.extend_from_slice(&encode_be_u16!(idx as u16));
+        }
+
+        self.code
+            .extend_from_slice(&[Instruction::PushOperand.repr(), count as u8, bitmask]);
+
+        for operand_type in operand_types {
+            self.code.push(operand_type.to_op());
+        }
+
+        for _ in 0..count {
+            self.code.push(Instruction::PopLocal.repr());
+        }
+
+        // Drop the original first operand
+        if count == 2 {
+            self.code.push(Instruction::PopLocal.repr());
+        }
+
+        Ok(())
+    }
+
+    /// Moves the single value on the operand stack to the locals stack.
+    fn store_operand(&mut self, operand_type: VmType) {
+        let mut bitmask: u8 = 0;
+        bitmask.set(0, true);
+
+        self.code.extend_from_slice(&[
+            Instruction::PushLocal.repr(),
+            0x01,
+            bitmask,
+            operand_type.to_op(),
+            Instruction::PopOperand.repr(),
+        ]);
+        self.frame().values.push(operand_type);
+    }
+
+    fn binary_op(&mut self, operand_type: VmType, op: Instruction) -> Result<(), &'static str> {
+        self.load_operands(&[operand_type, operand_type])?;
+        self.code.push(op.repr());
+        self.store_operand(operand_type);
+        Ok(())
+    }
+
+    fn convert(&mut self, from: VmType, to: VmType, op: Instruction) -> Result<(), &'static str> {
+        self.load_operands(&[from])?;
+        self.code.push(op.repr());
+        self.store_operand(to);
+        Ok(())
+    }
+
+    /// Reads the immediates of a memory access and returns its offset.
+    fn read_memarg(&mut self, reader: &mut Reader) -> Result<u32, &'static str> {
+        if !self.module.has_memory {
+            return Err("The module does not define a memory");
+        }
+
+        // The alignment is only a hint
+        reader.read_var_u32()?;
+        reader.read_var_u32()
+    }
+
+    fn read_memory_idx(&mut self, reader: &mut Reader) -> Result<(), &'static str> {
+        if !self.module.has_memory || reader.read_u8()? != 0x00 {
+            return Err("The module does not define a memory");
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the comparison that holds when the given one does not.
+fn negate(comparison: Instruction) -> Instruction {
+    match comparison {
+        Instruction::Eq => Instruction::Ne,
+        Instruction::Ne => Instruction::Eq,
+        Instruction::LtSigned => Instruction::GeSigned,
+        Instruction::LtUnsigned => Instruction::GeUnsigned,
+        Instruction::GtSigned => Instruction::LeSigned,
+        Instruction::GtUnsigned => Instruction::LeUnsigned,
+        Instruction::LeSigned => Instruction::GtSigned,
+        Instruction::LeUnsigned => Instruction::GtUnsigned,
+        Instruction::GeSigned => Instruction::LtSigned,
+        Instruction::GeUnsigned => Instruction::LtUnsigned,
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the signed comparison that holds for values with flipped
+/// sign bits when the given unsigned one holds for the original values.
+fn signed_comparison(comparison: Instruction) -> Option<Instruction> {
+    match comparison {
+        Instruction::LtUnsigned => Some(Instruction::LtSigned),
+        Instruction::GtUnsigned => Some(Instruction::GtSigned),
+        Instruction::LeUnsigned => Some(Instruction::LeSigned),
+        Instruction::GeUnsigned => Some(Instruction::GeSigned),
+        _ => None,
+    }
+}
+
+/// Returns the heap coordinates of the given cell.
+fn coordinates(cell: usize) -> [u8; 2] {
+    [(cell / 256) as u8, (cell % 256) as u8]
+}
+
+/// Moves the value of the given cell to the operand stack.
+fn heap_load(value_type: VmType, cell: usize) -> Vec<u8> {
+    let op = match value_type {
+        VmType::I32 => Instruction::i32Load,
+        _ => Instruction::i64Load,
+    };
+
+    let mut code = vec![op.repr()];
+    code.extend_from_slice(&coordinates(cell));
+    code
+}
+
+/// Moves the topmost value on the operand stack to the given cell.
+fn heap_store(value_type: VmType, cell: usize) -> Vec<u8> {
+    let op = match value_type {
+        VmType::I32 => Instruction::i32Store,
+        _ => Instruction::i64Store,
+    };
+
+    let mut code = vec![op.repr()];
+    code.extend_from_slice(&coordinates(cell));
+    code
+}
+
+/// Moves the topmost value on the locals stack to the operand stack.
+fn pop_local(value_type: VmType) -> Vec<u8> {
+    let mut bitmask: u8 = 0;
+    bitmask.set(0, true);
+
+    vec![
+        Instruction::PushOperand.repr(),
+        0x01,
+        bitmask,
+        value_type.to_op(),
+        Instruction::PopLocal.repr(),
+    ]
+}
+
+/// Pushes a constant to the operand stack.
+fn push_const(value_type: VmType, bytes: &[u8]) -> Vec<u8> {
+    let mut code = vec![
+        Instruction::PushOperand.repr(),
+        0x01,
+        0x00,
+        value_type.to_op(),
+    ];
+
+    code.extend_from_slice(bytes);
+    code
+}
+
+fn push_zero(value_type: VmType) -> Vec<u8> {
+    push_const(value_type, &zero_bytes(value_type))
+}
+
+fn zero_bytes(value_type: VmType) -> Vec<u8> {
+    match value_type {
+        VmType::I32 => vec![0; 4],
+        _ => vec![0; 8],
+    }
+}
+
+fn memory_access(instruction: Instruction, op: Instruction, offset: u32) -> Vec<u8> {
+    let mut code = vec![instruction.repr(), op.repr()];
+    code.extend_from_slice(&encode_be_u32!(offset));
+    code
+}
+
+/// Reads the declared locals of a function body.
+fn read_locals(reader: &mut Reader) -> Result<Vec<VmType>, &'static str> {
+    let mut locals = Vec::new();
+    let groups = reader.read_var_u32()?;
+
+    for _ in 0..groups {
+        let count = reader.read_var_u32()?;
+        let local_type = read_value_type(reader)?;
+
+        if locals.len() + count as usize > u16::max_value() as usize {
+            return Err("Too many locals");
+        }
+
+        for _ in 0..count {
+            locals.push(local_type);
+        }
+    }
+
+    Ok(locals)
+}
+
+/// Reads the type of a block, which can have at most one result.
+fn read_block_type(reader: &mut Reader) -> Result<Option<VmType>, &'static str> {
+    match reader.peek_u8()? {
+        0x40 => {
+            reader.read_u8()?;
+            Ok(None)
+        }
+        0x7f | 0x7e | 0x7d | 0x7c => Ok(Some(read_value_type(reader)?)),
+        _ => Err("Blocks with parameters or several results are not supported"),
+    }
+}
+
+/// Returns `true` if the `if` block starting at the
+/// position of the reader has an `else` part.
+fn has_else(reader: &Reader) -> Result<bool, &'static str> {
+    let mut reader = reader.clone();
+
+    skip_unreachable(&mut reader)?;
+    Ok(reader.peek_u8()? == 0x05)
+}
+
+/// Skips the unreachable instructions until the `else` or `end`
+/// instruction that closes the current block.
+fn skip_unreachable(reader: &mut Reader) -> Result<(), &'static str> {
+    let mut depth = 0;
+
+    loop {
+        match reader.peek_u8()? {
+            0x05 | 0x0b if depth == 0 => return Ok(()),
+            _ => {}
+        }
+
+        match reader.read_u8()? {
+            // block, loop, if
+            0x02 | 0x03 | 0x04 => {
+                reader.read_var_i64()?;
+                depth += 1;
+            }
+            0x0b => depth -= 1,
+            op => skip_immediates(op, reader)?,
+        }
+    }
+}
+
+/// Skips the immediates of the given instruction.
+fn skip_immediates(op: u8, reader: &mut Reader) -> Result<(), &'static str> {
+    match op {
+        // block, loop, if
+        0x02 | 0x03 | 0x04 => {
+            reader.read_var_i64()?;
+        }
+        0x0c | 0x0d | 0x10 | 0x20..=0x24 => {
+            reader.read_var_u32()?;
+        }
+        // br_table
+        0x0e => {
+            let count = reader.read_var_u32()?;
+
+            for _ in 0..=count {
+                reader.read_var_u32()?;
+            }
+        }
+        // call_indirect
+        0x11 => {
+            reader.read_var_u32()?;
+            reader.read_u8()?;
+        }
+        // select with the type of the operands
+        0x1c => {
+            let count = reader.read_var_u32()?;
+
+            for _ in 0..count {
+                reader.read_u8()?;
+            }
+        }
+        // Memory instructions
+        0x28..=0x3e => {
+            reader.read_var_u32()?;
+            reader.read_var_u32()?;
+        }
+        0x3f | 0x40 => {
+            reader.read_u8()?;
+        }
+        0x41 => {
+            reader.read_var_i32()?;
+        }
+        0x42 => {
+            reader.read_var_i64()?;
+        }
+        0x43 => {
+            reader.read_bytes(4)?;
+        }
+        0x44 => {
+            reader.read_bytes(8)?;
+        }
+        0xfc..=0xff => return Err("Unsupported wasm instruction"),
+        _ => {}
+    }
+
+    Ok(())
+}
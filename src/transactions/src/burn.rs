@@ -16,11 +16,12 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use crate::journal::JournaledTrieMut;
 use account::{Balance, NormalAddress};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use crypto::{Hash, ShortHash, Signature};
 use crypto::{PublicKey as Pk, SecretKey as Sk};
-use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
 use persistence::{Codec, DbHasher};
 use rand::Rng;
 use std::io::Cursor;
@@ -44,6 +45,18 @@ impl Burn {
 
     /// Validates the transaction against the provided state.
     pub fn validate(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
+        // TODO: Signature verification should be done in batches
+        // and happen before validation.
+        if !self.verify_sig() {
+            return false;
+        }
+
+        self.validate_unsigned(trie)
+    }
+
+    /// Validates the transaction against the provided state
+    /// without verifying its signature.
+    pub fn validate_unsigned(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
         let zero = Balance::zero();
 
         // You cannot send 0 coins
@@ -51,12 +64,6 @@ impl Burn {
             return false;
         }
 
-        // TODO: Signature verification should be done in batches
-        // and happen before validation.
-        if !self.verify_sig() {
-            return false;
-        }
-
         let bin_asset_hash = &self.asset_hash.0;
         let bin_fee_hash = &self.fee_hash.0;
 
@@ -16,12 +16,14 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use crate::contract::self_destruct;
+use crate::journal::{record_writes, JournaledTrieMut};
 use account::{Address, Balance, ContractAddress, NormalAddress};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use crypto::{Hash, PublicKey as Pk, SecretKey as Sk, ShortHash, Signature};
-use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
 use persistence::{Codec, DbHasher};
-use purple_vm::Gas;
+use purple_vm::{Function, Gas, Module, Vm, VmError};
 use rand::Rng;
 use std::io::Cursor;
 use std::str;
@@ -48,11 +50,366 @@ impl Call {
 
     /// Validates the transaction against the provided state.
     pub fn validate(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
-        unimplemented!();
+        // TODO: Signature verification should be done in batches
+        // and happen before validation.
+        if !self.verify_sig() {
+            return false;
+        }
+
+        self.validate_unsigned(trie)
+    }
+
+    /// Validates the transaction against the provided state
+    /// without verifying its signature.
+    pub fn validate_unsigned(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
+        let zero = Balance::zero();
+        let bin_asset_hash = &self.asset_hash.0;
+        let bin_fee_hash = &self.fee_hash.0;
+        let caller_signing_addr = NormalAddress::from_pkey(&self.from);
+
+        // Do not allow address re-usage
+        if self.next_address == caller_signing_addr {
+            return false;
+        }
+
+        // Calculate code key
+        //
+        // The key of a contract's code has the following format:
+        // `<contract-address>.c`
+        let code_key = [self.to.as_bytes(), &b".c"[..]].concat();
+
+        // Validate against calling a non-existing or destroyed contract
+        match trie.get(&code_key) {
+            Ok(Some(_)) => {}
+            Ok(None) => return false,
+            Err(err) => panic!(err),
+        }
+
+        // Calculate address mapping key
+        //
+        // An address mapping is a mapping between
+        // the account's signing address and an
+        // account's receiving address.
+        //
+        // They key of the address mapping has the following format:
+        // `<signing-address>.am`
+        let addr_mapping_key = [caller_signing_addr.as_bytes(), &b".am"[..]].concat();
+
+        // Retrieve caller account permanent address
+        let permanent_addr = match trie.get(&addr_mapping_key) {
+            Ok(Some(perm_addr)) => NormalAddress::from_bytes(&perm_addr).unwrap(),
+            Ok(None) => return false,
+            Err(err) => panic!(err),
+        };
+
+        // Do not allow address re-usage
+        if self.next_address == permanent_addr {
+            return false;
+        }
+
+        // Calculate nonce key
+        //
+        // The key of a nonce has the following format:
+        // `<permanent-addr>.n`
+        let nonce_key = [permanent_addr.as_bytes(), &b".n"[..]].concat();
+
+        // Retrieve serialized nonce
+        let bin_nonce = match trie.get(&nonce_key) {
+            Ok(Some(nonce)) => nonce,
+            Ok(None) => return false,
+            Err(err) => panic!(err),
+        };
+
+        let stored_nonce = decode_be_u64!(bin_nonce).unwrap();
+        if stored_nonce + 1 != self.nonce {
+            return false;
+        }
+
+        // Calculate currency keys
+        //
+        // The key of a currency entry has the following format:
+        // `<permanent-addr>.<currency-hash>`
+        let cur_key = [permanent_addr.as_bytes(), &b"."[..], &bin_asset_hash[..]].concat();
+        let fee_key = [permanent_addr.as_bytes(), &b"."[..], &bin_fee_hash[..]].concat();
+
+        // The caller must be able to pay for all of the gas
+        // even though only the consumed gas is charged.
+        let max_gas_cost = match gas_cost(&self.gas_limit, &self.gas_price) {
+            Some(cost) => cost,
+            None => return false,
+        };
+
+        if bin_fee_hash == bin_asset_hash {
+            // The transaction's fee is paid in the same currency
+            // that is being transferred, so we only retrieve one balance.
+            let mut balance = match trie.get(&cur_key) {
+                Ok(Some(balance)) => match Balance::from_bytes(&balance) {
+                    Ok(balance) => balance,
+                    Err(err) => panic!(err),
+                },
+                Ok(None) => return false,
+                Err(err) => panic!(err),
+            };
+
+            // Subtract fee and gas from balance
+            balance -= self.fee.clone();
+            balance -= max_gas_cost;
+
+            // Subtract amount transferred from balance
+            balance -= self.amount.clone();
+
+            balance >= zero
+        } else {
+            // The transaction's fee is paid in a different currency
+            // than the one being transferred so we retrieve both balances.
+            let mut cur_balance = match trie.get(&cur_key) {
+                Ok(Some(balance)) => match Balance::from_bytes(&balance) {
+                    Ok(balance) => balance,
+                    Err(err) => panic!(err),
+                },
+                Ok(None) => return false,
+                Err(err) => panic!(err),
+            };
+
+            let mut fee_balance = match trie.get(&fee_key) {
+                Ok(Some(balance)) => match Balance::from_bytes(&balance) {
+                    Ok(balance) => balance,
+                    Err(err) => panic!(err),
+                },
+                Ok(None) => return false,
+                Err(err) => panic!(err),
+            };
+
+            // Subtract fee and gas from caller
+            fee_balance -= self.fee.clone();
+            fee_balance -= max_gas_cost;
+
+            // Subtract amount transferred from caller
+            cur_balance -= self.amount.clone();
+
+            cur_balance >= zero && fee_balance >= zero
+        }
     }
 
+    /// Applies the call transaction to the provided database.
+    ///
+    /// The executed contract code observes a current time of zero,
+    /// use `apply_metered` to execute it at the time of a block.
+    ///
+    /// This function will panic if the `from` account does not exist.
     pub fn apply(&self, trie: &mut TrieDBMut<DbHasher, Codec>) {
-        unimplemented!();
+        // A failed execution still consumes the fee, the
+        // transferred amount and all of the gas.
+        let _ = self.apply_metered(trie, 0);
+    }
+
+    /// Applies the call transaction to the provided database
+    /// and returns the amount of gas consumed by the executed
+    /// contract code.
+    ///
+    /// The consumed gas is charged at the gas price of the
+    /// transaction in the currency of the fee. If the execution
+    /// fails, all of its writes are rolled back and the caller
+    /// only pays the fee and all of the gas. The transferred
+    /// amount is not credited to the contract in that case.
+    ///
+    /// The given timestamp, in seconds since the unix epoch, is the
+    /// current time observed by the executed contract code.
+    ///
+    /// This function will panic if the `from` account does not exist.
+    pub fn apply_metered(
+        &self,
+        trie: &mut TrieDBMut<DbHasher, Codec>,
+        timestamp: i64,
+    ) -> Result<Gas, VmError> {
+        let bin_asset_hash = &self.asset_hash.0;
+        let bin_fee_hash = &self.fee_hash.0;
+        let caller_signing_addr = NormalAddress::from_pkey(&self.from);
+
+        // Calculate address mapping keys
+        //
+        // They key of the address mapping has the following format:
+        // `<signing-address>.am`
+        let from_addr_mapping_key = [caller_signing_addr.as_bytes(), &b".am"[..]].concat();
+        let next_addr_mapping_key = [self.next_address.as_bytes(), &b".am"[..]].concat();
+
+        // Retrieve caller account permanent address
+        let from_perm_addr = trie.get(&from_addr_mapping_key).unwrap().unwrap();
+        let from_perm_addr = NormalAddress::from_bytes(&from_perm_addr).unwrap();
+
+        // Calculate nonce key
+        //
+        // The key of a nonce has the following format:
+        // `<permanent-addr>.n`
+        let from_nonce_key = [from_perm_addr.as_bytes(), &b".n"[..]].concat();
+
+        // Read and increment the nonce of the caller
+        let bin_from_nonce = &trie.get(&from_nonce_key).unwrap().unwrap();
+        let from_nonce = decode_be_u64!(bin_from_nonce).unwrap() + 1;
+        let from_nonce: Vec<u8> = encode_be_u64!(from_nonce);
+
+        // Calculate currency keys
+        //
+        // The key of a currency entry has the following format:
+        // `<account-address>.<currency-hash>`
+        let from_cur_key = [from_perm_addr.as_bytes(), &b"."[..], &bin_asset_hash[..]].concat();
+        let from_fee_key = [from_perm_addr.as_bytes(), &b"."[..], &bin_fee_hash[..]].concat();
+        let to_cur_key = [self.to.as_bytes(), &b"."[..], &bin_asset_hash[..]].concat();
+
+        if bin_fee_hash == bin_asset_hash {
+            // The transaction's fee is paid in the same currency
+            // that is being transferred, so we only retrieve one
+            // balance.
+            let mut caller_balance = unwrap!(
+                Balance::from_bytes(&unwrap!(
+                    trie.get(&from_cur_key).unwrap(),
+                    "The caller does not have an entry for the given currency"
+                )),
+                "Invalid stored balance format"
+            );
+
+            caller_balance -= self.fee.clone();
+            caller_balance -= self.amount.clone();
+
+            trie.insert(&from_cur_key, &caller_balance.to_bytes())
+                .unwrap();
+        } else {
+            // The transaction's fee is paid in a different currency
+            // than the one being transferred so we retrieve both balances.
+            let mut caller_cur_balance = unwrap!(
+                Balance::from_bytes(&unwrap!(
+                    trie.get(&from_cur_key).unwrap(),
+                    "The caller does not have an entry for the given currency"
+                )),
+                "Invalid stored balance format"
+            );
+
+            let mut caller_fee_balance = unwrap!(
+                Balance::from_bytes(&unwrap!(
+                    trie.get(&from_fee_key).unwrap(),
+                    "The caller does not have an entry for the given currency"
+                )),
+                "Invalid stored balance format"
+            );
+
+            caller_fee_balance -= self.fee.clone();
+            caller_cur_balance -= self.amount.clone();
+
+            trie.insert(&from_cur_key, &caller_cur_balance.to_bytes())
+                .unwrap();
+            trie.insert(&from_fee_key, &caller_fee_balance.to_bytes())
+                .unwrap();
+        }
+
+        trie.insert(&from_nonce_key, &from_nonce).unwrap();
+
+        // Update caller address mapping
+        trie.remove(&from_addr_mapping_key).unwrap();
+        trie.insert(&next_addr_mapping_key, from_perm_addr.as_bytes())
+            .unwrap();
+
+        let snapshot = *trie.root();
+
+        // Record the writes of the call so that they
+        // can be rolled back if the execution fails.
+        let (result, journal) = record_writes(|| {
+            // Transfer the amount to the contract
+            if self.amount > Balance::zero() {
+                let contract_balance: Balance = match trie.get(&to_cur_key) {
+                    Ok(Some(balance)) => {
+                        Balance::from_bytes(&balance).unwrap() + self.amount.clone()
+                    }
+                    Ok(None) => self.amount.clone(),
+                    Err(err) => panic!(err),
+                };
+
+                trie.insert(&to_cur_key, &contract_balance.to_bytes())
+                    .unwrap();
+            }
+
+            self.execute(trie, &from_perm_addr, timestamp)
+        });
+
+        let gas_used = match result {
+            Ok(ref gas_used) => gas_used.clone(),
+            Err(_) => {
+                journal.revert(trie);
+                debug_assert_eq!(*trie.root(), snapshot);
+                self.gas_limit.clone()
+            }
+        };
+
+        // Charge the gas after the execution since
+        // contract code cannot debit the caller.
+        let mut caller_fee_balance = unwrap!(
+            Balance::from_bytes(&trie.get(&from_fee_key).unwrap().unwrap()),
+            "Invalid stored balance format"
+        );
+
+        caller_fee_balance -= unwrap!(
+            gas_cost(&gas_used, &self.gas_price),
+            "The gas cost is not a valid balance"
+        );
+
+        trie.insert(&from_fee_key, &caller_fee_balance.to_bytes())
+            .unwrap();
+
+        result
+    }
+
+    /// Executes the code of the called contract with
+    /// the gas limit of the transaction at the given
+    /// timestamp.
+    ///
+    /// If the code self-destructs, the contract is destroyed
+    /// and its balances are swept to the caller.
+    ///
+    /// TODO: Pass the call inputs as arguments once
+    /// they have a concrete type.
+    fn execute(
+        &self,
+        trie: &mut TrieDBMut<DbHasher, Codec>,
+        caller: &NormalAddress,
+        timestamp: i64,
+    ) -> Result<Gas, VmError> {
+        // Calculate code key
+        //
+        // The key of a contract's code has the following format:
+        // `<contract-address>.c`
+        let code_key = [self.to.as_bytes(), &b".c"[..]].concat();
+
+        let code = match trie.get(&code_key) {
+            Ok(Some(code)) => code.to_vec(),
+            Ok(None) => return Err(VmError::NotLoaded),
+            Err(err) => panic!(err),
+        };
+
+        let function = Function {
+            arity: 0,
+            name: "main".to_owned(),
+            block: code.clone(),
+            return_type: None,
+            arguments: vec![],
+        };
+
+        let module = Module {
+            module_hash: crypto::hash_slice(&code),
+            functions: vec![function],
+            imports: vec![],
+        };
+
+        let mut vm = Vm::new();
+        vm.set_current_time(timestamp);
+        vm.load(module)?;
+
+        let gas_used = vm.execute(trie, 0, 0, &[], self.gas_limit.clone())?;
+
+        if vm.self_destructed() {
+            self_destruct(trie, &self.to, &Address::Normal(caller.clone()))
+                .map_err(|_| VmError::NotLoaded)?;
+        }
+
+        Ok(gas_used)
     }
 
     /// Signs the transaction with the given secret key.
@@ -72,7 +429,7 @@ impl Call {
     /// Verifies the signature of the transaction.
     ///
     /// Returns `false` if the signature field is missing.
-    pub fn verify_sig(&mut self) -> bool {
+    pub fn verify_sig(&self) -> bool {
         let message = assemble_message(&self);
 
         match self.signature {
@@ -386,6 +743,67 @@ impl Call {
     impl_hash!();
 }
 
+/// The number of decimals of balances and gas amounts.
+const DECIMALS: u32 = 18;
+
+/// Returns the cost of the given amount of gas at the given
+/// price, truncated to 18 decimals, or `None` if the cost is
+/// not a valid balance.
+///
+/// The product is computed on fixed point integers so that
+/// it cannot overflow.
+fn gas_cost(gas: &Gas, gas_price: &Balance) -> Option<Balance> {
+    let scale = 10u128.pow(DECIMALS);
+    let (gas_int, gas_frac) = fixed_point(&gas.to_bytes())?;
+    let (price_int, price_frac) = fixed_point(&gas_price.to_bytes())?;
+    let int = gas_int * price_int;
+
+    if int >= scale {
+        return None;
+    }
+
+    // Both parts of each operand are smaller
+    // than `scale` so none of these overflow.
+    let cost =
+        int * scale + gas_int * price_frac + gas_frac * price_int + gas_frac * price_frac / scale;
+
+    let (int, frac) = (cost / scale, cost % scale);
+
+    if int >= scale {
+        return None;
+    }
+
+    let cost = if frac == 0 {
+        format!("{}", int)
+    } else {
+        format!("{}.{:018}", int, frac)
+            .trim_end_matches('0')
+            .to_owned()
+    };
+
+    Balance::from_bytes(cost.as_bytes()).ok()
+}
+
+/// Splits the given decimal string into its integer part and
+/// its fractional part scaled to 18 decimals.
+fn fixed_point(bytes: &[u8]) -> Option<(u128, u128)> {
+    let mut parts = str::from_utf8(bytes).ok()?.splitn(2, '.');
+    let int = parts.next()?.parse::<u128>().ok()?;
+    let frac = match parts.next() {
+        Some(frac) if frac.len() <= DECIMALS as usize => {
+            format!("{:0<18}", frac).parse::<u128>().ok()?
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+
+    if int >= 10u128.pow(DECIMALS) {
+        return None;
+    }
+
+    Some((int, frac))
+}
+
 fn assemble_message(obj: &Call) -> Vec<u8> {
     let mut buf: Vec<u8> = Vec::new();
     let to = obj.to.to_bytes();
@@ -451,9 +869,318 @@ impl Arbitrary for Call {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::OpenContract;
     use account::NormalAddress;
     use crypto::Identity;
 
+    const RETURN_42: &'static str = "
+        Begin 0x00
+        PushLocal 0x01 0x00
+        i32Const 0x00 0x00 0x00 0x2a
+        Return 0x01
+        End
+    ";
+
+    const SUICIDE: &'static str = "
+        Begin 0x00
+        Suicide
+        End
+    ";
+
+    fn open_contract(
+        trie: &mut TrieDBMut<DbHasher, Codec>,
+        id: &Identity,
+        asset_hash: ShortHash,
+    ) -> (ContractAddress, Identity) {
+        open_contract_with_code(trie, id, asset_hash, RETURN_42)
+    }
+
+    fn open_contract_with_code(
+        trie: &mut TrieDBMut<DbHasher, Codec>,
+        id: &Identity,
+        asset_hash: ShortHash,
+        code: &str,
+    ) -> (ContractAddress, Identity) {
+        let next_id = Identity::new();
+
+        let mut tx = OpenContract {
+            creator: id.pkey().clone(),
+            next_address: NormalAddress::from_pkey(next_id.pkey()),
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            code: purple_vm::assemble(code).unwrap(),
+            default_state: vec![],
+            fee_hash: asset_hash,
+            amount: Balance::from_bytes(b"30.0").unwrap(),
+            asset_hash,
+            self_payable: true,
+            admin: None,
+            nonce: 1,
+            address: None,
+            signature: None,
+            hash: None,
+        };
+
+        tx.compute_address();
+        tx.sign(id.skey().clone());
+        tx.compute_hash();
+        tx.apply(trie);
+
+        (tx.address.unwrap(), next_id)
+    }
+
+    fn call(from: &Identity, to: ContractAddress, asset_hash: ShortHash) -> Call {
+        let next_id = Identity::new();
+
+        let mut tx = Call {
+            from: from.pkey().clone(),
+            next_address: NormalAddress::from_pkey(next_id.pkey()),
+            to,
+            inputs: String::new(),
+            amount: Balance::from_bytes(b"20.0").unwrap(),
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            gas_price: Balance::from_bytes(b"0.01").unwrap(),
+            gas_limit: Gas::from_bytes(b"1000.0").unwrap(),
+            asset_hash,
+            fee_hash: asset_hash,
+            nonce: 2,
+            hash: None,
+            signature: None,
+        };
+
+        tx.sign(from.skey().clone());
+        tx.compute_hash();
+        tx
+    }
+
+    #[test]
+    fn validate() {
+        let id = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        let (contract, caller_id) = {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+            open_contract(&mut trie, &id, asset_hash)
+        };
+
+        let mut tx = call(&caller_id, contract, asset_hash);
+        let trie = TrieDB::<DbHasher, Codec>::new(&mut db, &root).unwrap();
+
+        assert!(tx.validate(&trie));
+
+        // An unsigned call is valid only if the signature is not verified
+        tx.signature = None;
+        assert!(!tx.validate(&trie));
+        assert!(tx.validate_unsigned(&trie));
+    }
+
+    #[test]
+    fn validate_fails_on_non_existing_contract() {
+        let id = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        let (_, caller_id) = {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+            open_contract(&mut trie, &id, asset_hash)
+        };
+
+        let tx = call(
+            &caller_id,
+            ContractAddress::new(crypto::hash_slice(b"non-existing")),
+            asset_hash,
+        );
+        let trie = TrieDB::<DbHasher, Codec>::new(&mut db, &root).unwrap();
+
+        assert!(!tx.validate(&trie));
+    }
+
+    #[test]
+    fn validate_fails_if_the_gas_cannot_be_paid() {
+        let id = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        let (contract, caller_id) = {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+            open_contract(&mut trie, &id, asset_hash)
+        };
+
+        // 60 left - 10 fee - 20 transferred leaves 30 for
+        // gas while the gas limit may cost up to 30.01.
+        let mut tx = call(&caller_id, contract, asset_hash);
+        tx.gas_limit = Gas::from_bytes(b"3001.0").unwrap();
+        tx.sign(caller_id.skey().clone());
+        tx.compute_hash();
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&mut db, &root).unwrap();
+        assert!(!tx.validate(&trie));
+    }
+
+    #[test]
+    fn apply_metered_executes_the_contract() {
+        let id = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        // Manually initialize creator balance
+        test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+        let (contract, caller_id) = open_contract(&mut trie, &id, asset_hash);
+
+        let tx = call(&caller_id, contract, asset_hash);
+        let gas_used = tx.apply_metered(&mut trie, 0).unwrap();
+        let gas_cost = gas_cost(&gas_used, &tx.gas_price).unwrap();
+
+        assert!(gas_used > Gas::from_bytes(b"0.0").unwrap());
+        assert!(gas_cost > Balance::zero());
+
+        let creator_cur_key = [creator_addr.as_bytes(), &b"."[..], &asset_hash.0].concat();
+        let creator_nonce_key = [creator_addr.as_bytes(), &b".n"[..]].concat();
+        let contract_cur_key = [contract.as_bytes(), &b"."[..], &asset_hash.0].concat();
+
+        let creator_balance =
+            Balance::from_bytes(&trie.get(&creator_cur_key).unwrap().unwrap()).unwrap();
+        let contract_balance =
+            Balance::from_bytes(&trie.get(&contract_cur_key).unwrap().unwrap()).unwrap();
+        let bin_creator_nonce = trie.get(&creator_nonce_key).unwrap().unwrap();
+
+        // 100 - 40 spent on opening the contract - 30 spent
+        // on the call - the cost of the consumed gas.
+        assert_eq!(
+            creator_balance,
+            Balance::from_bytes(b"30.0").unwrap() - gas_cost
+        );
+        assert_eq!(contract_balance, Balance::from_bytes(b"50.0").unwrap());
+        assert_eq!(bin_creator_nonce.to_vec(), vec![0, 0, 0, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn apply_metered_destroys_a_self_destructing_contract() {
+        let id = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        let (contract, gas_cost) = {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+            let (contract, caller_id) =
+                open_contract_with_code(&mut trie, &id, asset_hash, SUICIDE);
+
+            let tx = call(&caller_id, contract.clone(), asset_hash);
+            let gas_used = tx.apply_metered(&mut trie, 0).unwrap();
+            (contract, gas_cost(&gas_used, &tx.gas_price).unwrap())
+        };
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        let creator_cur_key = [creator_addr.as_bytes(), &b"."[..], &asset_hash.0].concat();
+        let contract_code_key = [contract.as_bytes(), &b".c"[..]].concat();
+        let creator_balance =
+            Balance::from_bytes(&trie.get(&creator_cur_key).unwrap().unwrap()).unwrap();
+
+        // 100 - 40 spent on opening the contract - 30 spent on the
+        // call - the cost of the consumed gas + 50 swept from the
+        // destroyed contract.
+        assert_eq!(
+            creator_balance,
+            Balance::from_bytes(b"80.0").unwrap() - gas_cost
+        );
+        assert_eq!(trie.get(&contract_code_key).unwrap(), None);
+        assert!(crate::contract::is_tombstoned(&trie, &contract));
+    }
+
+    #[test]
+    fn apply_metered_rolls_back_failed_executions() {
+        let id = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        // Manually initialize creator balance
+        test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+        let (contract, caller_id) = open_contract(&mut trie, &id, asset_hash);
+
+        // The code cannot run without any gas
+        let mut tx = call(&caller_id, contract.clone(), asset_hash);
+        tx.gas_limit = Gas::from_bytes(b"0.0").unwrap();
+
+        assert_eq!(tx.apply_metered(&mut trie, 0), Err(VmError::OutOfGas));
+
+        let creator_cur_key = [creator_addr.as_bytes(), &b"."[..], &asset_hash.0].concat();
+        let creator_nonce_key = [creator_addr.as_bytes(), &b".n"[..]].concat();
+        let contract_cur_key = [contract.as_bytes(), &b"."[..], &asset_hash.0].concat();
+
+        let creator_balance =
+            Balance::from_bytes(&trie.get(&creator_cur_key).unwrap().unwrap()).unwrap();
+        let contract_balance =
+            Balance::from_bytes(&trie.get(&contract_cur_key).unwrap().unwrap()).unwrap();
+        let bin_creator_nonce = trie.get(&creator_nonce_key).unwrap().unwrap();
+
+        // The fee and the amount are still debited but
+        // the amount is not credited to the contract.
+        assert_eq!(creator_balance, Balance::from_bytes(b"30.0").unwrap());
+        assert_eq!(contract_balance, Balance::from_bytes(b"30.0").unwrap());
+        assert_eq!(bin_creator_nonce.to_vec(), vec![0, 0, 0, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn gas_cost_is_exact() {
+        let cost = |gas: &[u8], price: &[u8]| {
+            gas_cost(
+                &Gas::from_bytes(gas).unwrap(),
+                &Balance::from_bytes(price).unwrap(),
+            )
+        };
+
+        assert_eq!(
+            cost(b"1000.0", b"0.01"),
+            Some(Balance::from_bytes(b"10").unwrap())
+        );
+        assert_eq!(
+            cost(b"1.5", b"1.5"),
+            Some(Balance::from_bytes(b"2.25").unwrap())
+        );
+        assert_eq!(
+            cost(b"0.000000001", b"0.000000001"),
+            Some(Balance::from_bytes(b"0.000000000000000001").unwrap())
+        );
+        assert_eq!(cost(b"0.0000000001", b"0.000000001"), Some(Balance::zero()));
+        assert_eq!(cost(b"1000000000", b"1000000000"), None);
+        assert_eq!(
+            cost(b"999999999999999999", b"1"),
+            Some(Balance::from_bytes(b"999999999999999999").unwrap())
+        );
+    }
+
     quickcheck! {
         fn serialize_deserialize(tx: Call) -> bool {
             tx == Call::from_bytes(&Call::to_bytes(&tx).unwrap()).unwrap()
@@ -16,10 +16,11 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use crate::journal::JournaledTrieMut;
 use account::{Address, Balance, NormalAddress};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use crypto::{Hash, PublicKey as Pk, SecretKey as Sk, ShortHash, Signature};
-use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
 use persistence::{Codec, DbHasher};
 use rand::Rng;
 use std::io::Cursor;
@@ -60,12 +61,20 @@ impl ChangeMinter {
 
     /// Validates the transaction against the provided state.
     pub fn validate(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
-        let zero = Balance::zero();
-
+        // TODO: Signature verification should be done in batches
+        // and happen before validation.
         if !self.verify_sig() {
             return false;
         }
 
+        self.validate_unsigned(trie)
+    }
+
+    /// Validates the transaction against the provided state
+    /// without verifying its signature.
+    pub fn validate_unsigned(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
+        let zero = Balance::zero();
+
         let bin_asset_hash = &self.asset_hash.0;
         let bin_fee_hash = &self.fee_hash.0;
         let minter_signing_addr = NormalAddress::from_pkey(&self.minter);
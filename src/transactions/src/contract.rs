@@ -0,0 +1,301 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Contract lifecycle helpers shared by the transactions
+//! that create, upgrade and destroy contracts.
+//!
+//! Besides the code (`.c`), state (`.q`) and self payable (`.y`)
+//! entries written on creation, a contract may have the following
+//! entries in the state trie:
+//!
+//! * `<contract-address>.a` - The permanent address of the contract's admin.
+//! This entry exists only if the contract is upgradable.
+//! * `<contract-address>.t` - Tombstone marker. Written when a contract is
+//! destroyed so that its address can never be re-used.
+
+use crate::journal::JournaledTrieMut;
+use account::{Address, Balance, ContractAddress, NormalAddress};
+use crypto::ShortHash;
+use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieIterator};
+use persistence::{Codec, DbHasher};
+use purple_vm::{Code, Validator};
+
+/// Returns `true` if the given contract code, which is the block of the
+/// contract's `main` function, is valid for the given version of the
+/// VM instruction set.
+///
+/// Code deployed with version 1 keeps the rules it has been deployed
+/// with. Later versions validate the code as a module whose only
+/// function is the `main` function.
+pub fn validate_code(code: &[u8], vm_version: u8) -> bool {
+    if vm_version < 2 {
+        Validator::new().validate_block(code)
+    } else {
+        Code::from_main_block(code, vm_version).validate(vm_version)
+    }
+}
+
+/// Returns `true` if the contract with the given address has been destroyed.
+pub fn is_tombstoned(trie: &TrieDB<DbHasher, Codec>, contract: &ContractAddress) -> bool {
+    // Calculate tombstone key
+    //
+    // The key of a contract's tombstone has the following format:
+    // `<contract-address>.t`
+    let tombstone_key = [contract.as_bytes(), &b".t"[..]].concat();
+
+    match trie.get(&tombstone_key) {
+        Ok(Some(_)) => true,
+        Ok(None) => false,
+        Err(err) => panic!(err),
+    }
+}
+
+/// Returns the permanent address of the admin of the given
+/// contract or `None` if the contract is not upgradable.
+pub fn contract_admin(
+    trie: &TrieDB<DbHasher, Codec>,
+    contract: &ContractAddress,
+) -> Option<NormalAddress> {
+    // Calculate admin key
+    //
+    // The key of a contract's admin has the following format:
+    // `<contract-address>.a`
+    let admin_key = [contract.as_bytes(), &b".a"[..]].concat();
+
+    match trie.get(&admin_key) {
+        Ok(Some(admin)) => Some(NormalAddress::from_bytes(&admin).unwrap()),
+        Ok(None) => None,
+        Err(err) => panic!(err),
+    }
+}
+
+/// Destroys the contract with the given address.
+///
+/// All of the contract's balances are swept to the `beneficiary`,
+/// its code, state and nonce entries are removed and a tombstone
+/// is written in their place so that the address cannot be re-used.
+///
+/// The beneficiary account is created if it doesn't exist. This
+/// function returns an error without modifying the state if the
+/// contract does not exist or if the beneficiary does not exist
+/// and its address is not a normal one.
+pub fn self_destruct(
+    trie: &mut TrieDBMut<DbHasher, Codec>,
+    contract: &ContractAddress,
+    beneficiary: &Address,
+) -> Result<(), &'static str> {
+    let bin_contract = contract.as_bytes();
+    let bin_beneficiary = beneficiary.as_bytes();
+
+    let code_key = [bin_contract, &b".c"[..]].concat();
+    let state_key = [bin_contract, &b".q"[..]].concat();
+    let self_payable_key = [bin_contract, &b".y"[..]].concat();
+    let admin_key = [bin_contract, &b".a"[..]].concat();
+    let nonce_key = [bin_contract, &b".n"[..]].concat();
+    let tombstone_key = [bin_contract, &b".t"[..]].concat();
+    let beneficiary_nonce_key = [bin_beneficiary, &b".n"[..]].concat();
+
+    if let Ok(None) = trie.get(&nonce_key) {
+        return Err("The destroyed contract does not exist in the ledger!");
+    }
+
+    // Calculate currency key prefix
+    //
+    // The key of a currency entry has the following format:
+    // `<account-address>.<currency-hash>`
+    let cur_key_prefix = [bin_contract, &b"."[..]].concat();
+
+    // Find the balances of the contract by enumerating the
+    // currency entries stored under its address.
+    let balances = {
+        let root = *trie.root();
+        let state = TrieDB::<DbHasher, Codec>::new(trie.db(), &root)
+            .map_err(|_| "Could not read the state trie!")?;
+        let mut iter = state
+            .iter()
+            .map_err(|_| "Could not iterate the state trie!")?;
+        let mut balances = Vec::new();
+
+        iter.seek(&cur_key_prefix)
+            .map_err(|_| "Could not iterate the state trie!")?;
+
+        for entry in iter {
+            let (key, balance) = entry.map_err(|_| "Could not iterate the state trie!")?;
+
+            if !key.starts_with(&cur_key_prefix) {
+                break;
+            }
+
+            if key.len() == cur_key_prefix.len() + 8 {
+                balances.push((key, balance.to_vec()));
+            }
+        }
+
+        balances
+    };
+
+    // Create the beneficiary account if it doesn't exist
+    if let Ok(None) = trie.get(&beneficiary_nonce_key) {
+        if let Address::Normal(_) = beneficiary {
+            let beneficiary_addr_mapping_key = [bin_beneficiary, &b".am"[..]].concat();
+
+            trie.insert(&beneficiary_nonce_key, &[0, 0, 0, 0, 0, 0, 0, 0])
+                .unwrap();
+            trie.insert(&beneficiary_addr_mapping_key, bin_beneficiary)
+                .unwrap();
+        } else {
+            return Err("The beneficiary does not exist and its address is not a normal one!");
+        }
+    }
+
+    // Sweep balances
+    for (contract_cur_key, balance) in balances.iter() {
+        let asset_hash = &contract_cur_key[cur_key_prefix.len()..];
+        let beneficiary_cur_key = [bin_beneficiary, &b"."[..], asset_hash].concat();
+        let contract_balance = unwrap!(
+            Balance::from_bytes(balance),
+            "Invalid stored balance format"
+        );
+
+        let beneficiary_balance = match trie.get(&beneficiary_cur_key) {
+            Ok(Some(balance)) => {
+                unwrap!(
+                    Balance::from_bytes(&balance),
+                    "Invalid stored balance format"
+                ) + contract_balance
+            }
+            Ok(None) => contract_balance,
+            Err(err) => panic!(err),
+        };
+
+        trie.insert(&beneficiary_cur_key, &beneficiary_balance.to_bytes())
+            .unwrap();
+        trie.remove(contract_cur_key).unwrap();
+    }
+
+    // Clear contract entries
+    trie.remove(&code_key).unwrap();
+    trie.remove(&state_key).unwrap();
+    trie.remove(&self_payable_key).unwrap();
+    trie.remove(&admin_key).unwrap();
+    trie.remove(&nonce_key).unwrap();
+
+    // Tombstone address
+    trie.insert(&tombstone_key, &[1]).unwrap();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::Identity;
+
+    #[test]
+    fn self_destruct_sweeps_balances_and_tombstones() {
+        let id = Identity::new();
+        let beneficiary = NormalAddress::from_pkey(id.pkey());
+        let contract = ContractAddress::new(crypto::hash_slice(b"test_contract"));
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+        let asset_hash2 = crypto::hash_slice(b"Test currency 2").to_short();
+        let bin_contract = contract.as_bytes();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+            let contract_cur_key = [bin_contract, &b"."[..], &asset_hash.0].concat();
+            let contract_cur_key2 = [bin_contract, &b"."[..], &asset_hash2.0].concat();
+
+            // Manually initialize contract
+            trie.insert(
+                &[bin_contract, &b".n"[..]].concat(),
+                &[0, 0, 0, 0, 0, 0, 0, 0],
+            )
+            .unwrap();
+            trie.insert(&[bin_contract, &b".c"[..]].concat(), &[0x00])
+                .unwrap();
+            trie.insert(&[bin_contract, &b".q"[..]].concat(), &[0x01])
+                .unwrap();
+            trie.insert(&[bin_contract, &b".y"[..]].concat(), &[1])
+                .unwrap();
+            trie.insert(
+                &contract_cur_key,
+                &Balance::from_bytes(b"10.0").unwrap().to_bytes(),
+            )
+            .unwrap();
+            trie.insert(
+                &contract_cur_key2,
+                &Balance::from_bytes(b"20.0").unwrap().to_bytes(),
+            )
+            .unwrap();
+
+            // Manually initialize beneficiary balance
+            test_helpers::init_balance(&mut trie, beneficiary.clone(), asset_hash, b"5.0");
+
+            self_destruct(&mut trie, &contract, &Address::Normal(beneficiary)).unwrap();
+        }
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        let beneficiary_cur_key = [beneficiary.as_bytes(), &b"."[..], &asset_hash.0].concat();
+        let beneficiary_cur_key2 = [beneficiary.as_bytes(), &b"."[..], &asset_hash2.0].concat();
+
+        assert_eq!(
+            Balance::from_bytes(&trie.get(&beneficiary_cur_key).unwrap().unwrap()).unwrap(),
+            Balance::from_bytes(b"15.0").unwrap()
+        );
+        assert_eq!(
+            Balance::from_bytes(&trie.get(&beneficiary_cur_key2).unwrap().unwrap()).unwrap(),
+            Balance::from_bytes(b"20.0").unwrap()
+        );
+
+        let suffixes: Vec<&[u8]> = vec![&b".n"[..], &b".c"[..], &b".q"[..], &b".y"[..]];
+
+        for suffix in suffixes {
+            assert_eq!(trie.get(&[bin_contract, suffix].concat()).unwrap(), None);
+        }
+
+        for asset_hash in [asset_hash, asset_hash2].iter() {
+            let contract_cur_key = [bin_contract, &b"."[..], &asset_hash.0].concat();
+            assert_eq!(trie.get(&contract_cur_key).unwrap(), None);
+        }
+
+        assert!(is_tombstoned(&trie, &contract));
+    }
+
+    #[test]
+    fn self_destruct_fails_on_non_existing_contract() {
+        let id = Identity::new();
+        let beneficiary = NormalAddress::from_pkey(id.pkey());
+        let contract = ContractAddress::new(crypto::hash_slice(b"test_contract"));
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+            assert!(self_destruct(&mut trie, &contract, &Address::Normal(beneficiary)).is_err());
+        }
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+
+        assert!(!is_tombstoned(&trie, &contract));
+        assert_eq!(root, ShortHash::NULL_RLP);
+    }
+}
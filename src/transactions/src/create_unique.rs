@@ -16,11 +16,12 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use crate::journal::JournaledTrieMut;
 use account::{Address, Balance, NormalAddress};
 use bitvec::Bits;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use crypto::{Hash, PublicKey as Pk, SecretKey as Sk, ShortHash, Signature};
-use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
 use persistence::{Codec, DbHasher};
 use rand::Rng;
 use std::io::Cursor;
@@ -74,6 +75,12 @@ impl CreateUnique {
         unimplemented!();
     }
 
+    /// Validates the transaction against the provided state
+    /// without verifying its signature.
+    pub fn validate_unsigned(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
+        unimplemented!();
+    }
+
     /// Applies the burn transaction to the provided database.
     pub fn apply(&self, trie: &mut TrieDBMut<DbHasher, Codec>) {
         unimplemented!();
@@ -0,0 +1,99 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Protocol features which change the consensus rules. Each feature
+/// is activated at the height scheduled for it by the chain spec so
+/// that nodes switch to the new rules at the same block.
+pub enum Feature {
+    /// Allows contracts to be upgraded and destroyed by their admin
+    /// via `UpgradeContract` and `DestroyContract` transactions.
+    ContractLifecycle,
+
+    /// Requires the timestamp of a block to be greater than the median
+    /// timestamp of the recent blocks and not too far in the future.
+    BlockTimestamps,
+
+    /// Rotates the transaction block slots between the miners of the
+    /// latest checkpoint blocks instead of granting all of them to the
+    /// miner of the last one.
+    ValidatorRotation,
+
+    /// Validates the code of deployed and upgraded contracts against
+    /// version 2 of the VM instruction set, which adds the linear
+    /// memory instructions and checks the calls between functions.
+    VmVersion2,
+}
+
+impl Feature {
+    /// All known features.
+    pub const ALL: &'static [Feature] = &[
+        Feature::ContractLifecycle,
+        Feature::BlockTimestamps,
+        Feature::ValidatorRotation,
+        Feature::VmVersion2,
+    ];
+
+    /// Returns the name of the feature as used in chain specs.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Feature::ContractLifecycle => "contract_lifecycle",
+            Feature::BlockTimestamps => "block_timestamps",
+            Feature::ValidatorRotation => "validator_rotation",
+            Feature::VmVersion2 => "vm_version_2",
+        }
+    }
+
+    /// Returns the version of the VM instruction set that contract
+    /// code is validated against, given whether each feature is active.
+    pub fn vm_version(is_active: impl Fn(Feature) -> bool) -> u8 {
+        if is_active(Feature::VmVersion2) {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Returns the feature with the given name.
+    pub fn from_name(name: &str) -> Option<Feature> {
+        Self::ALL
+            .iter()
+            .find(|feature| feature.name() == name)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_feature_names() {
+        for feature in Feature::ALL {
+            assert_eq!(Feature::from_name(feature.name()), Some(*feature));
+        }
+
+        assert_eq!(Feature::from_name("unknown"), None);
+    }
+
+    #[test]
+    fn it_returns_the_active_vm_version() {
+        assert_eq!(Feature::vm_version(|_| false), 1);
+        assert_eq!(Feature::vm_version(|_| true), purple_vm::VM_VERSION);
+    }
+}
@@ -16,9 +16,10 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use crate::journal::JournaledTrieMut;
 use account::{Balance, NormalAddress};
 use crypto::{Hash, ShortHash};
-use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
 use persistence::{Codec, DbHasher};
 use std::default::Default;
 
@@ -29,74 +30,217 @@ use crate::TestAccount;
 pub const MAIN_CUR_NAME: &'static [u8] = b"purple";
 
 /// The main currency coin supply
-pub(crate) const COIN_SUPPLY: u64 = 500000000;
+pub const COIN_SUPPLY: u64 = 500000000;
 
 /// Balances that will be initialized with the genesis transaction
 pub(crate) const INIT_ACCOUNTS: &'static [(&'static str, u64)] = &[];
 
+#[derive(Debug, Clone, PartialEq)]
+/// A currency created by the genesis transaction.
+pub struct GenesisCurrency {
+    /// The name of the currency
+    pub name: Vec<u8>,
+
+    /// The total supply of the currency
+    pub coin_supply: u64,
+
+    /// Balances that are initialized from the supply. The
+    /// remaining supply is assigned to the coinbase.
+    pub allocations: Vec<(NormalAddress, u64)>,
+}
+
+impl GenesisCurrency {
+    /// Returns the hash of the currency.
+    pub fn asset_hash(&self) -> ShortHash {
+        crypto::hash_slice(&self.name).to_short()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Genesis {
-    asset_hash: ShortHash,
-    coin_supply: u64,
+    /// The created currencies. The first one is the main currency.
+    currencies: Vec<GenesisCurrency>,
 }
 
 impl Default for Genesis {
     fn default() -> Genesis {
-        let main_asset_hash = crypto::hash_slice(MAIN_CUR_NAME).to_short();
+        #[cfg(any(test, feature = "test"))]
+        let allocations: Vec<(NormalAddress, u64)> = vec![
+            (TestAccount::A.to_perm_address(), 100000),
+            (TestAccount::B.to_perm_address(), 100000),
+            (TestAccount::C.to_perm_address(), 100000),
+        ];
+
+        #[cfg(not(any(test, feature = "test")))]
+        let allocations: Vec<(NormalAddress, u64)> = INIT_ACCOUNTS
+            .iter()
+            .map(|(a, b)| (NormalAddress::from_base58(a).unwrap(), *b))
+            .collect();
 
         Genesis {
-            coin_supply: COIN_SUPPLY,
-            asset_hash: main_asset_hash,
+            currencies: vec![GenesisCurrency {
+                name: MAIN_CUR_NAME.to_vec(),
+                coin_supply: COIN_SUPPLY,
+                allocations,
+            }],
         }
     }
 }
 
 impl Genesis {
-    /// Applies the genesis transaction to the provided database.
+    /// Creates a genesis transaction creating the given currencies,
+    /// the first of which is the main currency.
     ///
-    /// This function will panic if the treasury account already exists.
-    pub fn apply(&self, trie: &mut TrieDBMut<DbHasher, Codec>) {
-        let bin_asset_hash = &self.asset_hash.0;
-        let coin_supply = Balance::from_u64(self.coin_supply).to_bytes();
-        let mut coinbase_supply = COIN_SUPPLY;
+    /// Returns `Err(_)` if there are no currencies, if two currencies
+    /// have the same name or if the allocations of a currency exceed
+    /// its supply.
+    pub fn new(currencies: Vec<GenesisCurrency>) -> Result<Genesis, &'static str> {
+        if currencies.is_empty() {
+            return Err("The genesis must create at least one currency");
+        }
 
-        #[cfg(any(test, feature = "test"))]
-        let iterable_vec: Vec<(NormalAddress, u64)> = vec![
-            (TestAccount::A.to_perm_address(), 100000),
-            (TestAccount::B.to_perm_address(), 100000),
-            (TestAccount::C.to_perm_address(), 100000),
-        ];
+        for (i, currency) in currencies.iter().enumerate() {
+            if currency.name.is_empty() {
+                return Err("Invalid currency name");
+            }
 
-        #[cfg(any(test, feature = "test"))]
-        let iterable = iterable_vec.iter();
+            if currencies[..i].iter().any(|c| c.name == currency.name) {
+                return Err("Duplicate currency name");
+            }
 
-        #[cfg(not(any(test, feature = "test")))]
-        let iterable = INIT_ACCOUNTS
-            .iter()
-            .map(|(a, b)| (NormalAddress::from_base58(a).unwrap(), b));
+            let mut allocated: u64 = 0;
+
+            for (_, balance) in currency.allocations.iter() {
+                allocated = allocated
+                    .checked_add(*balance)
+                    .ok_or("Allocations exceed the coin supply")?;
+            }
+
+            if allocated > currency.coin_supply {
+                return Err("Allocations exceed the coin supply");
+            }
+        }
+
+        Ok(Genesis { currencies })
+    }
+
+    /// Returns the currencies created by the genesis transaction.
+    pub fn currencies(&self) -> &[GenesisCurrency] {
+        &self.currencies
+    }
 
-        // Write initial balances
-        for (addr, balance) in iterable {
-            if *balance > coinbase_supply {
-                panic!("We are assigning more coins than there are in the coinbase! This shouldn't ever happen...");
+    /// Returns the hash of the main currency.
+    pub fn main_asset_hash(&self) -> ShortHash {
+        self.currencies[0].asset_hash()
+    }
+
+    /// Applies the genesis transaction to the provided database.
+    ///
+    /// This function will panic if the treasury account already exists.
+    pub fn apply(&self, trie: &mut TrieDBMut<DbHasher, Codec>) {
+        for currency in self.currencies.iter() {
+            let asset_hash = currency.asset_hash();
+            let bin_asset_hash = &asset_hash.0;
+            let mut coinbase_supply = currency.coin_supply;
+
+            // Write initial balances
+            for (addr, balance) in currency.allocations.iter() {
+                if *balance > coinbase_supply {
+                    panic!("We are assigning more coins than there are in the coinbase! This shouldn't ever happen...");
+                }
+
+                coinbase_supply -= balance;
+
+                let nonce_key = [addr.as_bytes(), &b".n"[..]].concat();
+                let addr_mapping_key = [addr.as_bytes(), &b".am"[..]].concat();
+                let cur_key = [addr.as_bytes(), &b"."[..], bin_asset_hash].concat();
+                let balance = Balance::from_u64(*balance).to_bytes();
+
+                trie.insert(&nonce_key, &[0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+                trie.insert(&cur_key, &balance).unwrap();
+                trie.insert(&addr_mapping_key, addr.as_bytes()).unwrap();
             }
 
-            coinbase_supply -= balance;
+            // Insert coinbase supply
+            let coinbase_cur_key = [&b"coinbase."[..], bin_asset_hash].concat();
+            let balance = Balance::from_u64(coinbase_supply).to_bytes();
 
-            let nonce_key = [addr.as_bytes(), &b".n"[..]].concat();
-            let addr_mapping_key = [addr.as_bytes(), &b".am"[..]].concat();
-            let cur_key = [addr.as_bytes(), &b"."[..], bin_asset_hash].concat();
-            let balance = Balance::from_u64(*balance).to_bytes();
+            trie.insert(&coinbase_cur_key, &balance).unwrap();
+        }
+    }
+}
 
-            trie.insert(&nonce_key, &[0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
-            trie.insert(&cur_key, &balance).unwrap();
-            trie.insert(&addr_mapping_key, addr.as_bytes()).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_applies_custom_currencies() {
+        let address = TestAccount::A.to_perm_address();
+        let genesis = Genesis::new(vec![
+            GenesisCurrency {
+                name: b"main".to_vec(),
+                coin_supply: 1000,
+                allocations: vec![(address.clone(), 400)],
+            },
+            GenesisCurrency {
+                name: b"other".to_vec(),
+                coin_supply: 50,
+                allocations: Vec::new(),
+            },
+        ])
+        .unwrap();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+            genesis.apply(&mut trie);
         }
 
-        // Insert coinbase supply
-        let coinbase_cur_key = [&b"coinbase."[..], bin_asset_hash].concat();
-        let balance = Balance::from_u64(coinbase_supply).to_bytes();
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        let balance =
+            |key: Vec<u8>| Balance::from_bytes(&trie.get(&key).unwrap().unwrap()).unwrap();
+        let main_hash = genesis.main_asset_hash();
+        let other_hash = genesis.currencies()[1].asset_hash();
+
+        assert_eq!(main_hash, crypto::hash_slice(b"main").to_short());
+        assert_eq!(
+            balance([address.as_bytes(), &b"."[..], &main_hash.0].concat()),
+            Balance::from_u64(400)
+        );
+        assert_eq!(
+            balance([&b"coinbase."[..], &main_hash.0].concat()),
+            Balance::from_u64(600)
+        );
+        assert_eq!(
+            balance([&b"coinbase."[..], &other_hash.0].concat()),
+            Balance::from_u64(50)
+        );
+        assert!(trie
+            .get(&[address.as_bytes(), &b"."[..], &other_hash.0].concat())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn it_rejects_invalid_currencies() {
+        let currency = GenesisCurrency {
+            name: b"main".to_vec(),
+            coin_supply: 100,
+            allocations: vec![(TestAccount::A.to_perm_address(), 101)],
+        };
+
+        assert!(Genesis::new(Vec::new()).is_err());
+        assert!(Genesis::new(vec![currency.clone()]).is_err());
+
+        let currency = GenesisCurrency {
+            allocations: Vec::new(),
+            ..currency
+        };
 
-        trie.insert(&coinbase_cur_key, &balance).unwrap();
+        assert!(Genesis::new(vec![currency.clone()]).is_ok());
+        assert!(Genesis::new(vec![currency.clone(), currency]).is_err());
     }
 }
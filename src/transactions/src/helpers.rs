@@ -16,11 +16,12 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
-use crate::{Send, Tx};
-use account::{Address, Balance, NormalAddress};
+use crate::{Call, OpenContract, Send, Tx};
+use account::{Address, Balance, ContractAddress, NormalAddress};
 use constants::*;
 use crypto::{PublicKey, SecretKey};
 use lazy_static::*;
+use purple_vm::Gas;
 use triomphe::Arc;
 
 #[cfg(any(test, feature = "test"))]
@@ -118,6 +119,83 @@ pub fn send_coins(
     Tx::Send(tx, byte_size)
 }
 
+#[cfg(any(test, feature = "test"))]
+/// Helper to create test `OpenContract` transactions from
+/// the genesis test accounts.
+///
+/// The created contract receives `amount` coins and the fee
+/// is paid in the main currency.
+pub fn open_contract(
+    creator: TestAccount,
+    code: &[u8],
+    default_state: &[u8],
+    amount: u64,
+    fee: u64,
+    creator_nonce: u64,
+) -> Tx {
+    let main_asset_hash = crypto::hash_slice(crate::genesis::MAIN_CUR_NAME).to_short();
+
+    let mut tx = OpenContract {
+        creator: creator.to_pkey(creator_nonce),
+        next_address: creator.to_signing_addr(creator_nonce + 1),
+        code: code.to_vec(),
+        default_state: default_state.to_vec(),
+        amount: Balance::from_u64(amount),
+        asset_hash: main_asset_hash,
+        fee: Balance::from_u64(fee),
+        fee_hash: main_asset_hash,
+        self_payable: true,
+        admin: None,
+        nonce: creator_nonce,
+        address: None,
+        signature: None,
+        hash: None,
+    };
+
+    tx.compute_address();
+    tx.sign(creator.to_skey(creator_nonce));
+    tx.compute_hash();
+    let byte_size = tx.to_bytes().unwrap().len() - 1;
+    Tx::OpenContract(tx, byte_size)
+}
+
+#[cfg(any(test, feature = "test"))]
+/// Helper to create test `Call` transactions from the
+/// genesis test accounts.
+///
+/// The called contract receives `amount` coins and the fee
+/// is paid in the main currency.
+pub fn call_contract(
+    caller: TestAccount,
+    contract: ContractAddress,
+    amount: u64,
+    fee: u64,
+    caller_nonce: u64,
+) -> Tx {
+    let main_asset_hash = crypto::hash_slice(crate::genesis::MAIN_CUR_NAME).to_short();
+
+    let mut tx = Call {
+        from: caller.to_pkey(caller_nonce),
+        next_address: caller.to_signing_addr(caller_nonce + 1),
+        to: contract,
+        inputs: String::new(),
+        amount: Balance::from_u64(amount),
+        fee: Balance::from_u64(fee),
+        gas_price: Balance::from_bytes(b"0.000001").unwrap(),
+        gas_limit: Gas::from_bytes(b"1000000.0").unwrap(),
+        asset_hash: main_asset_hash,
+        fee_hash: main_asset_hash,
+        nonce: caller_nonce,
+        signature: None,
+        hash: None,
+    };
+
+    tx.sign(caller.to_skey(caller_nonce));
+    tx.compute_hash();
+    let byte_size = tx.to_bytes().unwrap().len() - 1;
+    Tx::Call(tx, byte_size)
+}
+
 #[cfg(any(test, feature = "test"))]
 pub fn get_tx_list_of_size(size: usize) -> Result<Vec<Arc<Tx>>, &'static str> {
     if (size == 0 || size > MAX_TX_SET_SIZE) {
@@ -0,0 +1,239 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Recording of the writes made by transactions to the state trie.
+//!
+//! Transactions write to the trie through `JournaledTrieMut` which
+//! shadows the methods of `TrieMut`. While a recording started with
+//! `record_writes` is active, every insert and removal also stores the
+//! previous value of the written key in a `Journal`. Journals are used
+//! to roll back failed contract calls and to report which entries a
+//! simulated transaction changed without walking the whole trie.
+
+use crypto::ShortHash;
+use patricia_trie::{DBValue, NodeCodec, TrieDBMut};
+use persistence::{Codec, DbHasher};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+type TrieResult<T> = patricia_trie::Result<T, ShortHash, <Codec as NodeCodec<DbHasher>>::Error>;
+
+/// A key paired with the value it had before it was written.
+type Entry = (Vec<u8>, Option<DBValue>);
+
+thread_local! {
+    /// The entries of the active recordings, innermost last.
+    static RECORDINGS: RefCell<Vec<Vec<Entry>>> = RefCell::new(Vec::new());
+}
+
+/// The writes made to a trie during a recording.
+#[derive(Clone, Debug, Default)]
+pub struct Journal {
+    entries: Vec<Entry>,
+}
+
+impl Journal {
+    /// Returns `true` if nothing was written during the recording.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the written keys along with the values they had
+    /// before the recording started, ordered by key.
+    pub fn changes(&self) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+        let mut changes = BTreeMap::new();
+
+        for (key, old_value) in self.entries.iter() {
+            changes
+                .entry(key.clone())
+                .or_insert_with(|| old_value.as_ref().map(|value| value.to_vec()));
+        }
+
+        changes.into_iter().collect()
+    }
+
+    /// Restores the values of the written keys, undoing the writes
+    /// in reverse order.
+    pub fn revert(self, trie: &mut TrieDBMut<DbHasher, Codec>) {
+        for (key, old_value) in self.entries.into_iter().rev() {
+            match old_value {
+                Some(value) => {
+                    trie.insert(&key, &value).unwrap();
+                }
+                None => {
+                    trie.remove(&key).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Pops the innermost recording when dropped so that a panic
+/// does not leave a stale recording behind.
+struct Recording;
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        RECORDINGS.with(|recordings| {
+            recordings.borrow_mut().pop();
+        });
+    }
+}
+
+/// Calls `f` and returns its result along with the journal of the
+/// trie writes it made on the current thread.
+///
+/// Recordings can be nested in which case the writes are recorded
+/// in all of the active journals.
+pub fn record_writes<T>(f: impl FnOnce() -> T) -> (T, Journal) {
+    RECORDINGS.with(|recordings| recordings.borrow_mut().push(Vec::new()));
+    let recording = Recording;
+    let result = f();
+    let entries = RECORDINGS.with(|recordings| {
+        std::mem::replace(recordings.borrow_mut().last_mut().unwrap(), Vec::new())
+    });
+
+    drop(recording);
+    (result, Journal { entries })
+}
+
+fn record_write(key: &[u8], old_value: &Option<DBValue>) {
+    RECORDINGS.with(|recordings| {
+        for entries in recordings.borrow_mut().iter_mut() {
+            entries.push((key.to_vec(), old_value.clone()));
+        }
+    });
+}
+
+/// The methods of `TrieMut` used by transactions. Inserts and
+/// removals are recorded in the active journals.
+///
+/// This is meant to be imported instead of `TrieMut`.
+pub trait JournaledTrieMut {
+    fn root(&mut self) -> &ShortHash;
+    fn is_empty(&self) -> bool;
+    fn contains(&self, key: &[u8]) -> TrieResult<bool>;
+    fn get<'a, 'key>(&'a self, key: &'key [u8]) -> TrieResult<Option<DBValue>>
+    where
+        'a: 'key;
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> TrieResult<Option<DBValue>>;
+    fn remove(&mut self, key: &[u8]) -> TrieResult<Option<DBValue>>;
+}
+
+impl<'db> JournaledTrieMut for TrieDBMut<'db, DbHasher, Codec> {
+    fn root(&mut self) -> &ShortHash {
+        patricia_trie::TrieMut::root(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        patricia_trie::TrieMut::is_empty(self)
+    }
+
+    fn contains(&self, key: &[u8]) -> TrieResult<bool> {
+        patricia_trie::TrieMut::contains(self, key)
+    }
+
+    fn get<'a, 'key>(&'a self, key: &'key [u8]) -> TrieResult<Option<DBValue>>
+    where
+        'a: 'key,
+    {
+        patricia_trie::TrieMut::get(self, key)
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> TrieResult<Option<DBValue>> {
+        let old_value = patricia_trie::TrieMut::insert(self, key, value)?;
+        record_write(key, &old_value);
+        Ok(old_value)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> TrieResult<Option<DBValue>> {
+        let old_value = patricia_trie::TrieMut::remove(self, key)?;
+
+        // Removing a missing key does not change the trie
+        if old_value.is_some() {
+            record_write(key, &old_value);
+        }
+
+        Ok(old_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_records_and_reverts_writes() {
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        trie.insert(b"a", b"1").unwrap();
+        trie.insert(b"b", b"2").unwrap();
+        let snapshot = *trie.root();
+
+        let (_, journal) = record_writes(|| {
+            trie.insert(b"a", b"3").unwrap();
+            trie.insert(b"a", b"4").unwrap();
+            trie.insert(b"c", b"5").unwrap();
+            trie.remove(b"b").unwrap();
+            trie.remove(b"d").unwrap();
+        });
+
+        assert_eq!(
+            journal.changes(),
+            vec![
+                (b"a".to_vec(), Some(b"1".to_vec())),
+                (b"b".to_vec(), Some(b"2".to_vec())),
+                (b"c".to_vec(), None),
+            ]
+        );
+
+        journal.revert(&mut trie);
+
+        assert_eq!(*trie.root(), snapshot);
+        assert_eq!(&trie.get(b"a").unwrap().unwrap()[..], b"1");
+        assert_eq!(&trie.get(b"b").unwrap().unwrap()[..], b"2");
+        assert!(trie.get(b"c").unwrap().is_none());
+    }
+
+    #[test]
+    fn it_records_nested_writes_in_all_journals() {
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        let (inner, outer) = record_writes(|| {
+            trie.insert(b"a", b"1").unwrap();
+            let (_, inner) = record_writes(|| {
+                trie.insert(b"b", b"2").unwrap();
+            });
+            inner
+        });
+
+        assert_eq!(inner.changes(), vec![(b"b".to_vec(), None)]);
+        assert_eq!(
+            outer.changes(),
+            vec![(b"a".to_vec(), None), (b"b".to_vec(), None)]
+        );
+
+        // Writes made outside of a recording are not recorded
+        trie.insert(b"c", b"3").unwrap();
+        assert!(RECORDINGS.with(|recordings| recordings.borrow().is_empty()));
+    }
+}
@@ -36,31 +36,43 @@ mod macros;
 mod burn;
 mod call;
 mod change_minter;
+mod contract;
 mod create_currency;
 mod create_mintable;
 mod create_unique;
+mod destroy_contract;
+mod feature;
 mod genesis;
 mod helpers;
+mod journal;
 mod mint;
 mod open_contract;
 mod send;
+mod upgrade_contract;
 
 pub use crate::burn::*;
 pub use crate::call::*;
 pub use crate::change_minter::*;
+pub use crate::contract::*;
 pub use crate::create_currency::*;
 pub use crate::create_mintable::*;
 pub use crate::create_unique::*;
+pub use crate::destroy_contract::*;
+pub use crate::feature::*;
 pub use crate::genesis::*;
 pub use crate::helpers::*;
+pub use crate::journal::*;
 pub use crate::mint::*;
 pub use crate::open_contract::*;
 pub use crate::send::*;
+pub use crate::upgrade_contract::*;
 
+use crate::journal::JournaledTrieMut;
 use account::{Address, Balance, NormalAddress};
 use crypto::{FromBase58, Hash, Identity, PublicKey, SecretKey, ShortHash};
-use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
 use persistence::{Codec, DbHasher};
+use purple_vm::{Gas, VmError};
 use quickcheck::Arbitrary;
 use rand::Rng;
 
@@ -75,13 +87,40 @@ pub enum Tx {
     Mint(Mint, usize),
     CreateUnique(CreateUnique, usize),
     ChangeMinter(ChangeMinter, usize),
+    UpgradeContract(UpgradeContract, usize),
+    DestroyContract(DestroyContract, usize),
 }
 
 impl Tx {
-    pub fn validate(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
+    /// Returns the protocol feature which enables
+    /// the transaction type, if there is any.
+    pub fn feature(&self) -> Option<Feature> {
+        match *self {
+            Tx::UpgradeContract(_, _) | Tx::DestroyContract(_, _) => {
+                Some(Feature::ContractLifecycle)
+            }
+            _ => None,
+        }
+    }
+
+    /// Validates the transaction against the provided state. The
+    /// given closure returns whether a protocol feature is active
+    /// at the height the transaction is validated at, which also
+    /// determines the VM version that contract code must target.
+    pub fn validate(
+        &self,
+        trie: &TrieDB<DbHasher, Codec>,
+        is_active: impl Fn(Feature) -> bool,
+    ) -> bool {
+        if !self.is_enabled(&is_active) {
+            return false;
+        }
+
+        let vm_version = Feature::vm_version(&is_active);
+
         match *self {
             Tx::Call(ref tx, _) => tx.validate(trie),
-            Tx::OpenContract(ref tx, _) => tx.validate(trie),
+            Tx::OpenContract(ref tx, _) => tx.validate(trie, vm_version),
             Tx::Send(ref tx, _) => tx.validate(trie),
             Tx::Burn(ref tx, _) => tx.validate(trie),
             Tx::CreateCurrency(ref tx, _) => tx.validate(trie),
@@ -89,6 +128,43 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.validate(trie),
             Tx::CreateUnique(ref tx, _) => tx.validate(trie),
             Tx::ChangeMinter(ref tx, _) => tx.validate(trie),
+            Tx::UpgradeContract(ref tx, _) => tx.validate(trie, vm_version),
+            Tx::DestroyContract(ref tx, _) => tx.validate(trie),
+        }
+    }
+
+    /// Validates the transaction against the provided
+    /// state without verifying its signature.
+    pub fn validate_unsigned(
+        &self,
+        trie: &TrieDB<DbHasher, Codec>,
+        is_active: impl Fn(Feature) -> bool,
+    ) -> bool {
+        if !self.is_enabled(&is_active) {
+            return false;
+        }
+
+        let vm_version = Feature::vm_version(&is_active);
+
+        match *self {
+            Tx::Call(ref tx, _) => tx.validate_unsigned(trie),
+            Tx::OpenContract(ref tx, _) => tx.validate_unsigned(trie, vm_version),
+            Tx::Send(ref tx, _) => tx.validate_unsigned(trie),
+            Tx::Burn(ref tx, _) => tx.validate_unsigned(trie),
+            Tx::CreateCurrency(ref tx, _) => tx.validate_unsigned(trie),
+            Tx::CreateMintable(ref tx, _) => tx.validate_unsigned(trie),
+            Tx::Mint(ref tx, _) => tx.validate_unsigned(trie),
+            Tx::CreateUnique(ref tx, _) => tx.validate_unsigned(trie),
+            Tx::ChangeMinter(ref tx, _) => tx.validate_unsigned(trie),
+            Tx::UpgradeContract(ref tx, _) => tx.validate_unsigned(trie, vm_version),
+            Tx::DestroyContract(ref tx, _) => tx.validate_unsigned(trie),
+        }
+    }
+
+    fn is_enabled(&self, is_active: &impl Fn(Feature) -> bool) -> bool {
+        match self.feature() {
+            Some(feature) => is_active(feature),
+            None => true,
         }
     }
 
@@ -103,6 +179,28 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.apply(trie),
             Tx::CreateUnique(ref tx, _) => tx.apply(trie),
             Tx::ChangeMinter(ref tx, _) => tx.apply(trie),
+            Tx::UpgradeContract(ref tx, _) => tx.apply(trie),
+            Tx::DestroyContract(ref tx, _) => tx.apply(trie),
+        }
+    }
+
+    /// Applies the transaction to the provided state and returns
+    /// the amount of gas consumed by contract execution.
+    ///
+    /// Only `Call` transactions execute contract code so this
+    /// returns zero gas for every other transaction type. The
+    /// given timestamp is the current time observed by the code.
+    pub fn apply_metered(
+        &self,
+        trie: &mut TrieDBMut<DbHasher, Codec>,
+        timestamp: i64,
+    ) -> Result<Gas, VmError> {
+        match *self {
+            Tx::Call(ref tx, _) => tx.apply_metered(trie, timestamp),
+            _ => {
+                self.apply(trie);
+                Ok(Gas::from_bytes(b"0.0").unwrap())
+            }
         }
     }
 
@@ -117,6 +215,8 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.to_bytes().unwrap(),
             Tx::CreateUnique(ref tx, _) => tx.to_bytes().unwrap(),
             Tx::ChangeMinter(ref tx, _) => tx.to_bytes().unwrap(),
+            Tx::UpgradeContract(ref tx, _) => tx.to_bytes().unwrap(),
+            Tx::DestroyContract(ref tx, _) => tx.to_bytes().unwrap(),
         }
     }
 
@@ -148,6 +248,14 @@ impl Tx {
                 bytes.len() - 1,
             )),
             Mint::TX_TYPE => Ok(Tx::Mint(Mint::from_bytes(bytes)?, bytes.len() - 1)),
+            UpgradeContract::TX_TYPE => Ok(Tx::UpgradeContract(
+                UpgradeContract::from_bytes(bytes)?,
+                bytes.len() - 1,
+            )),
+            DestroyContract::TX_TYPE => Ok(Tx::DestroyContract(
+                DestroyContract::from_bytes(bytes)?,
+                bytes.len() - 1,
+            )),
             OpenContract::TX_TYPE => Ok(Tx::OpenContract(
                 OpenContract::from_bytes(bytes)?,
                 bytes.len() - 1,
@@ -167,6 +275,8 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.compute_hash_message(),
             Tx::CreateUnique(ref tx, _) => tx.compute_hash_message(),
             Tx::ChangeMinter(ref tx, _) => tx.compute_hash_message(),
+            Tx::UpgradeContract(ref tx, _) => tx.compute_hash_message(),
+            Tx::DestroyContract(ref tx, _) => tx.compute_hash_message(),
         }
     }
 
@@ -181,6 +291,8 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.hash,
             Tx::CreateUnique(ref tx, _) => tx.hash,
             Tx::ChangeMinter(ref tx, _) => tx.hash,
+            Tx::UpgradeContract(ref tx, _) => tx.hash,
+            Tx::DestroyContract(ref tx, _) => tx.hash,
         }
     }
 
@@ -195,6 +307,8 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.nonce,
             Tx::CreateUnique(ref tx, _) => tx.nonce,
             Tx::ChangeMinter(ref tx, _) => tx.nonce,
+            Tx::UpgradeContract(ref tx, _) => tx.nonce,
+            Tx::DestroyContract(ref tx, _) => tx.nonce,
         }
     }
 
@@ -209,6 +323,8 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.hash,
             Tx::CreateUnique(ref tx, _) => tx.hash,
             Tx::ChangeMinter(ref tx, _) => tx.hash,
+            Tx::UpgradeContract(ref tx, _) => tx.hash,
+            Tx::DestroyContract(ref tx, _) => tx.hash,
         }
     }
 
@@ -223,6 +339,8 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.fee.clone(),
             Tx::CreateUnique(ref tx, _) => tx.fee.clone(),
             Tx::ChangeMinter(ref tx, _) => tx.fee.clone(),
+            Tx::UpgradeContract(ref tx, _) => tx.fee.clone(),
+            Tx::DestroyContract(ref tx, _) => tx.fee.clone(),
         }
     }
 
@@ -237,6 +355,8 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.fee_hash,
             Tx::CreateUnique(ref tx, _) => tx.fee_hash,
             Tx::ChangeMinter(ref tx, _) => tx.fee_hash,
+            Tx::UpgradeContract(ref tx, _) => tx.fee_hash,
+            Tx::DestroyContract(ref tx, _) => tx.fee_hash,
         }
     }
 
@@ -252,6 +372,8 @@ impl Tx {
             Tx::Mint(ref tx, _) => NormalAddress::from_pkey(&tx.minter),
             Tx::CreateUnique(ref tx, _) => NormalAddress::from_pkey(&tx.creator),
             Tx::ChangeMinter(ref tx, _) => NormalAddress::from_pkey(&tx.minter),
+            Tx::UpgradeContract(ref tx, _) => NormalAddress::from_pkey(&tx.admin),
+            Tx::DestroyContract(ref tx, _) => NormalAddress::from_pkey(&tx.admin),
         }
     }
 
@@ -266,6 +388,44 @@ impl Tx {
             Tx::Mint(ref tx, _) => tx.next_address.clone(),
             Tx::CreateUnique(ref tx, _) => tx.next_address.clone(),
             Tx::ChangeMinter(ref tx, _) => tx.next_address.clone(),
+            Tx::UpgradeContract(ref tx, _) => tx.next_address.clone(),
+            Tx::DestroyContract(ref tx, _) => tx.next_address.clone(),
+        }
+    }
+
+    /// Returns the address on the receiving end of the transaction,
+    /// if there is any.
+    pub fn receiver(&self) -> Option<Address> {
+        match *self {
+            Tx::Call(ref tx, _) => Some(Address::Contract(tx.to)),
+            Tx::OpenContract(ref tx, _) => tx.address.map(Address::Contract),
+            Tx::Send(ref tx, _) => Some(tx.to),
+            Tx::Burn(_, _) => None,
+            Tx::CreateCurrency(ref tx, _) => Some(tx.receiver),
+            Tx::CreateMintable(ref tx, _) => Some(tx.receiver),
+            Tx::Mint(ref tx, _) => Some(tx.receiver),
+            Tx::CreateUnique(ref tx, _) => Some(tx.receiver),
+            Tx::ChangeMinter(ref tx, _) => Some(tx.new_minter),
+            Tx::UpgradeContract(ref tx, _) => Some(Address::Contract(tx.contract)),
+            Tx::DestroyContract(ref tx, _) => Some(tx.beneficiary),
+        }
+    }
+
+    /// Returns the hash of the asset the transaction operates on. This
+    /// is `None` for transactions which only pay a fee.
+    pub fn asset_hash(&self) -> Option<ShortHash> {
+        match *self {
+            Tx::Call(ref tx, _) => Some(tx.asset_hash),
+            Tx::OpenContract(ref tx, _) => Some(tx.asset_hash),
+            Tx::Send(ref tx, _) => Some(tx.asset_hash),
+            Tx::Burn(ref tx, _) => Some(tx.asset_hash),
+            Tx::CreateCurrency(ref tx, _) => Some(tx.asset_hash),
+            Tx::CreateMintable(ref tx, _) => Some(tx.asset_hash),
+            Tx::Mint(ref tx, _) => Some(tx.asset_hash),
+            Tx::CreateUnique(ref tx, _) => Some(tx.asset_hash),
+            Tx::ChangeMinter(ref tx, _) => Some(tx.asset_hash),
+            Tx::UpgradeContract(_, _) => None,
+            Tx::DestroyContract(_, _) => None,
         }
     }
 
@@ -281,12 +441,14 @@ impl Tx {
             Tx::Mint(_, byte_size) => byte_size,
             Tx::CreateUnique(_, byte_size) => byte_size,
             Tx::ChangeMinter(_, byte_size) => byte_size,
+            Tx::UpgradeContract(_, byte_size) => byte_size,
+            Tx::DestroyContract(_, byte_size) => byte_size,
         }
     }
 
     pub fn arbitrary_valid(trie: &mut TrieDBMut<DbHasher, Codec>) -> Tx {
         let mut rng = rand::thread_rng();
-        let random = rng.gen_range(0, 8);
+        let random = rng.gen_range(0, 11);
         let id = Identity::new();
 
         match random {
@@ -335,6 +497,16 @@ impl Tx {
                 let byte_size = tx.to_bytes().unwrap().len() - 1;
                 Tx::ChangeMinter(tx, byte_size)
             }
+            9 => {
+                let tx: UpgradeContract = UpgradeContract::arbitrary_valid(trie, id.skey().clone());
+                let byte_size = tx.to_bytes().unwrap().len() - 1;
+                Tx::UpgradeContract(tx, byte_size)
+            }
+            10 => {
+                let tx: DestroyContract = DestroyContract::arbitrary_valid(trie, id.skey().clone());
+                let byte_size = tx.to_bytes().unwrap().len() - 1;
+                Tx::DestroyContract(tx, byte_size)
+            }
             _ => panic!(),
         }
     }
@@ -343,7 +515,7 @@ impl Tx {
 impl Arbitrary for Tx {
     fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> Tx {
         let mut rng = rand::thread_rng();
-        let random = rng.gen_range(0, 9);
+        let random = rng.gen_range(0, 11);
 
         match random {
             0 => {
@@ -391,6 +563,16 @@ impl Arbitrary for Tx {
                 let byte_size = tx.to_bytes().unwrap().len() - 1;
                 Tx::ChangeMinter(tx, byte_size)
             }
+            9 => {
+                let tx: UpgradeContract = Arbitrary::arbitrary(g);
+                let byte_size = tx.to_bytes().unwrap().len() - 1;
+                Tx::UpgradeContract(tx, byte_size)
+            }
+            10 => {
+                let tx: DestroyContract = Arbitrary::arbitrary(g);
+                let byte_size = tx.to_bytes().unwrap().len() - 1;
+                Tx::DestroyContract(tx, byte_size)
+            }
             _ => panic!(),
         }
     }
@@ -16,12 +16,13 @@
   along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use crate::contract::{is_tombstoned, validate_code};
+use crate::journal::JournaledTrieMut;
 use account::{Address, Balance, ContractAddress, NormalAddress};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use crypto::{Hash, PublicKey as Pk, SecretKey as Sk, ShortHash, Signature};
-use patricia_trie::{Trie, TrieDB, TrieDBMut, TrieMut};
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
 use persistence::{Codec, DbHasher};
-use purple_vm::Validator;
 use rand::Rng;
 use std::io::Cursor;
 use std::str;
@@ -37,6 +38,7 @@ pub struct OpenContract {
     pub(crate) fee: Balance,
     pub(crate) fee_hash: ShortHash,
     pub(crate) self_payable: bool,
+    pub(crate) admin: Option<NormalAddress>,
     pub(crate) nonce: u64,
 
     pub(crate) address: Option<ContractAddress>,
@@ -49,8 +51,21 @@ pub struct OpenContract {
 impl OpenContract {
     pub const TX_TYPE: u8 = 2;
 
-    /// Validates the transaction against the provided state.
-    pub fn validate(&self, trie: &TrieDB<DbHasher, Codec>) -> bool {
+    /// Validates the transaction against the provided state. The code
+    /// must be valid for the given version of the VM instruction set.
+    pub fn validate(&self, trie: &TrieDB<DbHasher, Codec>, vm_version: u8) -> bool {
+        // TODO: Signature verification should be done in batches
+        // and happen before validation.
+        if !self.verify_sig() {
+            return false;
+        }
+
+        self.validate_unsigned(trie, vm_version)
+    }
+
+    /// Validates the transaction against the provided state
+    /// without verifying its signature.
+    pub fn validate_unsigned(&self, trie: &TrieDB<DbHasher, Codec>, vm_version: u8) -> bool {
         let zero = Balance::zero();
 
         // Invalidate if 0 coins are set as amount
@@ -64,13 +79,7 @@ impl OpenContract {
         }
 
         // Validate contract code
-        let mut validator = Validator::new();
-        if !validator.validate_block(self.code.as_slice()) {
-            return false;
-        }
-
-        // Invalidate if signature is not valid
-        if !self.verify_sig() {
+        if !validate_code(&self.code, vm_version) {
             return false;
         }
 
@@ -84,6 +93,11 @@ impl OpenContract {
             return false;
         }
 
+        // Invalidate if the address belongs to a destroyed contract
+        if is_tombstoned(trie, address) {
+            return false;
+        }
+
         let bin_asset_hash = &self.asset_hash.0;
         let bin_fee_hash = &self.fee_hash.0;
 
@@ -247,6 +261,12 @@ impl OpenContract {
         // `<contract-address>.y`
         let self_payable_key = [bin_address, &b".y"[..]].concat();
 
+        // Calculate admin key
+        //
+        // The key of a contract's admin has the following format:
+        // `<contract-address>.a`
+        let admin_key = [bin_address, &b".a"[..]].concat();
+
         // Retrieve serialized nonce
         let bin_creator_nonce = &trie.get(&creator_nonce_key).unwrap().unwrap();
 
@@ -353,6 +373,16 @@ impl OpenContract {
             trie.insert(&next_addr_mapping_key, creator_perm_addr.as_bytes())
                 .unwrap();
         }
+
+        // Only contracts that have an admin are upgradable
+        if let Some(admin) = &self.admin {
+            trie.insert(&admin_key, admin.as_bytes()).unwrap();
+        }
+    }
+
+    /// Returns the address of the opened contract, if computed.
+    pub fn address(&self) -> Option<ContractAddress> {
+        self.address
     }
 
     /// Computes the address of the opened contract.
@@ -410,16 +440,18 @@ impl OpenContract {
     /// 6) Code length              - 16bits
     /// 7) Nonce                    - 64bits
     /// 8) Currency flag            - 1byte (Value is 1 if currency and fee hashes are identical. Otherwise is 0)
-    /// 9) Asset hash               - 8byte binary
-    /// 10) Fee hash                - 8byte binary (Non-existent if currency flag is true)
-    /// 11) Owner                   - 32byte binary
-    /// 12) Address                 - 33byte binary
-    /// 13) Next address            - 33byte binary
-    /// 14) Signature               - 64byte binary
-    /// 15) Amount                  - Binary of amount length
-    /// 16) Fee                     - Binary of fee length
-    /// 17) Default state           - Binary of state length
-    /// 18) Code                    - Binary of code length
+    /// 9) Admin flag               - 1byte (Value is 1 if the contract has an admin. Otherwise is 0)
+    /// 10) Asset hash              - 8byte binary
+    /// 11) Fee hash                - 8byte binary (Non-existent if currency flag is true)
+    /// 12) Owner                   - 32byte binary
+    /// 13) Address                 - 33byte binary
+    /// 14) Next address            - 33byte binary
+    /// 15) Admin                   - 33byte binary (Non-existent if admin flag is false)
+    /// 16) Signature               - 64byte binary
+    /// 17) Amount                  - Binary of amount length
+    /// 18) Fee                     - Binary of fee length
+    /// 19) Default state           - Binary of state length
+    /// 20) Code                    - Binary of code length
     pub fn to_bytes(&self) -> Result<Vec<u8>, &'static str> {
         let mut buffer: Vec<u8> = Vec::new();
         let tx_type: u8 = Self::TX_TYPE;
@@ -445,6 +477,7 @@ impl OpenContract {
         let fee = self.fee.to_bytes();
         let nonce = &self.nonce;
         let currency_flag = if asset_hash == fee_hash { 1 } else { 0 };
+        let admin_flag = if self.admin.is_some() { 1 } else { 0 };
 
         let amount_len = amount.len();
         let fee_len = fee.len();
@@ -459,6 +492,7 @@ impl OpenContract {
         buffer.write_u16::<BigEndian>(code_len as u16).unwrap();
         buffer.write_u64::<BigEndian>(*nonce).unwrap();
         buffer.write_u8(currency_flag).unwrap();
+        buffer.write_u8(admin_flag).unwrap();
         buffer.extend_from_slice(asset_hash);
 
         if currency_flag == 0 {
@@ -468,6 +502,11 @@ impl OpenContract {
         buffer.extend_from_slice(&self.creator.0);
         buffer.extend_from_slice(&address);
         buffer.extend_from_slice(self.next_address.as_bytes());
+
+        if let Some(admin) = &self.admin {
+            buffer.extend_from_slice(admin.as_bytes());
+        }
+
         buffer.extend_from_slice(&signature);
         buffer.extend_from_slice(&amount);
         buffer.extend_from_slice(&fee);
@@ -553,9 +592,21 @@ impl OpenContract {
             return Err("Bad currency flag");
         };
 
+        rdr.set_position(17);
+
+        let admin_flag = if let Ok(result) = rdr.read_u8() {
+            if result == 0 || result == 1 {
+                result
+            } else {
+                return Err("Bad admin flag value");
+            }
+        } else {
+            return Err("Bad admin flag");
+        };
+
         // Consume cursor
         let mut buf: Vec<u8> = rdr.into_inner();
-        let _: Vec<u8> = buf.drain(..17).collect();
+        let _: Vec<u8> = buf.drain(..18).collect();
 
         let asset_hash = if buf.len() > 8 as usize {
             let mut hash = [0; 8];
@@ -615,6 +666,21 @@ impl OpenContract {
             return Err("Incorrect packet structure! Buffer size is smaller than the size for the next address field");
         };
 
+        let admin = if admin_flag == 1 {
+            if buf.len() > 33 as usize {
+                let admin_vec: Vec<u8> = buf.drain(..33).collect();
+
+                match NormalAddress::from_bytes(&admin_vec) {
+                    Ok(addr) => Some(addr),
+                    Err(err) => return Err(err),
+                }
+            } else {
+                return Err("Incorrect packet structure! Buffer size is smaller than the size for the admin field");
+            }
+        } else {
+            None
+        };
+
         let signature = if buf.len() > 64 as usize {
             let sig_vec: Vec<u8> = buf.drain(..64 as usize).collect();
 
@@ -669,6 +735,7 @@ impl OpenContract {
             fee,
             default_state,
             self_payable,
+            admin,
             nonce,
             code,
             address: Some(address),
@@ -704,6 +771,11 @@ fn assemble_message(obj: &OpenContract) -> Vec<u8> {
     buf.extend_from_slice(&obj.creator.0);
     buf.extend_from_slice(&obj.next_address.to_bytes());
     buf.extend_from_slice(&contract_address);
+
+    if let Some(admin) = &obj.admin {
+        buf.extend_from_slice(admin.as_bytes());
+    }
+
     buf.extend_from_slice(&obj.asset_hash.0);
     buf.extend_from_slice(&obj.fee_hash.0);
     buf.extend_from_slice(&code);
@@ -734,6 +806,7 @@ impl Arbitrary for OpenContract {
             code: Arbitrary::arbitrary(g),
             default_state: Arbitrary::arbitrary(g),
             self_payable: Arbitrary::arbitrary(g),
+            admin: Arbitrary::arbitrary(g),
             amount: Arbitrary::arbitrary(g),
             asset_hash,
             fee: Arbitrary::arbitrary(g),
@@ -756,6 +829,7 @@ mod tests {
     use super::*;
     use account::NormalAddress;
     use crypto::Identity;
+    use purple_vm::Instruction;
 
     #[test]
     fn apply_it_opens_a_contract() {
@@ -788,6 +862,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 3429,
             address: None,
             signature: None,
@@ -877,6 +952,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 1,
             address: None,
             signature: None,
@@ -888,7 +964,61 @@ mod tests {
         tx.compute_hash();
 
         let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
-        assert!(tx.validate(&trie));
+        assert!(tx.validate(&trie, 1));
+    }
+
+    #[test]
+    fn it_validates_code_against_the_vm_version() {
+        let id = Identity::new();
+        let id2 = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let next_address = NormalAddress::from_pkey(id2.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+        }
+
+        // Linear memory instructions are added by version 2
+        let code: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,
+            Instruction::MemorySize.repr(),
+            Instruction::MemoryGrow.repr(),
+            Instruction::Return.repr(),
+            0x00,
+            Instruction::End.repr(),
+        ];
+
+        let mut tx = OpenContract {
+            creator: id.pkey().clone(),
+            next_address,
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            code,
+            default_state: Vec::new(),
+            fee_hash: asset_hash,
+            amount: Balance::from_bytes(b"30.0").unwrap(),
+            asset_hash,
+            self_payable: true,
+            admin: None,
+            nonce: 1,
+            address: None,
+            signature: None,
+            hash: None,
+        };
+
+        tx.compute_address();
+        tx.sign(id.skey().clone());
+        tx.compute_hash();
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        assert!(!tx.validate(&trie, 1));
+        assert!(tx.validate(&trie, 2));
     }
 
     #[test]
@@ -925,6 +1055,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 1,
             address: None,
             signature: None,
@@ -936,7 +1067,7 @@ mod tests {
         tx.compute_hash();
 
         let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
-        assert!(tx.validate(&trie));
+        assert!(tx.validate(&trie, 1));
     }
 
     #[test]
@@ -971,6 +1102,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 1,
             address: None,
             signature: None,
@@ -982,7 +1114,7 @@ mod tests {
         tx.compute_hash();
 
         let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
-        assert!(!tx.validate(&trie));
+        assert!(!tx.validate(&trie, 1));
     }
 
     #[test]
@@ -1019,6 +1151,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 1,
             address: None,
             signature: None,
@@ -1030,7 +1163,7 @@ mod tests {
         tx.compute_hash();
 
         let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
-        assert!(!tx.validate(&trie));
+        assert!(!tx.validate(&trie, 1));
     }
 
     #[test]
@@ -1067,6 +1200,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 1,
             address: None,
             signature: None,
@@ -1078,7 +1212,7 @@ mod tests {
         tx.compute_hash();
 
         let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
-        assert!(!tx.validate(&trie));
+        assert!(!tx.validate(&trie, 1));
     }
 
     #[test]
@@ -1113,6 +1247,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 1,
             address: None,
             signature: None,
@@ -1124,7 +1259,7 @@ mod tests {
         tx.compute_hash();
 
         let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
-        assert!(!tx.validate(&trie));
+        assert!(!tx.validate(&trie, 1));
     }
 
     #[test]
@@ -1159,6 +1294,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 1,
             address: None,
             signature: None,
@@ -1170,7 +1306,7 @@ mod tests {
         tx.compute_hash();
 
         let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
-        assert!(!tx.validate(&trie));
+        assert!(!tx.validate(&trie, 1));
     }
 
     #[test]
@@ -1205,6 +1341,7 @@ mod tests {
             amount: amount.clone(),
             asset_hash,
             self_payable: true,
+            admin: None,
             nonce: 123456,
             address: None,
             signature: None,
@@ -1216,7 +1353,58 @@ mod tests {
         tx.compute_hash();
 
         let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
-        assert!(!tx.validate(&trie));
+        assert!(!tx.validate(&trie, 1));
+    }
+
+    #[test]
+    fn it_invalidates_tombstoned_address() {
+        let id = Identity::new();
+        let id2 = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let next_address = NormalAddress::from_pkey(id2.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let amount = Balance::from_bytes(b"10.0").unwrap();
+        let fee = Balance::from_bytes(b"10.0").unwrap();
+        let code: Vec<u8> = vec![0x02, 0x00, 0x01, 0x06];
+        let default_state: Vec<u8> = vec![0x1a, 0xff, 0x22, 0x2a];
+
+        let mut tx = OpenContract {
+            creator: id.pkey().clone(),
+            next_address,
+            fee: fee.clone(),
+            code: code.clone(),
+            default_state: default_state.clone(),
+            fee_hash: asset_hash,
+            amount: amount.clone(),
+            asset_hash,
+            self_payable: true,
+            admin: Some(creator_addr),
+            nonce: 1,
+            address: None,
+            signature: None,
+            hash: None,
+        };
+
+        tx.compute_address();
+        tx.sign(id.skey().clone());
+        tx.compute_hash();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"1000.0");
+
+            // Manually tombstone the contract address
+            let tombstone_key = [tx.address.as_ref().unwrap().as_bytes(), &b".t"[..]].concat();
+            trie.insert(&tombstone_key, &[1]).unwrap();
+        }
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        assert!(!tx.validate(&trie, 1));
     }
 
     quickcheck! {
@@ -1255,6 +1443,7 @@ mod tests {
                 nonce: 54432,
                 fee: fee,
                 self_payable: self_payable,
+                admin: None,
                 default_state: default_state,
                 code: code,
                 address: None,
@@ -0,0 +1,771 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use crate::contract::{contract_admin, is_tombstoned, validate_code};
+use crate::journal::JournaledTrieMut;
+use account::{Balance, ContractAddress, NormalAddress};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use crypto::{Hash, PublicKey as Pk, SecretKey as Sk, ShortHash, Signature};
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
+use persistence::{Codec, DbHasher};
+use std::io::Cursor;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpgradeContract {
+    /// The admin of the contract
+    pub(crate) admin: Pk,
+
+    /// The admin's next address
+    pub(crate) next_address: NormalAddress,
+
+    /// The address of the upgraded contract
+    pub(crate) contract: ContractAddress,
+
+    /// The new code of the contract. The
+    /// contract's state is left untouched.
+    pub(crate) code: Vec<u8>,
+
+    /// The global identifier of the asset in which
+    /// the transaction fee is paid in.
+    pub(crate) fee_hash: ShortHash,
+
+    /// The transaction's fee
+    pub(crate) fee: Balance,
+
+    /// Nonce
+    pub(crate) nonce: u64,
+
+    /// Transaction hash
+    pub(crate) hash: Option<Hash>,
+
+    /// Transaction signature
+    pub(crate) signature: Option<Signature>,
+}
+
+impl UpgradeContract {
+    pub const TX_TYPE: u8 = 10;
+
+    /// Validates the transaction against the provided state. The code
+    /// must be valid for the given version of the VM instruction set.
+    pub fn validate(&self, trie: &TrieDB<DbHasher, Codec>, vm_version: u8) -> bool {
+        // TODO: Signature verification should be done in batches
+        // and happen before validation.
+        if !self.verify_sig() {
+            return false;
+        }
+
+        self.validate_unsigned(trie, vm_version)
+    }
+
+    /// Validates the transaction against the provided state
+    /// without verifying its signature.
+    pub fn validate_unsigned(&self, trie: &TrieDB<DbHasher, Codec>, vm_version: u8) -> bool {
+        let zero = Balance::zero();
+
+        // Validate contract code
+        if !validate_code(&self.code, vm_version) {
+            return false;
+        }
+
+        let bin_fee_hash = &self.fee_hash.0;
+        let admin_signing_addr = NormalAddress::from_pkey(&self.admin);
+
+        // Do not allow address re-usage
+        if self.next_address == admin_signing_addr {
+            return false;
+        }
+
+        // Calculate address mapping key
+        //
+        // An address mapping is a mapping between
+        // the account's signing address and an
+        // account's receiving address.
+        //
+        // They key of the address mapping has the following format:
+        // `<signing-address>.am`
+        let addr_mapping_key = [admin_signing_addr.as_bytes(), &b".am"[..]].concat();
+
+        // Retrieve admin account permanent address
+        let permanent_addr = match trie.get(&addr_mapping_key) {
+            Ok(Some(perm_addr)) => NormalAddress::from_bytes(&perm_addr).unwrap(),
+            Ok(None) => return false,
+            Err(err) => panic!(err),
+        };
+
+        // Do not allow address re-usage
+        if self.next_address == permanent_addr {
+            return false;
+        }
+
+        // Destroyed contracts cannot be upgraded
+        if is_tombstoned(trie, &self.contract) {
+            return false;
+        }
+
+        // Validate admin. This also invalidates contracts
+        // that do not exist or that are not upgradable.
+        match contract_admin(trie, &self.contract) {
+            Some(admin) if admin == permanent_addr => {}
+            _ => return false,
+        }
+
+        // Calculate nonce key
+        //
+        // The key of a nonce has the following format:
+        // `<account-address>.n`
+        let admin_nonce_key = [permanent_addr.as_bytes(), &b".n"[..]].concat();
+
+        // Calculate currency key
+        //
+        // The key of a currency entry has the following format:
+        // `<account-address>.<currency-hash>`
+        let fee_key = [permanent_addr.as_bytes(), &b"."[..], bin_fee_hash].concat();
+
+        // Retrieve serialized nonce
+        let bin_nonce = match trie.get(&admin_nonce_key) {
+            Ok(Some(nonce)) => nonce,
+            Ok(None) => return false,
+            Err(err) => panic!(err),
+        };
+
+        let stored_nonce = decode_be_u64!(bin_nonce).unwrap();
+        if stored_nonce + 1 != self.nonce {
+            return false;
+        }
+
+        let mut fee_balance = match trie.get(&fee_key) {
+            Ok(Some(balance)) => match Balance::from_bytes(&balance) {
+                Ok(balance) => balance,
+                Err(err) => panic!(err),
+            },
+            Ok(None) => return false,
+            Err(err) => panic!(err),
+        };
+
+        // Subtract fee from admin balance
+        fee_balance -= self.fee.clone();
+        fee_balance >= zero
+    }
+
+    /// Applies the upgrade contract transaction to the provided database.
+    ///
+    /// The contract's code is replaced while its state and balances
+    /// are kept intact.
+    pub fn apply(&self, trie: &mut TrieDBMut<DbHasher, Codec>) {
+        let bin_contract = self.contract.as_bytes();
+        let bin_fee_hash = &self.fee_hash.0;
+        let admin_signing_addr = NormalAddress::from_pkey(&self.admin);
+
+        // Calculate address mapping key
+        //
+        // An address mapping is a mapping between
+        // the account's signing address and an
+        // account's receiving address.
+        //
+        // They key of the address mapping has the following format:
+        // `<signing-address>.am`
+        let admin_addr_mapping_key = [admin_signing_addr.as_bytes(), &b".am"[..]].concat();
+        let next_addr_mapping_key = [self.next_address.as_bytes(), &b".am"[..]].concat();
+
+        // Retrieve admin account permanent address
+        let admin_perm_addr = trie.get(&admin_addr_mapping_key).unwrap().unwrap();
+        let admin_perm_addr = NormalAddress::from_bytes(&admin_perm_addr).unwrap();
+
+        // Calculate nonce key
+        //
+        // The key of a nonce has the following format:
+        // `<account-address>.n`
+        let admin_nonce_key = [admin_perm_addr.as_bytes(), &b".n"[..]].concat();
+
+        // Calculate code key
+        //
+        // The key of a contract's code has the following format:
+        // `<contract-address>.c`
+        let code_key = [bin_contract, &b".c"[..]].concat();
+
+        // Retrieve serialized nonce
+        let bin_admin_nonce = &trie.get(&admin_nonce_key).unwrap().unwrap();
+
+        // Read the nonce of the admin
+        let mut nonce = decode_be_u64!(bin_admin_nonce).unwrap();
+
+        // Increment admin nonce
+        nonce += 1;
+
+        let nonce: Vec<u8> = encode_be_u64!(nonce);
+
+        // Calculate currency key
+        //
+        // The key of a currency entry has the following format:
+        // `<account-address>.<currency-hash>`
+        let admin_fee_key = [admin_perm_addr.as_bytes(), &b"."[..], bin_fee_hash].concat();
+
+        let mut admin_fee_balance = unwrap!(
+            Balance::from_bytes(&unwrap!(
+                trie.get(&admin_fee_key).unwrap(),
+                "The admin does not have an entry for the given currency"
+            )),
+            "Invalid stored balance format"
+        );
+
+        // Subtract fee from admin balance
+        admin_fee_balance -= self.fee.clone();
+
+        // Update trie
+        trie.insert(&code_key, &self.code).unwrap();
+        trie.insert(&admin_nonce_key, &nonce).unwrap();
+        trie.insert(&admin_fee_key, &admin_fee_balance.to_bytes())
+            .unwrap();
+
+        // Update admin address mapping
+        trie.remove(&admin_addr_mapping_key).unwrap();
+        trie.insert(&next_addr_mapping_key, admin_perm_addr.as_bytes())
+            .unwrap();
+    }
+
+    /// Signs the transaction with the given secret key.
+    pub fn sign(&mut self, skey: Sk) {
+        // Assemble data
+        let message = assemble_message(&self);
+
+        // Sign data
+        let signature = crypto::sign(&message, &skey);
+        self.signature = Some(signature);
+    }
+
+    /// Verifies the signature of the transaction.
+    ///
+    /// Returns `false` if the signature field is missing.
+    pub fn verify_sig(&self) -> bool {
+        let message = assemble_message(&self);
+
+        match self.signature {
+            Some(ref sig) => crypto::verify(&message, sig, &self.admin),
+            None => false,
+        }
+    }
+
+    /// Serializes the transaction struct to a binary format.
+    ///
+    /// Fields:
+    /// 1) Transaction type(10) - 8bits
+    /// 2) Fee length           - 8bits
+    /// 3) Code length          - 16bits
+    /// 4) Nonce                - 64bits
+    /// 5) Fee hash             - 8byte binary
+    /// 6) Admin                - 32byte binary
+    /// 7) Contract             - 33byte binary
+    /// 8) Next address         - 33byte binary
+    /// 9) Signature            - 64byte binary
+    /// 10) Fee                 - Binary of fee length
+    /// 11) Code                - Binary of code length
+    pub fn to_bytes(&self) -> Result<Vec<u8>, &'static str> {
+        let mut buf: Vec<u8> = Vec::new();
+
+        let signature = if let Some(signature) = &self.signature {
+            signature.to_bytes()
+        } else {
+            return Err("Signature field is missing");
+        };
+
+        let tx_type: u8 = Self::TX_TYPE;
+        let contract = &self.contract.to_bytes();
+        let next_address = &self.next_address.to_bytes();
+        let fee_hash = &self.fee_hash.0;
+        let fee = &self.fee.to_bytes();
+        let code = &self.code;
+        let fee_len = fee.len();
+        let code_len = code.len();
+        let nonce = &self.nonce;
+
+        // Write to buffer
+        buf.write_u8(tx_type).unwrap();
+        buf.write_u8(fee_len as u8).unwrap();
+        buf.write_u16::<BigEndian>(code_len as u16).unwrap();
+        buf.write_u64::<BigEndian>(*nonce).unwrap();
+        buf.extend_from_slice(fee_hash);
+        buf.extend_from_slice(&self.admin.0);
+        buf.extend_from_slice(contract);
+        buf.extend_from_slice(next_address);
+        buf.extend_from_slice(&signature);
+        buf.extend_from_slice(fee);
+        buf.extend_from_slice(code);
+
+        Ok(buf)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<UpgradeContract, &'static str> {
+        let mut rdr = Cursor::new(bytes.to_vec());
+        let tx_type = if let Ok(result) = rdr.read_u8() {
+            result
+        } else {
+            return Err("Bad transaction type");
+        };
+
+        if tx_type != Self::TX_TYPE {
+            return Err("Bad transation type");
+        }
+
+        rdr.set_position(1);
+
+        let fee_len = if let Ok(result) = rdr.read_u8() {
+            result
+        } else {
+            return Err("Bad fee len");
+        };
+
+        rdr.set_position(2);
+
+        let code_len = if let Ok(result) = rdr.read_u16::<BigEndian>() {
+            result
+        } else {
+            return Err("Bad code len");
+        };
+
+        rdr.set_position(4);
+
+        let nonce = if let Ok(result) = rdr.read_u64::<BigEndian>() {
+            result
+        } else {
+            return Err("Bad nonce");
+        };
+
+        let mut buf: Vec<u8> = rdr.into_inner();
+        let _: Vec<u8> = buf.drain(..12).collect();
+
+        let fee_hash = if buf.len() > 8 as usize {
+            let mut hash = [0; 8];
+            let hash_vec: Vec<u8> = buf.drain(..8).collect();
+
+            hash.copy_from_slice(&hash_vec);
+
+            ShortHash(hash)
+        } else {
+            return Err("Incorrect packet structure");
+        };
+
+        let admin = if buf.len() > 32 as usize {
+            let admin_vec: Vec<u8> = buf.drain(..32).collect();
+            let mut admin_bytes = [0; 32];
+
+            admin_bytes.copy_from_slice(&admin_vec);
+            Pk(admin_bytes)
+        } else {
+            return Err("Incorrect packet structure");
+        };
+
+        let contract = if buf.len() > 33 as usize {
+            let contract_vec: Vec<u8> = buf.drain(..33).collect();
+
+            match ContractAddress::from_bytes(&contract_vec) {
+                Ok(addr) => addr,
+                Err(err) => return Err(err),
+            }
+        } else {
+            return Err("Incorrect packet structure");
+        };
+
+        let next_address = if buf.len() > 33 as usize {
+            let next_address_vec: Vec<u8> = buf.drain(..33).collect();
+
+            match NormalAddress::from_bytes(&next_address_vec) {
+                Ok(addr) => addr,
+                Err(err) => return Err(err),
+            }
+        } else {
+            return Err("Incorrect packet structure");
+        };
+
+        let signature = if buf.len() > 64 as usize {
+            let sig_vec: Vec<u8> = buf.drain(..64).collect();
+
+            match Signature::from_bytes(&sig_vec) {
+                Ok(sig) => sig,
+                Err(_) => return Err("Bad signature"),
+            }
+        } else {
+            return Err("Incorrect packet structure");
+        };
+
+        let fee = if buf.len() >= fee_len as usize {
+            let fee_vec: Vec<u8> = buf.drain(..fee_len as usize).collect();
+
+            match Balance::from_bytes(&fee_vec) {
+                Ok(result) => result,
+                Err(_) => return Err("Bad fee"),
+            }
+        } else {
+            return Err("Incorrect packet structure");
+        };
+
+        let code = if buf.len() == code_len as usize {
+            buf.drain(..code_len as usize).collect()
+        } else {
+            return Err("Incorrect packet structure! Buffer size is not equal with the size for the code field");
+        };
+
+        let mut upgrade_contract = UpgradeContract {
+            admin,
+            next_address,
+            contract,
+            code,
+            fee_hash,
+            fee,
+            nonce,
+            hash: None,
+            signature: Some(signature),
+        };
+
+        upgrade_contract.compute_hash();
+        Ok(upgrade_contract)
+    }
+
+    /// Returns a random valid transaction for the provided state.
+    pub fn arbitrary_valid(trie: &mut TrieDBMut<DbHasher, Codec>, sk: Sk) -> Self {
+        unimplemented!();
+    }
+
+    impl_hash!();
+}
+
+fn assemble_message(obj: &UpgradeContract) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::new();
+    let contract = obj.contract.to_bytes();
+    let next_address = obj.next_address.to_bytes();
+    let fee = obj.fee.to_bytes();
+    let fee_hash = &obj.fee_hash.0;
+
+    // Compose data to hash
+    buf.write_u64::<BigEndian>(obj.nonce).unwrap();
+    buf.extend_from_slice(&obj.admin.0);
+    buf.extend_from_slice(&contract);
+    buf.extend_from_slice(&next_address);
+    buf.extend_from_slice(fee_hash);
+    buf.extend_from_slice(&fee);
+    buf.extend_from_slice(&obj.code);
+    buf
+}
+
+use quickcheck::Arbitrary;
+
+impl Arbitrary for UpgradeContract {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> UpgradeContract {
+        let (pk, _) = crypto::gen_keypair();
+
+        let mut tx = UpgradeContract {
+            admin: pk,
+            next_address: Arbitrary::arbitrary(g),
+            contract: Arbitrary::arbitrary(g),
+            code: Arbitrary::arbitrary(g),
+            fee_hash: Arbitrary::arbitrary(g),
+            fee: Arbitrary::arbitrary(g),
+            nonce: Arbitrary::arbitrary(g),
+            hash: None,
+            signature: Some(Arbitrary::arbitrary(g)),
+        };
+
+        tx.compute_hash();
+        tx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpenContract;
+    use crypto::Identity;
+
+    /// Opens a contract created by the given identity and
+    /// returns its address along with the creator's next
+    /// identity.
+    fn open_contract(
+        trie: &mut TrieDBMut<DbHasher, Codec>,
+        id: &Identity,
+        admin: Option<NormalAddress>,
+        asset_hash: ShortHash,
+    ) -> (ContractAddress, Identity) {
+        let next_id = Identity::new();
+
+        let mut tx = OpenContract {
+            creator: id.pkey().clone(),
+            next_address: NormalAddress::from_pkey(next_id.pkey()),
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            code: vec![0x02, 0x00, 0x01, 0x06],
+            default_state: vec![0x1a, 0xff, 0x22, 0x2a],
+            fee_hash: asset_hash,
+            amount: Balance::from_bytes(b"30.0").unwrap(),
+            asset_hash,
+            self_payable: true,
+            admin,
+            nonce: 1,
+            address: None,
+            signature: None,
+            hash: None,
+        };
+
+        tx.compute_address();
+        tx.sign(id.skey().clone());
+        tx.compute_hash();
+        tx.apply(trie);
+
+        (tx.address.unwrap(), next_id)
+    }
+
+    #[test]
+    fn validate() {
+        let id = Identity::new();
+        let id2 = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        let (contract, admin_id) = {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+            open_contract(&mut trie, &id, Some(creator_addr), asset_hash)
+        };
+
+        let mut tx = UpgradeContract {
+            admin: admin_id.pkey().clone(),
+            next_address: NormalAddress::from_pkey(id2.pkey()),
+            contract,
+            code: vec![0x02, 0x00, 0x01, 0x01, 0x06],
+            fee_hash: asset_hash,
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            nonce: 2,
+            signature: None,
+            hash: None,
+        };
+
+        tx.sign(admin_id.skey().clone());
+        tx.compute_hash();
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        assert!(tx.validate(&trie, 1));
+    }
+
+    #[test]
+    fn validate_fails_on_contract_without_admin() {
+        let id = Identity::new();
+        let id2 = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        let (contract, admin_id) = {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+            open_contract(&mut trie, &id, None, asset_hash)
+        };
+
+        let mut tx = UpgradeContract {
+            admin: admin_id.pkey().clone(),
+            next_address: NormalAddress::from_pkey(id2.pkey()),
+            contract,
+            code: vec![0x02, 0x00, 0x01, 0x01, 0x06],
+            fee_hash: asset_hash,
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            nonce: 2,
+            signature: None,
+            hash: None,
+        };
+
+        tx.sign(admin_id.skey().clone());
+        tx.compute_hash();
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        assert!(!tx.validate(&trie, 1));
+    }
+
+    #[test]
+    fn validate_fails_on_wrong_admin() {
+        let id = Identity::new();
+        let id2 = Identity::new();
+        let id3 = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let other_addr = NormalAddress::from_pkey(id3.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        let (contract, admin_id) = {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+            open_contract(&mut trie, &id, Some(other_addr), asset_hash)
+        };
+
+        let mut tx = UpgradeContract {
+            admin: admin_id.pkey().clone(),
+            next_address: NormalAddress::from_pkey(id2.pkey()),
+            contract,
+            code: vec![0x02, 0x00, 0x01, 0x01, 0x06],
+            fee_hash: asset_hash,
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            nonce: 2,
+            signature: None,
+            hash: None,
+        };
+
+        tx.sign(admin_id.skey().clone());
+        tx.compute_hash();
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        assert!(!tx.validate(&trie, 1));
+    }
+
+    #[test]
+    fn validate_fails_on_bad_code() {
+        let id = Identity::new();
+        let id2 = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+
+        let (contract, admin_id) = {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+            // Manually initialize creator balance
+            test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+            open_contract(&mut trie, &id, Some(creator_addr), asset_hash)
+        };
+
+        let mut tx = UpgradeContract {
+            admin: admin_id.pkey().clone(),
+            next_address: NormalAddress::from_pkey(id2.pkey()),
+            contract,
+            code: vec![0x02, 0x12, 0x03, 0x04],
+            fee_hash: asset_hash,
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            nonce: 2,
+            signature: None,
+            hash: None,
+        };
+
+        tx.sign(admin_id.skey().clone());
+        tx.compute_hash();
+
+        let trie = TrieDB::<DbHasher, Codec>::new(&db, &root).unwrap();
+        assert!(!tx.validate(&trie, 1));
+    }
+
+    #[test]
+    fn apply_replaces_code_and_keeps_state() {
+        let id = Identity::new();
+        let id2 = Identity::new();
+        let creator_addr = NormalAddress::from_pkey(id.pkey());
+        let asset_hash = crypto::hash_slice(b"Test currency").to_short();
+        let code = vec![0x02, 0x00, 0x01, 0x01, 0x06];
+
+        let mut db = test_helpers::init_tempdb();
+        let mut root = ShortHash::NULL_RLP;
+        let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+
+        // Manually initialize creator balance
+        test_helpers::init_balance(&mut trie, creator_addr.clone(), asset_hash, b"100.0");
+        let (contract, admin_id) = open_contract(&mut trie, &id, Some(creator_addr), asset_hash);
+
+        let mut tx = UpgradeContract {
+            admin: admin_id.pkey().clone(),
+            next_address: NormalAddress::from_pkey(id2.pkey()),
+            contract,
+            code: code.clone(),
+            fee_hash: asset_hash,
+            fee: Balance::from_bytes(b"10.0").unwrap(),
+            nonce: 2,
+            signature: None,
+            hash: None,
+        };
+
+        tx.sign(admin_id.skey().clone());
+        tx.compute_hash();
+        tx.apply(&mut trie);
+
+        let code_key = [contract.as_bytes(), &b".c"[..]].concat();
+        let state_key = [contract.as_bytes(), &b".q"[..]].concat();
+        let creator_nonce_key = [creator_addr.as_bytes(), &b".n"[..]].concat();
+        let creator_balance_key = [creator_addr.as_bytes(), &b"."[..], &asset_hash.0].concat();
+
+        assert_eq!(trie.get(&code_key).unwrap().unwrap().to_vec(), code);
+        assert_eq!(
+            trie.get(&state_key).unwrap().unwrap().to_vec(),
+            vec![0x1a, 0xff, 0x22, 0x2a]
+        );
+        assert_eq!(
+            trie.get(&creator_nonce_key).unwrap().unwrap().to_vec(),
+            vec![0, 0, 0, 0, 0, 0, 0, 2]
+        );
+        assert_eq!(
+            Balance::from_bytes(&trie.get(&creator_balance_key).unwrap().unwrap()).unwrap(),
+            Balance::from_bytes(b"50.0").unwrap()
+        );
+    }
+
+    quickcheck! {
+        fn serialize_deserialize(tx: UpgradeContract) -> bool {
+            tx == UpgradeContract::from_bytes(&UpgradeContract::to_bytes(&tx).unwrap()).unwrap()
+        }
+
+        fn verify_hash(tx: UpgradeContract) -> bool {
+            let mut tx = tx;
+
+            for _ in 0..3 {
+                tx.compute_hash();
+            }
+
+            tx.verify_hash()
+        }
+
+        fn verify_signature(
+            contract: ContractAddress,
+            code: Vec<u8>,
+            fee: Balance,
+            fee_hash: ShortHash
+        ) -> bool {
+            let id = Identity::new();
+            let id2 = Identity::new();
+
+            let mut tx = UpgradeContract {
+                admin: id.pkey().clone(),
+                next_address: NormalAddress::from_pkey(id2.pkey()),
+                contract,
+                code,
+                fee_hash,
+                fee,
+                nonce: 54432,
+                signature: None,
+                hash: None
+            };
+
+            tx.sign(id.skey().clone());
+            tx.verify_sig()
+        }
+    }
+}
@@ -0,0 +1,308 @@
+/*
+  Copyright (C) 2018-2020 The Purple Core Developers.
+  This file is part of the Purple Core Library.
+
+  The Purple Core Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Core Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Core Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Harness for testing contracts against an in-memory chain state.
+//!
+//! A `TestState` starts from the genesis state, in which each of the
+//! test accounts holds a balance of the main currency. Contracts are
+//! deployed through regular `OpenContract` transactions, either from
+//! raw bytecode or from PASM source, and their functions can then be
+//! invoked with typed arguments.
+//!
+//! ```ignore
+//! let mut state = TestState::new();
+//! let contract = state.deploy_pasm(TestAccount::A, SOURCE, &[], 100).unwrap();
+//! let result = state.call(&contract, &[VmValue::I32(1)]).unwrap();
+//!
+//! assert_eq!(result.return_values, vec![VmValue::I32(1)]);
+//! ```
+
+use account::{Address, Balance, ContractAddress};
+use crypto::ShortHash;
+use patricia_trie::{Trie, TrieDB, TrieDBMut};
+use persistence::{Codec, DbHasher, PersistentDb};
+use purple_vm::{Function, Module, Vm};
+use transactions::{Genesis, Tx};
+
+pub use purple_vm::{Gas, VmError, VmValue};
+pub use transactions::TestAccount;
+
+/// The amount of gas each call is allowed to consume.
+const CALL_GAS: &'static [u8] = b"1000000000.0";
+
+/// The outcome of a successful contract call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallResult {
+    /// The values returned by the called function.
+    pub return_values: Vec<VmValue>,
+
+    /// The amount of gas consumed by the call.
+    pub gas_used: Gas,
+}
+
+/// In-memory chain state on which contracts can be deployed and called.
+pub struct TestState {
+    db: PersistentDb,
+    root: ShortHash,
+
+    /// The next nonce of each test account.
+    nonces: [u64; 3],
+}
+
+impl TestState {
+    /// Creates a new state with the genesis transaction applied.
+    pub fn new() -> TestState {
+        let mut db = PersistentDb::new_in_memory();
+        let mut root = ShortHash::NULL_RLP;
+
+        {
+            let mut trie = TrieDBMut::<DbHasher, Codec>::new(&mut db, &mut root);
+            Genesis::default().apply(&mut trie);
+        }
+
+        TestState {
+            db,
+            root,
+            nonces: [1, 1, 1],
+        }
+    }
+
+    /// Deploys the given bytecode from the `deployer` account, transferring
+    /// `amount` of the main currency to the new contract.
+    ///
+    /// Returns an error if the resulting `OpenContract` transaction is invalid.
+    pub fn deploy(
+        &mut self,
+        deployer: TestAccount,
+        code: &[u8],
+        default_state: &[u8],
+        amount: u64,
+    ) -> Result<ContractAddress, &'static str> {
+        let nonce = self.nonces[deployer as usize];
+        let tx = transactions::open_contract(deployer, code, default_state, amount, 1, nonce);
+
+        {
+            let trie = TrieDB::<DbHasher, Codec>::new(&self.db, &self.root).unwrap();
+
+            // Contracts are tested against the latest rules
+            if !tx.validate(&trie, |_| true) {
+                return Err("Invalid contract deployment");
+            }
+        }
+
+        {
+            let mut trie =
+                TrieDBMut::<DbHasher, Codec>::from_existing(&mut self.db, &mut self.root).unwrap();
+            tx.apply(&mut trie);
+        }
+
+        self.nonces[deployer as usize] += 1;
+
+        match tx {
+            Tx::OpenContract(ref tx, _) => Ok(tx.address().unwrap()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Assembles the given PASM source and deploys it
+    /// in the same way as `deploy()`.
+    pub fn deploy_pasm(
+        &mut self,
+        deployer: TestAccount,
+        source: &str,
+        default_state: &[u8],
+        amount: u64,
+    ) -> Result<ContractAddress, &'static str> {
+        let code = purple_vm::assemble(source)?;
+        self.deploy(deployer, &code, default_state, amount)
+    }
+
+    /// Calls the code of the given contract with the given arguments.
+    pub fn call(
+        &mut self,
+        contract: &ContractAddress,
+        args: &[VmValue],
+    ) -> Result<CallResult, VmError> {
+        self.call_with_gas(contract, args, Gas::from_bytes(CALL_GAS).unwrap())
+    }
+
+    /// Calls the code of the given contract with the given arguments,
+    /// failing with `VmError::OutOfGas` if the call would consume more
+    /// than the given gas limit. The consumed gas is metered by the VM.
+    pub fn call_with_gas(
+        &mut self,
+        contract: &ContractAddress,
+        args: &[VmValue],
+        gas_limit: Gas,
+    ) -> Result<CallResult, VmError> {
+        let code = match self.code(contract) {
+            Some(code) => code,
+            None => return Err(VmError::NotLoaded),
+        };
+
+        let function = Function {
+            arity: args.len() as u8,
+            name: "main".to_owned(),
+            block: code.clone(),
+            return_type: None,
+            arguments: args.iter().map(|arg| arg.get_type()).collect(),
+        };
+
+        let module = Module {
+            module_hash: crypto::hash_slice(&code),
+            functions: vec![function],
+            imports: vec![],
+        };
+
+        let mut vm = Vm::new();
+        vm.load(module)?;
+
+        let mut trie =
+            TrieDBMut::<DbHasher, Codec>::from_existing(&mut self.db, &mut self.root).unwrap();
+        let gas_used = vm.execute(&mut trie, 0, 0, args, gas_limit)?;
+
+        Ok(CallResult {
+            return_values: vm.return_values().to_vec(),
+            gas_used,
+        })
+    }
+
+    /// Returns the stored state of the given contract.
+    pub fn storage(&self, contract: &ContractAddress) -> Option<Vec<u8>> {
+        self.get(&[contract.as_bytes(), &b".q"[..]].concat())
+    }
+
+    /// Returns the code of the given contract.
+    pub fn code(&self, contract: &ContractAddress) -> Option<Vec<u8>> {
+        self.get(&[contract.as_bytes(), &b".c"[..]].concat())
+    }
+
+    /// Returns the balance of the given address in the asset with the given hash.
+    pub fn balance(&self, address: &Address, asset_hash: &ShortHash) -> Option<Balance> {
+        self.get(&[address.as_bytes(), &b"."[..], &asset_hash.0].concat())
+            .map(|balance| Balance::from_bytes(&balance).unwrap())
+    }
+
+    /// Returns the balance of the given test account in the main currency.
+    pub fn balance_of(&self, account: TestAccount) -> Option<Balance> {
+        let address = Address::Normal(account.to_perm_address());
+        self.balance(&address, &self.main_asset_hash())
+    }
+
+    /// Returns the hash of the main currency.
+    pub fn main_asset_hash(&self) -> ShortHash {
+        crypto::hash_slice(transactions::MAIN_CUR_NAME).to_short()
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let trie = TrieDB::<DbHasher, Codec>::new(&self.db, &self.root).unwrap();
+
+        match trie.get(key) {
+            Ok(Some(value)) => Some(value.to_vec()),
+            Ok(None) => None,
+            Err(err) => panic!(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RETURN_42: &'static str = "
+        Begin 0x00
+        PushLocal 0x01 0x00
+        i32Const 0x00 0x00 0x00 0x2a
+        Return 0x01
+        End
+    ";
+
+    #[test]
+    fn it_deploys_and_calls_contracts() {
+        let mut state = TestState::new();
+        let contract = state
+            .deploy_pasm(TestAccount::A, RETURN_42, b"initial", 100)
+            .unwrap();
+        let result = state.call(&contract, &[]).unwrap();
+
+        assert_eq!(result.return_values, vec![VmValue::I32(42)]);
+        assert!(result.gas_used > Gas::from_bytes(b"0.0").unwrap());
+        assert_eq!(state.storage(&contract), Some(b"initial".to_vec()));
+    }
+
+    #[test]
+    fn it_enforces_the_gas_limit() {
+        let mut state = TestState::new();
+        let contract = state
+            .deploy_pasm(TestAccount::A, RETURN_42, &[], 100)
+            .unwrap();
+        let gas_used = state.call(&contract, &[]).unwrap().gas_used;
+
+        assert_eq!(
+            state
+                .call_with_gas(&contract, &[], gas_used.clone())
+                .unwrap()
+                .gas_used,
+            gas_used
+        );
+        assert_eq!(
+            state.call_with_gas(&contract, &[], gas_used - Gas::from_bytes(b"1.0").unwrap()),
+            Err(VmError::OutOfGas)
+        );
+        assert_eq!(
+            state.call_with_gas(&contract, &[], Gas::from_bytes(b"0.0").unwrap()),
+            Err(VmError::OutOfGas)
+        );
+    }
+
+    #[test]
+    fn it_transfers_the_deployed_amount() {
+        let mut state = TestState::new();
+        let asset_hash = state.main_asset_hash();
+        let contract = state
+            .deploy_pasm(TestAccount::A, RETURN_42, &[], 100)
+            .unwrap();
+
+        assert_eq!(
+            state.balance(&Address::Contract(contract), &asset_hash),
+            Some(Balance::from_u64(100))
+        );
+
+        // The deployed amount plus a fee of 1
+        assert_eq!(
+            state.balance_of(TestAccount::A),
+            Some(Balance::from_u64(100000 - 101))
+        );
+        assert_eq!(
+            state.balance_of(TestAccount::B),
+            Some(Balance::from_u64(100000))
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_code() {
+        let mut state = TestState::new();
+
+        assert!(state
+            .deploy(TestAccount::A, &[0x00, 0x01], &[], 100)
+            .is_err());
+        assert!(state
+            .deploy_pasm(TestAccount::A, "Begin Unknown", &[], 100)
+            .is_err());
+    }
+}